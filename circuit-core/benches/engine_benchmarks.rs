@@ -1,3 +1,4 @@
+use circuit_core::blocks::control::GateBlock;
 use circuit_core::blocks::core::ConstantBlock;
 use circuit_core::blocks::math::AddBlock;
 use circuit_core::graph::{Connection, Graph, Node};
@@ -10,7 +11,7 @@ use circuit_core::block::Block;
 
 /// Create a linear chain graph: const → add → add → ... → add
 fn build_linear_chain(size: usize) -> (Engine, String) {
-    let mut engine = Engine::new();
+    let engine = Engine::new();
     engine.register_block(Arc::new(ConstantBlock)).unwrap();
     engine.register_block(Arc::new(AddBlock)).unwrap();
 
@@ -21,12 +22,7 @@ fn build_linear_chain(size: usize) -> (Engine, String) {
     let mut config = HashMap::new();
     config.insert("value".to_string(), Value::Float(1.0));
     graph
-        .add_node(Node {
-            id: "const_0".to_string(),
-            block_type: "core.constant".to_string(),
-            config,
-            position: None,
-        })
+        .add_node(Node::new("const_0", "core.constant").with_config(config))
         .unwrap();
 
     // Build chain of add nodes, each adding a constant
@@ -35,22 +31,12 @@ fn build_linear_chain(size: usize) -> (Engine, String) {
         let mut config = HashMap::new();
         config.insert("value".to_string(), Value::Float(1.0));
         graph
-            .add_node(Node {
-                id: format!("const_{}", i + 1),
-                block_type: "core.constant".to_string(),
-                config,
-                position: None,
-            })
+            .add_node(Node::new(format!("const_{}", i + 1), "core.constant").with_config(config))
             .unwrap();
 
         // Add node
         graph
-            .add_node(Node {
-                id: format!("add_{}", i),
-                block_type: "math.add".to_string(),
-                config: HashMap::new(),
-                position: None,
-            })
+            .add_node(Node::new(format!("add_{}", i), "math.add"))
             .unwrap();
 
         // Connect previous output to add.a
@@ -91,7 +77,7 @@ fn build_linear_chain(size: usize) -> (Engine, String) {
 
 /// Create a wide graph: N independent chains of const → add
 fn build_wide_graph(width: usize) -> (Engine, String) {
-    let mut engine = Engine::new();
+    let engine = Engine::new();
     engine.register_block(Arc::new(ConstantBlock)).unwrap();
     engine.register_block(Arc::new(AddBlock)).unwrap();
 
@@ -103,33 +89,18 @@ fn build_wide_graph(width: usize) -> (Engine, String) {
         let mut config_a = HashMap::new();
         config_a.insert("value".to_string(), Value::Float(i as f64));
         graph
-            .add_node(Node {
-                id: format!("const_a_{}", i),
-                block_type: "core.constant".to_string(),
-                config: config_a,
-                position: None,
-            })
+            .add_node(Node::new(format!("const_a_{}", i), "core.constant").with_config(config_a))
             .unwrap();
 
         let mut config_b = HashMap::new();
         config_b.insert("value".to_string(), Value::Float(1.0));
         graph
-            .add_node(Node {
-                id: format!("const_b_{}", i),
-                block_type: "core.constant".to_string(),
-                config: config_b,
-                position: None,
-            })
+            .add_node(Node::new(format!("const_b_{}", i), "core.constant").with_config(config_b))
             .unwrap();
 
         // Add node
         graph
-            .add_node(Node {
-                id: format!("add_{}", i),
-                block_type: "math.add".to_string(),
-                config: HashMap::new(),
-                position: None,
-            })
+            .add_node(Node::new(format!("add_{}", i), "math.add"))
             .unwrap();
 
         graph
@@ -156,7 +127,7 @@ fn build_wide_graph(width: usize) -> (Engine, String) {
 
 /// Build a graph without loading it (for load benchmarks)
 fn build_unloaded_graph(size: usize) -> (Engine, Graph) {
-    let mut engine = Engine::new();
+    let engine = Engine::new();
     engine.register_block(Arc::new(ConstantBlock)).unwrap();
     engine.register_block(Arc::new(AddBlock)).unwrap();
 
@@ -166,33 +137,18 @@ fn build_unloaded_graph(size: usize) -> (Engine, Graph) {
     let mut config = HashMap::new();
     config.insert("value".to_string(), Value::Float(1.0));
     graph
-        .add_node(Node {
-            id: "const_0".to_string(),
-            block_type: "core.constant".to_string(),
-            config,
-            position: None,
-        })
+        .add_node(Node::new("const_0", "core.constant").with_config(config))
         .unwrap();
 
     for i in 0..size {
         let mut config = HashMap::new();
         config.insert("value".to_string(), Value::Float(1.0));
         graph
-            .add_node(Node {
-                id: format!("const_{}", i + 1),
-                block_type: "core.constant".to_string(),
-                config,
-                position: None,
-            })
+            .add_node(Node::new(format!("const_{}", i + 1), "core.constant").with_config(config))
             .unwrap();
 
         graph
-            .add_node(Node {
-                id: format!("add_{}", i),
-                block_type: "math.add".to_string(),
-                config: HashMap::new(),
-                position: None,
-            })
+            .add_node(Node::new(format!("add_{}", i), "math.add"))
             .unwrap();
 
         if i == 0 {
@@ -234,12 +190,7 @@ fn build_topo_graph(size: usize) -> Graph {
 
     for i in 0..size {
         graph
-            .add_node(Node {
-                id: format!("node_{}", i),
-                block_type: "test".to_string(),
-                config: HashMap::new(),
-                position: None,
-            })
+            .add_node(Node::new(format!("node_{}", i), "test"))
             .unwrap();
 
         if i > 0 {
@@ -257,6 +208,62 @@ fn build_topo_graph(size: usize) -> Graph {
     graph
 }
 
+/// Create a chain of gates forwarding one large array: array → gate → gate → ...
+fn build_array_pipeline(array_len: usize, gates: usize) -> (Engine, String) {
+    let engine = Engine::new();
+    engine.register_block(Arc::new(ConstantBlock)).unwrap();
+    engine.register_block(Arc::new(GateBlock)).unwrap();
+
+    let graph_id = format!("array_pipeline_{}", array_len);
+    let mut graph = Graph::new(graph_id.clone(), "Array pipeline".to_string());
+
+    let mut config = HashMap::new();
+    config.insert(
+        "value".to_string(),
+        Value::from((0..array_len as i64).collect::<Vec<_>>()),
+    );
+    graph
+        .add_node(Node::new("array", "core.constant").with_config(config))
+        .unwrap();
+
+    let mut config = HashMap::new();
+    config.insert("value".to_string(), Value::Bool(true));
+    graph
+        .add_node(Node::new("open", "core.constant").with_config(config))
+        .unwrap();
+
+    for i in 0..gates {
+        graph
+            .add_node(Node::new(format!("gate_{}", i), "control.gate"))
+            .unwrap();
+
+        let (from_node, from_port) = if i == 0 {
+            ("array".to_string(), "value")
+        } else {
+            (format!("gate_{}", i - 1), "result")
+        };
+        graph
+            .add_connection(Connection {
+                from_node,
+                from_port: from_port.to_string(),
+                to_node: format!("gate_{}", i),
+                to_port: "value".to_string(),
+            })
+            .unwrap();
+        graph
+            .add_connection(Connection {
+                from_node: "open".to_string(),
+                from_port: "value".to_string(),
+                to_node: format!("gate_{}", i),
+                to_port: "open".to_string(),
+            })
+            .unwrap();
+    }
+
+    engine.load_graph(graph).unwrap();
+    (engine, graph_id)
+}
+
 fn bench_single_block_execution(c: &mut Criterion) {
     let block = AddBlock;
     let mut context = BlockContext::new();
@@ -303,6 +310,26 @@ fn bench_graph_execution_wide(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_large_array_propagation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("graph_execution/large_array");
+
+    for array_len in [1_000, 100_000] {
+        let (engine, graph_id) = build_array_pipeline(array_len, 10);
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(array_len),
+            &array_len,
+            |b, _| {
+                b.iter(|| {
+                    black_box(engine.execute_graph(&graph_id).unwrap());
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
 fn bench_graph_loading(c: &mut Criterion) {
     let mut group = c.benchmark_group("graph_loading");
 
@@ -311,7 +338,7 @@ fn bench_graph_loading(c: &mut Criterion) {
 
         group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
             b.iter(|| {
-                let mut engine = Engine::new();
+                let engine = Engine::new();
                 // Re-register blocks (engine doesn't clone)
                 engine.register_block(Arc::new(ConstantBlock)).unwrap();
                 engine.register_block(Arc::new(AddBlock)).unwrap();
@@ -374,7 +401,7 @@ fn bench_flow_parse_and_execute(c: &mut Criterion) {
             let flow = circuit_lang::parse_flow(black_box(source)).unwrap();
             let graph = circuit_lang::flow_to_graph(&flow).unwrap();
 
-            let mut engine = Engine::new();
+            let engine = Engine::new();
             engine.register_block(Arc::new(ConstantBlock)).unwrap();
             engine.register_block(Arc::new(AddBlock)).unwrap();
             engine
@@ -391,6 +418,7 @@ criterion_group!(
     bench_single_block_execution,
     bench_graph_execution_linear,
     bench_graph_execution_wide,
+    bench_large_array_propagation,
     bench_graph_loading,
     bench_topological_sort,
     bench_flow_parse_and_execute,
@@ -0,0 +1,157 @@
+use crate::{
+    block::{Block, BlockContext, BlockMetadata, NodePorts},
+    error::Result,
+    value::Value,
+};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// Future returned by `AsyncBlock::execute_async`
+///
+/// On native targets it must be `Send` so executions can move between
+/// threads; on wasm32 it may hold JavaScript values, which are not.
+#[cfg(not(target_arch = "wasm32"))]
+pub type BlockFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<HashMap<String, Value>>> + Send + 'a>>;
+
+/// Future returned by `AsyncBlock::execute_async`
+#[cfg(target_arch = "wasm32")]
+pub type BlockFuture<'a> = Pin<Box<dyn Future<Output = Result<HashMap<String, Value>>> + 'a>>;
+
+/// A block that awaits IO, timers or JavaScript promises instead of blocking
+///
+/// Register it with `Engine::register_async_block`. `Engine::execute_async`
+/// runs independent async nodes concurrently; the synchronous `execute*`
+/// methods drive the future on the calling thread, which is not possible on
+/// wasm32.
+pub trait AsyncBlock: Send + Sync {
+    /// Get metadata about this block
+    fn metadata(&self) -> BlockMetadata;
+
+    /// Execute the block with given context
+    fn execute_async(&self, context: BlockContext) -> BlockFuture<'_>;
+
+    /// Validate the block configuration (optional)
+    fn validate(&self, _config: &HashMap<String, Value>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Version of the block implementation, recorded in engine snapshots
+    fn version(&self) -> u32 {
+        1
+    }
+
+    /// Whether the block has no effects outside the execution context
+    ///
+    /// Async blocks usually await IO, so replays substitute their recorded
+    /// outputs unless they override this.
+    fn is_pure(&self) -> bool {
+        false
+    }
+
+    /// Ports of a node using this block with the given config
+    fn ports_for_config(&self, _config: &HashMap<String, Value>) -> Result<NodePorts> {
+        Ok(NodePorts::from(self.metadata()))
+    }
+}
+
+/// Lets an async block live in the block registry next to sync blocks
+pub(crate) struct AsyncBlockAdapter(pub(crate) Arc<dyn AsyncBlock>);
+
+impl Block for AsyncBlockAdapter {
+    fn metadata(&self) -> BlockMetadata {
+        self.0.metadata()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+        block_on(self.0.execute_async(context))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn execute(&self, _context: BlockContext) -> Result<HashMap<String, Value>> {
+        Err(crate::error::CircuitError::BlockExecution(format!(
+            "Block '{}' is async; run the graph with Engine::execute_async",
+            self.0.metadata().id
+        )))
+    }
+
+    fn validate(&self, config: &HashMap<String, Value>) -> Result<()> {
+        self.0.validate(config)
+    }
+
+    fn version(&self) -> u32 {
+        self.0.version()
+    }
+
+    fn is_pure(&self) -> bool {
+        self.0.is_pure()
+    }
+
+    fn ports_for_config(&self, config: &HashMap<String, Value>) -> Result<NodePorts> {
+        self.0.ports_for_config(config)
+    }
+
+    fn as_async(&self) -> Option<&dyn AsyncBlock> {
+        Some(self.0.as_ref())
+    }
+}
+
+/// Drive a future to completion on the current thread
+///
+/// A minimal executor for hosts without an async runtime; it parks the
+/// thread while the future is pending.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread::{self, Thread};
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut context = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use std::task::{Context, Poll};
+
+    // Pending once, waking itself, then ready
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = u32;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u32> {
+            if self.0 {
+                Poll::Ready(7)
+            } else {
+                self.0 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn test_block_on() {
+        assert_eq!(block_on(YieldOnce(false)), 7);
+        assert_eq!(block_on(async { 1 + 1 }), 2);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{
+    engine::NodeOutputs,
+    error::{CircuitError, Result},
+    value::Value,
+};
+use std::collections::HashMap;
+
+/// Parse batch rows from JSON lines
+///
+/// Each non-empty line is a JSON object mapping `"node.port"` to a value.
+pub fn rows_from_jsonl(input: &str) -> Result<Vec<HashMap<String, Value>>> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                CircuitError::InvalidInput(format!("Batch row on line {}: {}", index + 1, e))
+            })
+        })
+        .collect()
+}
+
+/// Render batch results as JSON lines, one line per row in row order
+///
+/// Successful rows become `{"ok": <outputs>}`, failed rows `{"error": "<message>"}`.
+pub fn results_to_jsonl(results: &[Result<NodeOutputs>]) -> String {
+    let mut output = String::new();
+    for result in results {
+        let line = match result {
+            Ok(outputs) => serde_json::json!({ "ok": outputs }),
+            Err(e) => serde_json::json!({ "error": e.to_string() }),
+        };
+        output.push_str(&line.to_string());
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_from_jsonl() {
+        let input = "{\"a.value\": {\"type\": \"Int\", \"value\": 1}}\n\n{}\n";
+        let rows = rows_from_jsonl(input).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["a.value"], Value::Int(1));
+        assert!(rows[1].is_empty());
+
+        let err = rows_from_jsonl("{}\nnot json").unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+    }
+
+    #[test]
+    fn test_results_to_jsonl() {
+        let mut outputs = NodeOutputs::new();
+        outputs.insert(
+            "a".to_string(),
+            HashMap::from([("value".to_string(), Value::Int(1))]),
+        );
+        let results = vec![
+            Ok(outputs),
+            Err(CircuitError::NodeNotFound("b".to_string())),
+        ];
+
+        let jsonl = results_to_jsonl(&results);
+        let lines: Vec<serde_json::Value> = jsonl
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["ok"]["a"]["value"]["value"], 1);
+        assert_eq!(lines[1]["error"], "Node not found: b");
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{error::Result, value::Value};
+use crate::{
+    async_block::AsyncBlock, context::ExecutionContext, error::Result,
+    execution::CancellationToken, state::NodeStateSlot, value::Value,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -20,7 +23,7 @@ pub struct BlockMetadata {
 }
 
 /// Definition of an input or output port
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PortDefinition {
     /// Port identifier
     pub id: String,
@@ -32,6 +35,66 @@ pub struct PortDefinition {
     pub required: bool,
 }
 
+impl PortDefinition {
+    /// Whether a value of this output port may flow into `input`
+    ///
+    /// Types match when they are equal or either side is `any`.
+    pub fn accepts_into(&self, input: &PortDefinition) -> bool {
+        self.data_type == input.data_type || self.data_type == "any" || input.data_type == "any"
+    }
+}
+
+/// Input and output ports of a node, resolved from its block and config
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodePorts {
+    pub inputs: Vec<PortDefinition>,
+    pub outputs: Vec<PortDefinition>,
+}
+
+impl NodePorts {
+    /// Get an input port by ID
+    pub fn input(&self, port_id: &str) -> Option<&PortDefinition> {
+        self.inputs.iter().find(|port| port.id == port_id)
+    }
+
+    /// Get an output port by ID
+    pub fn output(&self, port_id: &str) -> Option<&PortDefinition> {
+        self.outputs.iter().find(|port| port.id == port_id)
+    }
+
+    /// Expose every config field that is not an input already as an
+    /// optional input port
+    ///
+    /// Blocks opt in from `ports_for_config` for the fields they read with
+    /// `BlockContext::get_param`, where a connected value takes precedence
+    /// over the static config.
+    pub fn with_parameters(mut self, config_schema: &HashMap<String, String>) -> Self {
+        let mut fields: Vec<(&String, &String)> = config_schema
+            .iter()
+            .filter(|(key, _)| self.input(key).is_none())
+            .collect();
+        fields.sort();
+        for (key, data_type) in fields {
+            self.inputs.push(PortDefinition {
+                id: key.clone(),
+                name: key.clone(),
+                data_type: data_type.clone(),
+                required: false,
+            });
+        }
+        self
+    }
+}
+
+impl From<BlockMetadata> for NodePorts {
+    fn from(metadata: BlockMetadata) -> Self {
+        Self {
+            inputs: metadata.inputs,
+            outputs: metadata.outputs,
+        }
+    }
+}
+
 /// Context provided to a block during execution
 #[derive(Debug, Clone)]
 pub struct BlockContext {
@@ -39,6 +102,14 @@ pub struct BlockContext {
     pub inputs: HashMap<String, Value>,
     /// Block-specific configuration
     pub config: HashMap<String, Value>,
+    /// Cancellation token of the execution this block runs in
+    pub(crate) cancellation: CancellationToken,
+    /// Variables and services of the execution this block runs in
+    pub(crate) execution: ExecutionContext,
+    /// Value the host resumed this node with, after it suspended
+    pub(crate) resume_value: Option<Value>,
+    /// State kept for this node between executions
+    pub(crate) node_state: Option<NodeStateSlot>,
 }
 
 impl BlockContext {
@@ -47,9 +118,96 @@ impl BlockContext {
         Self {
             inputs: HashMap::new(),
             config: HashMap::new(),
+            cancellation: CancellationToken::new(),
+            execution: ExecutionContext::new(),
+            resume_value: None,
+            node_state: None,
+        }
+    }
+
+    // Context for a node of a running execution
+    pub(crate) fn for_execution(
+        cancellation: CancellationToken,
+        execution: ExecutionContext,
+    ) -> Self {
+        Self {
+            inputs: HashMap::new(),
+            config: HashMap::new(),
+            cancellation,
+            execution,
+            resume_value: None,
+            node_state: None,
+        }
+    }
+
+    /// Take ownership of an input value, leaving the port empty
+    ///
+    /// Pass-through blocks use this to forward a value without copying it.
+    pub fn take_input(&mut self, port_id: &str) -> Option<Value> {
+        self.inputs.remove(port_id)
+    }
+
+    /// Take ownership of a config value, leaving the key empty
+    pub fn take_config(&mut self, key: &str) -> Option<Value> {
+        self.config.remove(key)
+    }
+
+    /// Attach a cancellation token to this context
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// Check whether the surrounding execution has been cancelled
+    ///
+    /// Long-running blocks should poll this and return
+    /// `CircuitError::Cancelled` once it turns true.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Attach execution variables and services to this context
+    pub fn with_execution(mut self, execution: ExecutionContext) -> Self {
+        self.execution = execution;
+        self
+    }
+
+    /// Variables and host services of the surrounding execution
+    pub fn execution(&self) -> &ExecutionContext {
+        &self.execution
+    }
+
+    /// Value passed to `Engine::resume` when this node is run again after
+    /// suspending with `CircuitError::Suspended`
+    pub fn resume_value(&self) -> Option<&Value> {
+        self.resume_value.as_ref()
+    }
+
+    /// Attach a resume value to this context
+    pub fn with_resume_value(mut self, value: Value) -> Self {
+        self.resume_value = Some(value);
+        self
+    }
+
+    /// State this node stored in an earlier execution
+    pub fn state(&self) -> Option<Value> {
+        self.node_state.as_ref().and_then(NodeStateSlot::get)
+    }
+
+    /// Store state for this node, kept by the engine between executions
+    ///
+    /// Has no effect outside an engine execution.
+    pub fn set_state(&self, value: Value) {
+        if let Some(slot) = &self.node_state {
+            slot.set(value);
         }
     }
 
+    /// Get an execution variable by name
+    pub fn variable(&self, name: &str) -> Option<&Value> {
+        self.execution.variable(name)
+    }
+
     /// Get an input value by port ID
     pub fn get_input(&self, port_id: &str) -> Option<&Value> {
         self.inputs.get(port_id)
@@ -59,6 +217,17 @@ impl BlockContext {
     pub fn get_config(&self, key: &str) -> Option<&Value> {
         self.config.get(key)
     }
+
+    /// Get a parameter: the value connected to the input port of that name,
+    /// or else the config field
+    pub fn get_param(&self, key: &str) -> Option<&Value> {
+        self.inputs.get(key).or_else(|| self.config.get(key))
+    }
+
+    /// Take ownership of a parameter, with the precedence of `get_param`
+    pub fn take_param(&mut self, key: &str) -> Option<Value> {
+        self.inputs.remove(key).or_else(|| self.config.remove(key))
+    }
 }
 
 impl Default for BlockContext {
@@ -75,10 +244,40 @@ pub trait Block: Send + Sync {
     /// Execute the block with given context
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>>;
 
+    /// Version of the block implementation, recorded in engine snapshots
+    ///
+    /// Bump it when the meaning of the block's config or state changes.
+    fn version(&self) -> u32 {
+        1
+    }
+
     /// Validate the block configuration (optional)
     fn validate(&self, _config: &HashMap<String, Value>) -> Result<()> {
         Ok(())
     }
+
+    /// Whether the block has no effects outside the execution context
+    ///
+    /// Replays re-run pure blocks and substitute the recorded outputs of
+    /// impure ones, such as blocks calling a network service. Blocks are
+    /// assumed impure unless they override this.
+    fn is_pure(&self) -> bool {
+        false
+    }
+
+    /// Ports of a node using this block with the given config
+    ///
+    /// Blocks whose ports depend on their config override this; the default
+    /// returns the ports listed in `metadata`. Blocks that read config fields
+    /// with `BlockContext::get_param` add them with `NodePorts::with_parameters`.
+    fn ports_for_config(&self, _config: &HashMap<String, Value>) -> Result<NodePorts> {
+        Ok(NodePorts::from(self.metadata()))
+    }
+
+    /// Async implementation of this block, awaited by `Engine::execute_async`
+    fn as_async(&self) -> Option<&dyn AsyncBlock> {
+        None
+    }
 }
 
 #[cfg(test)]
@@ -113,4 +312,17 @@ mod tests {
         let result = block.execute(context).unwrap();
         assert_eq!(result.get("result"), Some(&Value::Int(42)));
     }
+
+    #[test]
+    fn test_connected_param_takes_precedence() {
+        let mut context = BlockContext::new();
+        context.config.insert("step".to_string(), Value::Int(1));
+        assert_eq!(context.get_param("step"), Some(&Value::Int(1)));
+
+        context.inputs.insert("step".to_string(), Value::Int(5));
+        assert_eq!(context.get_param("step"), Some(&Value::Int(5)));
+        assert_eq!(context.take_param("step"), Some(Value::Int(5)));
+        assert_eq!(context.take_param("step"), Some(Value::Int(1)));
+        assert_eq!(context.get_param("step"), None);
+    }
 }
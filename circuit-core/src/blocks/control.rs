@@ -1,4 +1,4 @@
-use crate::block::{Block, BlockContext, BlockMetadata, PortDefinition};
+use crate::block::{Block, BlockContext, BlockMetadata, NodePorts, PortDefinition};
 use crate::error::{CircuitError, Result};
 use crate::value::Value;
 use std::collections::HashMap;
@@ -42,7 +42,11 @@ impl Block for IfBlock {
         }
     }
 
-    fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, mut context: BlockContext) -> Result<HashMap<String, Value>> {
         let condition = context
             .get_input("condition")
             .and_then(|v| v.as_bool())
@@ -50,13 +54,11 @@ impl Block for IfBlock {
                 CircuitError::InvalidInput("Missing or invalid input 'condition'".to_string())
             })?;
         let then_value = context
-            .get_input("then_value")
-            .ok_or_else(|| CircuitError::InvalidInput("Missing input 'then_value'".to_string()))?
-            .clone();
+            .take_input("then_value")
+            .ok_or_else(|| CircuitError::InvalidInput("Missing input 'then_value'".to_string()))?;
         let else_value = context
-            .get_input("else_value")
-            .ok_or_else(|| CircuitError::InvalidInput("Missing input 'else_value'".to_string()))?
-            .clone();
+            .take_input("else_value")
+            .ok_or_else(|| CircuitError::InvalidInput("Missing input 'else_value'".to_string()))?;
 
         let mut outputs = HashMap::new();
         outputs.insert(
@@ -68,6 +70,9 @@ impl Block for IfBlock {
 }
 
 /// Select from multiple values based on a numeric selector
+///
+/// With a `cases` config listing port names, the switch gets one input port
+/// per case instead of `a` and `b`, selected by name or by index.
 pub struct SwitchBlock;
 
 impl Block for SwitchBlock {
@@ -108,11 +113,44 @@ impl Block for SwitchBlock {
                 data_type: "any".to_string(),
                 required: true,
             }],
-            config_schema: HashMap::new(),
+            config_schema: HashMap::from([("cases".to_string(), "array".to_string())]),
         }
     }
 
-    fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+    fn validate(&self, config: &HashMap<String, Value>) -> Result<()> {
+        switch_cases(config).map(|_| ())
+    }
+
+    fn ports_for_config(&self, config: &HashMap<String, Value>) -> Result<NodePorts> {
+        // `cases` shapes the ports, so it cannot be a connected parameter
+        let Some(cases) = switch_cases(config)? else {
+            return Ok(NodePorts::from(self.metadata()));
+        };
+
+        let port = |id: &str, name: &str, data_type: &str| PortDefinition {
+            id: id.to_string(),
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            required: true,
+        };
+        let mut inputs = vec![port("selector", "Selector", "any")];
+        inputs.extend(cases.iter().map(|case| port(case, case, "any")));
+        inputs.push(port("default", "Default", "any"));
+        Ok(NodePorts {
+            inputs,
+            outputs: vec![port("result", "Result", "any")],
+        })
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, mut context: BlockContext) -> Result<HashMap<String, Value>> {
+        if let Some(cases) = switch_cases(&context.config)? {
+            return select_case(context, &cases);
+        }
+
         let selector_f = context
             .get_input("selector")
             .and_then(|v| v.as_float())
@@ -126,17 +164,14 @@ impl Block for SwitchBlock {
         }
         let selector = selector_f.round() as i64;
         let a = context
-            .get_input("a")
-            .ok_or_else(|| CircuitError::InvalidInput("Missing input 'a'".to_string()))?
-            .clone();
+            .take_input("a")
+            .ok_or_else(|| CircuitError::InvalidInput("Missing input 'a'".to_string()))?;
         let b = context
-            .get_input("b")
-            .ok_or_else(|| CircuitError::InvalidInput("Missing input 'b'".to_string()))?
-            .clone();
+            .take_input("b")
+            .ok_or_else(|| CircuitError::InvalidInput("Missing input 'b'".to_string()))?;
         let default = context
-            .get_input("default")
-            .ok_or_else(|| CircuitError::InvalidInput("Missing input 'default'".to_string()))?
-            .clone();
+            .take_input("default")
+            .ok_or_else(|| CircuitError::InvalidInput("Missing input 'default'".to_string()))?;
 
         let selected = match selector {
             0 => a,
@@ -150,6 +185,61 @@ impl Block for SwitchBlock {
     }
 }
 
+// Port names listed in the `cases` config of a switch, if any
+fn switch_cases(config: &HashMap<String, Value>) -> Result<Option<Vec<String>>> {
+    let Some(cases) = config.get("cases") else {
+        return Ok(None);
+    };
+    let invalid = |reason: &str| CircuitError::InvalidInput(format!("Switch cases {}", reason));
+    let cases = cases
+        .as_array()
+        .ok_or_else(|| invalid("must be an array of strings"))?;
+
+    let mut names: Vec<String> = Vec::with_capacity(cases.len());
+    for case in cases.iter() {
+        let name = case
+            .as_str()
+            .ok_or_else(|| invalid("must be an array of strings"))?;
+        if name.is_empty() || name == "selector" || name == "default" {
+            return Err(invalid(&format!("cannot use the port name '{}'", name)));
+        }
+        if names.iter().any(|existing| existing == name) {
+            return Err(invalid(&format!("list '{}' twice", name)));
+        }
+        names.push(name.to_string());
+    }
+    Ok(Some(names))
+}
+
+// Pick the case port named by a string selector or indexed by a number
+fn select_case(mut context: BlockContext, cases: &[String]) -> Result<HashMap<String, Value>> {
+    let selector = context.get_input("selector").ok_or_else(|| {
+        CircuitError::InvalidInput("Missing or invalid input 'selector'".to_string())
+    })?;
+    let case = if let Some(name) = selector.as_str() {
+        cases.iter().find(|case| *case == name)
+    } else if let Some(index) = selector.as_float() {
+        if !index.is_finite() {
+            return Err(CircuitError::BlockExecution(
+                "Switch: selector must be finite".to_string(),
+            ));
+        }
+        usize::try_from(index.round() as i64)
+            .ok()
+            .and_then(|index| cases.get(index))
+    } else {
+        return Err(CircuitError::InvalidInput(
+            "Missing or invalid input 'selector'".to_string(),
+        ));
+    };
+
+    let port = case.map_or("default", String::as_str);
+    let selected = context
+        .take_input(port)
+        .ok_or_else(|| CircuitError::InvalidInput(format!("Missing input '{}'", port)))?;
+    Ok(HashMap::from([("result".to_string(), selected)]))
+}
+
 /// Pass through a value when gate is open, otherwise output Null
 pub struct GateBlock;
 
@@ -184,11 +274,14 @@ impl Block for GateBlock {
         }
     }
 
-    fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, mut context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
-            .get_input("value")
-            .ok_or_else(|| CircuitError::InvalidInput("Missing input 'value'".to_string()))?
-            .clone();
+            .take_input("value")
+            .ok_or_else(|| CircuitError::InvalidInput("Missing input 'value'".to_string()))?;
         let open = context
             .get_input("open")
             .and_then(|v| v.as_bool())
@@ -231,6 +324,17 @@ impl Block for CounterBlock {
         }
     }
 
+    // `step` can be connected instead of configured
+    fn ports_for_config(&self, _config: &HashMap<String, Value>) -> Result<NodePorts> {
+        let metadata = self.metadata();
+        let schema = metadata.config_schema.clone();
+        Ok(NodePorts::from(metadata).with_parameters(&schema))
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -239,7 +343,7 @@ impl Block for CounterBlock {
                 CircuitError::InvalidInput("Missing or invalid input 'value'".to_string())
             })?;
         let step = context
-            .get_config("step")
+            .get_param("step")
             .and_then(|v| v.as_float())
             .unwrap_or(1.0);
 
@@ -282,6 +386,10 @@ impl Block for AccumulatorBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -681,6 +789,62 @@ mod tests {
         assert!(result.is_err());
     }
 
+    fn rgb_switch_config() -> HashMap<String, Value> {
+        let cases: Vec<Value> = ["red", "green", "blue"]
+            .iter()
+            .map(|case| Value::String(case.to_string()))
+            .collect();
+        HashMap::from([("cases".to_string(), Value::Array(cases.into()))])
+    }
+
+    #[test]
+    fn test_switch_ports_follow_cases() {
+        let ports = SwitchBlock.ports_for_config(&rgb_switch_config()).unwrap();
+        let ids: Vec<&str> = ports.inputs.iter().map(|port| port.id.as_str()).collect();
+        assert_eq!(ids, vec!["selector", "red", "green", "blue", "default"]);
+
+        let fixed = SwitchBlock.ports_for_config(&HashMap::new()).unwrap();
+        assert!(fixed.input("a").is_some());
+    }
+
+    #[test]
+    fn test_switch_selects_case_by_name_or_index() {
+        let run = |selector: Value| {
+            let mut context = BlockContext::new();
+            context.config = rgb_switch_config();
+            context.inputs.insert("selector".to_string(), selector);
+            for port in ["red", "green", "blue", "default"] {
+                context
+                    .inputs
+                    .insert(port.to_string(), Value::String(port.to_string()));
+            }
+            SwitchBlock.execute(context).unwrap()["result"].clone()
+        };
+
+        assert_eq!(
+            run(Value::String("green".into())),
+            Value::String("green".into())
+        );
+        assert_eq!(run(Value::Float(2.0)), Value::String("blue".into()));
+        assert_eq!(
+            run(Value::String("pink".into())),
+            Value::String("default".into())
+        );
+        assert_eq!(run(Value::Float(-1.0)), Value::String("default".into()));
+    }
+
+    #[test]
+    fn test_switch_rejects_invalid_cases() {
+        let config = |cases: Value| HashMap::from([("cases".to_string(), cases)]);
+        assert!(SwitchBlock.validate(&rgb_switch_config()).is_ok());
+        assert!(SwitchBlock.validate(&config(Value::Int(3))).is_err());
+        let duplicate =
+            Value::Array(vec![Value::String("x".into()), Value::String("x".into())].into());
+        assert!(SwitchBlock.validate(&config(duplicate)).is_err());
+        let reserved = Value::Array(vec![Value::String("default".into())].into());
+        assert!(SwitchBlock.ports_for_config(&config(reserved)).is_err());
+    }
+
     // ── GateBlock tests ────────────────────────────────────────────
 
     #[test]
@@ -781,6 +945,22 @@ mod tests {
         assert_eq!(result.get("result"), Some(&Value::Float(15.0)));
     }
 
+    #[test]
+    fn test_counter_step_from_input() {
+        let ports = CounterBlock.ports_for_config(&HashMap::new()).unwrap();
+        assert!(ports.input("step").is_some_and(|port| !port.required));
+
+        let mut context = BlockContext::new();
+        context
+            .inputs
+            .insert("value".to_string(), Value::Float(10.0));
+        context.inputs.insert("step".to_string(), Value::Float(2.0));
+        context.config.insert("step".to_string(), Value::Float(5.0));
+
+        let result = CounterBlock.execute(context).unwrap();
+        assert_eq!(result.get("result"), Some(&Value::Float(12.0)));
+    }
+
     #[test]
     fn test_counter_negative_step() {
         let block = CounterBlock;
@@ -1,4 +1,5 @@
-use crate::block::{Block, BlockContext, BlockMetadata, PortDefinition};
+use crate::block::{Block, BlockContext, BlockMetadata, NodePorts, PortDefinition};
+use crate::context::LogLevel;
 use crate::error::{CircuitError, Result};
 use crate::value::Value;
 use std::collections::HashMap;
@@ -27,11 +28,21 @@ impl Block for ConstantBlock {
         }
     }
 
-    fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+    // `value` can be connected instead of configured
+    fn ports_for_config(&self, _config: &HashMap<String, Value>) -> Result<NodePorts> {
+        let metadata = self.metadata();
+        let schema = metadata.config_schema.clone();
+        Ok(NodePorts::from(metadata).with_parameters(&schema))
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, mut context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
-            .get_config("value")
-            .ok_or_else(|| CircuitError::InvalidInput("Missing config 'value'".to_string()))?
-            .clone();
+            .take_param("value")
+            .ok_or_else(|| CircuitError::InvalidInput("Missing config 'value'".to_string()))?;
 
         let mut outputs = HashMap::new();
         outputs.insert("value".to_string(), value);
@@ -39,7 +50,7 @@ impl Block for ConstantBlock {
     }
 }
 
-/// Debug block that prints values
+/// Debug block that logs values through the execution's logger
 pub struct DebugBlock;
 
 impl Block for DebugBlock {
@@ -64,13 +75,19 @@ impl Block for DebugBlock {
         }
     }
 
-    fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+    fn is_pure(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, mut context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
-            .get_input("value")
-            .ok_or_else(|| CircuitError::InvalidInput("Missing input 'value'".to_string()))?
-            .clone();
+            .take_input("value")
+            .ok_or_else(|| CircuitError::InvalidInput("Missing input 'value'".to_string()))?;
 
-        println!("DEBUG: {:?}", value);
+        context
+            .execution()
+            .logger()
+            .log(LogLevel::Debug, &format!("{:?}", value));
 
         let mut outputs = HashMap::new();
         outputs.insert("value".to_string(), value);
@@ -81,6 +98,8 @@ impl Block for DebugBlock {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::context::{ExecutionContext, MemoryLogger};
+    use std::sync::Arc;
 
     #[test]
     fn test_constant_block() {
@@ -96,4 +115,19 @@ mod tests {
             Some(&Value::String("Hello".to_string()))
         );
     }
+
+    #[test]
+    fn test_debug_block_logs_through_context() {
+        let logger = Arc::new(MemoryLogger::new());
+        let mut context =
+            BlockContext::new().with_execution(ExecutionContext::new().with_logger(logger.clone()));
+        context.inputs.insert("value".to_string(), Value::Int(7));
+
+        let result = DebugBlock.execute(context).unwrap();
+        assert_eq!(result.get("value"), Some(&Value::Int(7)));
+        assert_eq!(
+            logger.entries(),
+            vec![(LogLevel::Debug, "Int(7)".to_string())]
+        );
+    }
 }
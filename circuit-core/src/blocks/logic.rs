@@ -36,6 +36,10 @@ impl Block for AndBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let a = context
             .get_input("a")
@@ -89,6 +93,10 @@ impl Block for OrBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let a = context
             .get_input("a")
@@ -134,6 +142,10 @@ impl Block for NotBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -181,6 +193,10 @@ impl Block for EqualBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let a = context
             .get_input("a")
@@ -228,6 +244,10 @@ impl Block for GreaterBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let a = context
             .get_input("a")
@@ -281,6 +301,10 @@ impl Block for LessBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let a = context
             .get_input("a")
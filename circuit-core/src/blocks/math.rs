@@ -36,6 +36,10 @@ impl Block for AddBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let a = context
             .get_input("a")
@@ -89,6 +93,10 @@ impl Block for SubtractBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let a = context
             .get_input("a")
@@ -142,6 +150,10 @@ impl Block for MultiplyBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let a = context
             .get_input("a")
@@ -195,6 +207,10 @@ impl Block for DivideBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let a = context
             .get_input("a")
@@ -252,6 +268,10 @@ impl Block for ModuloBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let a = context
             .get_input("a")
@@ -301,6 +321,10 @@ impl Block for AbsBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -340,6 +364,10 @@ impl Block for NegateBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -387,6 +415,10 @@ impl Block for PowerBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let base = context
             .get_input("base")
@@ -432,6 +464,10 @@ impl Block for SqrtBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -485,6 +521,10 @@ impl Block for MinBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let a = context
             .get_input("a")
@@ -538,6 +578,10 @@ impl Block for MaxBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let a = context
             .get_input("a")
@@ -597,6 +641,10 @@ impl Block for ClampBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -660,6 +708,10 @@ impl Block for RoundBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -699,6 +751,10 @@ impl Block for FloorBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -738,6 +794,10 @@ impl Block for CeilBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -777,6 +837,10 @@ impl Block for SinBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -816,6 +880,10 @@ impl Block for CosBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -855,6 +923,10 @@ impl Block for TanBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -1,7 +1,8 @@
-use crate::block::{Block, BlockContext, BlockMetadata, PortDefinition};
+use crate::block::{Block, BlockContext, BlockMetadata, NodePorts, PortDefinition};
 use crate::error::{CircuitError, Result};
 use crate::value::Value;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Convert a Value to its display string representation
 fn value_to_display(v: &Value) -> String {
@@ -53,6 +54,10 @@ impl Block for ConcatBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let a = context
             .get_input("a")
@@ -98,6 +103,10 @@ impl Block for LengthBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -137,6 +146,10 @@ impl Block for UppercaseBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -176,6 +189,10 @@ impl Block for LowercaseBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -215,6 +232,10 @@ impl Block for TrimBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -265,6 +286,10 @@ impl Block for ContainsBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -324,6 +349,10 @@ impl Block for ReplaceBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -386,6 +415,10 @@ impl Block for SplitBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -403,12 +436,12 @@ impl Block for SplitBlock {
         let mut outputs = HashMap::new();
         outputs.insert(
             "result".to_string(),
-            Value::Array(
+            Value::Array(Arc::new(
                 value
                     .split(delimiter)
                     .map(|s| Value::String(s.to_string()))
                     .collect(),
-            ),
+            )),
         );
         Ok(outputs)
     }
@@ -447,6 +480,10 @@ impl Block for JoinBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let arr = context
             .get_input("value")
@@ -514,6 +551,10 @@ impl Block for SubstringBlock {
         }
     }
 
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
         let value = context
             .get_input("value")
@@ -551,6 +592,9 @@ impl Block for SubstringBlock {
 }
 
 /// Replace {} placeholders with a value
+///
+/// With a `template` config, every `{name}` placeholder in it becomes an
+/// input port and is replaced by that port's value.
 pub struct TemplateBlock;
 
 impl Block for TemplateBlock {
@@ -579,33 +623,139 @@ impl Block for TemplateBlock {
                 data_type: "string".to_string(),
                 required: true,
             }],
-            config_schema: HashMap::new(),
+            config_schema: HashMap::from([("template".to_string(), "string".to_string())]),
         }
     }
 
+    fn validate(&self, config: &HashMap<String, Value>) -> Result<()> {
+        config_template(config).map(|_| ())
+    }
+
+    fn ports_for_config(&self, config: &HashMap<String, Value>) -> Result<NodePorts> {
+        // The template config shapes the ports, so it is not a parameter
+        let Some(template) = config_template(config)? else {
+            return Ok(NodePorts::from(self.metadata()));
+        };
+
+        let inputs = placeholder_names(template)
+            .into_iter()
+            .map(|name| PortDefinition {
+                id: name.to_string(),
+                name: name.to_string(),
+                data_type: "any".to_string(),
+                required: true,
+            })
+            .collect();
+        Ok(NodePorts {
+            inputs,
+            outputs: self.metadata().outputs,
+        })
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+        if let Some(template) = config_template(&context.config)? {
+            // One pass over the template, so values are never scanned for
+            // placeholders themselves
+            let mut result = String::with_capacity(template.len());
+            for segment in template_segments(template) {
+                match segment {
+                    TemplateSegment::Text(text) => result.push_str(text),
+                    TemplateSegment::Placeholder(name) => {
+                        let value = context.get_input(name).ok_or_else(|| {
+                            CircuitError::InvalidInput(format!("Missing input '{}'", name))
+                        })?;
+                        result.push_str(&value_to_display(value));
+                    }
+                }
+            }
+            return Ok(HashMap::from([(
+                "result".to_string(),
+                Value::String(result),
+            )]));
+        }
+
         let template = context
             .get_input("template")
             .and_then(|v| v.as_str())
             .ok_or_else(|| {
                 CircuitError::InvalidInput("Missing or invalid input 'template'".to_string())
             })?;
-        let value = context
-            .get_input("value")
-            .ok_or_else(|| {
-                CircuitError::InvalidInput("Missing or invalid input 'value'".to_string())
-            })?
-            .clone();
+        let value = context.get_input("value").ok_or_else(|| {
+            CircuitError::InvalidInput("Missing or invalid input 'value'".to_string())
+        })?;
 
         let mut outputs = HashMap::new();
         outputs.insert(
             "result".to_string(),
-            Value::String(template.replace("{}", &value_to_display(&value))),
+            Value::String(template.replace("{}", &value_to_display(value))),
         );
         Ok(outputs)
     }
 }
 
+// Template given in the node config, if any
+fn config_template(config: &HashMap<String, Value>) -> Result<Option<&str>> {
+    match config.get("template") {
+        None => Ok(None),
+        Some(value) => value.as_str().map(Some).ok_or_else(|| {
+            CircuitError::InvalidInput("Config 'template' must be a string".to_string())
+        }),
+    }
+}
+
+// Piece of a template: literal text or a `{name}` placeholder
+enum TemplateSegment<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+// Split a template into text and `{name}` placeholders, left to right;
+// `{}` and braces around anything but an identifier are kept as text
+fn template_segments(template: &str) -> Vec<TemplateSegment<'_>> {
+    let mut segments = Vec::new();
+    let mut text_start = 0;
+    let mut pos = 0;
+    while let Some(open) = template[pos..].find('{').map(|i| pos + i) {
+        let Some(close) = template[open + 1..].find(['{', '}']).map(|i| open + 1 + i) else {
+            break;
+        };
+        let name = &template[open + 1..close];
+        let is_identifier = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if template[close..].starts_with('}') && is_identifier {
+            if text_start < open {
+                segments.push(TemplateSegment::Text(&template[text_start..open]));
+            }
+            segments.push(TemplateSegment::Placeholder(name));
+            text_start = close + 1;
+            pos = close + 1;
+        } else {
+            pos = close;
+        }
+    }
+    if text_start < template.len() {
+        segments.push(TemplateSegment::Text(&template[text_start..]));
+    }
+    segments
+}
+
+// Names of the `{name}` placeholders of a template, in order of first use
+fn placeholder_names(template: &str) -> Vec<&str> {
+    let mut names: Vec<&str> = Vec::new();
+    for segment in template_segments(template) {
+        if let TemplateSegment::Placeholder(name) = segment {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1181,7 +1331,7 @@ mod tests {
         let result = block.execute(context).unwrap();
         assert_eq!(
             result.get("result"),
-            Some(&Value::Array(vec![
+            Some(&Value::from(vec![
                 Value::String("a".to_string()),
                 Value::String("b".to_string()),
                 Value::String("c".to_string()),
@@ -1203,7 +1353,7 @@ mod tests {
         let result = block.execute(context).unwrap();
         assert_eq!(
             result.get("result"),
-            Some(&Value::Array(vec![Value::String("hello".to_string())]))
+            Some(&Value::from(vec![Value::String("hello".to_string())]))
         );
     }
 
@@ -1222,7 +1372,7 @@ mod tests {
         let result = block.execute(context).unwrap();
         assert_eq!(
             result.get("result"),
-            Some(&Value::Array(vec![
+            Some(&Value::from(vec![
                 Value::String("".to_string()),
                 Value::String("h".to_string()),
                 Value::String("i".to_string()),
@@ -1264,7 +1414,7 @@ mod tests {
         let mut context = BlockContext::new();
         context.inputs.insert(
             "value".to_string(),
-            Value::Array(vec![
+            Value::from(vec![
                 Value::String("a".to_string()),
                 Value::String("b".to_string()),
                 Value::String("c".to_string()),
@@ -1287,7 +1437,7 @@ mod tests {
         let mut context = BlockContext::new();
         context.inputs.insert(
             "value".to_string(),
-            Value::Array(vec![Value::String("only".to_string())]),
+            Value::from(vec![Value::String("only".to_string())]),
         );
         context
             .inputs
@@ -1306,7 +1456,7 @@ mod tests {
         let mut context = BlockContext::new();
         context
             .inputs
-            .insert("value".to_string(), Value::Array(vec![]));
+            .insert("value".to_string(), Value::from(Vec::<Value>::new()));
         context
             .inputs
             .insert("delimiter".to_string(), Value::String(",".to_string()));
@@ -1321,7 +1471,7 @@ mod tests {
         let mut context = BlockContext::new();
         context.inputs.insert(
             "value".to_string(),
-            Value::Array(vec![Value::Int(1), Value::Bool(true), Value::Null]),
+            Value::from(vec![Value::Int(1), Value::Bool(true), Value::Null]),
         );
         context
             .inputs
@@ -1582,4 +1732,51 @@ mod tests {
         let result = block.execute(context);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_template_config_placeholders_become_ports() {
+        let config = HashMap::from([(
+            "template".to_string(),
+            Value::String("Hi {name}, {name} is {age} {} {not valid}".to_string()),
+        )]);
+        let ports = TemplateBlock.ports_for_config(&config).unwrap();
+        let ids: Vec<&str> = ports.inputs.iter().map(|port| port.id.as_str()).collect();
+        assert_eq!(ids, vec!["name", "age"]);
+
+        let mut context = BlockContext::new();
+        context.config = config;
+        context
+            .inputs
+            .insert("name".to_string(), Value::String("Ada".to_string()));
+        context.inputs.insert("age".to_string(), Value::Int(36));
+        let result = TemplateBlock.execute(context.clone()).unwrap();
+        assert_eq!(
+            result.get("result"),
+            Some(&Value::String(
+                "Hi Ada, Ada is 36 {} {not valid}".to_string()
+            ))
+        );
+
+        context.inputs.remove("age");
+        assert!(TemplateBlock.execute(context).is_err());
+    }
+
+    #[test]
+    fn test_template_values_are_not_expanded() {
+        let mut context = BlockContext::new();
+        context.config =
+            HashMap::from([("template".to_string(), Value::String("{a}-{b}".to_string()))]);
+        context
+            .inputs
+            .insert("a".to_string(), Value::String("{b}".to_string()));
+        context
+            .inputs
+            .insert("b".to_string(), Value::String("{a}".to_string()));
+
+        let result = TemplateBlock.execute(context).unwrap();
+        assert_eq!(
+            result.get("result"),
+            Some(&Value::String("{b}-{a}".to_string()))
+        );
+    }
 }
@@ -0,0 +1,346 @@
+use crate::{
+    engine::NodeOutputs,
+    error::{CircuitError, Result},
+    graph::NodeId,
+    value::Value,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind as IoErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+/// Progress of an execution
+///
+/// The engine saves it when the execution starts and appends a
+/// `CheckpointStep` after every node that finishes. `completed` is the
+/// position in the plan: those nodes are not run again when the execution is
+/// continued with `Engine::resume_from_checkpoint`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub execution_id: String,
+    pub graph_id: String,
+    /// Nodes that finished, in execution order
+    pub completed: Vec<NodeId>,
+    /// Outputs of the finished nodes
+    pub outputs: NodeOutputs,
+    /// Caller-supplied inputs of the nodes that have not finished
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub inputs: HashMap<NodeId, HashMap<String, Value>>,
+}
+
+impl Checkpoint {
+    /// Record a node that finished
+    pub fn apply(&mut self, step: CheckpointStep) {
+        self.inputs.remove(&step.node_id);
+        self.completed.push(step.node_id.clone());
+        if !step.outputs.is_empty() {
+            self.outputs.insert(step.node_id, step.outputs);
+        }
+    }
+}
+
+/// A node that finished, appended to the checkpoint of its execution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckpointStep {
+    pub node_id: NodeId,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub outputs: HashMap<String, Value>,
+}
+
+/// Durable storage for execution checkpoints
+pub trait CheckpointStore: Send + Sync {
+    /// Store `checkpoint`, replacing the previous one of the same execution
+    fn save(&self, checkpoint: &Checkpoint) -> Result<()>;
+
+    /// Add a finished node to the saved checkpoint of an execution
+    ///
+    /// Called once per node, so stores should write only the step; the
+    /// default rewrites the whole checkpoint.
+    fn append(&self, execution_id: &str, step: &CheckpointStep) -> Result<()> {
+        let mut checkpoint = self.load(execution_id)?.ok_or_else(|| {
+            CircuitError::InvalidInput(format!("No checkpoint for execution '{}'", execution_id))
+        })?;
+        checkpoint.apply(step.clone());
+        self.save(&checkpoint)
+    }
+
+    /// Get the latest checkpoint of an execution
+    fn load(&self, execution_id: &str) -> Result<Option<Checkpoint>>;
+
+    /// Delete the checkpoint of an execution
+    fn remove(&self, execution_id: &str) -> Result<()>;
+
+    /// IDs of the executions with a checkpoint, e.g. to find interrupted runs
+    fn list(&self) -> Result<Vec<String>>;
+}
+
+/// Checkpoint store kept in memory, for tests and short-lived hosts
+#[derive(Debug, Default)]
+pub struct MemoryCheckpointStore {
+    checkpoints: Mutex<HashMap<String, Checkpoint>>,
+}
+
+impl MemoryCheckpointStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CheckpointStore for MemoryCheckpointStore {
+    fn save(&self, checkpoint: &Checkpoint) -> Result<()> {
+        self.checkpoints
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(checkpoint.execution_id.clone(), checkpoint.clone());
+        Ok(())
+    }
+
+    fn append(&self, execution_id: &str, step: &CheckpointStep) -> Result<()> {
+        self.checkpoints
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(execution_id)
+            .ok_or_else(|| {
+                CircuitError::InvalidInput(format!(
+                    "No checkpoint for execution '{}'",
+                    execution_id
+                ))
+            })?
+            .apply(step.clone());
+        Ok(())
+    }
+
+    fn load(&self, execution_id: &str) -> Result<Option<Checkpoint>> {
+        Ok(self
+            .checkpoints
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(execution_id)
+            .cloned())
+    }
+
+    fn remove(&self, execution_id: &str) -> Result<()> {
+        self.checkpoints
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(execution_id);
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        Ok(self
+            .checkpoints
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .keys()
+            .cloned()
+            .collect())
+    }
+}
+
+/// Checkpoint store writing one JSON file per execution into a directory
+///
+/// Saved checkpoints are written to a temporary name and renamed into place,
+/// so a crash while saving leaves the previous checkpoint intact. Steps are
+/// appended as JSON lines to a `<execution_id>.steps` file next to it; a
+/// line cut short by a crash is ignored.
+#[derive(Debug, Clone)]
+pub struct FileCheckpointStore {
+    dir: PathBuf,
+}
+
+impl FileCheckpointStore {
+    /// Store checkpoints in `dir`, creating it if needed
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|e| io_error(&dir, e))?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, execution_id: &str) -> Result<PathBuf> {
+        // Execution IDs become file names, so keep them to a safe alphabet
+        if execution_id.is_empty()
+            || !execution_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            || execution_id.starts_with('.')
+        {
+            return Err(CircuitError::InvalidInput(format!(
+                "Execution ID '{}' cannot be used as a checkpoint file name",
+                execution_id
+            )));
+        }
+        Ok(self.dir.join(format!("{}.json", execution_id)))
+    }
+}
+
+fn steps_path(path: &Path) -> PathBuf {
+    path.with_extension("steps")
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != IoErrorKind::NotFound => Err(io_error(path, e)),
+        _ => Ok(()),
+    }
+}
+
+impl CheckpointStore for FileCheckpointStore {
+    fn save(&self, checkpoint: &Checkpoint) -> Result<()> {
+        let path = self.path(&checkpoint.execution_id)?;
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, serde_json::to_vec(checkpoint)?).map_err(|e| io_error(&temp, e))?;
+        // Steps of the previous checkpoint must not apply to the new one; a
+        // crash in between leaves the previous checkpoint without its steps
+        remove_if_exists(&steps_path(&path))?;
+        fs::rename(&temp, &path).map_err(|e| io_error(&path, e))
+    }
+
+    fn append(&self, execution_id: &str, step: &CheckpointStep) -> Result<()> {
+        let path = steps_path(&self.path(execution_id)?);
+        let mut line = serde_json::to_vec(step)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(&line))
+            .map_err(|e| io_error(&path, e))
+    }
+
+    fn load(&self, execution_id: &str) -> Result<Option<Checkpoint>> {
+        let path = self.path(execution_id)?;
+        let mut checkpoint: Checkpoint = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == IoErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(io_error(&path, e)),
+        };
+
+        let steps = steps_path(&path);
+        let text = match fs::read_to_string(&steps) {
+            Ok(text) => text,
+            Err(e) if e.kind() == IoErrorKind::NotFound => String::new(),
+            Err(e) => return Err(io_error(&steps, e)),
+        };
+        // Only newline-terminated lines were written completely
+        let complete = text.rfind('\n').map_or("", |end| &text[..end]);
+        for line in complete.lines() {
+            checkpoint.apply(serde_json::from_str(line)?);
+        }
+        Ok(Some(checkpoint))
+    }
+
+    fn remove(&self, execution_id: &str) -> Result<()> {
+        let path = self.path(execution_id)?;
+        remove_if_exists(&steps_path(&path))?;
+        remove_if_exists(&path)
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        let entries = fs::read_dir(&self.dir).map_err(|e| io_error(&self.dir, e))?;
+        let mut ids = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| io_error(&self.dir, e))?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    ids.push(stem.to_string());
+                }
+            }
+        }
+        Ok(ids)
+    }
+}
+
+fn io_error(path: &Path, error: std::io::Error) -> CircuitError {
+    CircuitError::Other(anyhow::anyhow!(
+        "Checkpoint file '{}': {}",
+        path.display(),
+        error
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint(execution_id: &str) -> Checkpoint {
+        let mut outputs = NodeOutputs::new();
+        outputs.insert(
+            "a".to_string(),
+            HashMap::from([("value".to_string(), Value::Int(1))]),
+        );
+        Checkpoint {
+            execution_id: execution_id.to_string(),
+            graph_id: "g".to_string(),
+            completed: vec!["a".to_string()],
+            outputs,
+            inputs: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_file_store_round_trip() {
+        let dir = std::env::temp_dir().join(format!("circuit-checkpoints-{}", std::process::id()));
+        let store = FileCheckpointStore::new(&dir).unwrap();
+
+        store.save(&checkpoint("run-1")).unwrap();
+        assert_eq!(store.load("run-1").unwrap(), Some(checkpoint("run-1")));
+        assert_eq!(store.list().unwrap(), vec!["run-1".to_string()]);
+        assert_eq!(store.load("run-2").unwrap(), None);
+        assert!(store.save(&checkpoint("../escape")).is_err());
+
+        store.remove("run-1").unwrap();
+        store.remove("run-1").unwrap();
+        assert!(store.list().unwrap().is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn step(node_id: &str) -> CheckpointStep {
+        CheckpointStep {
+            node_id: node_id.to_string(),
+            outputs: HashMap::from([("value".to_string(), Value::Int(2))]),
+        }
+    }
+
+    #[test]
+    fn test_file_store_appends_steps() {
+        let dir = std::env::temp_dir().join(format!("circuit-steps-{}", std::process::id()));
+        let store = FileCheckpointStore::new(&dir).unwrap();
+        store.save(&checkpoint("run-1")).unwrap();
+        store.append("run-1", &step("b")).unwrap();
+
+        // A step cut short by a crash is ignored
+        let steps = dir.join("run-1.steps");
+        let mut file = OpenOptions::new().append(true).open(&steps).unwrap();
+        file.write_all(br#"{"node_id": "c", "out"#).unwrap();
+
+        let mut expected = checkpoint("run-1");
+        expected.apply(step("b"));
+        assert_eq!(store.load("run-1").unwrap(), Some(expected));
+        assert_eq!(store.list().unwrap(), vec!["run-1".to_string()]);
+
+        // Saving starts over
+        store.save(&checkpoint("run-1")).unwrap();
+        assert_eq!(store.load("run-1").unwrap(), Some(checkpoint("run-1")));
+        store.remove("run-1").unwrap();
+        assert!(fs::read_dir(&dir).unwrap().next().is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_memory_store() {
+        let store = MemoryCheckpointStore::new();
+        assert!(store.append("run-1", &step("b")).is_err());
+        store.save(&checkpoint("run-1")).unwrap();
+        store.append("run-1", &step("b")).unwrap();
+        let loaded = store.load("run-1").unwrap().unwrap();
+        assert_eq!(loaded.completed, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(loaded.outputs["b"]["value"], Value::Int(2));
+        assert_eq!(store.list().unwrap(), vec!["run-1".to_string()]);
+        store.remove("run-1").unwrap();
+        assert_eq!(store.load("run-1").unwrap(), None);
+    }
+}
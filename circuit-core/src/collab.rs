@@ -0,0 +1,368 @@
+use crate::{
+    editor::GraphEdit,
+    error::{CircuitError, Result},
+    graph::Graph,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Mutex, PoisonError};
+
+/// Highest op counter seen from each replica
+pub type VersionVector = BTreeMap<String, u64>;
+
+/// Unique, totally ordered ID of an op
+///
+/// Ops are ordered by Lamport counter first and replica ID second, so every
+/// replica replays a given set of ops in the same order.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct OpId {
+    pub counter: u64,
+    pub replica: String,
+}
+
+/// An edit made on one replica, as exchanged with the others
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphOp {
+    pub id: OpId,
+    pub edit: GraphEdit,
+}
+
+/// One copy of a graph edited concurrently with other replicas
+///
+/// The graph is the base graph with every known op applied in `OpId` order.
+/// An op that no longer applies at its place in that order, e.g. a connection
+/// that would close a cycle or a config edit on a node removed concurrently,
+/// is skipped on every replica alike. Replicas that know the same ops
+/// therefore have the same graph, whatever order the ops arrived in, and the
+/// graph keeps every invariant of `Graph`. Concurrent edits of the same
+/// config field or position resolve to the edit with the highest `OpId`.
+///
+/// All replicas of a graph must start from the same base graph.
+#[derive(Debug, Clone)]
+pub struct Replica {
+    id: String,
+    base: Graph,
+    graph: Graph,
+    clock: u64,
+    ops: BTreeMap<OpId, GraphEdit>,
+    rejected: BTreeSet<OpId>,
+}
+
+impl Replica {
+    /// Create a replica of `base` with no ops
+    pub fn new(id: impl Into<String>, base: Graph) -> Self {
+        Self {
+            id: id.into(),
+            graph: base.clone(),
+            base,
+            clock: 0,
+            ops: BTreeMap::new(),
+            rejected: BTreeSet::new(),
+        }
+    }
+
+    /// ID of this replica, stamped on the ops it creates
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The merged graph
+    pub fn graph(&self) -> &Graph {
+        &self.graph
+    }
+
+    /// Make an edit locally, returning the op to send to the other replicas
+    ///
+    /// An edit that does not apply to the current graph is an error and
+    /// creates no op.
+    pub fn edit(&mut self, edit: GraphEdit) -> Result<GraphOp> {
+        // The new op has the highest ID known, so it goes on top of the graph
+        edit.apply(&mut self.graph)?;
+        self.clock += 1;
+        let id = OpId {
+            counter: self.clock,
+            replica: self.id.clone(),
+        };
+        self.ops.insert(id.clone(), edit.clone());
+        Ok(GraphOp { id, edit })
+    }
+
+    /// Merge ops from other replicas, returning how many were new
+    ///
+    /// Merging is idempotent: ops already known are ignored.
+    pub fn merge(&mut self, ops: impl IntoIterator<Item = GraphOp>) -> usize {
+        let last = self.ops.keys().next_back().cloned();
+        let mut added = Vec::new();
+        for op in ops {
+            if self.ops.contains_key(&op.id) {
+                continue;
+            }
+            self.clock = self.clock.max(op.id.counter);
+            self.ops.insert(op.id.clone(), op.edit);
+            added.push(op.id);
+        }
+        if added.is_empty() {
+            return 0;
+        }
+        added.sort();
+        if last.is_some_and(|last| added[0] < last) {
+            self.materialize();
+        } else {
+            for id in &added {
+                self.apply_op(id.clone());
+            }
+        }
+        added.len()
+    }
+
+    /// Make an edit and broadcast its op
+    pub fn edit_and_send(&mut self, edit: GraphEdit, transport: &dyn OpTransport) -> Result<()> {
+        let op = self.edit(edit)?;
+        transport.broadcast(&self.id, &[op])
+    }
+
+    /// Merge the ops waiting on the transport, returning how many were new
+    pub fn sync(&mut self, transport: &dyn OpTransport) -> Result<usize> {
+        let ops = transport.receive(&self.id)?;
+        Ok(self.merge(ops))
+    }
+
+    /// All known ops, in merge order
+    pub fn ops(&self) -> Vec<GraphOp> {
+        self.ops
+            .iter()
+            .map(|(id, edit)| GraphOp {
+                id: id.clone(),
+                edit: edit.clone(),
+            })
+            .collect()
+    }
+
+    /// Highest op counter known from each replica
+    pub fn version(&self) -> VersionVector {
+        let mut version = VersionVector::new();
+        for id in self.ops.keys() {
+            let counter = version.entry(id.replica.clone()).or_insert(0);
+            *counter = (*counter).max(id.counter);
+        }
+        version
+    }
+
+    /// Ops a replica at `version` has not seen, e.g. to catch up a late joiner
+    pub fn ops_since(&self, version: &VersionVector) -> Vec<GraphOp> {
+        self.ops()
+            .into_iter()
+            .filter(|op| op.id.counter > version.get(&op.id.replica).copied().unwrap_or(0))
+            .collect()
+    }
+
+    /// Ops skipped because they did not apply at their place in the merge order
+    pub fn rejected(&self) -> Vec<OpId> {
+        self.rejected.iter().cloned().collect()
+    }
+
+    /// Rebuild the graph from the base graph and every known op
+    fn materialize(&mut self) {
+        self.graph = self.base.clone();
+        self.rejected.clear();
+        let ids: Vec<OpId> = self.ops.keys().cloned().collect();
+        for id in ids {
+            self.apply_op(id);
+        }
+    }
+
+    fn apply_op(&mut self, id: OpId) {
+        // A failed edit leaves the graph unchanged
+        if self.ops[&id].apply(&mut self.graph).is_err() {
+            self.rejected.insert(id);
+        }
+    }
+}
+
+/// Delivers ops between replicas
+pub trait OpTransport: Send + Sync {
+    /// Send ops made or merged by replica `from` to every other replica
+    fn broadcast(&self, from: &str, ops: &[GraphOp]) -> Result<()>;
+
+    /// Take the ops waiting for `replica`
+    fn receive(&self, replica: &str) -> Result<Vec<GraphOp>>;
+}
+
+/// Transport between replicas in the same process, for tests and local hosts
+#[derive(Debug, Default)]
+pub struct MemoryTransport {
+    inboxes: Mutex<HashMap<String, Vec<GraphOp>>>,
+}
+
+impl MemoryTransport {
+    /// Create a transport with no replicas
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a replica so it receives broadcasts
+    pub fn join(&self, replica: impl Into<String>) {
+        self.inboxes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(replica.into())
+            .or_default();
+    }
+
+    /// Number of ops waiting for `replica`
+    pub fn pending(&self, replica: &str) -> usize {
+        self.inboxes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(replica)
+            .map_or(0, Vec::len)
+    }
+}
+
+impl OpTransport for MemoryTransport {
+    fn broadcast(&self, from: &str, ops: &[GraphOp]) -> Result<()> {
+        let mut inboxes = self.inboxes.lock().unwrap_or_else(PoisonError::into_inner);
+        if !inboxes.contains_key(from) {
+            return Err(CircuitError::InvalidInput(format!(
+                "Replica '{}' has not joined the transport",
+                from
+            )));
+        }
+        for (replica, inbox) in inboxes.iter_mut() {
+            if replica != from {
+                inbox.extend_from_slice(ops);
+            }
+        }
+        Ok(())
+    }
+
+    fn receive(&self, replica: &str) -> Result<Vec<GraphOp>> {
+        let mut inboxes = self.inboxes.lock().unwrap_or_else(PoisonError::into_inner);
+        inboxes.get_mut(replica).map(std::mem::take).ok_or_else(|| {
+            CircuitError::InvalidInput(format!(
+                "Replica '{}' has not joined the transport",
+                replica
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::fixtures::{self, connection, node};
+    use crate::value::Value;
+
+    fn connect(from: &str, to: &str) -> GraphEdit {
+        GraphEdit::AddConnection {
+            connection: connection(from, to),
+            index: None,
+        }
+    }
+
+    fn set(node_id: &str, value: i64) -> GraphEdit {
+        GraphEdit::UpdateConfig {
+            node_id: node_id.to_string(),
+            key: "value".to_string(),
+            value: Some(Value::Int(value)),
+        }
+    }
+
+    fn base() -> Graph {
+        fixtures::graph(&["a", "b", "c"], &[])
+    }
+
+    #[test]
+    fn test_concurrent_edits_converge() {
+        let mut left = Replica::new("left", base());
+        let mut right = Replica::new("right", base());
+
+        let left_ops = [
+            left.edit(GraphEdit::AddNode { node: node("d", 0) })
+                .unwrap(),
+            left.edit(connect("a", "d")).unwrap(),
+            left.edit(set("a", 1)).unwrap(),
+        ];
+        let right_ops = [
+            right.edit(set("a", 2)).unwrap(),
+            right
+                .edit(GraphEdit::RemoveNode {
+                    node_id: "b".to_string(),
+                })
+                .unwrap(),
+            right.edit(set("c", 3)).unwrap(),
+        ];
+        // Config edits of a node removed concurrently are dropped
+        let late = left.edit(set("b", 4)).unwrap();
+
+        // Deliver in opposite orders
+        right.merge(left_ops.iter().cloned().chain([late.clone()]));
+        left.merge(right_ops.iter().rev().cloned());
+
+        assert_eq!(left.graph(), right.graph());
+        let graph = left.graph();
+        assert!(graph.nodes.contains_key("d"));
+        assert!(!graph.nodes.contains_key("b"));
+        // Left's edit of `a` has the higher counter
+        assert_eq!(graph.nodes["a"].config["value"], Value::Int(1));
+        assert_eq!(graph.nodes["c"].config["value"], Value::Int(3));
+        assert_eq!(left.rejected(), vec![late.id.clone()]);
+        assert_eq!(right.rejected(), vec![late.id]);
+
+        // Merging again changes nothing
+        assert_eq!(left.merge(right_ops), 0);
+        assert_eq!(left.graph(), right.graph());
+    }
+
+    #[test]
+    fn test_concurrent_connections_never_form_a_cycle() {
+        let mut left = Replica::new("left", base());
+        let mut right = Replica::new("right", base());
+
+        let left_ops = [
+            left.edit(connect("a", "b")).unwrap(),
+            left.edit(connect("b", "c")).unwrap(),
+        ];
+        let right_op = right.edit(connect("c", "a")).unwrap();
+
+        left.merge([right_op.clone()]);
+        right.merge(left_ops);
+
+        assert_eq!(left.graph(), right.graph());
+        assert!(left.graph().topological_sort().is_ok());
+        assert_eq!(left.graph().connections.len(), 2);
+        assert_eq!(left.rejected(), right.rejected());
+        assert_eq!(left.rejected().len(), 1);
+    }
+
+    #[test]
+    fn test_memory_transport_and_catch_up() {
+        let transport = MemoryTransport::new();
+        let mut left = Replica::new("left", base());
+        let mut right = Replica::new("right", base());
+        transport.join("left");
+        transport.join("right");
+
+        left.edit_and_send(connect("a", "b"), &transport).unwrap();
+        right.edit_and_send(set("c", 5), &transport).unwrap();
+        assert_eq!(transport.pending("right"), 1);
+        assert!(right.edit_and_send(connect("b", "x"), &transport).is_err());
+
+        assert_eq!(left.sync(&transport).unwrap(), 1);
+        assert_eq!(right.sync(&transport).unwrap(), 1);
+        assert_eq!(left.graph(), right.graph());
+        assert!(transport.receive("nobody").is_err());
+
+        // A late joiner catches up from another replica's log, sent as JSON
+        let mut late = Replica::new("late", base());
+        let json = serde_json::to_string(&left.ops_since(&late.version())).unwrap();
+        late.merge(serde_json::from_str::<Vec<GraphOp>>(&json).unwrap());
+        assert_eq!(late.graph(), left.graph());
+        assert_eq!(late.version(), left.version());
+        assert!(left.ops_since(&late.version()).is_empty());
+
+        // New edits by the late joiner order after everything it has seen
+        let op = late.edit(set("a", 9)).unwrap();
+        assert_eq!(op.id.counter, 2);
+    }
+}
@@ -0,0 +1,586 @@
+use crate::value::Value;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+
+/// Future returned by `Clock::timer`
+///
+/// `Send` on native targets only, like `BlockFuture`.
+#[cfg(not(target_arch = "wasm32"))]
+pub type TimerFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Future returned by `Clock::timer`
+#[cfg(target_arch = "wasm32")]
+pub type TimerFuture = Pin<Box<dyn Future<Output = ()>>>;
+
+/// Source of wall-clock time for blocks
+pub trait Clock: Send + Sync {
+    /// Time elapsed since the Unix epoch
+    fn now(&self) -> Duration;
+
+    /// Block the current thread for `duration`, e.g. between retries
+    ///
+    /// wasm32 cannot block its thread, so there the default returns at once.
+    fn sleep(&self, duration: Duration) {
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::sleep(duration);
+        #[cfg(target_arch = "wasm32")]
+        let _ = duration;
+    }
+
+    /// Future that completes once `duration` has elapsed, e.g. to time out
+    /// async executions
+    ///
+    /// The default waits on a helper thread. wasm32 has no threads, so there
+    /// the default never completes and clocks should override it.
+    fn timer(&self, duration: Duration) -> TimerFuture {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            Box::pin(ThreadTimer::new(duration))
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = duration;
+            Box::pin(std::future::pending())
+        }
+    }
+}
+
+/// Timer completed by a helper thread, which stops early when it is dropped
+#[cfg(not(target_arch = "wasm32"))]
+struct ThreadTimer {
+    shared: Arc<Mutex<TimerState>>,
+    thread: std::thread::Thread,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+struct TimerState {
+    elapsed: bool,
+    dropped: bool,
+    waker: Option<std::task::Waker>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ThreadTimer {
+    fn new(duration: Duration) -> Self {
+        let shared = Arc::new(Mutex::new(TimerState::default()));
+        let state = Arc::clone(&shared);
+        let thread = std::thread::spawn(move || {
+            let deadline = std::time::Instant::now() + duration;
+            loop {
+                let now = std::time::Instant::now();
+                if state.lock().unwrap_or_else(PoisonError::into_inner).dropped {
+                    return;
+                }
+                if now >= deadline {
+                    break;
+                }
+                std::thread::park_timeout(deadline - now);
+            }
+            let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+            state.elapsed = true;
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        })
+        .thread()
+        .clone();
+        Self { shared, thread }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Future for ThreadTimer {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<()> {
+        let mut state = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.elapsed {
+            return std::task::Poll::Ready(());
+        }
+        state.waker = Some(cx.waker().clone());
+        std::task::Poll::Pending
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for ThreadTimer {
+    fn drop(&mut self) {
+        self.shared
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .dropped = true;
+        self.thread.unpark();
+    }
+}
+
+/// Source of random numbers for blocks
+pub trait RandomSource: Send + Sync {
+    /// Next random 64-bit integer
+    fn next_u64(&self) -> u64;
+
+    /// Next random float in `[0, 1)`
+    fn next_f64(&self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Severity of a log message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Sink for log messages emitted by blocks
+pub trait Logger: Send + Sync {
+    /// Record a message
+    fn log(&self, level: LogLevel, message: &str);
+}
+
+/// Key-value storage shared by the blocks of an execution
+pub trait KeyValueStore: Send + Sync {
+    /// Get the value stored under `key`
+    fn get(&self, key: &str) -> Option<Value>;
+
+    /// Store `value` under `key`, returning the previous value
+    fn set(&self, key: &str, value: Value) -> Option<Value>;
+
+    /// Remove the value stored under `key`
+    fn remove(&self, key: &str) -> Option<Value>;
+}
+
+/// Clock reading the system time
+///
+/// wasm32 has no system time without JavaScript, so there it always reads
+/// zero; `circuit-wasm` installs a clock reading `Date.now()` instead.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn now(&self) -> Duration {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn now(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// Clock that only moves when told to, for deterministic tests
+#[derive(Debug, Default)]
+pub struct FakeClock {
+    now: Mutex<Duration>,
+}
+
+impl FakeClock {
+    /// Create a clock reading `now`
+    pub fn new(now: Duration) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    /// Set the current time
+    pub fn set(&self, now: Duration) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) = now;
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) += by;
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Advance the clock instead of waiting
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+/// Deterministic pseudo-random generator (SplitMix64)
+///
+/// The same seed always yields the same sequence, which makes it the fake to
+/// use in tests; `from_entropy` seeds it randomly for production use.
+#[derive(Debug, Default)]
+pub struct SeededRandom {
+    state: AtomicU64,
+}
+
+impl SeededRandom {
+    /// Create a generator from a fixed seed
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: AtomicU64::new(seed),
+        }
+    }
+
+    /// Create a generator with a random seed
+    pub fn from_entropy() -> Self {
+        Self::new(RandomState::new().build_hasher().finish())
+    }
+}
+
+impl RandomSource for SeededRandom {
+    fn next_u64(&self) -> u64 {
+        let mut z = self
+            .state
+            .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed)
+            .wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// Logger discarding every message; the default, so the library stays quiet
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullLogger;
+
+impl Logger for NullLogger {
+    fn log(&self, _level: LogLevel, _message: &str) {}
+}
+
+/// Logger writing `LEVEL: message` lines to standard output
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutLogger;
+
+impl Logger for StdoutLogger {
+    fn log(&self, level: LogLevel, message: &str) {
+        println!("{}: {}", level, message);
+    }
+}
+
+/// Logger keeping every message in memory, for tests
+#[derive(Debug, Default)]
+pub struct MemoryLogger {
+    entries: Mutex<Vec<(LogLevel, String)>>,
+}
+
+impl MemoryLogger {
+    /// Create an empty logger
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Messages logged so far
+    pub fn entries(&self) -> Vec<(LogLevel, String)> {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl Logger for MemoryLogger {
+    fn log(&self, level: LogLevel, message: &str) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((level, message.to_string()));
+    }
+}
+
+/// In-memory key-value store
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    values: Mutex<HashMap<String, Value>>,
+}
+
+impl MemoryStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KeyValueStore for MemoryStore {
+    fn get(&self, key: &str) -> Option<Value> {
+        self.values
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(key)
+            .cloned()
+    }
+
+    fn set(&self, key: &str, value: Value) -> Option<Value> {
+        self.values
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key.to_string(), value)
+    }
+
+    fn remove(&self, key: &str) -> Option<Value> {
+        self.values
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(key)
+    }
+}
+
+/// Stateless default services, created on first use and shared by every
+/// context
+struct DefaultServices {
+    variables: Arc<HashMap<String, Value>>,
+    clock: Arc<dyn Clock>,
+    random: Arc<dyn RandomSource>,
+    logger: Arc<dyn Logger>,
+}
+
+fn default_services() -> &'static DefaultServices {
+    static DEFAULTS: OnceLock<DefaultServices> = OnceLock::new();
+    DEFAULTS.get_or_init(|| DefaultServices {
+        variables: Arc::new(HashMap::new()),
+        clock: Arc::new(SystemClock),
+        random: Arc::new(SeededRandom::from_entropy()),
+        logger: Arc::new(NullLogger),
+    })
+}
+
+// Entropy-seeded generator shared by everything that needs one by default
+pub(crate) fn default_random() -> &'static dyn RandomSource {
+    default_services().random.as_ref()
+}
+
+/// Per-execution variables and host services made available to blocks
+///
+/// Cloning is cheap: variables and services are shared, so the same context
+/// is handed to every node of an execution. Each new context gets its own
+/// `MemoryStore`; the stateless default services are shared.
+#[derive(Clone)]
+pub struct ExecutionContext {
+    variables: Arc<HashMap<String, Value>>,
+    /// Engine variables, used when the execution does not set a variable
+    engine_variables: Arc<HashMap<String, Value>>,
+    clock: Arc<dyn Clock>,
+    random: Arc<dyn RandomSource>,
+    logger: Arc<dyn Logger>,
+    store: Arc<dyn KeyValueStore>,
+}
+
+impl ExecutionContext {
+    /// Create a context with no variables and the default services
+    pub fn new() -> Self {
+        let defaults = default_services();
+        Self {
+            variables: Arc::clone(&defaults.variables),
+            engine_variables: Arc::clone(&defaults.variables),
+            clock: Arc::clone(&defaults.clock),
+            random: Arc::clone(&defaults.random),
+            logger: Arc::clone(&defaults.logger),
+            store: Arc::new(MemoryStore::new()),
+        }
+    }
+
+    /// Set a variable, such as a locale or request ID
+    pub fn with_variable(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        Arc::make_mut(&mut self.variables).insert(name.into(), value.into());
+        self
+    }
+
+    /// Use a different clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Use a different random source
+    pub fn with_random(mut self, random: Arc<dyn RandomSource>) -> Self {
+        self.random = random;
+        self
+    }
+
+    /// Use a different logger
+    pub fn with_logger(mut self, logger: Arc<dyn Logger>) -> Self {
+        self.logger = logger;
+        self
+    }
+
+    /// Use a different key-value store
+    pub fn with_store(mut self, store: Arc<dyn KeyValueStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Get a variable by name, falling back to the engine's variables
+    pub fn variable(&self, name: &str) -> Option<&Value> {
+        self.variables
+            .get(name)
+            .or_else(|| self.engine_variables.get(name))
+    }
+
+    /// Variables set for this execution, without the engine's variables
+    pub fn variables(&self) -> &HashMap<String, Value> {
+        &self.variables
+    }
+
+    pub(crate) fn with_engine_variables(mut self, variables: Arc<HashMap<String, Value>>) -> Self {
+        self.engine_variables = variables;
+        self
+    }
+
+    /// Clock service
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Random number service
+    pub fn random(&self) -> &dyn RandomSource {
+        self.random.as_ref()
+    }
+
+    /// Logging service
+    pub fn logger(&self) -> &dyn Logger {
+        self.logger.as_ref()
+    }
+
+    /// Key-value store service
+    pub fn store(&self) -> &dyn KeyValueStore {
+        self.store.as_ref()
+    }
+
+    pub(crate) fn shared_logger(&self) -> Arc<dyn Logger> {
+        Arc::clone(&self.logger)
+    }
+
+    pub(crate) fn shared_clock(&self) -> Arc<dyn Clock> {
+        Arc::clone(&self.clock)
+    }
+
+    pub(crate) fn shared_random(&self) -> Arc<dyn RandomSource> {
+        Arc::clone(&self.random)
+    }
+
+    pub(crate) fn shared_store(&self) -> Arc<dyn KeyValueStore> {
+        Arc::clone(&self.store)
+    }
+}
+
+impl Default for ExecutionContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ExecutionContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExecutionContext")
+            .field("variables", &self.variables)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variables() {
+        let context = ExecutionContext::new()
+            .with_variable("locale", "de-DE")
+            .with_variable("user_id", 42i64);
+
+        assert_eq!(
+            context.variable("locale"),
+            Some(&Value::String("de-DE".to_string()))
+        );
+        assert_eq!(context.variable("user_id"), Some(&Value::Int(42)));
+        assert_eq!(context.variable("missing"), None);
+
+        // Clones share the variables
+        let clone = context.clone();
+        assert!(Arc::ptr_eq(&clone.variables, &context.variables));
+    }
+
+    #[test]
+    fn test_default_services_are_shared() {
+        let a = ExecutionContext::new();
+        let b = ExecutionContext::default();
+        assert!(Arc::ptr_eq(&a.shared_random(), &b.shared_random()));
+        assert!(Arc::ptr_eq(&a.shared_logger(), &b.shared_logger()));
+
+        // Stores hold per-execution data, so each context gets its own
+        a.store().set("k", Value::Int(1));
+        assert_eq!(b.store().get("k"), None);
+        assert_eq!(a.clone().store().get("k"), Some(Value::Int(1)));
+        assert!(a.clock().now() > Duration::ZERO);
+
+        // Setting a variable does not leak into other contexts
+        let a = a.with_variable("x", 1i64);
+        assert_eq!(b.variable("x"), None);
+        assert_eq!(a.variable("x"), Some(&Value::Int(1)));
+    }
+
+    #[test]
+    fn test_fake_clock() {
+        let clock = FakeClock::new(Duration::from_secs(100));
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(clock.now(), Duration::from_secs(105));
+        clock.sleep(Duration::from_secs(1));
+        assert_eq!(clock.now(), Duration::from_secs(106));
+        clock.set(Duration::ZERO);
+        assert_eq!(clock.now(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_seeded_random_is_deterministic() {
+        let a = SeededRandom::new(7);
+        let b = SeededRandom::new(7);
+        let first: Vec<u64> = (0..4).map(|_| a.next_u64()).collect();
+        let second: Vec<u64> = (0..4).map(|_| b.next_u64()).collect();
+        assert_eq!(first, second);
+        assert_ne!(first[0], first[1]);
+
+        let f = a.next_f64();
+        assert!((0.0..1.0).contains(&f));
+    }
+
+    #[test]
+    fn test_memory_logger_and_store() {
+        let logger = MemoryLogger::new();
+        logger.log(LogLevel::Warn, "careful");
+        assert_eq!(
+            logger.entries(),
+            vec![(LogLevel::Warn, "careful".to_string())]
+        );
+
+        let store = MemoryStore::new();
+        assert_eq!(store.set("k", Value::Int(1)), None);
+        assert_eq!(store.set("k", Value::Int(2)), Some(Value::Int(1)));
+        assert_eq!(store.get("k"), Some(Value::Int(2)));
+        assert_eq!(store.remove("k"), Some(Value::Int(2)));
+        assert_eq!(store.get("k"), None);
+    }
+}
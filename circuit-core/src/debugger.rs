@@ -0,0 +1,511 @@
+use crate::{
+    engine::{Engine, NodeOutputs, RunEnv, RunState},
+    error::{CircuitError, Result},
+    execution::ExecutionOptions,
+    graph::{Graph, NodeId},
+    plan::ExecutionPlan,
+    value::Value,
+};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Comparison used by a breakpoint condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Condition on one resolved input of the node a breakpoint is set on
+///
+/// Numbers compare by value whether they are ints or floats, strings
+/// compare lexicographically; a missing input never matches.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BreakCondition {
+    pub port: String,
+    pub op: CompareOp,
+    pub value: Value,
+}
+
+impl BreakCondition {
+    /// Whether the condition holds for the given inputs
+    pub fn matches(&self, inputs: &HashMap<String, Value>) -> bool {
+        let Some(input) = inputs.get(&self.port) else {
+            return false;
+        };
+        let ordering = match (input, &self.value) {
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (a, b) => match (a.as_float(), b.as_float()) {
+                (Some(a), Some(b)) => a.partial_cmp(&b),
+                _ => (a == b).then_some(Ordering::Equal),
+            },
+        };
+        match self.op {
+            CompareOp::Eq => ordering == Some(Ordering::Equal),
+            CompareOp::Ne => ordering != Some(Ordering::Equal),
+            CompareOp::Lt => ordering == Some(Ordering::Less),
+            CompareOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            CompareOp::Gt => ordering == Some(Ordering::Greater),
+            CompareOp::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+/// Pause a debug session before a node runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Breakpoint {
+    pub node_id: NodeId,
+    /// All must hold for the breakpoint to pause; none means always
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<BreakCondition>,
+}
+
+impl Breakpoint {
+    /// Break every time `node_id` is about to run
+    pub fn new(node_id: impl Into<NodeId>) -> Self {
+        Self {
+            node_id: node_id.into(),
+            conditions: Vec::new(),
+        }
+    }
+
+    /// Only break when input `port` compares to `value` as given
+    pub fn with_condition(mut self, port: impl Into<String>, op: CompareOp, value: Value) -> Self {
+        self.conditions.push(BreakCondition {
+            port: port.into(),
+            op,
+            value,
+        });
+        self
+    }
+
+    /// Whether the breakpoint pauses before its node given the node's inputs
+    pub fn matches(&self, inputs: &HashMap<String, Value>) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.matches(inputs))
+    }
+}
+
+/// Where a debug session stands
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum DebugStatus {
+    /// No node has run yet
+    Ready,
+    /// Stopped before `node_id` runs
+    Paused {
+        node_id: NodeId,
+        /// Resolved inputs the node will run with
+        inputs: HashMap<String, Value>,
+        /// Whether a breakpoint stopped the run, rather than a step
+        breakpoint: bool,
+    },
+    /// `node_id` failed; continuing runs it again
+    Failed { node_id: NodeId, error: String },
+    /// Every node ran
+    Finished,
+}
+
+/// Step-through execution of a loaded graph, created by `Engine::debug`
+///
+/// The session runs the same node loop as `Engine::execute`, stopping
+/// before nodes with a matching breakpoint or, when stepping, before the
+/// next node. While stopped, inputs of the paused node and outputs of the
+/// nodes that ran can be inspected and changed.
+pub struct DebugSession {
+    graph: Arc<Graph>,
+    plan: ExecutionPlan,
+    env: RunEnv,
+    options: ExecutionOptions,
+    state: RunState,
+    breakpoints: Vec<Breakpoint>,
+    status: DebugStatus,
+}
+
+impl DebugSession {
+    pub(crate) fn new(
+        graph: Arc<Graph>,
+        plan: ExecutionPlan,
+        env: RunEnv,
+        options: ExecutionOptions,
+        state: RunState,
+    ) -> Self {
+        Self {
+            graph,
+            plan,
+            env,
+            options,
+            state,
+            breakpoints: Vec::new(),
+            status: DebugStatus::Ready,
+        }
+    }
+
+    /// ID of the graph being debugged
+    pub fn graph_id(&self) -> &str {
+        &self.graph.id
+    }
+
+    /// Set a breakpoint, replacing any other breakpoint on the same node
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> Result<()> {
+        if !self.graph.nodes.contains_key(&breakpoint.node_id) {
+            return Err(CircuitError::NodeNotFound(breakpoint.node_id));
+        }
+        self.breakpoints
+            .retain(|existing| existing.node_id != breakpoint.node_id);
+        self.breakpoints.push(breakpoint);
+        Ok(())
+    }
+
+    /// Remove the breakpoint on a node, returning whether there was one
+    pub fn remove_breakpoint(&mut self, node_id: &str) -> bool {
+        let before = self.breakpoints.len();
+        self.breakpoints
+            .retain(|existing| existing.node_id != node_id);
+        self.breakpoints.len() != before
+    }
+
+    /// Breakpoints in the order they were set
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Run until a breakpoint matches, a node fails or the graph finishes
+    pub fn resume(&mut self) -> &DebugStatus {
+        self.advance(false)
+    }
+
+    /// Run the paused node and stop before the next one
+    ///
+    /// From `Ready` this stops before the first node without running it.
+    pub fn step(&mut self) -> &DebugStatus {
+        self.advance(true)
+    }
+
+    /// Where the session stands
+    pub fn status(&self) -> &DebugStatus {
+        &self.status
+    }
+
+    /// Outputs of the nodes that ran so far
+    pub fn outputs(&self) -> &NodeOutputs {
+        &self.state.outputs
+    }
+
+    /// Change an input of the paused or failed node before it runs
+    ///
+    /// The value takes precedence over the connection feeding that port.
+    pub fn set_input(&mut self, port: impl Into<String>, value: Value) -> Result<()> {
+        let node_id = self.pending_node().ok_or_else(|| {
+            CircuitError::InvalidInput("No node is paused in this debug session".to_string())
+        })?;
+        self.state
+            .graph_inputs
+            .entry(node_id)
+            .or_default()
+            .insert(port.into(), value);
+        self.refresh_inputs();
+        Ok(())
+    }
+
+    /// Change an output of a node that already ran
+    ///
+    /// Nodes that have not run yet read the new value.
+    pub fn set_output(
+        &mut self,
+        node_id: &str,
+        port: impl Into<String>,
+        value: Value,
+    ) -> Result<()> {
+        if !self.state.completed.iter().any(|done| done == node_id) {
+            return Err(CircuitError::InvalidInput(format!(
+                "Node '{}' has not run in this debug session",
+                node_id
+            )));
+        }
+        self.state
+            .outputs
+            .entry(node_id.to_string())
+            .or_default()
+            .insert(port.into(), value);
+        self.refresh_inputs();
+        Ok(())
+    }
+
+    // Run the node loop until the pause hook stops it
+    fn advance(&mut self, step: bool) -> &DebugStatus {
+        if self.status == DebugStatus::Finished {
+            return &self.status;
+        }
+
+        // The node the session stands at runs instead of pausing again
+        let mut current = self.pending_node();
+        let breakpoints = self.breakpoints.clone();
+        self.state.pause_before = Some(Box::new(move |node_id, inputs| {
+            if current.as_deref() == Some(node_id) {
+                current = None;
+                return false;
+            }
+            step || breakpoints
+                .iter()
+                .any(|bp| bp.node_id == node_id && bp.matches(inputs))
+        }));
+        let result = Engine::run_plan(
+            &self.graph,
+            &self.plan,
+            &self.env,
+            &self.options,
+            &mut self.state,
+            None,
+        );
+        self.state.pause_before = None;
+
+        self.status = match (result, self.state.paused_at.take()) {
+            (Ok(()), Some(node_id)) => {
+                let inputs = self.resolve_inputs(&node_id);
+                let breakpoint = self
+                    .breakpoints
+                    .iter()
+                    .any(|bp| bp.node_id == node_id && bp.matches(&inputs));
+                DebugStatus::Paused {
+                    node_id,
+                    inputs,
+                    breakpoint,
+                }
+            }
+            (Ok(()), None) => DebugStatus::Finished,
+            (Err(e), _) => DebugStatus::Failed {
+                node_id: self.next_node().unwrap_or_default(),
+                error: e.to_string(),
+            },
+        };
+        &self.status
+    }
+
+    // Node the session is stopped at, if any
+    fn pending_node(&self) -> Option<NodeId> {
+        match &self.status {
+            DebugStatus::Paused { node_id, .. } | DebugStatus::Failed { node_id, .. } => {
+                Some(node_id.clone())
+            }
+            DebugStatus::Ready | DebugStatus::Finished => None,
+        }
+    }
+
+    // First node in plan order that has not finished
+    fn next_node(&self) -> Option<NodeId> {
+        self.plan
+            .steps
+            .iter()
+            .map(|step| &step.node_id)
+            .find(|node_id| !self.state.completed.contains(node_id))
+            .cloned()
+    }
+
+    fn resolve_inputs(&self, node_id: &str) -> HashMap<String, Value> {
+        self.plan
+            .steps
+            .iter()
+            .find(|step| step.node_id == node_id)
+            .map(|step| step.resolve_inputs(&self.state.outputs, &self.state.graph_inputs))
+            .unwrap_or_default()
+    }
+
+    // Keep the reported inputs of the paused node current after an edit
+    fn refresh_inputs(&mut self) {
+        if let DebugStatus::Paused { node_id, .. } = &self.status {
+            let resolved = self.resolve_inputs(node_id);
+            if let DebugStatus::Paused { inputs, .. } = &mut self.status {
+                *inputs = resolved;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::core::ConstantBlock;
+    use crate::blocks::math::{AddBlock, NegateBlock};
+    use crate::graph::{Connection, Node};
+
+    // a + b -> neg, with a = 1 and b = 2
+    fn build_engine() -> Engine {
+        let engine = Engine::new();
+        engine.register_block(Arc::new(ConstantBlock)).unwrap();
+        engine.register_block(Arc::new(AddBlock)).unwrap();
+        engine.register_block(Arc::new(NegateBlock)).unwrap();
+
+        let mut graph = Graph::new("calc".to_string(), "Calc".to_string());
+        let nodes = [
+            ("a", "core.constant", Some(1.0)),
+            ("b", "core.constant", Some(2.0)),
+            ("add", "math.add", None),
+            ("neg", "math.negate", None),
+        ];
+        for (id, block_type, value) in nodes {
+            let config = value
+                .map(|v| HashMap::from([("value".to_string(), Value::Float(v))]))
+                .unwrap_or_default();
+            graph
+                .add_node(Node::new(id, block_type).with_config(config))
+                .unwrap();
+        }
+        for (from, from_port, to, to_port) in [
+            ("a", "value", "add", "a"),
+            ("b", "value", "add", "b"),
+            ("add", "result", "neg", "value"),
+        ] {
+            graph
+                .add_connection(Connection {
+                    from_node: from.to_string(),
+                    from_port: from_port.to_string(),
+                    to_node: to.to_string(),
+                    to_port: to_port.to_string(),
+                })
+                .unwrap();
+        }
+        engine.load_graph(graph).unwrap();
+        engine
+    }
+
+    fn session(engine: &Engine) -> DebugSession {
+        engine
+            .debug("calc", &HashMap::new(), &ExecutionOptions::new())
+            .unwrap()
+    }
+
+    fn paused_node(status: &DebugStatus) -> &str {
+        match status {
+            DebugStatus::Paused { node_id, .. } => node_id,
+            other => panic!("Expected a paused session, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_breakpoint_step_and_edit() {
+        let engine = build_engine();
+        let mut session = session(&engine);
+        session
+            .add_breakpoint(Breakpoint::new("add").with_condition(
+                "a",
+                CompareOp::Eq,
+                Value::Int(1),
+            ))
+            .unwrap();
+
+        let status = session.resume().clone();
+        assert_eq!(paused_node(&status), "add");
+        let DebugStatus::Paused {
+            inputs, breakpoint, ..
+        } = status
+        else {
+            unreachable!()
+        };
+        assert!(breakpoint);
+        assert_eq!(inputs["b"], Value::Float(2.0));
+        assert!(!session.outputs().contains_key("add"));
+
+        // Run `add` with a changed input, then stop before `neg`
+        session.set_input("b", Value::Float(10.0)).unwrap();
+        let status = session.step().clone();
+        assert_eq!(paused_node(&status), "neg");
+        assert!(matches!(
+            status,
+            DebugStatus::Paused {
+                breakpoint: false,
+                ..
+            }
+        ));
+        assert_eq!(session.outputs()["add"]["result"], Value::Float(11.0));
+
+        session
+            .set_output("add", "result", Value::Float(5.0))
+            .unwrap();
+        let DebugStatus::Paused { inputs, .. } = session.status() else {
+            unreachable!()
+        };
+        assert_eq!(inputs["value"], Value::Float(5.0));
+        assert!(session.set_output("neg", "result", Value::Null).is_err());
+
+        assert_eq!(session.resume(), &DebugStatus::Finished);
+        assert_eq!(session.outputs()["neg"]["result"], Value::Float(-5.0));
+        assert_eq!(session.step(), &DebugStatus::Finished);
+    }
+
+    #[test]
+    fn test_condition_skips_breakpoint() {
+        let engine = build_engine();
+        let mut session = session(&engine);
+        session
+            .add_breakpoint(Breakpoint::new("add").with_condition(
+                "a",
+                CompareOp::Gt,
+                Value::Int(1),
+            ))
+            .unwrap();
+        assert!(matches!(
+            session.add_breakpoint(Breakpoint::new("missing")),
+            Err(CircuitError::NodeNotFound(_))
+        ));
+        assert!(session.set_input("a", Value::Null).is_err());
+
+        assert_eq!(session.resume(), &DebugStatus::Finished);
+        assert_eq!(session.outputs()["neg"]["result"], Value::Float(-3.0));
+    }
+
+    #[test]
+    fn test_failed_node_runs_again_after_fix() {
+        let engine = build_engine();
+        let mut session = session(&engine);
+        session.add_breakpoint(Breakpoint::new("neg")).unwrap();
+        assert_eq!(paused_node(session.resume()), "neg");
+
+        session
+            .set_input("value", Value::String("x".to_string()))
+            .unwrap();
+        let status = session.resume().clone();
+        assert!(matches!(&status, DebugStatus::Failed { node_id, .. } if node_id == "neg"));
+
+        session.set_input("value", Value::Float(4.0)).unwrap();
+        assert_eq!(session.resume(), &DebugStatus::Finished);
+        assert_eq!(session.outputs()["neg"]["result"], Value::Float(-4.0));
+        assert!(session.remove_breakpoint("neg"));
+        assert!(!session.remove_breakpoint("neg"));
+    }
+
+    fn inputs(value: Value) -> HashMap<String, Value> {
+        HashMap::from([("a".to_string(), value)])
+    }
+
+    #[test]
+    fn test_break_conditions() {
+        let at_least_two = Breakpoint::new("n").with_condition("a", CompareOp::Ge, Value::Int(2));
+        assert!(at_least_two.matches(&inputs(Value::Float(2.0))));
+        assert!(at_least_two.matches(&inputs(Value::Int(3))));
+        assert!(!at_least_two.matches(&inputs(Value::Int(1))));
+        assert!(!at_least_two.matches(&inputs(Value::String("x".to_string()))));
+        assert!(!at_least_two.matches(&HashMap::new()));
+
+        let not_x =
+            Breakpoint::new("n").with_condition("a", CompareOp::Ne, Value::String("x".to_string()));
+        assert!(not_x.matches(&inputs(Value::String("y".to_string()))));
+        assert!(!not_x.matches(&inputs(Value::String("x".to_string()))));
+        assert!(Breakpoint::new("n").matches(&HashMap::new()));
+
+        let json = r#"{"node_id": "n", "conditions": [{"port": "a", "op": "lt", "value": {"type": "Int", "value": 5}}]}"#;
+        let parsed: Breakpoint = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            parsed,
+            Breakpoint::new("n").with_condition("a", CompareOp::Lt, Value::Int(5))
+        );
+    }
+}
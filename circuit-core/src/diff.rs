@@ -0,0 +1,457 @@
+use crate::{
+    error::{CircuitError, Result},
+    graph::{Connection, Graph, GraphOutput, Node, NodeId, NodeMode},
+    retry::RetryPolicy,
+    value::Value,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// A value before and after a change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Change<T> {
+    pub from: T,
+    pub to: T,
+}
+
+impl<T: Clone> Change<T> {
+    fn inverted(&self) -> Self {
+        Change {
+            from: self.to.clone(),
+            to: self.from.clone(),
+        }
+    }
+}
+
+/// A config field set, changed or removed; `None` means the field is absent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigChange {
+    pub key: String,
+    #[serde(default)]
+    pub from: Option<Value>,
+    #[serde(default)]
+    pub to: Option<Value>,
+}
+
+/// Changes to a node present in both graphs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeDiff {
+    pub node_id: NodeId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_type: Option<Change<String>>,
+    /// Changed config fields, sorted by key
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub config: Vec<ConfigChange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<Change<Option<(f64, f64)>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<Change<Option<RetryPolicy>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<Change<NodeMode>>,
+}
+
+/// Structured difference between two graphs, produced by `Graph::diff`
+///
+/// Added and removed nodes are kept whole, so a patch can be checked against
+/// the graph it is applied to and inverted. Nodes are sorted by ID.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GraphPatch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<Change<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<Change<Option<String>>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nodes_added: Vec<Node>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nodes_removed: Vec<Node>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nodes_changed: Vec<NodeDiff>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub connections_added: Vec<Connection>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub connections_removed: Vec<Connection>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs_added: Vec<GraphOutput>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs_removed: Vec<GraphOutput>,
+}
+
+impl GraphPatch {
+    /// Whether the patch changes nothing
+    pub fn is_empty(&self) -> bool {
+        *self == GraphPatch::default()
+    }
+
+    /// The patch that reverts this one
+    pub fn inverted(&self) -> GraphPatch {
+        GraphPatch {
+            name: self.name.as_ref().map(Change::inverted),
+            description: self.description.as_ref().map(Change::inverted),
+            nodes_added: self.nodes_removed.clone(),
+            nodes_removed: self.nodes_added.clone(),
+            nodes_changed: self
+                .nodes_changed
+                .iter()
+                .map(|node| NodeDiff {
+                    node_id: node.node_id.clone(),
+                    block_type: node.block_type.as_ref().map(Change::inverted),
+                    config: node
+                        .config
+                        .iter()
+                        .map(|change| ConfigChange {
+                            key: change.key.clone(),
+                            from: change.to.clone(),
+                            to: change.from.clone(),
+                        })
+                        .collect(),
+                    position: node.position.as_ref().map(Change::inverted),
+                    retry: node.retry.as_ref().map(Change::inverted),
+                    mode: node.mode.as_ref().map(Change::inverted),
+                })
+                .collect(),
+            connections_added: self.connections_removed.clone(),
+            connections_removed: self.connections_added.clone(),
+            outputs_added: self.outputs_removed.clone(),
+            outputs_removed: self.outputs_added.clone(),
+        }
+    }
+}
+
+/// A change in a patch that does not fit the graph it is applied to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatchConflict {
+    /// What the change is about, e.g. `nodes.add.config.value`
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for PatchConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl Graph {
+    /// Compute the changes that turn graph `a` into graph `b`
+    ///
+    /// The graph IDs are not compared.
+    pub fn diff(a: &Graph, b: &Graph) -> GraphPatch {
+        let mut patch = GraphPatch {
+            name: change(&a.name, &b.name),
+            description: change(&a.description, &b.description),
+            ..GraphPatch::default()
+        };
+
+        let ids: BTreeSet<&NodeId> = a.nodes.keys().chain(b.nodes.keys()).collect();
+        for id in ids {
+            match (a.nodes.get(id), b.nodes.get(id)) {
+                (Some(old), None) => patch.nodes_removed.push(old.clone()),
+                (None, Some(new)) => patch.nodes_added.push(new.clone()),
+                (Some(old), Some(new)) if old != new => {
+                    patch.nodes_changed.push(node_diff(old, new));
+                }
+                _ => {}
+            }
+        }
+
+        patch.connections_removed = missing_from(&a.connections, &b.connections);
+        patch.connections_added = missing_from(&b.connections, &a.connections);
+        patch.outputs_removed = missing_from(&a.outputs, &b.outputs);
+        patch.outputs_added = missing_from(&b.outputs, &a.outputs);
+        patch
+    }
+
+    /// Apply a patch produced by `Graph::diff`
+    ///
+    /// Every change must start from what the graph holds; a change whose
+    /// result the graph already holds is skipped, so applying a patch twice
+    /// is harmless. When any change conflicts the graph is left unchanged and
+    /// all conflicts are returned in `CircuitError::PatchConflicts`.
+    pub fn apply_patch(&mut self, patch: &GraphPatch) -> Result<()> {
+        let mut graph = self.clone();
+        let conflicts = graph.patch_in_place(patch);
+        if !conflicts.is_empty() {
+            return Err(CircuitError::PatchConflicts(conflicts));
+        }
+        *self = graph;
+        Ok(())
+    }
+
+    /// Conflicts `apply_patch` would report, without changing the graph
+    pub fn check_patch(&self, patch: &GraphPatch) -> Vec<PatchConflict> {
+        self.clone().patch_in_place(patch)
+    }
+
+    fn patch_in_place(&mut self, patch: &GraphPatch) -> Vec<PatchConflict> {
+        let mut conflicts = Vec::new();
+        let mut conflict = |path: String, message: &str| {
+            conflicts.push(PatchConflict {
+                path,
+                message: message.to_string(),
+            });
+        };
+
+        if let Some(name) = &patch.name {
+            if let Err(message) = apply_change(&mut self.name, name) {
+                conflict("name".to_string(), message);
+            }
+        }
+        if let Some(description) = &patch.description {
+            if let Err(message) = apply_change(&mut self.description, description) {
+                conflict("description".to_string(), message);
+            }
+        }
+
+        // Removals first, so connections of removed nodes are gone already
+        for connection in &patch.connections_removed {
+            if let Some(index) = self.connections.iter().position(|c| c == connection) {
+                self.connections.remove(index);
+            }
+        }
+        for output in &patch.outputs_removed {
+            self.outputs.retain(|o| o != output);
+        }
+        for node in &patch.nodes_removed {
+            let path = format!("nodes.{}", node.id);
+            match self.nodes.get(&node.id) {
+                None => {}
+                Some(current) if current != node => {
+                    conflict(path, "Node was changed, so it cannot be removed");
+                }
+                Some(_)
+                    if !self.get_incoming_connections(&node.id).is_empty()
+                        || !self.get_outgoing_connections(&node.id).is_empty() =>
+                {
+                    conflict(path, "Node has connections the patch does not remove");
+                }
+                Some(_) => {
+                    self.outputs.retain(|output| output.node != node.id);
+                    self.nodes.remove(&node.id);
+                }
+            }
+        }
+
+        for diff in &patch.nodes_changed {
+            let path = format!("nodes.{}", diff.node_id);
+            let Some(node) = self.nodes.get_mut(&diff.node_id) else {
+                conflict(path, "Node does not exist");
+                continue;
+            };
+            if let Some(block_type) = &diff.block_type {
+                if let Err(message) = apply_change(&mut node.block_type, block_type) {
+                    conflict(format!("{}.block_type", path), message);
+                }
+            }
+            for change in &diff.config {
+                let current = node.config.get(&change.key);
+                if current == change.from.as_ref() {
+                    match &change.to {
+                        Some(value) => node.config.insert(change.key.clone(), value.clone()),
+                        None => node.config.remove(&change.key),
+                    };
+                } else if current != change.to.as_ref() {
+                    conflict(
+                        format!("{}.config.{}", path, change.key),
+                        "Config field was changed",
+                    );
+                }
+            }
+            if let Some(position) = &diff.position {
+                if let Err(message) = apply_change(&mut node.position, position) {
+                    conflict(format!("{}.position", path), message);
+                }
+            }
+            if let Some(retry) = &diff.retry {
+                if let Err(message) = apply_change(&mut node.retry, retry) {
+                    conflict(format!("{}.retry", path), message);
+                }
+            }
+            if let Some(mode) = &diff.mode {
+                if let Err(message) = apply_change(&mut node.mode, mode) {
+                    conflict(format!("{}.mode", path), message);
+                }
+            }
+        }
+
+        for node in &patch.nodes_added {
+            match self.nodes.get(&node.id) {
+                None => {
+                    self.nodes.insert(node.id.clone(), node.clone());
+                }
+                Some(current) if current == node => {}
+                Some(_) => conflict(
+                    format!("nodes.{}", node.id),
+                    "A different node with this ID exists",
+                ),
+            }
+        }
+
+        for connection in &patch.connections_added {
+            if self.connections.contains(connection) {
+                continue;
+            }
+            if let Err(e) = self.add_connection(connection.clone()) {
+                conflict(format!("connections.{}", connection), &e.to_string());
+            }
+        }
+        for output in &patch.outputs_added {
+            if let Err(e) = self.add_output(&output.node, &output.port) {
+                conflict(
+                    format!("outputs.{}.{}", output.node, output.port),
+                    &e.to_string(),
+                );
+            }
+        }
+        conflicts
+    }
+}
+
+fn change<T: Clone + PartialEq>(from: &T, to: &T) -> Option<Change<T>> {
+    (from != to).then(|| Change {
+        from: from.clone(),
+        to: to.clone(),
+    })
+}
+
+// Set `current` to `change.to` if it holds `change.from`, or accept it if it
+// already holds `change.to`
+fn apply_change<T: Clone + PartialEq>(
+    current: &mut T,
+    change: &Change<T>,
+) -> std::result::Result<(), &'static str> {
+    if *current == change.from {
+        *current = change.to.clone();
+        Ok(())
+    } else if *current == change.to {
+        Ok(())
+    } else {
+        Err("Value was changed")
+    }
+}
+
+fn node_diff(old: &Node, new: &Node) -> NodeDiff {
+    let keys: BTreeSet<&String> = old.config.keys().chain(new.config.keys()).collect();
+    NodeDiff {
+        node_id: new.id.clone(),
+        block_type: change(&old.block_type, &new.block_type),
+        config: keys
+            .into_iter()
+            .filter_map(|key| {
+                let (from, to) = (old.config.get(key), new.config.get(key));
+                (from != to).then(|| ConfigChange {
+                    key: key.clone(),
+                    from: from.cloned(),
+                    to: to.cloned(),
+                })
+            })
+            .collect(),
+        position: change(&old.position, &new.position),
+        retry: change(&old.retry, &new.retry),
+        mode: change(&old.mode, &new.mode),
+    }
+}
+
+// Items of `items` not in `other`, in order
+fn missing_from<T: Clone + PartialEq>(items: &[T], other: &[T]) -> Vec<T> {
+    items
+        .iter()
+        .filter(|item| !other.contains(item))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::fixtures::{self, connection, node};
+
+    // a -> sum <- b
+    fn base() -> Graph {
+        fixtures::graph(&["a", "b", "sum"], &[("a", "sum"), ("b", "sum")])
+    }
+
+    #[test]
+    fn test_diff_and_apply_patch() {
+        let a = base();
+        let mut b = a.clone();
+        b.name = "Renamed".to_string();
+        b.remove_node("b").unwrap();
+        b.add_node(node("c", 3)).unwrap();
+        b.add_connection(connection("c", "sum")).unwrap();
+        b.update_config("a", "value", Some(Value::Int(2))).unwrap();
+        b.update_config("a", "label", Some(Value::from("first")))
+            .unwrap();
+        b.move_node("sum", Some((5.0, 0.0))).unwrap();
+        b.add_output("sum", "result").unwrap();
+
+        let patch = Graph::diff(&a, &b);
+        assert_eq!(patch.name.as_ref().unwrap().to, "Renamed");
+        assert_eq!(patch.nodes_added[0].id, "c");
+        assert_eq!(patch.nodes_removed[0].id, "b");
+        assert_eq!(patch.connections_removed, vec![connection("b", "sum")]);
+        assert_eq!(patch.connections_added, vec![connection("c", "sum")]);
+        let changed: Vec<&str> = patch
+            .nodes_changed
+            .iter()
+            .map(|n| n.node_id.as_str())
+            .collect();
+        assert_eq!(changed, vec!["a", "sum"]);
+        let keys: Vec<&str> = patch.nodes_changed[0]
+            .config
+            .iter()
+            .map(|c| c.key.as_str())
+            .collect();
+        assert_eq!(keys, vec!["label", "value"]);
+        assert_eq!(patch.nodes_changed[1].position.as_ref().unwrap().from, None);
+
+        let json = serde_json::to_string(&patch).unwrap();
+        let parsed: GraphPatch = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, patch);
+
+        let mut patched = a.clone();
+        patched.apply_patch(&parsed).unwrap();
+        assert_eq!(patched, b);
+        assert!(Graph::diff(&patched, &b).is_empty());
+
+        // Applying twice changes nothing; the inverse restores the original
+        patched.apply_patch(&patch).unwrap();
+        assert_eq!(patched, b);
+        patched.apply_patch(&patch.inverted()).unwrap();
+        assert!(Graph::diff(&patched, &a).is_empty());
+    }
+
+    #[test]
+    fn test_conflicts_leave_graph_unchanged() {
+        let a = base();
+        let mut ours = a.clone();
+        ours.update_config("a", "value", Some(Value::Int(2)))
+            .unwrap();
+        ours.remove_node("b").unwrap();
+        let patch = Graph::diff(&a, &ours);
+
+        // Someone else changed the same field and connected to the removed node
+        let mut theirs = a.clone();
+        theirs
+            .update_config("a", "value", Some(Value::Int(9)))
+            .unwrap();
+        theirs.add_node(node("d", 1)).unwrap();
+        theirs.add_connection(connection("b", "d")).unwrap();
+        let before = theirs.clone();
+
+        let conflicts = theirs.check_patch(&patch);
+        let paths: Vec<&str> = conflicts.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, vec!["nodes.b", "nodes.a.config.value"]);
+        match theirs.apply_patch(&patch) {
+            Err(CircuitError::PatchConflicts(reported)) => assert_eq!(reported, conflicts),
+            other => panic!("Expected conflicts, got {:?}", other),
+        }
+        assert_eq!(theirs, before);
+
+        // A connection that would close a cycle is a conflict too
+        let mut cyclic = GraphPatch::default();
+        cyclic.connections_added.push(connection("sum", "a"));
+        let conflicts = base().check_patch(&cyclic);
+        assert_eq!(conflicts[0].path, "connections.sum.out -> a.in");
+    }
+}
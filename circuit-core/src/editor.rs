@@ -0,0 +1,652 @@
+use crate::{
+    error::{CircuitError, Result},
+    graph::{Connection, Graph, GraphOutput, Node, NodeId},
+    lifecycle::ListenerId,
+    value::Value,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// A single change to a graph, as made by a visual editor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum GraphEdit {
+    AddNode {
+        node: Node,
+    },
+    /// Remove a node with its connections and declared outputs
+    RemoveNode {
+        node_id: NodeId,
+    },
+    /// Add a connection at `index` in the graph's connections, or last
+    AddConnection {
+        connection: Connection,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        index: Option<usize>,
+    },
+    RemoveConnection {
+        connection: Connection,
+    },
+    MoveNode {
+        node_id: NodeId,
+        position: Option<(f64, f64)>,
+    },
+    /// Set a config field, or remove it when `value` is `None`
+    UpdateConfig {
+        node_id: NodeId,
+        key: String,
+        #[serde(default)]
+        value: Option<Value>,
+    },
+    RenameNode {
+        node_id: NodeId,
+        new_id: NodeId,
+    },
+    /// Declare an output at `index` in the graph's outputs, or last
+    AddOutput {
+        output: GraphOutput,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        index: Option<usize>,
+    },
+    RemoveOutput {
+        output: GraphOutput,
+    },
+}
+
+impl GraphEdit {
+    /// Apply the edit, returning the edits that undo it
+    ///
+    /// The graph is unchanged when the edit fails.
+    pub fn apply(&self, graph: &mut Graph) -> Result<Vec<GraphEdit>> {
+        Ok(match self {
+            GraphEdit::AddNode { node } => {
+                graph.add_node(node.clone())?;
+                vec![GraphEdit::RemoveNode {
+                    node_id: node.id.clone(),
+                }]
+            }
+            GraphEdit::RemoveNode { node_id } => {
+                let node = graph
+                    .nodes
+                    .get(node_id)
+                    .cloned()
+                    .ok_or_else(|| CircuitError::NodeNotFound(node_id.clone()))?;
+                // Re-inserted in ascending order, each lands at its old index
+                let connections: Vec<GraphEdit> = graph
+                    .connections
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, conn)| conn.from_node == *node_id || conn.to_node == *node_id)
+                    .map(|(index, connection)| GraphEdit::AddConnection {
+                        connection: connection.clone(),
+                        index: Some(index),
+                    })
+                    .collect();
+                let outputs: Vec<GraphEdit> = graph
+                    .outputs
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, output)| output.node == *node_id)
+                    .map(|(index, output)| GraphEdit::AddOutput {
+                        output: output.clone(),
+                        index: Some(index),
+                    })
+                    .collect();
+                graph.remove_node(node_id)?;
+
+                std::iter::once(GraphEdit::AddNode { node })
+                    .chain(connections)
+                    .chain(outputs)
+                    .collect()
+            }
+            GraphEdit::AddConnection { connection, index } => {
+                let index = index.unwrap_or(graph.connections.len());
+                graph.insert_connection(index, connection.clone())?;
+                vec![GraphEdit::RemoveConnection {
+                    connection: connection.clone(),
+                }]
+            }
+            GraphEdit::RemoveConnection { connection } => {
+                let index = graph.connections.iter().position(|conn| conn == connection);
+                graph.remove_connection(connection)?;
+                vec![GraphEdit::AddConnection {
+                    connection: connection.clone(),
+                    index,
+                }]
+            }
+            GraphEdit::MoveNode { node_id, position } => {
+                let previous = graph.move_node(node_id, *position)?;
+                vec![GraphEdit::MoveNode {
+                    node_id: node_id.clone(),
+                    position: previous,
+                }]
+            }
+            GraphEdit::UpdateConfig {
+                node_id,
+                key,
+                value,
+            } => {
+                let previous = graph.update_config(node_id, key, value.clone())?;
+                vec![GraphEdit::UpdateConfig {
+                    node_id: node_id.clone(),
+                    key: key.clone(),
+                    value: previous,
+                }]
+            }
+            GraphEdit::RenameNode { node_id, new_id } => {
+                graph.rename_node(node_id, new_id)?;
+                vec![GraphEdit::RenameNode {
+                    node_id: new_id.clone(),
+                    new_id: node_id.clone(),
+                }]
+            }
+            GraphEdit::AddOutput { output, index } => {
+                if graph.outputs.contains(output) {
+                    return Ok(Vec::new());
+                }
+                let index = index.unwrap_or(graph.outputs.len());
+                graph.insert_output(index, &output.node, &output.port)?;
+                vec![GraphEdit::RemoveOutput {
+                    output: output.clone(),
+                }]
+            }
+            GraphEdit::RemoveOutput { output } => {
+                let index = graph.outputs.iter().position(|declared| declared == output);
+                graph.remove_output(&output.node, &output.port)?;
+                vec![GraphEdit::AddOutput {
+                    output: output.clone(),
+                    index,
+                }]
+            }
+        })
+    }
+}
+
+/// Apply edits in order, all or nothing; returns the edits that undo them
+fn apply_all(graph: &mut Graph, edits: &[GraphEdit]) -> Result<Vec<GraphEdit>> {
+    let mut undo: Vec<Vec<GraphEdit>> = Vec::with_capacity(edits.len());
+    for edit in edits {
+        match edit.apply(graph) {
+            Ok(inverse) => undo.push(inverse),
+            Err(e) => {
+                for inverse in undo.iter().rev().flatten() {
+                    inverse.apply(graph).map_err(|rollback| {
+                        CircuitError::Graph(format!(
+                            "{}; rolling back the earlier edits also failed: {}",
+                            e, rollback
+                        ))
+                    })?;
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(undo.into_iter().rev().flatten().collect())
+}
+
+/// A change made through a `GraphEditor`, passed to its listeners
+///
+/// `edits` are the edits that were applied to the graph; for an undo these
+/// are the inverse edits.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EditEvent {
+    Applied {
+        #[serde(default)]
+        label: Option<String>,
+        edits: Vec<GraphEdit>,
+    },
+    Undone {
+        #[serde(default)]
+        label: Option<String>,
+        edits: Vec<GraphEdit>,
+    },
+    Redone {
+        #[serde(default)]
+        label: Option<String>,
+        edits: Vec<GraphEdit>,
+    },
+}
+
+/// Callback invoked after every change made through a `GraphEditor`
+pub type EditListener = Arc<dyn Fn(&EditEvent) + Send + Sync>;
+
+/// One undo step: edits applied together and the edits that revert them
+#[derive(Debug, Clone)]
+struct EditGroup {
+    label: Option<String>,
+    edits: Vec<GraphEdit>,
+    inverse: Vec<GraphEdit>,
+}
+
+impl EditGroup {
+    fn new(label: Option<String>) -> Self {
+        Self {
+            label,
+            edits: Vec::new(),
+            inverse: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, edits: Vec<GraphEdit>, mut inverse: Vec<GraphEdit>) {
+        self.edits.extend(edits);
+        inverse.append(&mut self.inverse);
+        self.inverse = inverse;
+    }
+}
+
+/// Edits a graph through `GraphEdit`s, keeping an undo/redo history
+///
+/// Every `apply` is one undo step unless a batch is open, in which case the
+/// edits are grouped until `commit_batch`. Applying an edit clears the redo
+/// history.
+pub struct GraphEditor {
+    graph: Graph,
+    undo_stack: Vec<EditGroup>,
+    redo_stack: Vec<EditGroup>,
+    batch: Option<EditGroup>,
+    listeners: Vec<(ListenerId, EditListener)>,
+    next_listener_id: ListenerId,
+}
+
+impl GraphEditor {
+    /// Start editing a graph with an empty history
+    pub fn new(graph: Graph) -> Self {
+        Self {
+            graph,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            batch: None,
+            listeners: Vec::new(),
+            next_listener_id: 0,
+        }
+    }
+
+    /// The graph as edited so far
+    pub fn graph(&self) -> &Graph {
+        &self.graph
+    }
+
+    /// Stop editing and take the graph
+    pub fn into_graph(self) -> Graph {
+        self.graph
+    }
+
+    /// Apply one edit
+    pub fn apply(&mut self, edit: GraphEdit) -> Result<()> {
+        self.apply_batch(None, vec![edit])
+    }
+
+    /// Apply edits as one undo step, all or nothing
+    ///
+    /// Inside an open batch the edits join that batch and `label` is ignored.
+    pub fn apply_batch(&mut self, label: Option<String>, edits: Vec<GraphEdit>) -> Result<()> {
+        let inverse = apply_all(&mut self.graph, &edits)?;
+        self.redo_stack.clear();
+        let label = match &mut self.batch {
+            Some(batch) => {
+                batch.push(edits.clone(), inverse);
+                batch.label.clone()
+            }
+            None => {
+                let mut group = EditGroup::new(label.clone());
+                group.push(edits.clone(), inverse);
+                self.undo_stack.push(group);
+                label
+            }
+        };
+        self.emit(EditEvent::Applied { label, edits });
+        Ok(())
+    }
+
+    /// Group the following edits into one undo step until `commit_batch`
+    pub fn begin_batch(&mut self, label: Option<String>) -> Result<()> {
+        if self.batch.is_some() {
+            return Err(CircuitError::InvalidInput(
+                "A batch is already open".to_string(),
+            ));
+        }
+        self.batch = Some(EditGroup::new(label));
+        Ok(())
+    }
+
+    /// Close the open batch, making its edits one undo step
+    pub fn commit_batch(&mut self) -> Result<()> {
+        let batch = self.take_batch()?;
+        if !batch.edits.is_empty() {
+            self.undo_stack.push(batch);
+        }
+        Ok(())
+    }
+
+    /// Close the open batch, reverting its edits
+    pub fn cancel_batch(&mut self) -> Result<()> {
+        let batch = self.take_batch()?;
+        if !batch.edits.is_empty() {
+            apply_all(&mut self.graph, &batch.inverse)?;
+            self.emit(EditEvent::Undone {
+                label: batch.label,
+                edits: batch.inverse,
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether a batch is open
+    pub fn in_batch(&self) -> bool {
+        self.batch.is_some()
+    }
+
+    /// Revert the latest undo step; returns false when there is none
+    pub fn undo(&mut self) -> Result<bool> {
+        self.check_no_batch()?;
+        let Some(group) = self.undo_stack.pop() else {
+            return Ok(false);
+        };
+        if let Err(e) = apply_all(&mut self.graph, &group.inverse) {
+            self.undo_stack.push(group);
+            return Err(e);
+        }
+        self.emit(EditEvent::Undone {
+            label: group.label.clone(),
+            edits: group.inverse.clone(),
+        });
+        self.redo_stack.push(group);
+        Ok(true)
+    }
+
+    /// Apply the latest undone step again; returns false when there is none
+    pub fn redo(&mut self) -> Result<bool> {
+        self.check_no_batch()?;
+        let Some(mut group) = self.redo_stack.pop() else {
+            return Ok(false);
+        };
+        match apply_all(&mut self.graph, &group.edits) {
+            Ok(inverse) => group.inverse = inverse,
+            Err(e) => {
+                self.redo_stack.push(group);
+                return Err(e);
+            }
+        }
+        self.emit(EditEvent::Redone {
+            label: group.label.clone(),
+            edits: group.edits.clone(),
+        });
+        self.undo_stack.push(group);
+        Ok(true)
+    }
+
+    /// Whether there is a step to undo
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    /// Whether there is a step to redo
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Forget the undo and redo history
+    pub fn clear_history(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    /// Register a callback invoked after every change
+    pub fn add_listener(&mut self, listener: EditListener) -> ListenerId {
+        let id = self.next_listener_id;
+        self.next_listener_id += 1;
+        self.listeners.push((id, listener));
+        id
+    }
+
+    /// Remove a listener, returning whether it was registered
+    pub fn remove_listener(&mut self, id: ListenerId) -> bool {
+        let before = self.listeners.len();
+        self.listeners.retain(|(listener_id, _)| *listener_id != id);
+        self.listeners.len() != before
+    }
+
+    fn take_batch(&mut self) -> Result<EditGroup> {
+        self.batch
+            .take()
+            .ok_or_else(|| CircuitError::InvalidInput("No batch is open".to_string()))
+    }
+
+    fn check_no_batch(&self) -> Result<()> {
+        if self.batch.is_some() {
+            return Err(CircuitError::InvalidInput(
+                "Commit or cancel the open batch first".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn emit(&self, event: EditEvent) {
+        for (_, listener) in &self.listeners {
+            listener(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::fixtures::{self, connection};
+
+    use std::sync::Mutex;
+
+    fn node(id: &str) -> Node {
+        fixtures::node(id, 1)
+    }
+
+    // a -> b, with a.out declared as an output
+    fn editor() -> GraphEditor {
+        let mut graph = fixtures::graph(&["a", "b"], &[("a", "b")]);
+        graph.add_output("a", "out").unwrap();
+        GraphEditor::new(graph)
+    }
+
+    #[test]
+    fn test_every_edit_is_undone_and_redone() {
+        let edits = vec![
+            GraphEdit::AddNode { node: node("c") },
+            GraphEdit::RemoveNode {
+                node_id: "b".to_string(),
+            },
+            GraphEdit::RemoveConnection {
+                connection: connection("a", "b"),
+            },
+            GraphEdit::MoveNode {
+                node_id: "a".to_string(),
+                position: Some((10.0, 20.0)),
+            },
+            GraphEdit::UpdateConfig {
+                node_id: "a".to_string(),
+                key: "value".to_string(),
+                value: None,
+            },
+            GraphEdit::RenameNode {
+                node_id: "a".to_string(),
+                new_id: "source".to_string(),
+            },
+            GraphEdit::RemoveOutput {
+                output: GraphOutput {
+                    node: "a".to_string(),
+                    port: "out".to_string(),
+                },
+            },
+        ];
+        for edit in edits {
+            let mut editor = editor();
+            let original = editor.graph().clone();
+            editor.apply(edit.clone()).unwrap();
+            let edited = editor.graph().clone();
+            assert_ne!(edited, original, "{:?}", edit);
+
+            assert!(editor.undo().unwrap());
+            assert_eq!(*editor.graph(), original, "{:?}", edit);
+            assert!(editor.redo().unwrap());
+            assert_eq!(*editor.graph(), edited, "{:?}", edit);
+            assert!(!editor.redo().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_undo_restores_connection_and_output_order() {
+        let mut editor = editor();
+        editor
+            .apply_batch(
+                None,
+                vec![
+                    GraphEdit::AddNode { node: node("c") },
+                    GraphEdit::AddConnection {
+                        connection: connection("b", "c"),
+                        index: None,
+                    },
+                    GraphEdit::AddConnection {
+                        connection: connection("a", "c"),
+                        index: None,
+                    },
+                    GraphEdit::AddOutput {
+                        output: GraphOutput {
+                            node: "b".to_string(),
+                            port: "out".to_string(),
+                        },
+                        index: None,
+                    },
+                    GraphEdit::AddOutput {
+                        output: GraphOutput {
+                            node: "c".to_string(),
+                            port: "out".to_string(),
+                        },
+                        index: None,
+                    },
+                ],
+            )
+            .unwrap();
+        editor.clear_history();
+        let original = editor.graph().clone();
+
+        // `b` has the first two connections and the middle output
+        for edit in [
+            GraphEdit::RemoveNode {
+                node_id: "b".to_string(),
+            },
+            GraphEdit::RemoveConnection {
+                connection: connection("a", "b"),
+            },
+            GraphEdit::RemoveOutput {
+                output: GraphOutput {
+                    node: "b".to_string(),
+                    port: "out".to_string(),
+                },
+            },
+        ] {
+            editor.apply(edit.clone()).unwrap();
+            assert!(editor.undo().unwrap());
+            assert_eq!(*editor.graph(), original, "{:?}", edit);
+        }
+    }
+
+    #[test]
+    fn test_failed_edits_leave_graph_unchanged() {
+        let mut editor = editor();
+        let original = editor.graph().clone();
+        let cycle = GraphEdit::AddConnection {
+            connection: connection("b", "a"),
+            index: None,
+        };
+        assert!(matches!(
+            editor.apply(cycle),
+            Err(CircuitError::CycleDetected)
+        ));
+
+        // The first edit of a failing batch is rolled back
+        let result = editor.apply_batch(
+            Some("broken".to_string()),
+            vec![
+                GraphEdit::AddNode { node: node("c") },
+                GraphEdit::RenameNode {
+                    node_id: "c".to_string(),
+                    new_id: "a".to_string(),
+                },
+            ],
+        );
+        assert!(result.is_err());
+        assert_eq!(*editor.graph(), original);
+        assert!(!editor.can_undo());
+    }
+
+    #[test]
+    fn test_batches_are_one_undo_step() {
+        let mut editor = editor();
+        let original = editor.graph().clone();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        let listener = editor.add_listener(Arc::new(move |event: &EditEvent| {
+            recorded.lock().unwrap().push(event.clone());
+        }));
+
+        editor.begin_batch(Some("drag".to_string())).unwrap();
+        assert!(editor.begin_batch(None).is_err());
+        for x in [1.0, 2.0, 3.0] {
+            editor
+                .apply(GraphEdit::MoveNode {
+                    node_id: "a".to_string(),
+                    position: Some((x, 0.0)),
+                })
+                .unwrap();
+        }
+        assert!(editor.undo().is_err());
+        editor.commit_batch().unwrap();
+        assert_eq!(editor.graph().nodes["a"].position, Some((3.0, 0.0)));
+
+        assert!(editor.undo().unwrap());
+        assert_eq!(*editor.graph(), original);
+        assert!(!editor.can_undo() && editor.can_redo());
+
+        // A new edit clears the redo history; a cancelled batch is reverted
+        editor.begin_batch(None).unwrap();
+        editor
+            .apply(GraphEdit::AddNode { node: node("c") })
+            .unwrap();
+        assert!(!editor.can_redo());
+        editor.cancel_batch().unwrap();
+        assert_eq!(*editor.graph(), original);
+        assert!(editor.commit_batch().is_err());
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 6);
+        assert!(matches!(
+            &events[3],
+            EditEvent::Undone { label: Some(label), edits } if label == "drag" && edits.len() == 3
+        ));
+        assert!(matches!(&events[5], EditEvent::Undone { label: None, .. }));
+        drop(events);
+        assert!(editor.remove_listener(listener));
+        assert!(!editor.remove_listener(listener));
+    }
+
+    #[test]
+    fn test_edit_json() {
+        let edit: GraphEdit = serde_json::from_str(
+            r#"{"op": "update_config", "node_id": "a", "key": "value",
+                "value": {"type": "Int", "value": 2}}"#,
+        )
+        .unwrap();
+        let mut editor = editor();
+        editor.apply(edit.clone()).unwrap();
+        assert_eq!(editor.graph().nodes["a"].config["value"], Value::Int(2));
+
+        let json = serde_json::to_string(&edit).unwrap();
+        assert_eq!(serde_json::from_str::<GraphEdit>(&json).unwrap(), edit);
+        let removal: GraphEdit =
+            serde_json::from_str(r#"{"op": "update_config", "node_id": "a", "key": "value"}"#)
+                .unwrap();
+        assert!(matches!(
+            removal,
+            GraphEdit::UpdateConfig { value: None, .. }
+        ));
+    }
+}
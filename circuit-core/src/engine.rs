@@ -1,290 +1,3594 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::pipeline::{self, Pipeline, PipelineOptions};
 use crate::{
-    block::{Block, BlockContext},
-    error::{CircuitError, Result},
-    graph::{Graph, NodeId},
+    async_block::{AsyncBlock, AsyncBlockAdapter, BlockFuture},
+    block::{Block, BlockContext, NodePorts},
+    checkpoint::{Checkpoint, CheckpointStep},
+    context::{Clock, ExecutionContext},
+    debugger::DebugSession,
+    error::{panic_message, CircuitError, Result},
+    execution::{new_execution_id, ExecutionEvent, ExecutionOptions},
+    graph::{Graph, NodeId, GRAPH_FORMAT_VERSION},
+    lifecycle::{LifecycleEvent, LifecycleListener, ListenerId, Listeners},
+    limits::{prefix_limit_error, EngineLimits, OutputMemory},
+    liveness::OutputLiveness,
+    migrate::{Aliases, MigrationFn, MigrationRegistry, MigrationReport},
+    plan::{group_graph_inputs, ExecutionPlan, PlanStep},
+    ports::{check_connections, resolve_ports},
+    record::{ExecutionRecording, Recorder, ReplayReport, RECORDING_FORMAT_VERSION},
+    report::{ExecutionReport, NodeReport, NodeStatus, WarningCollector},
+    snapshot::{BlockVersion, EngineSnapshot, SNAPSHOT_FORMAT_VERSION},
+    state::{NodeStateSlot, NodeStates},
+    suspend::{ExecutionOutcome, SuspendedExecution},
     value::Value,
 };
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::task::Poll;
+use std::time::Duration;
 
 /// Block registry that maps block type IDs to block implementations
 pub type BlockRegistry = HashMap<String, Arc<dyn Block>>;
 
+/// Outputs of every executed node, keyed by node ID and then by port
+pub type NodeOutputs = HashMap<NodeId, HashMap<String, Value>>;
+
 /// The main execution engine for running graphs
+///
+/// The engine is `Send + Sync`: registries are kept behind `RwLock`s and each
+/// execution works on `Arc` snapshots taken when it starts, so any number of
+/// executions can run in parallel while graphs are loaded or replaced.
 pub struct Engine {
-    /// Registered block types
-    blocks: BlockRegistry,
+    /// Registered block types, replaced copy-on-write
+    blocks: RwLock<Arc<BlockRegistry>>,
     /// Loaded graphs
-    pub graphs: HashMap<String, Graph>,
+    graphs: RwLock<HashMap<String, Arc<Graph>>>,
+    /// Resource limits for loaded graphs and produced values
+    limits: RwLock<EngineLimits>,
+    /// Callbacks notified of graph and block lifecycle changes
+    listeners: Listeners,
+    /// Executions waiting to be resumed, keyed by execution ID
+    suspended: RwLock<HashMap<String, SuspendedExecution>>,
+    /// Variables visible to every execution, replaced copy-on-write
+    variables: RwLock<Arc<HashMap<String, Value>>>,
+    /// Block state of every node, kept between executions
+    node_states: Arc<NodeStates>,
+    /// Upgrades graphs written in an older format when they are loaded
+    migrations: RwLock<MigrationRegistry>,
+    /// Renamed block and port IDs, resolved when a graph is loaded
+    aliases: RwLock<Aliases>,
+}
+
+/// What loading a graph whose ID is already loaded does
+#[derive(Clone, Copy)]
+enum LoadMode {
+    /// Overwrite it, or load the graph if it is new
+    Overwrite,
+    /// Fail
+    New,
+    /// Swap it in; fail if the graph is new
+    Replace,
+}
+
+/// Engine state an execution works with, taken when it starts
+#[derive(Clone)]
+pub(crate) struct RunEnv {
+    pub(crate) limits: EngineLimits,
+    pub(crate) variables: Arc<HashMap<String, Value>>,
+    pub(crate) node_states: Arc<NodeStates>,
+}
+
+/// Decides, given a node and its resolved inputs, whether the run pauses before it
+pub(crate) type PauseHook = dyn FnMut(&str, &HashMap<String, Value>) -> bool + Send;
+
+/// Per-run state threaded through `Engine::run_plan`
+#[derive(Default)]
+pub(crate) struct RunState {
+    /// Caller-supplied inputs of the nodes that have not finished
+    pub(crate) graph_inputs: HashMap<NodeId, HashMap<String, Value>>,
+    /// Outputs of the nodes that ran
+    pub(crate) outputs: NodeOutputs,
+    /// Nodes that finished, in order; skipped when the run is resumed
+    pub(crate) completed: Vec<NodeId>,
+    /// Value handed to the node being resumed
+    resume: Option<(NodeId, Value)>,
+    /// Node that suspended the run
+    suspended_at: Option<NodeId>,
+    /// Checked before every node; the run stops there when it returns true
+    pub(crate) pause_before: Option<Box<PauseHook>>,
+    /// Node the run paused before
+    pub(crate) paused_at: Option<NodeId>,
+    /// Records the nodes that run, or checks them against a recording
+    pub(crate) recorder: Option<Recorder>,
+    /// Clock the timeout is measured with, when not the run's context clock,
+    /// e.g. the caller's clock under a recorder that wraps it
+    pub(crate) deadline_clock: Option<Arc<dyn Clock>>,
+}
+
+impl RunState {
+    pub(crate) fn new(graph_inputs: HashMap<NodeId, HashMap<String, Value>>) -> Self {
+        Self {
+            graph_inputs,
+            ..Self::default()
+        }
+    }
 }
 
 impl Engine {
     /// Create a new engine instance
     pub fn new() -> Self {
         Self {
-            blocks: HashMap::new(),
-            graphs: HashMap::new(),
+            blocks: RwLock::new(Arc::new(HashMap::new())),
+            graphs: RwLock::new(HashMap::new()),
+            limits: RwLock::new(EngineLimits::default()),
+            listeners: Listeners::default(),
+            suspended: RwLock::new(HashMap::new()),
+            variables: RwLock::new(Arc::new(HashMap::new())),
+            node_states: Arc::new(NodeStates::default()),
+            migrations: RwLock::new(MigrationRegistry::new()),
+            aliases: RwLock::new(Aliases::default()),
+        }
+    }
+
+    /// Create a new engine instance that enforces the given resource limits
+    pub fn with_limits(limits: EngineLimits) -> Self {
+        Self {
+            limits: RwLock::new(limits),
+            ..Self::new()
         }
     }
 
+    /// Get the resource limits enforced by this engine
+    pub fn limits(&self) -> EngineLimits {
+        read(&self.limits).clone()
+    }
+
+    /// Replace the resource limits enforced by this engine
+    ///
+    /// Graphs that are already loaded are not re-checked against the new
+    /// graph-shape limits. Running executions keep the limits they started with.
+    pub fn set_limits(&self, limits: EngineLimits) {
+        *write(&self.limits) = limits;
+    }
+
     /// Register a block type with the engine
-    pub fn register_block(&mut self, block: Arc<dyn Block>) -> Result<()> {
+    ///
+    /// Fails if the block type's ID is a block alias, which would hide it.
+    pub fn register_block(&self, block: Arc<dyn Block>) -> Result<()> {
         let metadata = block.metadata();
-        if self.blocks.contains_key(&metadata.id) {
-            return Err(CircuitError::Graph(format!(
-                "Block type '{}' is already registered",
-                metadata.id
-            )));
+        {
+            let mut blocks = write(&self.blocks);
+            if blocks.contains_key(&metadata.id) {
+                return Err(CircuitError::Graph(format!(
+                    "Block type '{}' is already registered",
+                    metadata.id
+                )));
+            }
+            if read(&self.aliases).is_block_alias(&metadata.id) {
+                return Err(CircuitError::InvalidInput(format!(
+                    "Block type '{}' is a block alias",
+                    metadata.id
+                )));
+            }
+            Arc::make_mut(&mut blocks).insert(metadata.id.clone(), block);
         }
-        self.blocks.insert(metadata.id, block);
+
+        self.listeners.emit(LifecycleEvent::BlockRegistered {
+            block_id: metadata.id,
+        });
         Ok(())
     }
 
-    /// Load a graph into the engine
-    pub fn load_graph(&mut self, graph: Graph) -> Result<()> {
-        // Validate that all block types are registered
-        for node in graph.nodes.values() {
-            if !self.blocks.contains_key(&node.block_type) {
+    /// Register a block type whose execution is async
+    ///
+    /// The block can be used in graphs like any other block type.
+    pub fn register_async_block(&self, block: Arc<dyn AsyncBlock>) -> Result<()> {
+        self.register_block(Arc::new(AsyncBlockAdapter(block)))
+    }
+
+    /// Swap the implementation of a registered block type
+    ///
+    /// New executions use the new implementation; executions already running
+    /// finish with the old one.
+    pub fn replace_block(&self, block: Arc<dyn Block>) -> Result<()> {
+        let block_id = block.metadata().id;
+        {
+            let mut blocks = write(&self.blocks);
+            if !blocks.contains_key(&block_id) {
                 return Err(CircuitError::Graph(format!(
-                    "Unknown block type: {}",
-                    node.block_type
+                    "Block type '{}' is not registered",
+                    block_id
                 )));
             }
+            Arc::make_mut(&mut blocks).insert(block_id.clone(), block);
         }
 
-        self.graphs.insert(graph.id.clone(), graph);
+        self.listeners
+            .emit(LifecycleEvent::BlockReplaced { block_id });
         Ok(())
     }
 
-    /// Execute a graph by ID
-    pub fn execute_graph(&self, graph_id: &str) -> Result<HashMap<NodeId, HashMap<String, Value>>> {
-        let graph = self
-            .graphs
-            .get(graph_id)
-            .ok_or_else(|| CircuitError::Graph(format!("Graph '{}' not found", graph_id)))?;
+    /// Remove a block type from the engine
+    ///
+    /// Fails if any loaded graph still uses the block type.
+    pub fn unregister_block(&self, block_id: &str) -> Result<()> {
+        {
+            // Lock order is blocks, then graphs, everywhere
+            let mut blocks = write(&self.blocks);
+            if !blocks.contains_key(block_id) {
+                return Err(CircuitError::Graph(format!(
+                    "Block type '{}' is not registered",
+                    block_id
+                )));
+            }
+
+            let graphs = read(&self.graphs);
+            let mut users: Vec<&str> = graphs
+                .values()
+                .filter(|graph| graph.nodes.values().any(|node| node.block_type == block_id))
+                .map(|graph| graph.id.as_str())
+                .collect();
+            if !users.is_empty() {
+                users.sort_unstable();
+                return Err(CircuitError::Graph(format!(
+                    "Block type '{}' is used by loaded graphs: {}",
+                    block_id,
+                    users.join(", ")
+                )));
+            }
 
-        self.execute(graph)
+            Arc::make_mut(&mut blocks).remove(block_id);
+        }
+
+        self.listeners.emit(LifecycleEvent::BlockUnregistered {
+            block_id: block_id.to_string(),
+        });
+        Ok(())
     }
 
-    /// Execute a graph
-    pub fn execute(&self, graph: &Graph) -> Result<HashMap<NodeId, HashMap<String, Value>>> {
-        // Get execution order
-        let execution_order = graph.topological_sort()?;
+    /// Load a graph into the engine
+    ///
+    /// A graph with the same ID is overwritten; use `load_new_graph` to
+    /// refuse that. Graphs in an older format are migrated and aliases are
+    /// resolved first, see `migrate_graph`.
+    pub fn load_graph(&self, graph: Graph) -> Result<()> {
+        let (graph, report) = self.upgrade_graph(graph)?;
+        self.load_upgraded(graph, report, LoadMode::Overwrite)
+            .map(|_| ())
+    }
 
-        // Store outputs from each node
-        let mut node_outputs: HashMap<NodeId, HashMap<String, Value>> = HashMap::new();
+    /// Load a graph from JSON, returning what was migrated
+    ///
+    /// Unlike `load_graph`, migrations see the document as written, so they
+    /// can upgrade layouts the current `Graph` no longer reads.
+    pub fn load_graph_json(&self, json: &str) -> Result<MigrationReport> {
+        let (graph, report) = self.migrate_graph(json)?;
+        self.load_upgraded(graph, report, LoadMode::Overwrite)
+    }
 
-        // Execute nodes in topological order
-        for node_id in execution_order {
-            let node = graph
-                .nodes
-                .get(&node_id)
-                .ok_or_else(|| CircuitError::NodeNotFound(node_id.clone()))?;
-
-            let block = self.blocks.get(&node.block_type).ok_or_else(|| {
-                CircuitError::Graph(format!("Block type '{}' not found", node.block_type))
-            })?;
-
-            // Build context for this node
-            let mut context = BlockContext::new();
-            context.config = node.config.clone();
-
-            // Gather inputs from connected nodes
-            for connection in graph.get_incoming_connections(&node_id) {
-                if let Some(source_outputs) = node_outputs.get(&connection.from_node) {
-                    if let Some(value) = source_outputs.get(&connection.from_port) {
-                        context
-                            .inputs
-                            .insert(connection.to_port.clone(), value.clone());
-                    }
-                }
-            }
+    /// Load a graph, failing if a graph with the same ID is already loaded
+    pub fn load_new_graph(&self, graph: Graph) -> Result<()> {
+        let (graph, report) = self.upgrade_graph(graph)?;
+        self.load_upgraded(graph, report, LoadMode::New).map(|_| ())
+    }
 
-            // Execute the block
-            let outputs = block
-                .execute(context)
-                .map_err(|e| CircuitError::BlockExecution(format!("Node '{}': {}", node_id, e)))?;
+    /// Load a graph from JSON, failing if a graph with the same ID is
+    /// already loaded
+    pub fn load_new_graph_json(&self, json: &str) -> Result<MigrationReport> {
+        let (graph, report) = self.migrate_graph(json)?;
+        self.load_upgraded(graph, report, LoadMode::New)
+    }
 
-            node_outputs.insert(node_id.clone(), outputs);
-        }
+    /// Replace a loaded graph with a new version
+    ///
+    /// The new graph is validated before the swap, which happens atomically;
+    /// executions already running keep the version they started with.
+    pub fn replace_graph(&self, graph: Graph) -> Result<()> {
+        let (graph, report) = self.upgrade_graph(graph)?;
+        self.load_upgraded(graph, report, LoadMode::Replace)
+            .map(|_| ())
+    }
 
-        Ok(node_outputs)
+    /// Replace a loaded graph with a new version from JSON, returning what
+    /// was migrated
+    pub fn replace_graph_json(&self, json: &str) -> Result<MigrationReport> {
+        let (graph, report) = self.migrate_graph(json)?;
+        self.load_upgraded(graph, report, LoadMode::Replace)
     }
 
-    /// Get list of registered block types
-    pub fn list_blocks(&self) -> Vec<String> {
-        self.blocks.keys().cloned().collect()
+    /// Upgrade a graph document to the current format and resolve block and
+    /// port aliases, without loading it
+    ///
+    /// Useful to rewrite saved graphs; the report lists every change.
+    pub fn migrate_graph(&self, json: &str) -> Result<(Graph, MigrationReport)> {
+        let document: serde_json::Value = serde_json::from_str(json)?;
+        self.upgrade_document(document)
     }
 
-    /// Get list of loaded graphs
-    pub fn list_graphs(&self) -> Vec<String> {
-        self.graphs.keys().cloned().collect()
+    /// Add a migration from graph format `from_version` to the next version
+    ///
+    /// It runs after the built-in migrations of that version.
+    pub fn register_migration(
+        &self,
+        from_version: u32,
+        description: impl Into<String>,
+        migrate: MigrationFn,
+    ) {
+        write(&self.migrations).register(from_version, description, migrate);
     }
-}
 
-impl Default for Engine {
-    fn default() -> Self {
-        Self::new()
+    /// Resolve block type `alias` to `block_id` in graphs loaded from now on,
+    /// e.g. after renaming a block type
+    ///
+    /// Fails if `alias` is a registered block type, which it would shadow.
+    pub fn add_block_alias(&self, alias: &str, block_id: &str) -> Result<()> {
+        let blocks = read(&self.blocks);
+        if blocks.contains_key(alias) {
+            return Err(CircuitError::InvalidInput(format!(
+                "Block alias '{}' is a registered block type",
+                alias
+            )));
+        }
+        write(&self.aliases).add_block(alias, block_id)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::block::{BlockMetadata, PortDefinition};
-    use crate::graph::{Connection, Node};
+    /// Resolve port `alias` of block type `block_id` to `port` in graphs
+    /// loaded from now on
+    ///
+    /// `block_id` is the block type after block aliases are resolved.
+    pub fn add_port_alias(&self, block_id: &str, alias: &str, port: &str) -> Result<()> {
+        write(&self.aliases).add_port(block_id, alias, port)
+    }
 
-    struct AddBlock;
-    impl Block for AddBlock {
-        fn metadata(&self) -> BlockMetadata {
-            BlockMetadata {
-                id: "add".to_string(),
-                name: "Add".to_string(),
-                description: "Adds two numbers".to_string(),
-                inputs: vec![
-                    PortDefinition {
-                        id: "a".to_string(),
-                        name: "A".to_string(),
-                        data_type: "number".to_string(),
-                        required: true,
-                    },
-                    PortDefinition {
-                        id: "b".to_string(),
-                        name: "B".to_string(),
-                        data_type: "number".to_string(),
-                        required: true,
-                    },
-                ],
-                outputs: vec![PortDefinition {
-                    id: "result".to_string(),
-                    name: "Result".to_string(),
-                    data_type: "number".to_string(),
-                    required: true,
-                }],
-                config_schema: HashMap::new(),
-            }
+    fn upgrade_graph(&self, graph: Graph) -> Result<(Graph, MigrationReport)> {
+        if graph.format_version != GRAPH_FORMAT_VERSION {
+            return self.upgrade_document(serde_json::to_value(graph)?);
         }
+        let mut graph = graph;
+        let changes = read(&self.aliases).apply(&mut graph);
+        let report = MigrationReport {
+            graph_id: graph.id.clone(),
+            from_version: GRAPH_FORMAT_VERSION,
+            to_version: GRAPH_FORMAT_VERSION,
+            changes,
+        };
+        Ok((graph, report))
+    }
 
-        fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
-            let a = context
-                .get_input("a")
-                .and_then(|v| v.as_float())
-                .ok_or_else(|| CircuitError::InvalidInput("Missing input 'a'".to_string()))?;
-            let b = context
-                .get_input("b")
-                .and_then(|v| v.as_float())
-                .ok_or_else(|| CircuitError::InvalidInput("Missing input 'b'".to_string()))?;
+    fn upgrade_document(
+        &self,
+        mut document: serde_json::Value,
+    ) -> Result<(Graph, MigrationReport)> {
+        let from_version = crate::migrate::document_version(&document)?;
+        let mut changes = read(&self.migrations).migrate(&mut document, GRAPH_FORMAT_VERSION)?;
+        let mut graph: Graph = serde_json::from_value(document)?;
+        changes.extend(read(&self.aliases).apply(&mut graph));
+        let report = MigrationReport {
+            graph_id: graph.id.clone(),
+            from_version,
+            to_version: GRAPH_FORMAT_VERSION,
+            changes,
+        };
+        Ok((graph, report))
+    }
 
-            let mut outputs = HashMap::new();
-            outputs.insert("result".to_string(), Value::Float(a + b));
-            Ok(outputs)
+    fn load_upgraded(
+        &self,
+        graph: Graph,
+        report: MigrationReport,
+        mode: LoadMode,
+    ) -> Result<MigrationReport> {
+        let graph_id = graph.id.clone();
+        let replaced = self.insert_graph(graph, mode)?;
+
+        self.listeners.emit(if replaced {
+            LifecycleEvent::GraphReplaced {
+                graph_id: graph_id.clone(),
+            }
+        } else {
+            LifecycleEvent::GraphLoaded {
+                graph_id: graph_id.clone(),
+            }
+        });
+        if !report.is_empty() {
+            self.listeners.emit(LifecycleEvent::GraphMigrated {
+                graph_id,
+                from_version: report.from_version,
+                to_version: report.to_version,
+                changes: report.changes.clone(),
+            });
         }
+        Ok(report)
     }
 
-    struct ConstantBlock;
-    impl Block for ConstantBlock {
-        fn metadata(&self) -> BlockMetadata {
-            BlockMetadata {
-                id: "constant".to_string(),
-                name: "Constant".to_string(),
-                description: "Outputs a constant value".to_string(),
-                inputs: vec![],
-                outputs: vec![PortDefinition {
-                    id: "value".to_string(),
-                    name: "Value".to_string(),
-                    data_type: "number".to_string(),
-                    required: true,
-                }],
-                config_schema: HashMap::new(),
+    /// Unload a graph, returning the removed graph
+    ///
+    /// Executions already running finish on their own snapshot.
+    pub fn unload_graph(&self, graph_id: &str) -> Result<Arc<Graph>> {
+        let graph = write(&self.graphs)
+            .remove(graph_id)
+            .ok_or_else(|| CircuitError::Graph(format!("Graph '{}' not found", graph_id)))?;
+        self.node_states.set_graph(graph_id, HashMap::new());
+
+        self.listeners.emit(LifecycleEvent::GraphUnloaded {
+            graph_id: graph_id.to_string(),
+        });
+        Ok(graph)
+    }
+
+    /// Call `listener` after every graph or block lifecycle change
+    pub fn add_lifecycle_listener(&self, listener: LifecycleListener) -> ListenerId {
+        self.listeners.add(listener)
+    }
+
+    /// Remove a lifecycle listener, returning whether it was registered
+    pub fn remove_lifecycle_listener(&self, id: ListenerId) -> bool {
+        self.listeners.remove(id)
+    }
+
+    // Validate a graph and insert it, returning whether it replaced one
+    fn insert_graph(&self, graph: Graph, mode: LoadMode) -> Result<bool> {
+        // Hold the registry lock so the blocks cannot be unregistered meanwhile
+        let blocks = read(&self.blocks);
+        let ports = resolve_ports(&graph, &blocks)?;
+        check_connections(&graph, &ports)?;
+        read(&self.limits).check_graph(&graph)?;
+
+        let mut graphs = write(&self.graphs);
+        match (graphs.contains_key(&graph.id), mode) {
+            (true, LoadMode::New) => Err(CircuitError::Graph(format!(
+                "Graph '{}' is already loaded",
+                graph.id
+            ))),
+            (false, LoadMode::Replace) => Err(CircuitError::Graph(format!(
+                "Graph '{}' not found",
+                graph.id
+            ))),
+            (exists, _) => {
+                graphs.insert(graph.id.clone(), Arc::new(graph));
+                Ok(exists)
             }
         }
+    }
 
-        fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
-            let value = context
-                .get_config("value")
-                .ok_or_else(|| CircuitError::InvalidInput("Missing config 'value'".to_string()))?
-                .clone();
+    /// Get a snapshot of a loaded graph
+    pub fn graph(&self, graph_id: &str) -> Option<Arc<Graph>> {
+        read(&self.graphs).get(graph_id).cloned()
+    }
 
-            let mut outputs = HashMap::new();
-            outputs.insert("value".to_string(), value);
-            Ok(outputs)
+    /// Ports of a node of a loaded graph, resolved from its block and config
+    pub fn node_ports(&self, graph_id: &str, node_id: &str) -> Result<NodePorts> {
+        let graph = self.get_graph(graph_id)?;
+        let node = graph
+            .nodes
+            .get(node_id)
+            .ok_or_else(|| CircuitError::NodeNotFound(node_id.to_string()))?;
+        self.block_ports(&node.block_type, &node.config)
+    }
+
+    /// Ports a node of the given block type would have with `config`
+    ///
+    /// Editors call this while the config is edited, before the node is
+    /// part of a loaded graph.
+    pub fn block_ports(
+        &self,
+        block_type: &str,
+        config: &HashMap<String, Value>,
+    ) -> Result<NodePorts> {
+        let blocks = self.block_registry();
+        let block = blocks
+            .get(block_type)
+            .ok_or_else(|| CircuitError::Graph(format!("Unknown block type: {}", block_type)))?;
+        block.ports_for_config(config)
+    }
+
+    /// Execute a graph by ID
+    pub fn execute_graph(&self, graph_id: &str) -> Result<NodeOutputs> {
+        let graph = self.get_graph(graph_id)?;
+
+        self.execute(&graph)
+    }
+
+    /// Execute a graph by ID with execution budgets and cancellation
+    pub fn execute_graph_with_options(
+        &self,
+        graph_id: &str,
+        options: &ExecutionOptions,
+    ) -> Result<NodeOutputs> {
+        let graph = self.get_graph(graph_id)?;
+
+        self.execute_with_options(&graph, options)
+    }
+
+    /// Execute a graph
+    pub fn execute(&self, graph: &Graph) -> Result<NodeOutputs> {
+        self.execute_with_options(graph, &ExecutionOptions::default())
+    }
+
+    /// Execute a graph with per-run variables and host services
+    pub fn execute_with_context(
+        &self,
+        graph: &Graph,
+        context: ExecutionContext,
+    ) -> Result<NodeOutputs> {
+        self.execute_with_options(graph, &ExecutionOptions::new().with_context(context))
+    }
+
+    /// Execute a graph, stopping early when a budget is exhausted or the
+    /// execution is cancelled
+    pub fn execute_with_options(
+        &self,
+        graph: &Graph,
+        options: &ExecutionOptions,
+    ) -> Result<NodeOutputs> {
+        self.execute_with_inputs(graph, &HashMap::new(), options)
+    }
+
+    /// Execute a loaded graph, awaiting async blocks
+    pub async fn execute_graph_async(
+        &self,
+        graph_id: &str,
+        options: &ExecutionOptions,
+    ) -> Result<NodeOutputs> {
+        let graph = self.get_graph(graph_id)?;
+
+        self.execute_async(&graph, options).await
+    }
+
+    /// Execute a graph, awaiting async blocks
+    ///
+    /// A node starts as soon as the nodes it reads from have finished, so
+    /// independent async nodes run concurrently. Sync blocks run inline on
+    /// the task polling the execution. The future works with any executor,
+    /// including the browser's event loop on wasm32.
+    ///
+    /// Cancellation, the node-execution limit and value limits are enforced,
+    /// and the timeout is checked whenever a node starts or finishes. Retry
+    /// policies, checkpoints and freeing intermediate outputs only apply to
+    /// the synchronous `execute*` methods.
+    pub async fn execute_async(
+        &self,
+        graph: &Graph,
+        options: &ExecutionOptions,
+    ) -> Result<NodeOutputs> {
+        if options.cancellation.is_cancelled() {
+            return Err(CircuitError::Cancelled);
         }
+
+        let plan = ExecutionPlan::new(graph, &self.block_registry())?;
+        Self::run_plan_async(graph, &plan, &self.run_env(), options).await
     }
 
-    #[test]
-    fn test_engine_registration() {
-        let mut engine = Engine::new();
-        engine.register_block(Arc::new(AddBlock)).unwrap();
-        assert_eq!(engine.list_blocks().len(), 1);
+    /// Execute a graph with values supplied for node input ports
+    ///
+    /// Inputs are keyed by `"node.port"` and take precedence over values
+    /// arriving through connections.
+    pub fn execute_with_inputs(
+        &self,
+        graph: &Graph,
+        inputs: &HashMap<String, Value>,
+        options: &ExecutionOptions,
+    ) -> Result<NodeOutputs> {
+        if options.cancellation.is_cancelled() {
+            return Err(CircuitError::Cancelled);
+        }
+
+        let plan = ExecutionPlan::new(graph, &self.block_registry())?;
+        let mut state = RunState::new(group_graph_inputs(graph, inputs)?);
+        Self::run_plan(graph, &plan, &self.run_env(), options, &mut state, None)?;
+        Ok(state.outputs)
     }
 
-    #[test]
-    fn test_simple_execution() {
-        let mut engine = Engine::new();
-        engine.register_block(Arc::new(ConstantBlock)).unwrap();
-        engine.register_block(Arc::new(AddBlock)).unwrap();
+    /// Execute a loaded graph and return a structured report
+    pub fn execute_graph_report(
+        &self,
+        graph_id: &str,
+        options: &ExecutionOptions,
+    ) -> Result<ExecutionReport> {
+        let graph = self.get_graph(graph_id)?;
 
-        // Create a simple graph: const1(5) + const2(3) = add(8)
-        let mut graph = Graph::new("test".to_string(), "Test Graph".to_string());
+        self.execute_report(&graph, &HashMap::new(), options)
+    }
 
-        // Create constant nodes
-        let mut config1 = HashMap::new();
-        config1.insert("value".to_string(), Value::Float(5.0));
-        let node1 = Node {
-            id: "const1".to_string(),
-            block_type: "constant".to_string(),
-            config: config1,
-            position: None,
+    /// Execute a graph and return a structured report
+    ///
+    /// Only problems that keep the run from starting, such as an invalid graph
+    /// or unknown graph inputs, are returned as errors. Failures during the
+    /// run are recorded in the report. Durations are measured with the
+    /// execution context's clock.
+    pub fn execute_report(
+        &self,
+        graph: &Graph,
+        inputs: &HashMap<String, Value>,
+        options: &ExecutionOptions,
+    ) -> Result<ExecutionReport> {
+        let plan = ExecutionPlan::new(graph, &self.block_registry())?;
+        let mut state = RunState::new(group_graph_inputs(graph, inputs)?);
+
+        // Route block logging through a collector to pick up warnings
+        let collector = Arc::new(WarningCollector::new(options.context.shared_logger()));
+        let execution_id = options
+            .execution_id
+            .clone()
+            .unwrap_or_else(new_execution_id);
+        let mut run_options = options.clone();
+        run_options.context = options.context.clone().with_logger(collector.clone());
+        run_options.execution_id = Some(execution_id.clone());
+
+        let clock = options.context.clock();
+        let started = clock.now();
+        let mut nodes = Vec::with_capacity(plan.len());
+        let result = Self::run_plan(
+            graph,
+            &plan,
+            &self.run_env(),
+            &run_options,
+            &mut state,
+            Some(&mut nodes),
+        );
+
+        Ok(ExecutionReport {
+            execution_id,
+            graph_id: graph.id.clone(),
+            nodes,
+            total_duration: clock.now().saturating_sub(started),
+            outputs: state.outputs,
+            declared_outputs: HashMap::new(),
+            warnings: collector.take(),
+            error: result.err().map(|e| e.to_string()),
+        }
+        .finish(graph, &plan.node_order(), plan.pruned()))
+    }
+
+    /// Execute a loaded graph that may suspend to await external events
+    ///
+    /// A block suspends the run by returning `CircuitError::Suspended(token)`.
+    /// The engine then keeps the state of the execution, returns it as
+    /// `ExecutionOutcome::Suspended`, and continues it when `resume` is
+    /// called with the same token.
+    pub fn execute_suspendable(
+        &self,
+        graph_id: &str,
+        inputs: &HashMap<String, Value>,
+        options: &ExecutionOptions,
+    ) -> Result<ExecutionOutcome> {
+        let graph = self.get_graph(graph_id)?;
+        let state = RunState::new(group_graph_inputs(&graph, inputs)?);
+        let execution_id = options
+            .execution_id
+            .clone()
+            .unwrap_or_else(new_execution_id);
+        // Fail early; the ID is checked again when the run suspends
+        if read(&self.suspended).contains_key(&execution_id) {
+            return Err(CircuitError::InvalidInput(format!(
+                "Execution '{}' is already suspended",
+                execution_id
+            )));
+        }
+
+        self.continue_execution(&graph, execution_id, state, options)
+    }
+
+    /// Resume a suspended execution with the value it was waiting for
+    ///
+    /// The node that suspended runs again and can read `value` through
+    /// `BlockContext::resume_value`. The execution may suspend again.
+    pub fn resume(
+        &self,
+        execution_id: &str,
+        token: &str,
+        value: Value,
+    ) -> Result<ExecutionOutcome> {
+        self.resume_with_options(execution_id, token, value, &ExecutionOptions::default())
+    }
+
+    /// Resume a suspended execution with budgets and a context for the rest of the run
+    pub fn resume_with_options(
+        &self,
+        execution_id: &str,
+        token: &str,
+        value: Value,
+        options: &ExecutionOptions,
+    ) -> Result<ExecutionOutcome> {
+        let suspended = write(&self.suspended).remove(execution_id).ok_or_else(|| {
+            CircuitError::InvalidInput(format!("No suspended execution '{}'", execution_id))
+        })?;
+        // Put the execution back when it cannot be resumed yet
+        let keep = |suspended: SuspendedExecution| {
+            write(&self.suspended).insert(suspended.execution_id.clone(), suspended);
+        };
+        if suspended.token != token {
+            let error = CircuitError::InvalidInput(format!(
+                "Execution '{}' is waiting for '{}', not '{}'",
+                execution_id, suspended.token, token
+            ));
+            keep(suspended);
+            return Err(error);
+        }
+        let graph = match self.get_graph(&suspended.graph_id) {
+            Ok(graph) => graph,
+            Err(e) => {
+                keep(suspended);
+                return Err(e);
+            }
         };
 
-        let mut config2 = HashMap::new();
-        config2.insert("value".to_string(), Value::Float(3.0));
-        let node2 = Node {
-            id: "const2".to_string(),
-            block_type: "constant".to_string(),
-            config: config2,
-            position: None,
+        let state = RunState {
+            graph_inputs: suspended.inputs,
+            outputs: suspended.outputs,
+            completed: suspended.completed,
+            resume: Some((suspended.node_id, value)),
+            ..RunState::default()
         };
+        self.continue_execution(&graph, suspended.execution_id, state, options)
+    }
 
-        // Create add node
-        let node3 = Node {
-            id: "add".to_string(),
-            block_type: "add".to_string(),
-            config: HashMap::new(),
-            position: None,
+    // Run until the graph completes or a node suspends, keeping suspended state
+    fn continue_execution(
+        &self,
+        graph: &Graph,
+        execution_id: String,
+        mut state: RunState,
+        options: &ExecutionOptions,
+    ) -> Result<ExecutionOutcome> {
+        let plan = ExecutionPlan::new(graph, &self.block_registry())?;
+        // Checkpoints are written under the ID the run is suspended under
+        let options = options.clone().with_execution_id(execution_id.clone());
+        match Self::run_plan(graph, &plan, &self.run_env(), &options, &mut state, None) {
+            Ok(()) => Ok(ExecutionOutcome::Completed {
+                outputs: state.outputs,
+            }),
+            Err(CircuitError::Suspended(token)) => {
+                let suspended = SuspendedExecution {
+                    execution_id: execution_id.clone(),
+                    graph_id: graph.id.clone(),
+                    node_id: state.suspended_at.unwrap_or_default(),
+                    token,
+                    completed: state.completed,
+                    outputs: state.outputs,
+                    inputs: state.graph_inputs,
+                };
+                // Another run may have suspended under the same ID meanwhile
+                self.restore_suspended(suspended.clone())?;
+                Ok(ExecutionOutcome::Suspended(suspended))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Start a step-through debug session over a loaded graph
+    ///
+    /// Nothing runs until the session is stepped or resumed. The session
+    /// keeps every node output for inspection, so
+    /// `free_intermediate_outputs` is ignored; timeouts apply to each
+    /// resume or step separately.
+    pub fn debug(
+        &self,
+        graph_id: &str,
+        inputs: &HashMap<String, Value>,
+        options: &ExecutionOptions,
+    ) -> Result<DebugSession> {
+        let graph = self.get_graph(graph_id)?;
+        let plan = ExecutionPlan::new(&graph, &self.block_registry())?;
+        let state = RunState::new(group_graph_inputs(&graph, inputs)?);
+        let mut options = options.clone();
+        options.free_intermediate_outputs = false;
+        Ok(DebugSession::new(
+            graph,
+            plan,
+            self.run_env(),
+            options,
+            state,
+        ))
+    }
+
+    /// Execute a loaded graph, recording what is needed to replay it
+    ///
+    /// The recording holds the graph, the versions of the blocks it uses,
+    /// the inputs, variables and node state the execution started with, and
+    /// every node's inputs, outputs or error along with the clock, random
+    /// and store calls it made. A failing node does not make this return an
+    /// error; it ends the run and is recorded in `ExecutionRecording::error`.
+    pub fn execute_recorded(
+        &self,
+        graph_id: &str,
+        inputs: &HashMap<String, Value>,
+        options: &ExecutionOptions,
+    ) -> Result<ExecutionRecording> {
+        let graph = self.get_graph(graph_id)?;
+        let plan = ExecutionPlan::new(&graph, &self.block_registry())?;
+        let env = self.run_env();
+        let execution_id = options
+            .execution_id
+            .clone()
+            .unwrap_or_else(new_execution_id);
+        let node_states = env.node_states.graph(&graph.id);
+
+        // The timeout is measured outside the recording
+        let clock = options.context.shared_clock();
+        let (recorder, context) = Recorder::record(&options.context);
+        let options = options
+            .clone()
+            .with_execution_id(execution_id.clone())
+            .with_context(context);
+        let mut state = RunState {
+            recorder: Some(recorder),
+            deadline_clock: Some(clock),
+            ..RunState::new(group_graph_inputs(&graph, inputs)?)
         };
+        let result = Self::run_plan(&graph, &plan, &env, &options, &mut state, None);
 
-        graph.add_node(node1).unwrap();
-        graph.add_node(node2).unwrap();
-        graph.add_node(node3).unwrap();
+        Ok(ExecutionRecording {
+            format_version: RECORDING_FORMAT_VERSION,
+            execution_id,
+            blocks: plan_block_versions(&graph, &plan),
+            graph: (*graph).clone(),
+            inputs: inputs.clone(),
+            variables: options.context.variables().clone(),
+            engine_variables: (*env.variables).clone(),
+            node_states,
+            nodes: state.recorder.map(Recorder::into_nodes).unwrap_or_default(),
+            error: result.err().map(|e| e.to_string()),
+        })
+    }
 
-        // Connect nodes
-        graph
-            .add_connection(Connection {
-                from_node: "const1".to_string(),
-                from_port: "value".to_string(),
-                to_node: "add".to_string(),
-                to_port: "a".to_string(),
+    /// Run a recording again and report the first divergence from it
+    ///
+    /// The recorded graph runs with the recorded inputs, variables and node
+    /// state, leaving the engine's own state untouched. Clock, random and
+    /// store calls are answered from the recording, and impure blocks are
+    /// not run; their recorded outputs are used instead. The replay stops at
+    /// the first node whose inputs, service calls, outputs or error differ
+    /// from the recording. Blocks registered at a different version than
+    /// recorded are reported as warnings.
+    pub fn replay(
+        &self,
+        recording: &ExecutionRecording,
+        options: &ExecutionOptions,
+    ) -> Result<ReplayReport> {
+        let registry = self.block_registry();
+        let graph = &recording.graph;
+        let plan = ExecutionPlan::new(graph, &registry)?;
+        let current = plan_block_versions(graph, &plan);
+        let warnings = recording
+            .blocks
+            .iter()
+            .filter_map(|recorded| {
+                let version = current.iter().find(|b| b.id == recorded.id)?.version;
+                (version != recorded.version).then(|| {
+                    format!(
+                        "Block '{}' is at version {}, recorded at version {}",
+                        recorded.id, version, recorded.version
+                    )
+                })
             })
-            .unwrap();
+            .collect();
 
-        graph
-            .add_connection(Connection {
-                from_node: "const2".to_string(),
-                from_port: "value".to_string(),
-                to_node: "add".to_string(),
-                to_port: "b".to_string(),
-            })
-            .unwrap();
+        let node_states = Arc::new(NodeStates::default());
+        node_states.set_graph(&graph.id, recording.node_states.clone());
+        let env = RunEnv {
+            limits: self.limits(),
+            variables: Arc::new(recording.engine_variables.clone()),
+            node_states,
+        };
+        let clock = options.context.shared_clock();
+        let (recorder, context) = Recorder::replay(recording, &options.context);
+        let options = options
+            .clone()
+            .with_execution_id(recording.execution_id.clone())
+            .with_context(context);
+        let mut state = RunState {
+            recorder: Some(recorder),
+            deadline_clock: Some(clock),
+            ..RunState::new(group_graph_inputs(graph, &recording.inputs)?)
+        };
+        let result = Self::run_plan(graph, &plan, &env, &options, &mut state, None);
 
-        engine.load_graph(graph).unwrap();
+        // A substituted node failing ends the replay as the recording ended
+        let (divergence, substituted_failure) = state
+            .recorder
+            .map(Recorder::into_divergence)
+            .unwrap_or_default();
+        let error = match (&divergence, result) {
+            (Some(_), _) | (None, Ok(())) => None,
+            (None, Err(_)) if substituted_failure => recording.error.clone(),
+            (None, Err(e)) => Some(e.to_string()),
+        };
+        Ok(ReplayReport {
+            execution_id: recording.execution_id.clone(),
+            divergence,
+            outputs: state.outputs,
+            error,
+            warnings,
+        })
+    }
 
-        // Execute
-        let results = engine.execute_graph("test").unwrap();
+    /// Get the state of a suspended execution, e.g. to persist it
+    pub fn suspended_execution(&self, execution_id: &str) -> Option<SuspendedExecution> {
+        read(&self.suspended).get(execution_id).cloned()
+    }
 
-        // Verify result
-        let add_output = results.get("add").unwrap();
-        let result = add_output.get("result").unwrap();
-        assert_eq!(result.as_float(), Some(8.0));
+    /// Get the IDs of all suspended executions
+    pub fn list_suspended(&self) -> Vec<String> {
+        read(&self.suspended).keys().cloned().collect()
+    }
+
+    /// Hand back a suspended execution persisted earlier, e.g. after a restart
+    pub fn restore_suspended(&self, execution: SuspendedExecution) -> Result<()> {
+        let mut suspended = write(&self.suspended);
+        if suspended.contains_key(&execution.execution_id) {
+            return Err(CircuitError::InvalidInput(format!(
+                "Execution '{}' is already suspended",
+                execution.execution_id
+            )));
+        }
+        suspended.insert(execution.execution_id.clone(), execution);
+        Ok(())
+    }
+
+    /// Drop a suspended execution without resuming it
+    pub fn discard_suspended(&self, execution_id: &str) -> Option<SuspendedExecution> {
+        write(&self.suspended).remove(execution_id)
+    }
+
+    /// Continue an execution from its latest checkpoint, e.g. after a crash
+    ///
+    /// The checkpoint is read from `options.checkpoints` and the nodes it
+    /// lists as completed are not run again. New checkpoints are written to
+    /// the same store, under the same execution ID, as the run progresses.
+    pub fn resume_from_checkpoint(
+        &self,
+        execution_id: &str,
+        options: &ExecutionOptions,
+    ) -> Result<NodeOutputs> {
+        let store = options.checkpoints.as_ref().ok_or_else(|| {
+            CircuitError::InvalidInput("No checkpoint store in the execution options".to_string())
+        })?;
+        let checkpoint = store.load(execution_id)?.ok_or_else(|| {
+            CircuitError::InvalidInput(format!("No checkpoint for execution '{}'", execution_id))
+        })?;
+        let graph = self.get_graph(&checkpoint.graph_id)?;
+        let plan = ExecutionPlan::new(&graph, &self.block_registry())?;
+
+        let mut state = RunState {
+            graph_inputs: checkpoint.inputs,
+            outputs: checkpoint.outputs,
+            completed: checkpoint.completed,
+            ..RunState::default()
+        };
+        let options = options.clone().with_execution_id(execution_id);
+        Self::run_plan(&graph, &plan, &self.run_env(), &options, &mut state, None)?;
+        Ok(state.outputs)
+    }
+
+    /// Resolve a loaded graph into a reusable execution plan
+    pub fn plan(&self, graph_id: &str) -> Result<ExecutionPlan> {
+        let graph = self.get_graph(graph_id)?;
+        ExecutionPlan::new(&graph, &self.block_registry())
+    }
+
+    /// Execute a loaded graph once per input row
+    ///
+    /// The graph is planned once and every row is run against that plan.
+    /// Rows are keyed like `execute_with_inputs`; each row gets its own result
+    /// so one failing row does not abort the batch.
+    pub fn execute_batch(
+        &self,
+        graph_id: &str,
+        rows: Vec<HashMap<String, Value>>,
+    ) -> Result<Vec<Result<NodeOutputs>>> {
+        let graph = self.get_graph(graph_id)?;
+        let plan = ExecutionPlan::new(&graph, &self.block_registry())?;
+        let env = self.run_env();
+        let options = ExecutionOptions::default();
+
+        Ok(rows
+            .iter()
+            .map(|row| Self::run_row(&graph, &plan, row, &env, &options))
+            .collect())
+    }
+
+    /// Execute a loaded graph once per input row on up to `threads` threads
+    ///
+    /// Results are returned in row order.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn execute_batch_parallel(
+        &self,
+        graph_id: &str,
+        rows: Vec<HashMap<String, Value>>,
+        threads: usize,
+    ) -> Result<Vec<Result<NodeOutputs>>> {
+        let graph = self.get_graph(graph_id)?;
+        let plan = ExecutionPlan::new(&graph, &self.block_registry())?;
+        let env = self.run_env();
+        let options = ExecutionOptions::default();
+        if rows.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let chunk_size = rows.len().div_ceil(threads.max(1));
+        Ok(std::thread::scope(|scope| {
+            let workers: Vec<_> = rows
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    let (graph, plan, env, options) = (&graph, &plan, &env, &options);
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|row| Self::run_row(graph, plan, row, env, options))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|payload| panic::resume_unwind(payload))
+                })
+                .collect()
+        }))
+    }
+
+    /// Run a loaded graph as a pipeline processing a stream of items
+    ///
+    /// Nodes downstream of `options.inputs` become actors connected by
+    /// channels holding up to `options.capacity` items, so a slow node makes
+    /// the nodes before it wait. The other nodes run once, now, and their
+    /// outputs are reused for every item.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn start_pipeline(&self, graph_id: &str, options: PipelineOptions) -> Result<Pipeline> {
+        let graph = self.get_graph(graph_id)?;
+        let plan = ExecutionPlan::new(&graph, &self.block_registry())?;
+        let streamed = pipeline::streamed_nodes(&graph, &plan, &options.inputs)?;
+        let (streaming, fixed): (Vec<PlanStep>, Vec<PlanStep>) = plan
+            .steps
+            .into_iter()
+            .partition(|step| streamed.contains(&step.node_id));
+
+        let env = self.run_env();
+        let run_options = ExecutionOptions::new()
+            .with_context(options.context.clone())
+            .with_cancellation(options.cancellation.clone());
+        let mut state = RunState::default();
+        Self::run_plan(
+            &graph,
+            &ExecutionPlan {
+                steps: fixed,
+                pruned: Vec::new(),
+            },
+            &env,
+            &run_options,
+            &mut state,
+            None,
+        )?;
+        Pipeline::start(&graph, streaming, &state.outputs, env, &options)
+    }
+
+    // Snapshot of the engine state an execution starting now works with
+    fn run_env(&self) -> RunEnv {
+        RunEnv {
+            limits: self.limits(),
+            variables: Arc::clone(&read(&self.variables)),
+            node_states: Arc::clone(&self.node_states),
+        }
+    }
+
+    fn get_graph(&self, graph_id: &str) -> Result<Arc<Graph>> {
+        self.graph(graph_id)
+            .ok_or_else(|| CircuitError::Graph(format!("Graph '{}' not found", graph_id)))
+    }
+
+    // Snapshot of the block registry; later registrations do not affect it
+    fn block_registry(&self) -> Arc<BlockRegistry> {
+        Arc::clone(&read(&self.blocks))
+    }
+
+    fn run_row(
+        graph: &Graph,
+        plan: &ExecutionPlan,
+        row: &HashMap<String, Value>,
+        env: &RunEnv,
+        options: &ExecutionOptions,
+    ) -> Result<NodeOutputs> {
+        let mut state = RunState::new(group_graph_inputs(graph, row)?);
+        Self::run_plan(graph, plan, env, options, &mut state, None)?;
+        Ok(state.outputs)
+    }
+
+    /// Run a plan node by node, enforcing budgets and limits
+    ///
+    /// Outputs are written to `state` as nodes finish, so they are still
+    /// available when the run fails or suspends. Nodes already listed as
+    /// completed are skipped. When `trace` is given, a report of every node
+    /// that ran is appended to it. A run stopped by the pause hook returns
+    /// `Ok` with `paused_at` set, and continues from that node when called
+    /// again.
+    pub(crate) fn run_plan(
+        graph: &Graph,
+        plan: &ExecutionPlan,
+        env: &RunEnv,
+        options: &ExecutionOptions,
+        state: &mut RunState,
+        mut trace: Option<&mut Vec<NodeReport>>,
+    ) -> Result<()> {
+        let RunState {
+            graph_inputs,
+            outputs: node_outputs,
+            completed,
+            resume,
+            suspended_at,
+            pause_before,
+            paused_at,
+            recorder,
+            deadline_clock,
+        } = state;
+
+        let clock = deadline_clock
+            .clone()
+            .unwrap_or_else(|| options.context.shared_clock());
+        let deadline = options
+            .timeout
+            .map(|timeout| (clock.now() + timeout, timeout));
+        if options.cancellation.is_cancelled() {
+            return Err(CircuitError::Cancelled);
+        }
+
+        let skip: HashSet<NodeId> = completed.iter().cloned().collect();
+        let mut output_memory = OutputMemory::default();
+        let mut liveness = options
+            .free_intermediate_outputs
+            .then(|| OutputLiveness::new(graph, plan, &skip, options));
+
+        // Execute nodes in topological order
+        let limits = &env.limits;
+        let execution = options
+            .context
+            .clone()
+            .with_engine_variables(Arc::clone(&env.variables));
+        let checkpoints = match (&options.checkpoints, &options.execution_id) {
+            (Some(store), Some(execution_id)) => {
+                store.save(&Checkpoint {
+                    execution_id: execution_id.clone(),
+                    graph_id: graph.id.clone(),
+                    completed: completed.clone(),
+                    outputs: node_outputs.clone(),
+                    inputs: graph_inputs.clone(),
+                })?;
+                Some((store, execution_id))
+            }
+            // A generated ID would leave the host unable to resume the run
+            (Some(_), None) => {
+                return Err(CircuitError::InvalidInput(
+                    "Checkpoints need an execution ID, see ExecutionOptions::with_execution_id"
+                        .to_string(),
+                ))
+            }
+            (None, _) => None,
+        };
+        // Nodes this call started; skipped ones do not count towards the budget
+        let mut executed = 0;
+        for step in &plan.steps {
+            let node_id = &step.node_id;
+            if skip.contains(node_id) {
+                continue;
+            }
+
+            // Enforce budgets before starting the next node
+            if options.cancellation.is_cancelled() {
+                return Err(CircuitError::Cancelled);
+            }
+            if let Some((deadline, timeout)) = deadline {
+                if clock.now() >= deadline {
+                    return Err(CircuitError::Timeout(timeout));
+                }
+            }
+            if let Some(max) = options.max_node_executions {
+                if executed >= max {
+                    return Err(CircuitError::StepLimitExceeded(max));
+                }
+            }
+
+            let mut inputs = step.resolve_inputs(node_outputs, graph_inputs);
+            if let Some(pause) = pause_before.as_mut() {
+                if pause(node_id, &inputs) {
+                    *paused_at = Some(node_id.clone());
+                    return Ok(());
+                }
+            }
+            executed += 1;
+            let resume_value = match resume {
+                Some((resume_node, _)) if resume_node == node_id => resume.take().map(|(_, v)| v),
+                _ => None,
+            };
+
+            options.notify(|| ExecutionEvent::NodeStarted {
+                node_id: node_id.clone(),
+            });
+            let started = trace.is_some().then(|| options.context.clock().now());
+
+            // Execute the block, isolating panics so they surface as errors,
+            // and repeat failed attempts as the node's retry policy allows.
+            // Nodes overridden for debugging, and impure nodes in a replay,
+            // do not run their block.
+            let replayed = recorder
+                .as_mut()
+                .and_then(|recorder| recorder.before_node(step, &inputs));
+            let overridden = step.override_outputs(&inputs).map(Ok).or(replayed);
+            let mut attempts = 0;
+            let mut timed_out = false;
+            let outcome = if let Some(outcome) = overridden {
+                outcome
+            } else {
+                loop {
+                    attempts += 1;
+                    let mut context = BlockContext::for_execution(
+                        options.cancellation.clone(),
+                        execution.clone(),
+                    );
+                    context.config = step.config.clone();
+                    context.node_state = Some(NodeStateSlot {
+                        states: Arc::clone(&env.node_states),
+                        graph_id: graph.id.clone(),
+                        node_id: node_id.clone(),
+                    });
+                    context.resume_value = resume_value.clone();
+                    context.inputs = if step.retry.is_some() {
+                        inputs.clone()
+                    } else {
+                        std::mem::take(&mut inputs)
+                    };
+
+                    let result =
+                        panic::catch_unwind(AssertUnwindSafe(|| step.block.execute(context)))
+                            .unwrap_or_else(|payload| {
+                                Err(CircuitError::BlockPanicked {
+                                    node: node_id.clone(),
+                                    message: panic_message(payload.as_ref()),
+                                })
+                            });
+                    let error = match (result, &step.retry) {
+                        (Err(e), Some(policy)) if policy.should_retry(attempts, &e) => e,
+                        (result, _) => break result,
+                    };
+
+                    let delay = step
+                        .retry
+                        .as_ref()
+                        .map_or(Duration::ZERO, |policy| policy.delay_after(attempts));
+                    // Give up before a wait the budgets would cut short
+                    if options.cancellation.is_cancelled() {
+                        break Err(CircuitError::Cancelled);
+                    }
+                    if let Some((deadline, timeout)) = deadline {
+                        if clock.now() + delay >= deadline {
+                            timed_out = true;
+                            break Err(CircuitError::Timeout(timeout));
+                        }
+                    }
+                    options.notify(|| ExecutionEvent::NodeRetrying {
+                        node_id: node_id.clone(),
+                        attempt: attempts,
+                        error: error.to_string(),
+                        delay,
+                    });
+                    if !delay.is_zero() {
+                        options.context.clock().sleep(delay);
+                    }
+                    if options.cancellation.is_cancelled() {
+                        break Err(CircuitError::Cancelled);
+                    }
+                    if let Some((deadline, timeout)) = deadline {
+                        if clock.now() >= deadline {
+                            timed_out = true;
+                            break Err(CircuitError::Timeout(timeout));
+                        }
+                    }
+                }
+            };
+
+            if let Some(recorder) = recorder.as_mut() {
+                if !recorder.after_node(step, &outcome) {
+                    return Ok(());
+                }
+            }
+
+            let outcome = outcome
+                .map_err(|e| match e {
+                    e @ (CircuitError::Cancelled
+                    | CircuitError::Suspended(_)
+                    | CircuitError::BlockPanicked { .. }) => e,
+                    e @ CircuitError::Timeout(_) if timed_out => e,
+                    e => CircuitError::BlockExecution(format!("Node '{}': {}", node_id, e)),
+                })
+                .and_then(|outputs| {
+                    // Enforce value limits on what the block produced
+                    if limits.has_value_limits() {
+                        for (port, value) in &outputs {
+                            limits.check_value(value).map_err(|e| {
+                                prefix_limit_error(
+                                    e,
+                                    &format!("Node '{}' output '{}'", node_id, port),
+                                )
+                            })?;
+                        }
+                    }
+                    Ok(outputs)
+                });
+
+            let status = if outcome.is_ok() {
+                step.success_status()
+            } else {
+                NodeStatus::Failed
+            };
+            if let (Some(trace), Some(started)) = (trace.as_deref_mut(), started) {
+                trace.push(NodeReport {
+                    node_id: node_id.clone(),
+                    status,
+                    duration: options.context.clock().now().saturating_sub(started),
+                    attempts,
+                    error: outcome.as_ref().err().map(ToString::to_string),
+                });
+            }
+            options.notify(|| ExecutionEvent::NodeFinished {
+                node_id: node_id.clone(),
+                status,
+                attempts,
+            });
+            if let Err(CircuitError::Suspended(_)) = &outcome {
+                *suspended_at = Some(node_id.clone());
+            }
+            let mut outputs = outcome?;
+            completed.push(node_id.clone());
+            graph_inputs.remove(node_id);
+
+            // Drop outputs that no remaining node will read. Unused ports of
+            // this node were never counted, only upstream outputs are released.
+            let mut released = Vec::new();
+            if let Some(liveness) = liveness.as_mut() {
+                drop(liveness.release_unused(node_id, &mut outputs));
+                released = liveness.consume(graph, node_id, node_outputs);
+            }
+
+            if let Some(max) = limits.max_output_memory {
+                outputs.values().for_each(|value| output_memory.add(value));
+                released
+                    .iter()
+                    .for_each(|value| output_memory.release(value));
+                if output_memory.bytes() > max {
+                    return Err(CircuitError::LimitExceeded(format!(
+                        "Node outputs use about {} bytes after node '{}', limit is {}",
+                        output_memory.bytes(),
+                        node_id,
+                        max
+                    )));
+                }
+            }
+            drop(released);
+
+            if let Some((store, execution_id)) = checkpoints {
+                store.append(
+                    execution_id,
+                    &CheckpointStep {
+                        node_id: node_id.clone(),
+                        outputs: outputs.clone(),
+                    },
+                )?;
+            }
+
+            if liveness.is_none() || !outputs.is_empty() {
+                node_outputs.insert(node_id.clone(), outputs);
+            }
+        }
+
+        // A finished execution has nothing left to resume
+        if let Some((store, execution_id)) = checkpoints {
+            store.remove(execution_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Set a variable visible to every execution
+    ///
+    /// Variables set in an execution's context take precedence.
+    pub fn set_variable(&self, name: impl Into<String>, value: impl Into<Value>) {
+        Arc::make_mut(&mut write(&self.variables)).insert(name.into(), value.into());
+    }
+
+    /// Remove an engine variable, returning its value
+    pub fn remove_variable(&self, name: &str) -> Option<Value> {
+        Arc::make_mut(&mut write(&self.variables)).remove(name)
+    }
+
+    /// Get an engine variable by name
+    pub fn variable(&self, name: &str) -> Option<Value> {
+        read(&self.variables).get(name).cloned()
+    }
+
+    /// Get the block state stored by a node
+    pub fn node_state(&self, graph_id: &str, node_id: &str) -> Option<Value> {
+        self.node_states.get(graph_id, node_id)
+    }
+
+    /// Replace the block state of a node
+    pub fn set_node_state(&self, graph_id: &str, node_id: &str, value: Value) {
+        self.node_states.set(graph_id, node_id, value);
+    }
+
+    /// Capture loaded graphs, block versions, node state and variables
+    pub fn snapshot(&self) -> EngineSnapshot {
+        let mut blocks: Vec<BlockVersion> = read(&self.blocks)
+            .iter()
+            .map(|(id, block)| BlockVersion {
+                id: id.clone(),
+                version: block.version(),
+            })
+            .collect();
+        blocks.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut graphs: Vec<Graph> = read(&self.graphs)
+            .values()
+            .map(|graph| (**graph).clone())
+            .collect();
+        graphs.sort_by(|a, b| a.id.cmp(&b.id));
+
+        EngineSnapshot {
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            graphs,
+            blocks,
+            node_states: self.node_states.all(),
+            variables: (**read(&self.variables)).clone(),
+        }
+    }
+
+    /// Rehydrate the state captured by `snapshot`
+    ///
+    /// The block types of the snapshot must be registered, at the recorded
+    /// version or newer. Graphs are loaded, replacing loaded graphs with the
+    /// same ID; their node state and the engine variables are replaced by the
+    /// snapshot's. Graphs are migrated and aliases resolved as by
+    /// `load_graph`. Nothing changes if a check fails.
+    pub fn restore(&self, mut snapshot: EngineSnapshot) -> Result<()> {
+        let mut events = Vec::new();
+        let mut migrated = Vec::new();
+        {
+            let mut graphs = Vec::with_capacity(snapshot.graphs.len());
+            for graph in std::mem::take(&mut snapshot.graphs) {
+                let (graph, report) = self.upgrade_graph(graph)?;
+                if !report.is_empty() {
+                    migrated.push(LifecycleEvent::GraphMigrated {
+                        graph_id: report.graph_id,
+                        from_version: report.from_version,
+                        to_version: report.to_version,
+                        changes: report.changes,
+                    });
+                }
+                graphs.push(graph);
+            }
+            snapshot.graphs = graphs;
+            let aliases = read(&self.aliases);
+            for recorded in &mut snapshot.blocks {
+                recorded.id = aliases.block(&recorded.id).to_string();
+            }
+            drop(aliases);
+
+            let blocks = read(&self.blocks);
+            let mut missing: Vec<&str> = snapshot
+                .graphs
+                .iter()
+                .flat_map(|graph| graph.nodes.values())
+                .map(|node| node.block_type.as_str())
+                .chain(snapshot.blocks.iter().map(|block| block.id.as_str()))
+                .filter(|id| !blocks.contains_key(*id))
+                .collect();
+            if !missing.is_empty() {
+                missing.sort_unstable();
+                missing.dedup();
+                return Err(CircuitError::Graph(format!(
+                    "Snapshot needs unregistered block types: {}",
+                    missing.join(", ")
+                )));
+            }
+            for recorded in &snapshot.blocks {
+                let version = blocks[&recorded.id].version();
+                if version < recorded.version {
+                    return Err(CircuitError::Graph(format!(
+                        "Block type '{}' is version {}, snapshot needs version {} or newer",
+                        recorded.id, version, recorded.version
+                    )));
+                }
+            }
+            let limits = read(&self.limits);
+            for graph in &snapshot.graphs {
+                check_connections(graph, &resolve_ports(graph, &blocks)?)?;
+                limits.check_graph(graph)?;
+            }
+
+            let mut graphs = write(&self.graphs);
+            for graph in snapshot.graphs {
+                let graph_id = graph.id.clone();
+                let states = snapshot
+                    .node_states
+                    .get(&graph_id)
+                    .cloned()
+                    .unwrap_or_default();
+                self.node_states.set_graph(&graph_id, states);
+                events.push(match graphs.insert(graph_id.clone(), Arc::new(graph)) {
+                    Some(_) => LifecycleEvent::GraphReplaced { graph_id },
+                    None => LifecycleEvent::GraphLoaded { graph_id },
+                });
+            }
+            *write(&self.variables) = Arc::new(snapshot.variables);
+        }
+
+        for event in events.into_iter().chain(migrated) {
+            self.listeners.emit(event);
+        }
+        Ok(())
+    }
+
+    /// Run a plan as a dataflow, keeping every ready node in flight
+    async fn run_plan_async(
+        graph: &Graph,
+        plan: &ExecutionPlan,
+        env: &RunEnv,
+        options: &ExecutionOptions,
+    ) -> Result<NodeOutputs> {
+        // `Instant` is unavailable on wasm32; the timer also fires while
+        // every running node is pending
+        let clock = options.context.clock();
+        let deadline = options
+            .timeout
+            .map(|timeout| (clock.now() + timeout, timeout));
+        let mut timer = options.timeout.map(|timeout| clock.timer(timeout));
+        let execution = options
+            .context
+            .clone()
+            .with_engine_variables(Arc::clone(&env.variables));
+        let mut outputs = NodeOutputs::new();
+        let mut done: HashSet<&str> = HashSet::new();
+        let mut waiting: Vec<&PlanStep> = plan.steps.iter().collect();
+        let mut running: Vec<(&PlanStep, BlockFuture<'_>)> = Vec::new();
+        let mut started = 0;
+
+        std::future::poll_fn(move |cx| loop {
+            if options.cancellation.is_cancelled() {
+                return Poll::Ready(Err(CircuitError::Cancelled));
+            }
+            if let Some((deadline, timeout)) = deadline {
+                let fired = timer
+                    .as_mut()
+                    .is_some_and(|timer| timer.as_mut().poll(cx).is_ready());
+                if fired || clock.now() >= deadline {
+                    return Poll::Ready(Err(CircuitError::Timeout(timeout)));
+                }
+            }
+            let mut finished = Vec::new();
+
+            // Start every node whose upstream nodes have finished
+            let mut index = 0;
+            while index < waiting.len() {
+                let step = waiting[index];
+                if !step
+                    .incoming
+                    .iter()
+                    .all(|connection| done.contains(connection.from_node.as_str()))
+                {
+                    index += 1;
+                    continue;
+                }
+                waiting.remove(index);
+                if let Some(max) = options.max_node_executions {
+                    if started >= max {
+                        return Poll::Ready(Err(CircuitError::StepLimitExceeded(max)));
+                    }
+                }
+                started += 1;
+                options.notify(|| ExecutionEvent::NodeStarted {
+                    node_id: step.node_id.clone(),
+                });
+
+                let mut context =
+                    BlockContext::for_execution(options.cancellation.clone(), execution.clone());
+                context.config = step.config.clone();
+                context.node_state = Some(NodeStateSlot {
+                    states: Arc::clone(&env.node_states),
+                    graph_id: graph.id.clone(),
+                    node_id: step.node_id.clone(),
+                });
+                context.inputs = step.resolve_inputs(&outputs, &HashMap::new());
+
+                if let Some(node_outputs) = step.override_outputs(&context.inputs) {
+                    finished.push((step, Ok(node_outputs)));
+                    continue;
+                }
+                match step.block.as_async() {
+                    Some(block) => running.push((step, block.execute_async(context))),
+                    None => {
+                        let result =
+                            panic::catch_unwind(AssertUnwindSafe(|| step.block.execute(context)))
+                                .unwrap_or_else(|payload| {
+                                    Err(CircuitError::BlockPanicked {
+                                        node: step.node_id.clone(),
+                                        message: panic_message(payload.as_ref()),
+                                    })
+                                });
+                        finished.push((step, result));
+                    }
+                }
+            }
+
+            // Poll the running nodes; each registers the execution's waker
+            let mut index = 0;
+            while index < running.len() {
+                let (step, future) = &mut running[index];
+                let step = *step;
+                let result =
+                    match panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+                        Ok(Poll::Pending) => {
+                            index += 1;
+                            continue;
+                        }
+                        Ok(Poll::Ready(result)) => result,
+                        Err(payload) => Err(CircuitError::BlockPanicked {
+                            node: step.node_id.clone(),
+                            message: panic_message(payload.as_ref()),
+                        }),
+                    };
+                drop(running.swap_remove(index));
+                finished.push((step, result));
+            }
+
+            if finished.is_empty() {
+                if !running.is_empty() {
+                    return Poll::Pending;
+                }
+                if !waiting.is_empty() {
+                    return Poll::Ready(Err(CircuitError::Graph(format!(
+                        "Node '{}' waits for a node that never runs",
+                        waiting[0].node_id
+                    ))));
+                }
+                return Poll::Ready(Ok(std::mem::take(&mut outputs)));
+            }
+
+            for (step, result) in finished {
+                let node_id = &step.node_id;
+                let result = result
+                    .map_err(|e| match e {
+                        e @ (CircuitError::Cancelled
+                        | CircuitError::Suspended(_)
+                        | CircuitError::BlockPanicked { .. }) => e,
+                        e => CircuitError::BlockExecution(format!("Node '{}': {}", node_id, e)),
+                    })
+                    .and_then(|node_outputs| {
+                        if env.limits.has_value_limits() {
+                            for (port, value) in &node_outputs {
+                                env.limits.check_value(value).map_err(|e| {
+                                    prefix_limit_error(
+                                        e,
+                                        &format!("Node '{}' output '{}'", node_id, port),
+                                    )
+                                })?;
+                            }
+                        }
+                        Ok(node_outputs)
+                    });
+                options.notify(|| ExecutionEvent::NodeFinished {
+                    node_id: node_id.clone(),
+                    status: if result.is_ok() {
+                        step.success_status()
+                    } else {
+                        NodeStatus::Failed
+                    },
+                    attempts: u32::from(step.mode.is_normal()),
+                });
+                match result {
+                    Ok(node_outputs) => {
+                        outputs.insert(node_id.clone(), node_outputs);
+                        done.insert(node_id.as_str());
+                    }
+                    Err(e) => return Poll::Ready(Err(e)),
+                }
+            }
+        })
+        .await
+    }
+
+    /// Get list of registered block types
+    pub fn list_blocks(&self) -> Vec<String> {
+        read(&self.blocks).keys().cloned().collect()
+    }
+
+    /// Get list of loaded graphs
+    pub fn list_graphs(&self) -> Vec<String> {
+        read(&self.graphs).keys().cloned().collect()
+    }
+}
+
+// Versions of the blocks a plan uses, sorted by ID
+fn plan_block_versions(graph: &Graph, plan: &ExecutionPlan) -> Vec<BlockVersion> {
+    let mut blocks: Vec<BlockVersion> = plan
+        .steps
+        .iter()
+        .filter_map(|step| {
+            let node = graph.nodes.get(&step.node_id)?;
+            Some(BlockVersion {
+                id: node.block_type.clone(),
+                version: step.block.version(),
+            })
+        })
+        .collect();
+    blocks.sort_by(|a, b| a.id.cmp(&b.id));
+    blocks.dedup();
+    blocks
+}
+
+// Lock helpers that recover from poisoning; every write leaves the data consistent
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{BlockMetadata, PortDefinition};
+    use crate::context::LogLevel;
+    use crate::graph::{Connection, Node, NodeMode};
+    use crate::retry::RetryPolicy;
+
+    struct AddBlock;
+    impl Block for AddBlock {
+        fn metadata(&self) -> BlockMetadata {
+            BlockMetadata {
+                id: "add".to_string(),
+                name: "Add".to_string(),
+                description: "Adds two numbers".to_string(),
+                inputs: vec![
+                    PortDefinition {
+                        id: "a".to_string(),
+                        name: "A".to_string(),
+                        data_type: "number".to_string(),
+                        required: true,
+                    },
+                    PortDefinition {
+                        id: "b".to_string(),
+                        name: "B".to_string(),
+                        data_type: "number".to_string(),
+                        required: true,
+                    },
+                ],
+                outputs: vec![PortDefinition {
+                    id: "result".to_string(),
+                    name: "Result".to_string(),
+                    data_type: "number".to_string(),
+                    required: true,
+                }],
+                config_schema: HashMap::new(),
+            }
+        }
+
+        fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+            let a = context
+                .get_input("a")
+                .and_then(|v| v.as_float())
+                .ok_or_else(|| CircuitError::InvalidInput("Missing input 'a'".to_string()))?;
+            let b = context
+                .get_input("b")
+                .and_then(|v| v.as_float())
+                .ok_or_else(|| CircuitError::InvalidInput("Missing input 'b'".to_string()))?;
+
+            let mut outputs = HashMap::new();
+            outputs.insert("result".to_string(), Value::Float(a + b));
+            Ok(outputs)
+        }
+    }
+
+    struct ConstantBlock;
+    impl Block for ConstantBlock {
+        fn metadata(&self) -> BlockMetadata {
+            BlockMetadata {
+                id: "constant".to_string(),
+                name: "Constant".to_string(),
+                description: "Outputs a constant value".to_string(),
+                inputs: vec![],
+                outputs: vec![PortDefinition {
+                    id: "value".to_string(),
+                    name: "Value".to_string(),
+                    data_type: "number".to_string(),
+                    required: true,
+                }],
+                config_schema: HashMap::new(),
+            }
+        }
+
+        fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+            let value = context
+                .get_config("value")
+                .ok_or_else(|| CircuitError::InvalidInput("Missing config 'value'".to_string()))?
+                .clone();
+
+            let mut outputs = HashMap::new();
+            outputs.insert("value".to_string(), value);
+            Ok(outputs)
+        }
+    }
+
+    #[test]
+    fn test_engine_registration() {
+        let engine = Engine::new();
+        engine.register_block(Arc::new(AddBlock)).unwrap();
+        assert_eq!(engine.list_blocks().len(), 1);
+    }
+
+    #[test]
+    fn test_simple_execution() {
+        let engine = Engine::new();
+        engine.register_block(Arc::new(ConstantBlock)).unwrap();
+        engine.register_block(Arc::new(AddBlock)).unwrap();
+
+        // Create a simple graph: const1(5) + const2(3) = add(8)
+        let mut graph = Graph::new("test".to_string(), "Test Graph".to_string());
+
+        // Create constant nodes
+        let mut config1 = HashMap::new();
+        config1.insert("value".to_string(), Value::Float(5.0));
+        let node1 = Node::new("const1", "constant").with_config(config1);
+
+        let mut config2 = HashMap::new();
+        config2.insert("value".to_string(), Value::Float(3.0));
+        let node2 = Node::new("const2", "constant").with_config(config2);
+
+        // Create add node
+        let node3 = Node::new("add", "add");
+
+        graph.add_node(node1).unwrap();
+        graph.add_node(node2).unwrap();
+        graph.add_node(node3).unwrap();
+
+        // Connect nodes
+        graph
+            .add_connection(Connection {
+                from_node: "const1".to_string(),
+                from_port: "value".to_string(),
+                to_node: "add".to_string(),
+                to_port: "a".to_string(),
+            })
+            .unwrap();
+
+        graph
+            .add_connection(Connection {
+                from_node: "const2".to_string(),
+                from_port: "value".to_string(),
+                to_node: "add".to_string(),
+                to_port: "b".to_string(),
+            })
+            .unwrap();
+
+        engine.load_graph(graph).unwrap();
+
+        // Execute
+        let results = engine.execute_graph("test").unwrap();
+
+        // Verify result
+        let add_output = results.get("add").unwrap();
+        let result = add_output.get("result").unwrap();
+        assert_eq!(result.as_float(), Some(8.0));
+    }
+
+    struct SlowBlock;
+    impl Block for SlowBlock {
+        fn metadata(&self) -> BlockMetadata {
+            BlockMetadata {
+                id: "slow".to_string(),
+                name: "Slow".to_string(),
+                description: "Sleeps before passing its input through".to_string(),
+                inputs: vec![],
+                outputs: vec![],
+                config_schema: HashMap::new(),
+            }
+        }
+
+        fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+            if context.is_cancelled() {
+                return Err(CircuitError::Cancelled);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            Ok(context.inputs)
+        }
+    }
+
+    /// Build a chain of `len` slow nodes: slow0 -> slow1 -> ...
+    fn build_slow_chain(len: usize) -> Engine {
+        let engine = Engine::new();
+        engine.register_block(Arc::new(SlowBlock)).unwrap();
+
+        let mut graph = Graph::new("chain".to_string(), "Chain".to_string());
+        for i in 0..len {
+            graph
+                .add_node(Node::new(format!("slow{}", i), "slow"))
+                .unwrap();
+            if i > 0 {
+                graph
+                    .add_connection(Connection {
+                        from_node: format!("slow{}", i - 1),
+                        from_port: "value".to_string(),
+                        to_node: format!("slow{}", i),
+                        to_port: "value".to_string(),
+                    })
+                    .unwrap();
+            }
+        }
+        engine.load_graph(graph).unwrap();
+        engine
+    }
+
+    #[test]
+    fn test_step_limit_exceeded() {
+        let engine = build_slow_chain(3);
+        let options = ExecutionOptions::new().with_max_node_executions(2);
+
+        let result = engine.execute_graph_with_options("chain", &options);
+        assert!(matches!(result, Err(CircuitError::StepLimitExceeded(2))));
+    }
+
+    #[test]
+    fn test_step_limit_ignores_completed_nodes() {
+        use crate::checkpoint::{CheckpointStore, MemoryCheckpointStore};
+
+        let engine = build_slow_chain(3);
+        let store = Arc::new(MemoryCheckpointStore::new());
+        store
+            .save(&Checkpoint {
+                execution_id: "run-1".to_string(),
+                graph_id: "chain".to_string(),
+                completed: vec!["slow0".to_string(), "slow1".to_string()],
+                outputs: NodeOutputs::new(),
+                inputs: HashMap::new(),
+            })
+            .unwrap();
+
+        // Only slow2 is left to run
+        let options = ExecutionOptions::new()
+            .with_max_node_executions(1)
+            .with_checkpoints(store);
+        assert!(engine.resume_from_checkpoint("run-1", &options).is_ok());
+    }
+
+    #[test]
+    fn test_timeout() {
+        let engine = build_slow_chain(5);
+        let options = ExecutionOptions::new().with_timeout(std::time::Duration::from_millis(30));
+
+        let result = engine.execute_graph_with_options("chain", &options);
+        assert!(matches!(result, Err(CircuitError::Timeout(_))));
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let engine = build_slow_chain(3);
+        let options = ExecutionOptions::new();
+        options.cancellation.cancel();
+
+        let result = engine.execute_graph_with_options("chain", &options);
+        assert!(matches!(result, Err(CircuitError::Cancelled)));
+    }
+
+    #[test]
+    fn test_options_within_budget() {
+        let engine = build_slow_chain(2);
+        let options = ExecutionOptions::new()
+            .with_max_node_executions(2)
+            .with_timeout(std::time::Duration::from_secs(10));
+
+        let results = engine
+            .execute_graph_with_options("chain", &options)
+            .unwrap();
+        assert_eq!(results.len(), 2);
+    }
+
+    struct PanicBlock;
+    impl Block for PanicBlock {
+        fn metadata(&self) -> BlockMetadata {
+            BlockMetadata {
+                id: "panic".to_string(),
+                name: "Panic".to_string(),
+                description: "Always panics".to_string(),
+                inputs: vec![],
+                outputs: vec![],
+                config_schema: HashMap::new(),
+            }
+        }
+
+        fn execute(&self, _context: BlockContext) -> Result<HashMap<String, Value>> {
+            panic!("block exploded");
+        }
+    }
+
+    #[test]
+    fn test_block_panic_is_isolated() {
+        let engine = Engine::new();
+        engine.register_block(Arc::new(PanicBlock)).unwrap();
+
+        let mut graph = Graph::new("panics".to_string(), "Panics".to_string());
+        graph.add_node(Node::new("boom", "panic")).unwrap();
+        engine.load_graph(graph).unwrap();
+
+        match engine.execute_graph("panics") {
+            Err(CircuitError::BlockPanicked { node, message }) => {
+                assert_eq!(node, "boom");
+                assert_eq!(message, "block exploded");
+            }
+            other => panic!("expected BlockPanicked, got {:?}", other),
+        }
+
+        // The engine stays usable after a block panicked
+        assert!(engine.execute_graph("panics").is_err());
+    }
+
+    fn constant_graph(value: Value) -> Graph {
+        let mut graph = Graph::new("const".to_string(), "Const".to_string());
+        let mut config = HashMap::new();
+        config.insert("value".to_string(), value);
+        graph
+            .add_node(Node::new("c", "constant").with_config(config))
+            .unwrap();
+        graph
+    }
+
+    #[test]
+    fn test_limits_checked_at_load_time() {
+        let engine = Engine::with_limits(EngineLimits::new().with_max_value_depth(1));
+        engine.register_block(Arc::new(ConstantBlock)).unwrap();
+
+        let nested = Value::from(vec![Value::from(vec![Value::Int(1)])]);
+        let result = engine.load_graph(constant_graph(nested));
+        assert!(matches!(result, Err(CircuitError::LimitExceeded(_))));
+        assert!(engine.list_graphs().is_empty());
+    }
+
+    #[test]
+    fn test_output_memory_limit() {
+        let engine = Engine::new();
+        engine.register_block(Arc::new(ConstantBlock)).unwrap();
+        engine
+            .load_graph(constant_graph(Value::String("x".repeat(1024))))
+            .unwrap();
+        assert!(engine.execute_graph("const").is_ok());
+
+        engine.set_limits(EngineLimits::new().with_max_output_memory(512));
+        let result = engine.execute_graph("const");
+        assert!(matches!(result, Err(CircuitError::LimitExceeded(_))));
+    }
+
+    /// Build slow0 -> slow1 -> slow2 with a payload at the root
+    fn build_payload_chain(payload: Value) -> Engine {
+        let engine = build_slow_chain(3);
+        engine.register_block(Arc::new(ConstantBlock)).unwrap();
+
+        let mut graph = (*engine.graph("chain").unwrap()).clone();
+        let mut config = HashMap::new();
+        config.insert("value".to_string(), payload);
+        graph
+            .add_node(Node::new("payload", "constant").with_config(config))
+            .unwrap();
+        graph
+            .add_connection(Connection {
+                from_node: "payload".to_string(),
+                from_port: "value".to_string(),
+                to_node: "slow0".to_string(),
+                to_port: "value".to_string(),
+            })
+            .unwrap();
+        engine.replace_graph(graph).unwrap();
+        engine
+    }
+
+    #[test]
+    fn test_free_intermediate_outputs_keeps_sinks() {
+        let engine = build_payload_chain(Value::Int(7));
+        let options = ExecutionOptions::new().with_free_intermediate_outputs(true);
+
+        let results = engine
+            .execute_graph_with_options("chain", &options)
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results["slow2"]["value"], Value::Int(7));
+    }
+
+    #[test]
+    fn test_free_intermediate_outputs_keeps_requested_and_declared() {
+        let engine = build_payload_chain(Value::Int(7));
+        let mut graph = (*engine.graph("chain").unwrap()).clone();
+        graph.add_output("slow0", "value").unwrap();
+        engine.replace_graph(graph).unwrap();
+
+        let options = ExecutionOptions::new()
+            .with_free_intermediate_outputs(true)
+            .keep_output("payload");
+
+        let results = engine
+            .execute_graph_with_options("chain", &options)
+            .unwrap();
+        let mut kept: Vec<&String> = results.keys().collect();
+        kept.sort();
+        assert_eq!(kept, vec!["payload", "slow0"]);
+    }
+
+    #[test]
+    fn test_free_intermediate_outputs_ignores_pruned_consumers() {
+        let engine = build_payload_chain(Value::Int(7));
+        let mut graph = (*engine.graph("chain").unwrap()).clone();
+        // `side` only feeds the pinned `pin`, so it is pruned and never
+        // consumes `payload`
+        graph.add_node(Node::new("side", "slow")).unwrap();
+        let pinned = HashMap::from([("value".to_string(), Value::Int(1))]);
+        graph
+            .add_node(Node::new("pin", "slow").with_mode(NodeMode::Pinned(pinned)))
+            .unwrap();
+        for (from, to) in [("payload", "side"), ("side", "pin")] {
+            graph
+                .add_connection(Connection {
+                    from_node: from.to_string(),
+                    from_port: "value".to_string(),
+                    to_node: to.to_string(),
+                    to_port: "value".to_string(),
+                })
+                .unwrap();
+        }
+        engine.replace_graph(graph).unwrap();
+
+        let options = ExecutionOptions::new().with_free_intermediate_outputs(true);
+        let results = engine
+            .execute_graph_with_options("chain", &options)
+            .unwrap();
+        let mut kept: Vec<&String> = results.keys().collect();
+        kept.sort();
+        assert_eq!(kept, vec!["pin", "slow2"]);
+    }
+
+    #[test]
+    fn test_free_intermediate_outputs_lowers_peak_memory() {
+        let payload = Value::String("x".repeat(1024));
+        let limits = EngineLimits::new().with_max_output_memory(2 * payload.estimated_size() + 64);
+
+        // Every node forwards the payload, so keeping all four copies exceeds the limit
+        let engine = build_payload_chain(payload);
+        engine.set_limits(limits);
+        let result = engine.execute_graph("chain");
+        assert!(matches!(result, Err(CircuitError::LimitExceeded(_))));
+
+        // At most two copies are alive at once when intermediates are freed
+        let options = ExecutionOptions::new().with_free_intermediate_outputs(true);
+        assert!(engine.execute_graph_with_options("chain", &options).is_ok());
+    }
+
+    #[test]
+    fn test_output_memory_counts_shared_payloads_once() {
+        let payload = Value::Bytes(Arc::new(vec![0; 4096]));
+        let limits = EngineLimits::new().with_max_output_memory(2 * payload.estimated_size());
+
+        // All four nodes output the same bytes, which are held only once
+        let engine = build_payload_chain(payload);
+        engine.set_limits(limits);
+        assert!(engine.execute_graph("chain").is_ok());
+    }
+
+    // Forwards its input and outputs an unread copy of it next to it
+    struct ForkBlock;
+    impl Block for ForkBlock {
+        fn metadata(&self) -> BlockMetadata {
+            BlockMetadata {
+                id: "fork".to_string(),
+                name: "Fork".to_string(),
+                description: "Passes its input through along with a copy".to_string(),
+                inputs: vec![],
+                outputs: vec![],
+                config_schema: HashMap::new(),
+            }
+        }
+
+        fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+            let value = context.inputs["value"].clone();
+            let copy = Value::String(value.as_str().unwrap_or_default().to_string());
+            Ok(HashMap::from([
+                ("value".to_string(), value),
+                ("copy".to_string(), copy),
+            ]))
+        }
+    }
+
+    #[test]
+    fn test_output_memory_ignores_unread_ports_when_freeing() {
+        let payload = Value::String("x".repeat(4096));
+        let size = payload.estimated_size();
+
+        // payload -> fork -> slow0 -> slow1, nobody reads fork.copy
+        let engine = build_payload_chain(payload);
+        engine.register_block(Arc::new(ForkBlock)).unwrap();
+        let mut graph = (*engine.graph("chain").unwrap()).clone();
+        graph.remove_node("slow2").unwrap();
+        graph.add_node(Node::new("fork", "fork")).unwrap();
+        graph.connections.clear();
+        for (from, to) in [("payload", "fork"), ("fork", "slow0"), ("slow0", "slow1")] {
+            graph
+                .add_connection(Connection {
+                    from_node: from.to_string(),
+                    from_port: "value".to_string(),
+                    to_node: to.to_string(),
+                    to_port: "value".to_string(),
+                })
+                .unwrap();
+        }
+        engine.replace_graph(graph).unwrap();
+        engine.set_limits(EngineLimits::new().with_max_output_memory(size * 5 / 2));
+
+        // payload, slow0 and slow1 are kept: three copies at the end
+        let options = ExecutionOptions::new()
+            .with_free_intermediate_outputs(true)
+            .keep_output("payload")
+            .keep_output("slow0")
+            .keep_output("slow1");
+        let result = engine.execute_graph_with_options("chain", &options);
+        assert!(matches!(result, Err(CircuitError::LimitExceeded(_))));
+    }
+
+    fn build_add_engine() -> Engine {
+        let engine = Engine::new();
+        engine.register_block(Arc::new(ConstantBlock)).unwrap();
+        engine.register_block(Arc::new(AddBlock)).unwrap();
+
+        let mut graph = Graph::new("sum".to_string(), "Sum".to_string());
+        let mut config = HashMap::new();
+        config.insert("value".to_string(), Value::Float(1.0));
+        graph
+            .add_node(Node::new("one", "constant").with_config(config))
+            .unwrap();
+        graph.add_node(Node::new("add", "add")).unwrap();
+        graph
+            .add_connection(Connection {
+                from_node: "one".to_string(),
+                from_port: "value".to_string(),
+                to_node: "add".to_string(),
+                to_port: "a".to_string(),
+            })
+            .unwrap();
+        engine.load_graph(graph).unwrap();
+        engine
+    }
+
+    fn row(entries: &[(&str, f64)]) -> HashMap<String, Value> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), Value::Float(*value)))
+            .collect()
+    }
+
+    #[test]
+    fn test_execute_with_inputs_overrides_connections() {
+        let engine = build_add_engine();
+        let graph = engine.graph("sum").unwrap();
+
+        let inputs = row(&[("add.a", 10.0), ("add.b", 2.0)]);
+        let results = engine
+            .execute_with_inputs(&graph, &inputs, &ExecutionOptions::default())
+            .unwrap();
+        assert_eq!(results["add"]["result"], Value::Float(12.0));
+    }
+
+    #[test]
+    fn test_execute_batch_reports_per_row_errors() {
+        let engine = build_add_engine();
+        let rows = vec![
+            row(&[("add.b", 2.0)]),
+            row(&[]),
+            row(&[("missing.b", 1.0)]),
+            row(&[("add.b", 5.0)]),
+        ];
+
+        let results = engine.execute_batch("sum", rows).unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(
+            results[0].as_ref().unwrap()["add"]["result"],
+            Value::Float(3.0)
+        );
+        assert!(matches!(results[1], Err(CircuitError::BlockExecution(_))));
+        assert!(matches!(results[2], Err(CircuitError::NodeNotFound(_))));
+        assert_eq!(
+            results[3].as_ref().unwrap()["add"]["result"],
+            Value::Float(6.0)
+        );
+
+        assert!(engine.execute_batch("missing", Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_execute_batch_parallel_preserves_order() {
+        let engine = build_add_engine();
+        let rows: Vec<_> = (0..25).map(|i| row(&[("add.b", i as f64)])).collect();
+
+        let results = engine.execute_batch_parallel("sum", rows, 4).unwrap();
+        assert_eq!(results.len(), 25);
+        for (i, result) in results.iter().enumerate() {
+            assert_eq!(
+                result.as_ref().unwrap()["add"]["result"],
+                Value::Float(i as f64 + 1.0)
+            );
+        }
+        assert!(engine
+            .execute_batch_parallel("sum", Vec::new(), 4)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_engine_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Engine>();
+    }
+
+    #[test]
+    fn test_running_execution_keeps_graph_snapshot() {
+        let engine = Arc::new(build_slow_chain(3));
+
+        let running = {
+            let engine = Arc::clone(&engine);
+            std::thread::spawn(move || engine.execute_graph("chain"))
+        };
+
+        // Replace the graph with a shorter one while the first run is in flight
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let mut graph = Graph::new("chain".to_string(), "Chain".to_string());
+        graph.add_node(Node::new("only", "slow")).unwrap();
+        engine.replace_graph(graph).unwrap();
+
+        assert_eq!(running.join().unwrap().unwrap().len(), 3);
+        assert_eq!(engine.execute_graph("chain").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_load_new_graph_refuses_duplicate_ids() {
+        let engine = build_add_engine();
+        let mut graph = (*engine.graph("sum").unwrap()).clone();
+
+        assert!(matches!(
+            engine.load_new_graph(graph.clone()),
+            Err(CircuitError::Graph(_))
+        ));
+        assert!(engine.replace_graph(graph.clone()).is_ok());
+
+        // Plain loading overwrites, as hosts reloading a graph expect
+        graph.remove_node("add").unwrap();
+        engine.load_graph(graph).unwrap();
+        assert_eq!(engine.graph("sum").unwrap().nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_replace_graph_validates_before_swapping() {
+        let engine = build_add_engine();
+        let mut graph = (*engine.graph("sum").unwrap()).clone();
+        graph.add_node(Node::new("unknown", "missing")).unwrap();
+
+        assert!(engine.replace_graph(graph).is_err());
+        assert_eq!(engine.graph("sum").unwrap().nodes.len(), 2);
+
+        let missing = Graph::new("other".to_string(), "Other".to_string());
+        assert!(engine.replace_graph(missing).is_err());
+    }
+
+    #[test]
+    fn test_unload_graph() {
+        let engine = build_add_engine();
+        let removed = engine.unload_graph("sum").unwrap();
+        assert_eq!(removed.id, "sum");
+        assert!(engine.list_graphs().is_empty());
+        assert!(engine.unload_graph("sum").is_err());
+    }
+
+    #[test]
+    fn test_unregister_block_refuses_while_in_use() {
+        let engine = build_add_engine();
+
+        let err = engine.unregister_block("add").unwrap_err();
+        assert!(err.to_string().contains("sum"));
+        assert_eq!(engine.list_blocks().len(), 2);
+
+        engine.unload_graph("sum").unwrap();
+        engine.unregister_block("add").unwrap();
+        assert_eq!(engine.list_blocks(), vec!["constant".to_string()]);
+        assert!(engine.unregister_block("add").is_err());
+    }
+
+    struct SubtractBlock;
+    impl Block for SubtractBlock {
+        fn metadata(&self) -> BlockMetadata {
+            BlockMetadata {
+                id: "add".to_string(),
+                ..AddBlock.metadata()
+            }
+        }
+
+        fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+            let a = context
+                .get_input("a")
+                .and_then(|v| v.as_float())
+                .unwrap_or(0.0);
+            let b = context
+                .get_input("b")
+                .and_then(|v| v.as_float())
+                .unwrap_or(0.0);
+
+            let mut outputs = HashMap::new();
+            outputs.insert("result".to_string(), Value::Float(a - b));
+            Ok(outputs)
+        }
+    }
+
+    #[test]
+    fn test_replace_block_hot_swaps_implementation() {
+        let engine = build_add_engine();
+        let inputs = row(&[("add.b", 3.0)]);
+        let graph = engine.graph("sum").unwrap();
+        let options = ExecutionOptions::default();
+
+        let results = engine
+            .execute_with_inputs(&graph, &inputs, &options)
+            .unwrap();
+        assert_eq!(results["add"]["result"], Value::Float(4.0));
+
+        engine.replace_block(Arc::new(SubtractBlock)).unwrap();
+        let results = engine
+            .execute_with_inputs(&graph, &inputs, &options)
+            .unwrap();
+        assert_eq!(results["add"]["result"], Value::Float(-2.0));
+
+        let err = Engine::new().replace_block(Arc::new(SubtractBlock));
+        assert!(matches!(err, Err(CircuitError::Graph(_))));
+    }
+
+    #[test]
+    fn test_lifecycle_events() {
+        let engine = Engine::new();
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let listener = engine.add_lifecycle_listener(Arc::new(move |event: &LifecycleEvent| {
+            sink.lock().unwrap().push(event.clone());
+        }));
+
+        engine.register_block(Arc::new(AddBlock)).unwrap();
+        engine.replace_block(Arc::new(SubtractBlock)).unwrap();
+        let graph = Graph::new("g".to_string(), "G".to_string());
+        engine.load_graph(graph.clone()).unwrap();
+        engine.replace_graph(graph).unwrap();
+        engine.unload_graph("g").unwrap();
+        engine.unregister_block("add").unwrap();
+
+        assert!(engine.remove_lifecycle_listener(listener));
+        engine.register_block(Arc::new(AddBlock)).unwrap();
+
+        let block_id = || "add".to_string();
+        let graph_id = || "g".to_string();
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                LifecycleEvent::BlockRegistered {
+                    block_id: block_id()
+                },
+                LifecycleEvent::BlockReplaced {
+                    block_id: block_id()
+                },
+                LifecycleEvent::GraphLoaded {
+                    graph_id: graph_id()
+                },
+                LifecycleEvent::GraphReplaced {
+                    graph_id: graph_id()
+                },
+                LifecycleEvent::GraphUnloaded {
+                    graph_id: graph_id()
+                },
+                LifecycleEvent::BlockUnregistered {
+                    block_id: block_id()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_graph_migrates_and_resolves_aliases() {
+        let engine = build_add_engine();
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        engine.add_lifecycle_listener(Arc::new(move |event: &LifecycleEvent| {
+            sink.lock().unwrap().push(event.clone());
+        }));
+        engine.register_migration(
+            0,
+            "Rename title to name",
+            Arc::new(|doc| {
+                let title = doc.as_object_mut().unwrap().remove("title");
+                doc["name"] = title.unwrap_or_default();
+                Ok(())
+            }),
+        );
+        engine.add_block_alias("const", "constant").unwrap();
+        let err = engine.add_block_alias("add", "constant").unwrap_err();
+        assert!(matches!(err, CircuitError::InvalidInput(_)));
+        engine.add_port_alias("constant", "out", "value").unwrap();
+
+        // An unversioned document written before the renames
+        let mut doc = serde_json::to_value(&*engine.graph("sum").unwrap()).unwrap();
+        let doc = doc.as_object_mut().unwrap();
+        doc.remove("format_version");
+        doc.insert("id".to_string(), "legacy".into());
+        doc.insert("title".to_string(), doc["name"].clone());
+        doc.remove("name");
+        doc["nodes"]["one"]["block_type"] = "const".into();
+        doc["connections"][0]["from_port"] = "out".into();
+        let json = serde_json::to_string(doc).unwrap();
+
+        let report = engine.load_graph_json(&json).unwrap();
+        assert_eq!(
+            (report.from_version, report.to_version),
+            (0, GRAPH_FORMAT_VERSION)
+        );
+        // The implicit upgrade to version 1 is not a change
+        assert_eq!(report.changes.len(), 3);
+        let graph = engine.graph("legacy").unwrap();
+        assert_eq!(graph.format_version, GRAPH_FORMAT_VERSION);
+        assert_eq!(graph.name, "Sum");
+        assert_eq!(graph.nodes["one"].block_type, "constant");
+        assert_eq!(graph.connections[0].from_port, "value");
+        assert_eq!(
+            events.lock().unwrap()[1],
+            LifecycleEvent::GraphMigrated {
+                graph_id: "legacy".to_string(),
+                from_version: 0,
+                to_version: GRAPH_FORMAT_VERSION,
+                changes: report.changes,
+            }
+        );
+
+        // Current graphs only get their aliases resolved, and announce nothing
+        // when there are none
+        let mut current = (*graph).clone();
+        current.nodes.get_mut("one").unwrap().block_type = "const".to_string();
+        engine.replace_graph(current).unwrap();
+        assert_eq!(
+            engine.graph("legacy").unwrap().nodes["one"].block_type,
+            "constant"
+        );
+        events.lock().unwrap().clear();
+        engine.replace_graph((*graph).clone()).unwrap();
+        assert_eq!(events.lock().unwrap().len(), 1);
+
+        // Neither is an unversioned graph that needs nothing else
+        let plain_engine = build_add_engine();
+        let sink = Arc::clone(&events);
+        plain_engine.add_lifecycle_listener(Arc::new(move |event: &LifecycleEvent| {
+            sink.lock().unwrap().push(event.clone());
+        }));
+        events.lock().unwrap().clear();
+        let mut plain = serde_json::to_value(&*plain_engine.graph("sum").unwrap()).unwrap();
+        plain.as_object_mut().unwrap().remove("format_version");
+        let report = plain_engine.load_graph_json(&plain.to_string()).unwrap();
+        assert!(report.is_empty());
+        assert_eq!(events.lock().unwrap().len(), 1);
+
+        let newer = json.replace("\"id\":", "\"format_version\":99,\"id\":");
+        assert!(engine.migrate_graph(&newer).is_err());
+    }
+
+    #[test]
+    fn test_register_block_rejects_alias() {
+        let engine = Engine::new();
+        engine.register_block(Arc::new(ConstantBlock)).unwrap();
+        engine.add_block_alias("add", "constant").unwrap();
+
+        let err = engine.register_block(Arc::new(AddBlock)).unwrap_err();
+        assert!(matches!(err, CircuitError::InvalidInput(_)));
+        assert!(engine.list_blocks().iter().all(|id| id != "add"));
+    }
+
+    struct ContextProbeBlock;
+    impl Block for ContextProbeBlock {
+        fn metadata(&self) -> BlockMetadata {
+            BlockMetadata {
+                id: "probe".to_string(),
+                name: "Probe".to_string(),
+                description: "Reads execution variables and services".to_string(),
+                inputs: vec![],
+                outputs: vec![],
+                config_schema: HashMap::new(),
+            }
+        }
+
+        fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+            let execution = context.execution();
+            execution.logger().log(LogLevel::Warn, "probe ran");
+            let mut outputs = HashMap::new();
+            if let Some(user) = context.variable("user_id") {
+                outputs.insert("user_id".to_string(), user.clone());
+            }
+            outputs.insert(
+                "now".to_string(),
+                Value::Int(execution.clock().now().as_secs() as i64),
+            );
+            outputs.insert(
+                "random".to_string(),
+                Value::Int((execution.random().next_u64() % 1000) as i64),
+            );
+            Ok(outputs)
+        }
+    }
+
+    #[test]
+    fn test_execution_context_is_exposed_to_blocks() {
+        use crate::context::{FakeClock, SeededRandom};
+
+        let engine = Engine::new();
+        engine.register_block(Arc::new(ContextProbeBlock)).unwrap();
+        let mut graph = Graph::new("probe".to_string(), "Probe".to_string());
+        graph.add_node(Node::new("probe", "probe")).unwrap();
+
+        let run = || {
+            let context = ExecutionContext::new()
+                .with_variable("user_id", "u-1")
+                .with_clock(Arc::new(FakeClock::new(std::time::Duration::from_secs(
+                    1_700,
+                ))))
+                .with_random(Arc::new(SeededRandom::new(42)));
+            engine.execute_with_context(&graph, context).unwrap()
+        };
+
+        let first = run();
+        assert_eq!(first["probe"]["user_id"], Value::String("u-1".to_string()));
+        assert_eq!(first["probe"]["now"], Value::Int(1_700));
+        assert_eq!(first, run());
+    }
+
+    #[test]
+    fn test_execution_report_statuses() {
+        let engine = build_add_engine();
+        let mut graph = (*engine.graph("sum").unwrap()).clone();
+        graph.add_node(Node::new("after", "add")).unwrap();
+        graph
+            .add_connection(Connection {
+                from_node: "add".to_string(),
+                from_port: "result".to_string(),
+                to_node: "after".to_string(),
+                to_port: "a".to_string(),
+            })
+            .unwrap();
+        graph.add_output("one", "value").unwrap();
+
+        // `add` is missing input `b`, so it fails and `after` never runs
+        let options = ExecutionOptions::new().with_execution_id("run-7");
+        let report = engine
+            .execute_report(&graph, &HashMap::new(), &options)
+            .unwrap();
+
+        assert_eq!(report.execution_id, "run-7");
+        assert!(!report.is_success());
+        assert!(report.error.as_ref().unwrap().contains("Node 'add'"));
+        let statuses: Vec<_> = report.nodes.iter().map(|n| n.status).collect();
+        assert_eq!(
+            statuses,
+            vec![NodeStatus::Ok, NodeStatus::Failed, NodeStatus::Skipped]
+        );
+        assert!(report.node("add").unwrap().error.is_some());
+        assert_eq!(report.declared_outputs["one.value"], Value::Float(1.0));
+        assert_eq!(report.outputs["one"]["value"], Value::Float(1.0));
+
+        // The plain API still reports the first error
+        assert!(engine.execute(&graph).is_err());
+    }
+
+    #[test]
+    fn test_node_modes_override_outputs() {
+        let engine = build_add_engine();
+        let mut graph = (*engine.graph("sum").unwrap()).clone();
+        let options = ExecutionOptions::new();
+
+        // A bypassed `add` forwards `a` instead of failing on the missing `b`
+        graph.nodes.get_mut("add").unwrap().mode = NodeMode::Bypass;
+        let report = engine
+            .execute_report(&graph, &HashMap::new(), &options)
+            .unwrap();
+        assert!(report.is_success());
+        assert_eq!(report.outputs["add"]["result"], Value::Float(1.0));
+        let statuses: Vec<_> = report.nodes.iter().map(|n| n.status).collect();
+        assert_eq!(statuses, vec![NodeStatus::Ok, NodeStatus::Bypassed]);
+        assert_eq!(report.node("add").unwrap().attempts, 0);
+
+        // Nothing reads `one` once `add` is muted, so it is skipped
+        graph.nodes.get_mut("add").unwrap().mode = NodeMode::Mute;
+        let report = engine
+            .execute_report(&graph, &HashMap::new(), &options)
+            .unwrap();
+        assert_eq!(report.outputs["add"]["result"], Value::Null);
+        let statuses: Vec<_> = report.nodes.iter().map(|n| n.status).collect();
+        assert_eq!(statuses, vec![NodeStatus::Muted, NodeStatus::Skipped]);
+        assert_eq!(report.nodes[1].node_id, "one");
+        assert!(!report.outputs.contains_key("one"));
+
+        let pinned = HashMap::from([("value".to_string(), Value::Float(5.0))]);
+        graph.nodes.get_mut("one").unwrap().mode = NodeMode::Pinned(pinned);
+        graph.nodes.get_mut("add").unwrap().mode = NodeMode::Bypass;
+        let report = engine
+            .execute_report(&graph, &HashMap::new(), &options)
+            .unwrap();
+        assert_eq!(report.node("one").unwrap().status, NodeStatus::Cached);
+        assert_eq!(report.outputs["add"]["result"], Value::Float(5.0));
+
+        let outputs = crate::async_block::block_on(engine.execute_async(&graph, &options)).unwrap();
+        assert_eq!(outputs["add"]["result"], Value::Float(5.0));
+    }
+
+    #[test]
+    fn test_execution_report_collects_warnings() {
+        use crate::context::MemoryLogger;
+
+        let engine = Engine::new();
+        engine.register_block(Arc::new(ContextProbeBlock)).unwrap();
+        let mut graph = Graph::new("probe".to_string(), "Probe".to_string());
+        graph.add_node(Node::new("probe", "probe")).unwrap();
+        engine.load_graph(graph).unwrap();
+
+        let logger = Arc::new(MemoryLogger::new());
+        let options = ExecutionOptions::new()
+            .with_context(ExecutionContext::new().with_logger(logger.clone()));
+        let report = engine.execute_graph_report("probe", &options).unwrap();
+
+        assert!(report.is_success());
+        assert_eq!(report.count(NodeStatus::Ok), 1);
+        assert_eq!(report.warnings, vec!["probe ran".to_string()]);
+        assert_eq!(logger.entries().len(), 1);
+        assert_eq!(report.execution_id.len(), 16);
+    }
+
+    // Fails with the given error until it has been called `failures` times
+    struct FlakyBlock {
+        failures: usize,
+        calls: std::sync::atomic::AtomicUsize,
+        error: fn() -> CircuitError,
+    }
+
+    impl FlakyBlock {
+        fn new(failures: usize, error: fn() -> CircuitError) -> Self {
+            Self {
+                failures,
+                calls: std::sync::atomic::AtomicUsize::new(0),
+                error,
+            }
+        }
+    }
+
+    impl Block for FlakyBlock {
+        fn metadata(&self) -> BlockMetadata {
+            BlockMetadata {
+                id: "flaky".to_string(),
+                name: "Flaky".to_string(),
+                description: "Fails a few times before succeeding".to_string(),
+                inputs: vec![],
+                outputs: vec![],
+                config_schema: HashMap::new(),
+            }
+        }
+
+        fn execute(&self, _context: BlockContext) -> Result<HashMap<String, Value>> {
+            let call = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if call < self.failures {
+                return Err((self.error)());
+            }
+            Ok(HashMap::from([(
+                "value".to_string(),
+                Value::Int(call as i64),
+            )]))
+        }
+    }
+
+    fn flaky_graph(retry: RetryPolicy) -> Graph {
+        let mut graph = Graph::new("flaky".to_string(), "Flaky".to_string());
+        graph
+            .add_node(Node::new("fetch", "flaky").with_retry(retry))
+            .unwrap();
+        graph
+    }
+
+    fn unavailable() -> CircuitError {
+        CircuitError::BlockExecution("service unavailable".to_string())
+    }
+
+    #[test]
+    fn test_retry_succeeds_after_backoff() {
+        use crate::context::{Clock, FakeClock};
+        use crate::execution::ExecutionEvent;
+        use std::sync::Mutex;
+        use std::time::Duration;
+
+        let engine = Engine::new();
+        engine
+            .register_block(Arc::new(FlakyBlock::new(2, unavailable)))
+            .unwrap();
+        let graph = flaky_graph(RetryPolicy::new(3).with_exponential_backoff(
+            Duration::from_millis(100),
+            2.0,
+            Duration::from_secs(1),
+        ));
+
+        let clock = Arc::new(FakeClock::new(Duration::ZERO));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let options = ExecutionOptions::new()
+            .with_context(ExecutionContext::new().with_clock(clock.clone()))
+            .with_observer(Arc::new(move |event| {
+                sink.lock().unwrap().push(event.clone())
+            }));
+        let report = engine
+            .execute_report(&graph, &HashMap::new(), &options)
+            .unwrap();
+
+        assert!(report.is_success());
+        assert_eq!(report.outputs["fetch"]["value"], Value::Int(2));
+        let node = report.node("fetch").unwrap();
+        assert_eq!(node.attempts, 3);
+        assert_eq!(node.duration, Duration::from_millis(300));
+        assert_eq!(clock.now(), Duration::from_millis(300));
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[1],
+            ExecutionEvent::NodeRetrying {
+                node_id: "fetch".to_string(),
+                attempt: 1,
+                error: "Block execution error: service unavailable".to_string(),
+                delay: Duration::from_millis(100),
+            }
+        );
+        assert_eq!(
+            events[3],
+            ExecutionEvent::NodeFinished {
+                node_id: "fetch".to_string(),
+                status: NodeStatus::Ok,
+                attempts: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_timeout_uses_context_clock() {
+        use crate::context::FakeClock;
+        use std::time::Duration;
+
+        let engine = Engine::new();
+        engine
+            .register_block(Arc::new(FlakyBlock::new(5, unavailable)))
+            .unwrap();
+        engine
+            .load_graph(flaky_graph(
+                RetryPolicy::new(5).with_fixed_backoff(Duration::from_secs(1)),
+            ))
+            .unwrap();
+
+        // Only the fake clock moves, by the retry delays
+        let options = ExecutionOptions::new()
+            .with_timeout(Duration::from_millis(1500))
+            .with_context(
+                ExecutionContext::new().with_clock(Arc::new(FakeClock::new(Duration::ZERO))),
+            );
+        let result = engine.execute_graph_with_options("flaky", &options);
+        assert!(matches!(result, Err(CircuitError::Timeout(_))));
+
+        // A recorded run measures it with the caller's clock too
+        let recording = engine
+            .execute_recorded("flaky", &HashMap::new(), &options)
+            .unwrap();
+        assert!(recording.error.unwrap().contains("timed out"));
+    }
+
+    #[test]
+    fn test_retry_backoff_past_timeout_fails_at_once() {
+        use crate::context::{Clock, FakeClock};
+        use std::time::Duration;
+
+        let engine = Engine::new();
+        engine
+            .register_block(Arc::new(FlakyBlock::new(1, unavailable)))
+            .unwrap();
+        let graph = flaky_graph(RetryPolicy::new(3).with_fixed_backoff(Duration::from_secs(60)));
+
+        let clock = Arc::new(FakeClock::new(Duration::ZERO));
+        let options = ExecutionOptions::new()
+            .with_timeout(Duration::from_secs(1))
+            .with_context(ExecutionContext::new().with_clock(clock.clone()));
+        let report = engine
+            .execute_report(&graph, &HashMap::new(), &options)
+            .unwrap();
+
+        assert!(report.error.as_ref().unwrap().contains("timed out"));
+        assert_eq!(report.node("fetch").unwrap().attempts, 1);
+        // The backoff was never slept
+        assert_eq!(clock.now(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_retry_gives_up() {
+        use crate::error::ErrorKind;
+
+        // Attempts exhausted
+        let engine = Engine::new();
+        engine
+            .register_block(Arc::new(FlakyBlock::new(5, unavailable)))
+            .unwrap();
+        let report = engine
+            .execute_report(
+                &flaky_graph(RetryPolicy::new(2)),
+                &HashMap::new(),
+                &ExecutionOptions::new(),
+            )
+            .unwrap();
+        assert_eq!(report.node("fetch").unwrap().attempts, 2);
+        assert!(report.error.unwrap().contains("service unavailable"));
+
+        // Error kind not listed in `retry_on`
+        let engine = Engine::new();
+        engine
+            .register_block(Arc::new(FlakyBlock::new(1, || {
+                CircuitError::InvalidInput("bad".to_string())
+            })))
+            .unwrap();
+        let graph = flaky_graph(RetryPolicy::new(3).retry_on(ErrorKind::BlockExecution));
+        let report = engine
+            .execute_report(&graph, &HashMap::new(), &ExecutionOptions::new())
+            .unwrap();
+        assert_eq!(report.node("fetch").unwrap().status, NodeStatus::Failed);
+        assert_eq!(report.node("fetch").unwrap().attempts, 1);
+
+        // Nor among the transient kinds retried by default
+        let engine = Engine::new();
+        engine
+            .register_block(Arc::new(FlakyBlock::new(1, || {
+                CircuitError::InvalidInput("bad".to_string())
+            })))
+            .unwrap();
+        let report = engine
+            .execute_report(
+                &flaky_graph(RetryPolicy::new(3)),
+                &HashMap::new(),
+                &ExecutionOptions::new(),
+            )
+            .unwrap();
+        assert_eq!(report.node("fetch").unwrap().attempts, 1);
+    }
+
+    // Waits for a host decision, then outputs the value it was resumed with
+    struct ApprovalBlock;
+    impl Block for ApprovalBlock {
+        fn metadata(&self) -> BlockMetadata {
+            BlockMetadata {
+                id: "approval".to_string(),
+                name: "Approval".to_string(),
+                description: "Suspends until approved".to_string(),
+                inputs: vec![],
+                outputs: vec![],
+                config_schema: HashMap::new(),
+            }
+        }
+
+        fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+            match context.resume_value() {
+                Some(amount) => Ok(HashMap::from([("amount".to_string(), amount.clone())])),
+                None => Err(CircuitError::Suspended("manager-approval".to_string())),
+            }
+        }
+    }
+
+    fn build_approval_engine() -> Engine {
+        let engine = build_add_engine();
+        engine.register_block(Arc::new(ApprovalBlock)).unwrap();
+        let mut graph = (*engine.graph("sum").unwrap()).clone();
+        graph.add_node(Node::new("approve", "approval")).unwrap();
+        graph
+            .add_connection(Connection {
+                from_node: "approve".to_string(),
+                from_port: "amount".to_string(),
+                to_node: "add".to_string(),
+                to_port: "b".to_string(),
+            })
+            .unwrap();
+        engine.replace_graph(graph).unwrap();
+        engine
+    }
+
+    #[test]
+    fn test_suspend_and_resume_after_restart() {
+        let engine = build_approval_engine();
+        let options = ExecutionOptions::new().with_execution_id("run-1");
+        let outcome = engine
+            .execute_suspendable("sum", &HashMap::new(), &options)
+            .unwrap();
+        let ExecutionOutcome::Suspended(suspended) = outcome else {
+            panic!("expected the run to suspend, got {:?}", outcome);
+        };
+        assert_eq!(suspended.node_id, "approve");
+        assert_eq!(suspended.token, "manager-approval");
+        assert!(!suspended.completed.contains(&"add".to_string()));
+        assert_eq!(engine.list_suspended(), vec!["run-1".to_string()]);
+
+        // Persist, "restart" and restore the execution in a new engine
+        let json = serde_json::to_string(&suspended).unwrap();
+        let restarted = build_approval_engine();
+        restarted
+            .restore_suspended(serde_json::from_str(&json).unwrap())
+            .unwrap();
+
+        assert!(restarted
+            .resume("run-1", "wrong-token", Value::Float(2.0))
+            .is_err());
+        assert!(restarted.suspended_execution("run-1").is_some());
+
+        match restarted
+            .resume("run-1", "manager-approval", Value::Float(2.0))
+            .unwrap()
+        {
+            ExecutionOutcome::Completed { outputs } => {
+                assert_eq!(outputs["add"]["result"], Value::Float(3.0));
+                assert_eq!(outputs["approve"]["amount"], Value::Float(2.0));
+            }
+            other => panic!("expected completion, got {:?}", other),
+        }
+        assert!(restarted.list_suspended().is_empty());
+        assert!(restarted
+            .resume("run-1", "manager-approval", Value::Null)
+            .is_err());
+    }
+
+    #[test]
+    fn test_suspend_refuses_id_taken_during_the_run() {
+        use crate::execution::ExecutionEvent;
+
+        let engine = Arc::new(build_approval_engine());
+        let first = engine
+            .execute_suspendable(
+                "sum",
+                &HashMap::new(),
+                &ExecutionOptions::new().with_execution_id("run-1"),
+            )
+            .unwrap();
+        let ExecutionOutcome::Suspended(taken) = first else {
+            panic!("expected the run to suspend, got {:?}", first);
+        };
+        engine.discard_suspended("run-1").unwrap();
+
+        // A concurrent run suspends under the same ID while this one runs
+        let other = Arc::clone(&engine);
+        let options = ExecutionOptions::new()
+            .with_execution_id("run-1")
+            .with_observer(Arc::new(move |event| {
+                if matches!(event, ExecutionEvent::NodeStarted { node_id } if node_id == "approve")
+                {
+                    other.restore_suspended(taken.clone()).unwrap();
+                }
+            }));
+        assert!(matches!(
+            engine.execute_suspendable("sum", &HashMap::new(), &options),
+            Err(CircuitError::InvalidInput(_))
+        ));
+        assert_eq!(engine.list_suspended(), vec!["run-1".to_string()]);
+    }
+
+    #[test]
+    fn test_suspending_block_in_straight_through_execution() {
+        let engine = build_approval_engine();
+        assert!(matches!(
+            engine.execute_graph("sum"),
+            Err(CircuitError::Suspended(token)) if token == "manager-approval"
+        ));
+        assert!(engine.list_suspended().is_empty());
+    }
+
+    // Counts how often it ran
+    struct CountingBlock(Arc<std::sync::atomic::AtomicUsize>);
+    impl Block for CountingBlock {
+        fn metadata(&self) -> BlockMetadata {
+            BlockMetadata {
+                id: "counting".to_string(),
+                name: "Counting".to_string(),
+                description: "Counts its executions".to_string(),
+                inputs: vec![],
+                outputs: vec![],
+                config_schema: HashMap::new(),
+            }
+        }
+
+        fn execute(&self, _context: BlockContext) -> Result<HashMap<String, Value>> {
+            let calls = self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            Ok(HashMap::from([(
+                "calls".to_string(),
+                Value::Int(calls as i64),
+            )]))
+        }
+    }
+
+    #[test]
+    fn test_resume_from_checkpoint_skips_completed_nodes() {
+        use crate::checkpoint::{CheckpointStore, MemoryCheckpointStore};
+
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let engine = Engine::new();
+        engine
+            .register_block(Arc::new(CountingBlock(calls.clone())))
+            .unwrap();
+        engine
+            .register_block(Arc::new(FlakyBlock::new(1, unavailable)))
+            .unwrap();
+        let mut graph = flaky_graph(RetryPolicy::new(1));
+        graph.add_node(Node::new("count", "counting")).unwrap();
+        graph
+            .add_connection(Connection {
+                from_node: "count".to_string(),
+                from_port: "calls".to_string(),
+                to_node: "fetch".to_string(),
+                to_port: "in".to_string(),
+            })
+            .unwrap();
+        engine.load_graph(graph).unwrap();
+
+        // The first run "crashes" at `fetch`, after `count` was checkpointed
+        let store = Arc::new(MemoryCheckpointStore::new());
+        let options = ExecutionOptions::new()
+            .with_execution_id("run-1")
+            .with_checkpoints(store.clone());
+        assert!(engine
+            .execute_graph_with_options("flaky", &options)
+            .is_err());
+        let checkpoint = store.load("run-1").unwrap().unwrap();
+        assert_eq!(checkpoint.completed, vec!["count".to_string()]);
+
+        let outputs = engine.resume_from_checkpoint("run-1", &options).unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(outputs["count"]["calls"], Value::Int(1));
+        assert_eq!(outputs["fetch"]["value"], Value::Int(1));
+
+        // Completed executions leave no checkpoint behind
+        assert!(store.list().unwrap().is_empty());
+        assert!(engine.resume_from_checkpoint("run-1", &options).is_err());
+
+        // Without an ID the checkpoints could not be found again
+        let unnamed = ExecutionOptions::new().with_checkpoints(store.clone());
+        assert!(matches!(
+            engine.execute_graph_with_options("flaky", &unnamed),
+            Err(CircuitError::InvalidInput(_))
+        ));
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    // Remembers how often its node ran, in the node's block state
+    struct TallyBlock(u32);
+    impl Block for TallyBlock {
+        fn metadata(&self) -> BlockMetadata {
+            BlockMetadata {
+                id: "tally".to_string(),
+                name: "Tally".to_string(),
+                description: "Counts executions in its node state".to_string(),
+                inputs: vec![],
+                outputs: vec![],
+                config_schema: HashMap::new(),
+            }
+        }
+
+        fn version(&self) -> u32 {
+            self.0
+        }
+
+        fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+            let count = context.state().and_then(|v| v.as_int()).unwrap_or(0) + 1;
+            context.set_state(Value::Int(count));
+            let mut outputs = HashMap::from([("count".to_string(), Value::Int(count))]);
+            if let Some(user) = context.variable("user_id") {
+                outputs.insert("user_id".to_string(), user.clone());
+            }
+            Ok(outputs)
+        }
+    }
+
+    fn build_tally_engine(version: u32) -> Engine {
+        let engine = Engine::new();
+        engine
+            .register_block(Arc::new(TallyBlock(version)))
+            .unwrap();
+        engine
+    }
+
+    fn tally_graph() -> Graph {
+        let mut graph = Graph::new("tally".to_string(), "Tally".to_string());
+        graph.add_node(Node::new("t", "tally")).unwrap();
+        graph
+    }
+
+    #[test]
+    fn test_node_state_and_engine_variables() {
+        let engine = build_tally_engine(1);
+        engine.load_graph(tally_graph()).unwrap();
+        engine.set_variable("user_id", "u-1");
+
+        engine.execute_graph("tally").unwrap();
+        let outputs = engine.execute_graph("tally").unwrap();
+        assert_eq!(outputs["t"]["count"], Value::Int(2));
+        assert_eq!(outputs["t"]["user_id"], Value::String("u-1".to_string()));
+        assert_eq!(engine.node_state("tally", "t"), Some(Value::Int(2)));
+
+        // Execution variables take precedence over engine variables
+        let context = ExecutionContext::new().with_variable("user_id", "u-2");
+        let outputs = engine
+            .execute_with_context(&engine.graph("tally").unwrap(), context)
+            .unwrap();
+        assert_eq!(outputs["t"]["user_id"], Value::String("u-2".to_string()));
+
+        // Unloading a graph drops its node state
+        engine.unload_graph("tally").unwrap();
+        assert_eq!(engine.node_state("tally", "t"), None);
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let engine = build_tally_engine(2);
+        engine.load_graph(tally_graph()).unwrap();
+        engine.set_variable("user_id", "u-1");
+        engine.execute_graph("tally").unwrap();
+        let json = engine.snapshot().to_json().unwrap();
+
+        // A restarted app registers its blocks, then restores
+        let restored = build_tally_engine(2);
+        restored
+            .restore(EngineSnapshot::from_json(&json).unwrap())
+            .unwrap();
+        assert_eq!(
+            restored.variable("user_id"),
+            Some(Value::String("u-1".to_string()))
+        );
+        let outputs = restored.execute_graph("tally").unwrap();
+        assert_eq!(outputs["t"]["count"], Value::Int(2));
+
+        // Missing or older block types are refused
+        let err = Engine::new()
+            .restore(EngineSnapshot::from_json(&json).unwrap())
+            .unwrap_err();
+        assert!(err.to_string().contains("unregistered block types: tally"));
+        let older = build_tally_engine(1);
+        let err = older
+            .restore(EngineSnapshot::from_json(&json).unwrap())
+            .unwrap_err();
+        assert!(err.to_string().contains("version 1"));
+        assert!(older.list_graphs().is_empty());
+    }
+
+    // Async block that finishes once `needed` nodes of its kind are in flight
+    struct RendezvousBlock {
+        arrived: Arc<std::sync::atomic::AtomicUsize>,
+        needed: usize,
+    }
+
+    impl AsyncBlock for RendezvousBlock {
+        fn metadata(&self) -> BlockMetadata {
+            BlockMetadata {
+                id: "rendezvous".to_string(),
+                name: "Rendezvous".to_string(),
+                description: "Waits for its peers".to_string(),
+                inputs: vec![],
+                outputs: vec![],
+                config_schema: HashMap::new(),
+            }
+        }
+
+        fn execute_async(&self, _context: BlockContext) -> BlockFuture<'_> {
+            use std::sync::atomic::Ordering;
+
+            self.arrived.fetch_add(1, Ordering::SeqCst);
+            let mut polls = 0;
+            Box::pin(std::future::poll_fn(move |cx| {
+                if self.arrived.load(Ordering::SeqCst) >= self.needed {
+                    return Poll::Ready(Ok(HashMap::from([(
+                        "value".to_string(),
+                        Value::Float(1.0),
+                    )])));
+                }
+                polls += 1;
+                if polls > 1000 {
+                    return Poll::Ready(Err(CircuitError::BlockExecution(
+                        "peers never arrived".to_string(),
+                    )));
+                }
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }))
+        }
+    }
+
+    fn build_rendezvous_engine(needed: usize) -> Engine {
+        let engine = Engine::new();
+        engine.register_block(Arc::new(AddBlock)).unwrap();
+        engine
+            .register_async_block(Arc::new(RendezvousBlock {
+                arrived: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+                needed,
+            }))
+            .unwrap();
+
+        let mut graph = Graph::new("meet".to_string(), "Meet".to_string());
+        for (id, block_type) in [
+            ("left", "rendezvous"),
+            ("right", "rendezvous"),
+            ("add", "add"),
+        ] {
+            graph.add_node(Node::new(id, block_type)).unwrap();
+        }
+        for (from, port) in [("left", "a"), ("right", "b")] {
+            graph
+                .add_connection(Connection {
+                    from_node: from.to_string(),
+                    from_port: "value".to_string(),
+                    to_node: "add".to_string(),
+                    to_port: port.to_string(),
+                })
+                .unwrap();
+        }
+        engine.load_graph(graph).unwrap();
+        engine
+    }
+
+    #[test]
+    fn test_execute_async_runs_independent_nodes_concurrently() {
+        use crate::async_block::block_on;
+
+        fn assert_send<T: Send>(_: &T) {}
+
+        let engine = build_rendezvous_engine(2);
+        let options = ExecutionOptions::new();
+        let future = engine.execute_graph_async("meet", &options);
+        assert_send(&future);
+
+        let outputs = block_on(future).unwrap();
+        assert_eq!(outputs["add"]["result"], Value::Float(2.0));
+        assert_eq!(outputs["left"]["value"], Value::Float(1.0));
+    }
+
+    // Never finishes and never wakes the execution
+    struct HangingBlock;
+    impl AsyncBlock for HangingBlock {
+        fn metadata(&self) -> BlockMetadata {
+            BlockMetadata {
+                id: "hanging".to_string(),
+                name: "Hanging".to_string(),
+                description: "Waits forever".to_string(),
+                inputs: vec![],
+                outputs: vec![],
+                config_schema: HashMap::new(),
+            }
+        }
+
+        fn execute_async(&self, _context: BlockContext) -> BlockFuture<'_> {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    #[test]
+    fn test_execute_async_times_out_pending_nodes() {
+        use crate::async_block::block_on;
+        use std::time::Duration;
+
+        let engine = Engine::new();
+        engine.register_async_block(Arc::new(HangingBlock)).unwrap();
+        let mut graph = Graph::new("hang".to_string(), "Hang".to_string());
+        graph.add_node(Node::new("wait", "hanging")).unwrap();
+
+        let options = ExecutionOptions::new().with_timeout(Duration::from_millis(20));
+        assert!(matches!(
+            block_on(engine.execute_async(&graph, &options)),
+            Err(CircuitError::Timeout(timeout)) if timeout == Duration::from_millis(20)
+        ));
+    }
+
+    #[test]
+    fn test_execute_async_pinned_and_muted_nodes_with_upstream() {
+        use crate::async_block::block_on;
+
+        let engine = build_add_engine();
+        let mut graph = (*engine.graph("sum").unwrap()).clone();
+        let options = ExecutionOptions::new();
+
+        // `one` is pruned, so `add` must not wait for it
+        let pinned = HashMap::from([("result".to_string(), Value::Float(9.0))]);
+        graph.nodes.get_mut("add").unwrap().mode = NodeMode::Pinned(pinned);
+        let outputs = block_on(engine.execute_async(&graph, &options)).unwrap();
+        assert_eq!(outputs["add"]["result"], Value::Float(9.0));
+        assert!(!outputs.contains_key("one"));
+
+        graph.nodes.get_mut("add").unwrap().mode = NodeMode::Mute;
+        let outputs = block_on(engine.execute_async(&graph, &options)).unwrap();
+        assert_eq!(outputs["add"]["result"], Value::Null);
+    }
+
+    #[test]
+    fn test_async_blocks_in_sync_execution() {
+        // The sync executor runs one node at a time
+        let engine = build_rendezvous_engine(1);
+        let outputs = engine.execute_graph("meet").unwrap();
+        assert_eq!(outputs["add"]["result"], Value::Float(2.0));
+
+        let engine = build_rendezvous_engine(2);
+        let err = engine.execute_graph("meet").unwrap_err();
+        assert!(err.to_string().contains("peers never arrived"));
+    }
+
+    #[test]
+    fn test_resolved_ports_checked_at_load_time() {
+        use crate::blocks::{ConcatBlock, SwitchBlock};
+
+        let engine = Engine::new();
+        engine.register_block(Arc::new(AddBlock)).unwrap();
+        engine.register_block(Arc::new(SwitchBlock)).unwrap();
+        engine.register_block(Arc::new(ConcatBlock)).unwrap();
+
+        let cases = vec![Value::String("red".into()), Value::String("blue".into())];
+        let switch_graph = |to_port: &str| {
+            let mut graph = Graph::new("g".to_string(), "G".to_string());
+            for (id, block_type, config) in [
+                ("sum", "add", HashMap::new()),
+                (
+                    "pick",
+                    "control.switch",
+                    HashMap::from([("cases".to_string(), Value::Array(cases.clone().into()))]),
+                ),
+            ] {
+                graph
+                    .add_node(Node::new(id, block_type).with_config(config))
+                    .unwrap();
+            }
+            graph
+                .add_connection(Connection {
+                    from_node: "sum".to_string(),
+                    from_port: "result".to_string(),
+                    to_node: "pick".to_string(),
+                    to_port: to_port.to_string(),
+                })
+                .unwrap();
+            graph
+        };
+
+        let err = engine.load_graph(switch_graph("a")).unwrap_err();
+        assert!(err.to_string().contains("no input port 'a'"));
+        engine.load_graph(switch_graph("red")).unwrap();
+
+        let ports = engine.node_ports("g", "pick").unwrap();
+        assert!(ports.input("red").is_some() && ports.input("a").is_none());
+        let fixed = engine
+            .block_ports("control.switch", &HashMap::new())
+            .unwrap();
+        assert!(fixed.input("a").is_some());
+
+        // A number output cannot feed a string input
+        let mut graph = switch_graph("red");
+        graph.id = "typed".to_string();
+        graph.add_node(Node::new("join", "string.concat")).unwrap();
+        graph
+            .add_connection(Connection {
+                from_node: "sum".to_string(),
+                from_port: "result".to_string(),
+                to_node: "join".to_string(),
+                to_port: "a".to_string(),
+            })
+            .unwrap();
+        let err = engine.load_graph(graph).unwrap_err();
+        assert!(matches!(err, CircuitError::InvalidConnection(_)));
+        assert!(err.to_string().contains("(number) to join.a (string)"));
+    }
+
+    // Reads its `factor` config without opting in to parameter ports
+    struct ScaleBlock;
+    impl Block for ScaleBlock {
+        fn metadata(&self) -> BlockMetadata {
+            BlockMetadata {
+                id: "scale".to_string(),
+                name: "Scale".to_string(),
+                description: "Multiply the input by a configured factor".to_string(),
+                inputs: vec![PortDefinition {
+                    id: "value".to_string(),
+                    name: "Value".to_string(),
+                    data_type: "number".to_string(),
+                    required: true,
+                }],
+                outputs: vec![PortDefinition {
+                    id: "result".to_string(),
+                    name: "Result".to_string(),
+                    data_type: "number".to_string(),
+                    required: true,
+                }],
+                config_schema: HashMap::from([("factor".to_string(), "number".to_string())]),
+            }
+        }
+
+        fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+            let value = context.get_input("value").and_then(Value::as_float);
+            let factor = context.get_config("factor").and_then(Value::as_float);
+            let result = value.unwrap_or(0.0) * factor.unwrap_or(1.0);
+            Ok(HashMap::from([(
+                "result".to_string(),
+                Value::Float(result),
+            )]))
+        }
+    }
+
+    #[test]
+    fn test_parameter_ports_are_opt_in() {
+        let engine = Engine::new();
+        engine.register_block(Arc::new(ConstantBlock)).unwrap();
+        engine.register_block(Arc::new(ScaleBlock)).unwrap();
+        engine
+            .register_block(Arc::new(crate::blocks::ConstantBlock))
+            .unwrap();
+
+        let ports = engine.block_ports("scale", &HashMap::new()).unwrap();
+        assert!(ports.input("factor").is_none());
+        let ports = engine
+            .block_ports("core.constant", &HashMap::new())
+            .unwrap();
+        assert!(ports.input("value").is_some_and(|port| !port.required));
+
+        let graph = |to_node: &str, to_port: &str| {
+            let mut graph = Graph::new("g".to_string(), "G".to_string());
+            graph
+                .add_node(
+                    Node::new("two", "constant")
+                        .with_config(HashMap::from([("value".to_string(), Value::Float(2.0))])),
+                )
+                .unwrap();
+            graph
+                .add_node(
+                    Node::new("scale", "scale")
+                        .with_config(HashMap::from([("factor".to_string(), Value::Float(3.0))])),
+                )
+                .unwrap();
+            graph
+                .add_node(
+                    Node::new("fixed", "core.constant")
+                        .with_config(HashMap::from([("value".to_string(), Value::Float(5.0))])),
+                )
+                .unwrap();
+            graph
+                .add_connection(Connection {
+                    from_node: "two".to_string(),
+                    from_port: "value".to_string(),
+                    to_node: to_node.to_string(),
+                    to_port: to_port.to_string(),
+                })
+                .unwrap();
+            graph
+        };
+
+        // The factor stays a config field, so it cannot be connected
+        let err = engine.load_graph(graph("scale", "factor")).unwrap_err();
+        assert!(matches!(err, CircuitError::InvalidConnection(_)));
+
+        // The built-in constant opts in, and the connection wins
+        engine.load_graph(graph("fixed", "value")).unwrap();
+        let results = engine.execute_graph("g").unwrap();
+        assert_eq!(results["fixed"]["value"], Value::Float(2.0));
+        assert_eq!(results["scale"]["result"], Value::Float(0.0));
     }
 }
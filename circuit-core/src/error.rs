@@ -1,3 +1,7 @@
+use crate::diff::PatchConflict;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::time::Duration;
 use thiserror::Error;
 
 /// Result type alias for Circuit operations
@@ -30,6 +34,86 @@ pub enum CircuitError {
     #[error("Type mismatch: expected {expected}, got {actual}")]
     TypeMismatch { expected: String, actual: String },
 
+    #[error("Resource limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    #[error("Block panicked in node '{node}': {message}")]
+    BlockPanicked { node: String, message: String },
+
+    #[error("Execution timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error("Execution exceeded the limit of {0} node executions")]
+    StepLimitExceeded(usize),
+
+    #[error("Execution cancelled")]
+    Cancelled,
+
+    /// Returned by a block to suspend the execution until it is resumed with this token
+    #[error("Execution suspended awaiting '{0}'")]
+    Suspended(String),
+
+    /// Changes of a graph patch that do not fit the graph it is applied to
+    #[error("Patch does not apply: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    PatchConflicts(Vec<PatchConflict>),
+
     #[error("Other error: {0}")]
     Other(#[from] anyhow::Error),
 }
+
+/// The variant of a `CircuitError`, without its payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    BlockExecution,
+    Graph,
+    NodeNotFound,
+    InvalidConnection,
+    Serialization,
+    CycleDetected,
+    InvalidInput,
+    TypeMismatch,
+    LimitExceeded,
+    BlockPanicked,
+    Timeout,
+    StepLimitExceeded,
+    Cancelled,
+    Suspended,
+    PatchConflicts,
+    Other,
+}
+
+impl CircuitError {
+    /// Get the kind of this error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            CircuitError::BlockExecution(_) => ErrorKind::BlockExecution,
+            CircuitError::Graph(_) => ErrorKind::Graph,
+            CircuitError::NodeNotFound(_) => ErrorKind::NodeNotFound,
+            CircuitError::InvalidConnection(_) => ErrorKind::InvalidConnection,
+            CircuitError::Serialization(_) => ErrorKind::Serialization,
+            CircuitError::CycleDetected => ErrorKind::CycleDetected,
+            CircuitError::InvalidInput(_) => ErrorKind::InvalidInput,
+            CircuitError::TypeMismatch { .. } => ErrorKind::TypeMismatch,
+            CircuitError::LimitExceeded(_) => ErrorKind::LimitExceeded,
+            CircuitError::BlockPanicked { .. } => ErrorKind::BlockPanicked,
+            CircuitError::Timeout(_) => ErrorKind::Timeout,
+            CircuitError::StepLimitExceeded(_) => ErrorKind::StepLimitExceeded,
+            CircuitError::Cancelled => ErrorKind::Cancelled,
+            CircuitError::Suspended(_) => ErrorKind::Suspended,
+            CircuitError::PatchConflicts(_) => ErrorKind::PatchConflicts,
+            CircuitError::Other(_) => ErrorKind::Other,
+        }
+    }
+}
+
+/// Extract a readable message from a panic payload
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{
+    checkpoint::CheckpointStore,
+    context::{self, ExecutionContext},
+    graph::NodeId,
+    report::NodeStatus,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Cooperative cancellation flag shared between the host and a running execution
+///
+/// Cloning a token yields a handle to the same flag, so the host can keep one
+/// clone and cancel the execution from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Create a new, non-cancelled token
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation of every execution observing this token
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Check whether cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// Progress of a running execution, delivered to observers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExecutionEvent {
+    /// The node is about to run
+    NodeStarted { node_id: NodeId },
+    /// An attempt failed and the node will be retried after `delay`
+    NodeRetrying {
+        node_id: NodeId,
+        attempt: u32,
+        error: String,
+        #[serde(rename = "delay_ms", with = "crate::serde_duration::millis")]
+        delay: Duration,
+    },
+    /// The node finished with `status` after `attempts` block runs
+    NodeFinished {
+        node_id: NodeId,
+        status: NodeStatus,
+        attempts: u32,
+    },
+}
+
+/// Callback receiving the events of an execution, on the executing thread
+pub type ExecutionObserver = Arc<dyn Fn(&ExecutionEvent) + Send + Sync>;
+
+/// Budgets and controls applied to a single graph execution
+#[derive(Clone, Default)]
+pub struct ExecutionOptions {
+    /// Wall-clock budget for the whole execution
+    pub timeout: Option<Duration>,
+    /// Maximum number of node executions before the run is aborted
+    pub max_node_executions: Option<usize>,
+    /// Token checked between nodes and exposed to blocks
+    pub cancellation: CancellationToken,
+    /// Drop each node output as soon as its last consumer has run
+    ///
+    /// Only declared graph outputs and nodes listed in `keep_outputs` are
+    /// returned; if there are none, the outputs of sink nodes are returned.
+    pub free_intermediate_outputs: bool,
+    /// Nodes whose outputs must be kept when freeing intermediate outputs
+    pub keep_outputs: Vec<NodeId>,
+    /// Variables and services exposed to every block
+    pub context: ExecutionContext,
+    /// ID reported for this execution; generated when not set
+    pub execution_id: Option<String>,
+    /// Callback notified as nodes start, retry and finish
+    pub observer: Option<ExecutionObserver>,
+    /// Store receiving a checkpoint when the execution starts and a step after
+    /// every node that finishes; requires `execution_id`
+    pub checkpoints: Option<Arc<dyn CheckpointStore>>,
+}
+
+impl ExecutionOptions {
+    /// Create options without any limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Abort the execution once `timeout` has elapsed
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Abort the execution after `max` node executions
+    pub fn with_max_node_executions(mut self, max: usize) -> Self {
+        self.max_node_executions = Some(max);
+        self
+    }
+
+    /// Observe an existing cancellation token
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// Free intermediate outputs once no downstream node needs them
+    pub fn with_free_intermediate_outputs(mut self, enabled: bool) -> Self {
+        self.free_intermediate_outputs = enabled;
+        self
+    }
+
+    /// Keep the outputs of `node_id` when freeing intermediate outputs
+    pub fn keep_output(mut self, node_id: impl Into<NodeId>) -> Self {
+        self.keep_outputs.push(node_id.into());
+        self
+    }
+
+    /// Run with the given variables and services
+    pub fn with_context(mut self, context: ExecutionContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Report this execution under the given ID
+    pub fn with_execution_id(mut self, execution_id: impl Into<String>) -> Self {
+        self.execution_id = Some(execution_id.into());
+        self
+    }
+
+    /// Notify `observer` as nodes start, retry and finish
+    pub fn with_observer(mut self, observer: ExecutionObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Write a checkpoint to `store` as the execution goes, so it can be
+    /// continued with `Engine::resume_from_checkpoint` after a crash
+    ///
+    /// The execution fails unless it has an ID set with `with_execution_id`.
+    pub fn with_checkpoints(mut self, store: Arc<dyn CheckpointStore>) -> Self {
+        self.checkpoints = Some(store);
+        self
+    }
+
+    pub(crate) fn notify(&self, event: impl FnOnce() -> ExecutionEvent) {
+        if let Some(observer) = &self.observer {
+            observer(&event());
+        }
+    }
+}
+
+impl fmt::Debug for ExecutionOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExecutionOptions")
+            .field("timeout", &self.timeout)
+            .field("max_node_executions", &self.max_node_executions)
+            .field("cancellation", &self.cancellation)
+            .field("free_intermediate_outputs", &self.free_intermediate_outputs)
+            .field("keep_outputs", &self.keep_outputs)
+            .field("context", &self.context)
+            .field("execution_id", &self.execution_id)
+            .field("observer", &self.observer.is_some())
+            .field("checkpoints", &self.checkpoints.is_some())
+            .finish()
+    }
+}
+
+// Random execution ID for runs the host did not name
+pub(crate) fn new_execution_id() -> String {
+    format!("{:016x}", context::default_random().next_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation_token_shared_between_clones() {
+        let token = CancellationToken::new();
+        let handle = token.clone();
+        assert!(!token.is_cancelled());
+
+        handle.cancel();
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn test_execution_options_builder() {
+        let options = ExecutionOptions::new()
+            .with_timeout(Duration::from_millis(50))
+            .with_max_node_executions(10);
+
+        assert_eq!(options.timeout, Some(Duration::from_millis(50)));
+        assert_eq!(options.max_node_executions, Some(10));
+        assert!(!options.cancellation.is_cancelled());
+    }
+}
@@ -1,12 +1,22 @@
-use crate::{error::CircuitError, error::Result, value::Value};
+use crate::{error::CircuitError, error::Result, retry::RetryPolicy, value::Value};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 
 /// Unique identifier for a node in the graph
 pub type NodeId = String;
 
+/// Graph format written by this version of the engine
+///
+/// Documents without a `format_version` are version 0. Older documents are
+/// upgraded by the migrations of `migrate::MigrationRegistry` when loaded.
+pub const GRAPH_FORMAT_VERSION: u32 = 1;
+
 /// A node in the execution graph
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Prefer `Node::new` and the `with_*` methods over a struct literal, so
+/// settings added later keep their defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Node {
     /// Unique identifier for this node instance
     pub id: NodeId,
@@ -16,10 +26,83 @@ pub struct Node {
     pub config: HashMap<String, Value>,
     /// Display position (for visual editors)
     pub position: Option<(f64, f64)>,
+    /// Retry configuration applied when the block fails
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryPolicy>,
+    /// Debugging override of how the node runs
+    #[serde(default, skip_serializing_if = "NodeMode::is_normal")]
+    pub mode: NodeMode,
+}
+
+impl Node {
+    /// Create a node of `block_type` without config
+    pub fn new(id: impl Into<NodeId>, block_type: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            block_type: block_type.into(),
+            config: HashMap::new(),
+            position: None,
+            retry: None,
+            mode: NodeMode::Normal,
+        }
+    }
+
+    /// Replace the config
+    pub fn with_config(mut self, config: HashMap<String, Value>) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Set one config field
+    pub fn with_config_value(mut self, key: impl Into<String>, value: Value) -> Self {
+        self.config.insert(key.into(), value);
+        self
+    }
+
+    /// Set the display position
+    pub fn with_position(mut self, x: f64, y: f64) -> Self {
+        self.position = Some((x, y));
+        self
+    }
+
+    /// Set the retry policy
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Set the debugging mode
+    pub fn with_mode(mut self, mode: NodeMode) -> Self {
+        self.mode = mode;
+        self
+    }
+}
+
+/// How the engine treats a node, e.g. to debug a graph in an editor
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeMode {
+    /// Run the block
+    #[default]
+    Normal,
+    /// Do not run the block; each output passes through the input with the
+    /// same port ID, or else the first input of a compatible type
+    Bypass,
+    /// Do not run the block; every output is `Null`
+    Mute,
+    /// Do not run the block; output these values, keyed by port
+    Pinned(HashMap<String, Value>),
+}
+
+impl NodeMode {
+    /// Whether the block runs as usual
+    pub fn is_normal(&self) -> bool {
+        matches!(self, NodeMode::Normal)
+    }
 }
 
 /// Connection between two nodes
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Connection {
     /// Source node ID
     pub from_node: NodeId,
@@ -31,9 +114,31 @@ pub struct Connection {
     pub to_port: String,
 }
 
+impl fmt::Display for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{} -> {}.{}",
+            self.from_node, self.from_port, self.to_node, self.to_port
+        )
+    }
+}
+
+/// A node output port declared as a result of the graph
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GraphOutput {
+    /// Node producing the output
+    pub node: NodeId,
+    /// Output port on that node
+    pub port: String,
+}
+
 /// A directed graph of nodes and connections
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Graph {
+    /// Format of the document the graph was read from
+    #[serde(default)]
+    pub format_version: u32,
     /// Graph identifier
     pub id: String,
     /// Human-readable name
@@ -44,17 +149,22 @@ pub struct Graph {
     pub nodes: HashMap<NodeId, Node>,
     /// Connections between nodes
     pub connections: Vec<Connection>,
+    /// Declared graph outputs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<GraphOutput>,
 }
 
 impl Graph {
     /// Create a new empty graph
     pub fn new(id: String, name: String) -> Self {
         Self {
+            format_version: GRAPH_FORMAT_VERSION,
             id,
             name,
             description: None,
             nodes: HashMap::new(),
             connections: Vec::new(),
+            outputs: Vec::new(),
         }
     }
 
@@ -76,9 +186,10 @@ impl Graph {
             return Err(CircuitError::NodeNotFound(node_id.to_string()));
         }
 
-        // Remove all connections involving this node
+        // Remove all connections and declared outputs involving this node
         self.connections
             .retain(|conn| conn.from_node != node_id && conn.to_node != node_id);
+        self.outputs.retain(|output| output.node != node_id);
 
         self.nodes.remove(node_id);
         Ok(())
@@ -86,6 +197,12 @@ impl Graph {
 
     /// Add a connection between two nodes
     pub fn add_connection(&mut self, connection: Connection) -> Result<()> {
+        self.insert_connection(self.connections.len(), connection)
+    }
+
+    /// Add a connection at `index` in `connections`, or last when `index` is
+    /// past the end
+    pub fn insert_connection(&mut self, index: usize, connection: Connection) -> Result<()> {
         // Validate nodes exist
         if !self.nodes.contains_key(&connection.from_node) {
             return Err(CircuitError::NodeNotFound(connection.from_node.clone()));
@@ -99,10 +216,87 @@ impl Graph {
             return Err(CircuitError::CycleDetected);
         }
 
-        self.connections.push(connection);
+        let index = index.min(self.connections.len());
+        self.connections.insert(index, connection);
+        Ok(())
+    }
+
+    /// Remove the first connection equal to `connection`
+    pub fn remove_connection(&mut self, connection: &Connection) -> Result<()> {
+        let index = self
+            .connections
+            .iter()
+            .position(|conn| conn == connection)
+            .ok_or_else(|| CircuitError::Graph(format!("Connection {} not found", connection)))?;
+        self.connections.remove(index);
+        Ok(())
+    }
+
+    /// Set the display position of a node, returning the previous one
+    pub fn move_node(
+        &mut self,
+        node_id: &str,
+        position: Option<(f64, f64)>,
+    ) -> Result<Option<(f64, f64)>> {
+        let node = self.node_mut(node_id)?;
+        Ok(std::mem::replace(&mut node.position, position))
+    }
+
+    /// Set a config field of a node, or remove it with `None`; returns the
+    /// previous value
+    pub fn update_config(
+        &mut self,
+        node_id: &str,
+        key: &str,
+        value: Option<Value>,
+    ) -> Result<Option<Value>> {
+        let config = &mut self.node_mut(node_id)?.config;
+        Ok(match value {
+            Some(value) => config.insert(key.to_string(), value),
+            None => config.remove(key),
+        })
+    }
+
+    /// Give a node a new ID, updating its connections and declared outputs
+    pub fn rename_node(&mut self, node_id: &str, new_id: &str) -> Result<()> {
+        if node_id == new_id {
+            return self.node_mut(node_id).map(|_| ());
+        }
+        if self.nodes.contains_key(new_id) {
+            return Err(CircuitError::Graph(format!(
+                "Node with id '{}' already exists",
+                new_id
+            )));
+        }
+        let mut node = self
+            .nodes
+            .remove(node_id)
+            .ok_or_else(|| CircuitError::NodeNotFound(node_id.to_string()))?;
+        node.id = new_id.to_string();
+        self.nodes.insert(node.id.clone(), node);
+
+        for conn in &mut self.connections {
+            if conn.from_node == node_id {
+                conn.from_node = new_id.to_string();
+            }
+            if conn.to_node == node_id {
+                conn.to_node = new_id.to_string();
+            }
+        }
+        for output in &mut self.outputs {
+            if output.node == node_id {
+                output.node = new_id.to_string();
+            }
+        }
         Ok(())
     }
 
+    fn node_mut(&mut self, node_id: &str) -> Result<&mut Node> {
+        self.nodes
+            .get_mut(node_id)
+            .ok_or_else(|| CircuitError::NodeNotFound(node_id.to_string()))
+    }
+
     /// Check if adding a connection would create a cycle
     fn would_create_cycle(&self, new_connection: &Connection) -> Result<bool> {
         let mut adjacency: HashMap<&str, Vec<&str>> = HashMap::new();
@@ -210,6 +404,49 @@ impl Graph {
         Ok(result)
     }
 
+    /// Declare a node output port as a result of the graph
+    pub fn add_output(&mut self, node: &str, port: &str) -> Result<()> {
+        self.insert_output(self.outputs.len(), node, port)
+    }
+
+    /// Declare an output at `index` in `outputs`, or last when `index` is
+    /// past the end; an output already declared stays where it is
+    pub fn insert_output(&mut self, index: usize, node: &str, port: &str) -> Result<()> {
+        if !self.nodes.contains_key(node) {
+            return Err(CircuitError::NodeNotFound(node.to_string()));
+        }
+        let output = GraphOutput {
+            node: node.to_string(),
+            port: port.to_string(),
+        };
+        if !self.outputs.contains(&output) {
+            let index = index.min(self.outputs.len());
+            self.outputs.insert(index, output);
+        }
+        Ok(())
+    }
+
+    /// Stop declaring a node output port as a result of the graph
+    pub fn remove_output(&mut self, node: &str, port: &str) -> Result<()> {
+        let index = self
+            .outputs
+            .iter()
+            .position(|output| output.node == node && output.port == port)
+            .ok_or_else(|| {
+                CircuitError::Graph(format!("Output {}.{} is not declared", node, port))
+            })?;
+        self.outputs.remove(index);
+        Ok(())
+    }
+
+    /// Get outgoing connections for a node
+    pub fn get_outgoing_connections(&self, node_id: &str) -> Vec<&Connection> {
+        self.connections
+            .iter()
+            .filter(|conn| conn.from_node == node_id)
+            .collect()
+    }
+
     /// Get incoming connections for a node
     pub fn get_incoming_connections(&self, node_id: &str) -> Vec<&Connection> {
         self.connections
@@ -219,6 +456,41 @@ impl Graph {
     }
 }
 
+/// Graphs shared by the tests of the editing, diff and collaboration modules
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::{Connection, Graph, Node};
+    use crate::value::Value;
+    use std::collections::HashMap;
+
+    /// Node of block type `test` with config `value`
+    pub(crate) fn node(id: &str, value: i64) -> Node {
+        Node::new(id, "test").with_config(HashMap::from([("value".to_string(), Value::Int(value))]))
+    }
+
+    /// Connection from port `out` of `from` to port `in` of `to`
+    pub(crate) fn connection(from: &str, to: &str) -> Connection {
+        Connection {
+            from_node: from.to_string(),
+            from_port: "out".to_string(),
+            to_node: to.to_string(),
+            to_port: "in".to_string(),
+        }
+    }
+
+    /// Graph "g" with a `node(id, 1)` per ID and the given connections
+    pub(crate) fn graph(node_ids: &[&str], connections: &[(&str, &str)]) -> Graph {
+        let mut graph = Graph::new("g".to_string(), "G".to_string());
+        for id in node_ids {
+            graph.add_node(node(id, 1)).unwrap();
+        }
+        for (from, to) in connections {
+            graph.add_connection(connection(from, to)).unwrap();
+        }
+        graph
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -234,32 +506,35 @@ mod tests {
     #[test]
     fn test_add_node() {
         let mut graph = Graph::new("test".to_string(), "Test".to_string());
-        let node = Node {
-            id: "node1".to_string(),
-            block_type: "test".to_string(),
-            config: HashMap::new(),
-            position: None,
-        };
+        let node = Node::new("node1", "test");
         graph.add_node(node).unwrap();
         assert_eq!(graph.nodes.len(), 1);
     }
 
+    #[test]
+    fn test_node_mode_json() {
+        let mut node = Node::new("node1", "test");
+        let json = serde_json::to_string(&node).unwrap();
+        assert!(!json.contains("mode"));
+
+        node.mode = NodeMode::Pinned(HashMap::from([("value".to_string(), Value::Int(1))]));
+        let json = serde_json::to_string(&node).unwrap();
+        let parsed: Node = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.mode, node.mode);
+
+        let parsed: Node = serde_json::from_str(
+            r#"{"id": "n", "block_type": "test", "config": {}, "position": null, "mode": "bypass"}"#,
+        )
+        .unwrap();
+        assert_eq!(parsed.mode, NodeMode::Bypass);
+    }
+
     #[test]
     fn test_add_connection() {
         let mut graph = Graph::new("test".to_string(), "Test".to_string());
 
-        let node1 = Node {
-            id: "node1".to_string(),
-            block_type: "test".to_string(),
-            config: HashMap::new(),
-            position: None,
-        };
-        let node2 = Node {
-            id: "node2".to_string(),
-            block_type: "test".to_string(),
-            config: HashMap::new(),
-            position: None,
-        };
+        let node1 = Node::new("node1", "test");
+        let node2 = Node::new("node2", "test");
 
         graph.add_node(node1).unwrap();
         graph.add_node(node2).unwrap();
@@ -279,18 +554,8 @@ mod tests {
     fn test_cycle_detection() {
         let mut graph = Graph::new("test".to_string(), "Test".to_string());
 
-        let node1 = Node {
-            id: "node1".to_string(),
-            block_type: "test".to_string(),
-            config: HashMap::new(),
-            position: None,
-        };
-        let node2 = Node {
-            id: "node2".to_string(),
-            block_type: "test".to_string(),
-            config: HashMap::new(),
-            position: None,
-        };
+        let node1 = Node::new("node1", "test");
+        let node2 = Node::new("node2", "test");
 
         graph.add_node(node1).unwrap();
         graph.add_node(node2).unwrap();
@@ -321,12 +586,7 @@ mod tests {
         let mut graph = Graph::new("test".to_string(), "Test".to_string());
 
         for i in 1..=3 {
-            let node = Node {
-                id: format!("node{}", i),
-                block_type: "test".to_string(),
-                config: HashMap::new(),
-                position: None,
-            };
+            let node = Node::new(format!("node{}", i), "test");
             graph.add_node(node).unwrap();
         }
 
@@ -361,4 +621,49 @@ mod tests {
         let node3_pos = order.iter().position(|n| n == "node3").unwrap();
         assert!(node2_pos < node3_pos);
     }
+
+    #[test]
+    fn test_rename_move_and_update_config() {
+        let mut graph = Graph::new("test".to_string(), "Test".to_string());
+        for id in ["a", "b"] {
+            graph.add_node(Node::new(id, "test")).unwrap();
+        }
+        let conn = Connection {
+            from_node: "a".to_string(),
+            from_port: "out".to_string(),
+            to_node: "b".to_string(),
+            to_port: "in".to_string(),
+        };
+        graph.add_connection(conn.clone()).unwrap();
+        graph.add_output("a", "out").unwrap();
+
+        graph.rename_node("a", "source").unwrap();
+        assert!(graph.nodes.contains_key("source") && !graph.nodes.contains_key("a"));
+        assert_eq!(graph.nodes["source"].id, "source");
+        assert_eq!(graph.connections[0].from_node, "source");
+        assert_eq!(graph.outputs[0].node, "source");
+        assert!(graph.rename_node("source", "b").is_err());
+        assert!(graph.rename_node("a", "c").is_err());
+
+        assert_eq!(graph.move_node("b", Some((1.0, 2.0))).unwrap(), None);
+        assert_eq!(graph.move_node("b", None).unwrap(), Some((1.0, 2.0)));
+        assert_eq!(
+            graph.update_config("b", "k", Some(Value::Int(1))).unwrap(),
+            None
+        );
+        assert_eq!(
+            graph.update_config("b", "k", None).unwrap(),
+            Some(Value::Int(1))
+        );
+
+        assert!(graph.remove_connection(&conn).is_err());
+        let renamed = Connection {
+            from_node: "source".to_string(),
+            ..conn
+        };
+        graph.remove_connection(&renamed).unwrap();
+        assert!(graph.connections.is_empty());
+        graph.remove_output("source", "out").unwrap();
+        assert!(graph.remove_output("source", "out").is_err());
+    }
 }
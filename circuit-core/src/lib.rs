@@ -11,17 +11,60 @@
 //! - **Graph**: The execution flow connecting nodes together
 //! - **Engine**: The runtime that executes the graph
 
+pub mod async_block;
+pub mod batch;
 pub mod block;
 pub mod blocks;
+pub mod checkpoint;
+pub mod collab;
+pub mod context;
+pub mod debugger;
+pub mod diff;
+pub mod editor;
 pub mod engine;
 pub mod error;
+pub mod execution;
 pub mod graph;
+pub mod lifecycle;
+pub mod limits;
+mod liveness;
+pub mod migrate;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pipeline;
+pub mod plan;
+mod ports;
+pub mod record;
+pub mod report;
+pub mod retry;
+mod serde_duration;
+pub mod snapshot;
+mod state;
+pub mod suspend;
 pub mod value;
 
-pub use block::{Block, BlockContext, BlockMetadata};
-pub use engine::Engine;
+pub use async_block::AsyncBlock;
+pub use block::{Block, BlockContext, BlockMetadata, NodePorts};
+pub use checkpoint::{Checkpoint, CheckpointStep, CheckpointStore};
+pub use collab::{GraphOp, MemoryTransport, OpId, OpTransport, Replica};
+pub use context::ExecutionContext;
+pub use debugger::{Breakpoint, CompareOp, DebugSession, DebugStatus};
+pub use diff::{GraphPatch, PatchConflict};
+pub use editor::{EditEvent, GraphEdit, GraphEditor};
+pub use engine::{Engine, NodeOutputs};
 pub use error::{CircuitError, Result};
-pub use graph::{Graph, NodeId};
+pub use execution::{CancellationToken, ExecutionEvent, ExecutionObserver, ExecutionOptions};
+pub use graph::{Graph, GraphOutput, NodeId, GRAPH_FORMAT_VERSION};
+pub use lifecycle::{LifecycleEvent, LifecycleListener, ListenerId};
+pub use limits::EngineLimits;
+pub use migrate::{GraphChange, MigrationRegistry, MigrationReport};
+#[cfg(not(target_arch = "wasm32"))]
+pub use pipeline::{Pipeline, PipelineInput, PipelineOptions};
+pub use plan::ExecutionPlan;
+pub use record::{Divergence, DivergenceKind, ExecutionRecording, ReplayReport};
+pub use report::{ExecutionReport, NodeReport, NodeStatus};
+pub use retry::{Backoff, RetryPolicy};
+pub use snapshot::EngineSnapshot;
+pub use suspend::{ExecutionOutcome, SuspendedExecution};
 pub use value::Value;
 
 #[cfg(test)]
@@ -31,6 +74,6 @@ mod tests {
     #[test]
     fn test_basic_workflow() {
         let engine = Engine::new();
-        assert!(engine.graphs.is_empty());
+        assert!(engine.list_graphs().is_empty());
     }
 }
@@ -0,0 +1,121 @@
+use crate::migrate::GraphChange;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// A change to the graphs or blocks registered with an engine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LifecycleEvent {
+    GraphLoaded {
+        graph_id: String,
+    },
+    GraphReplaced {
+        graph_id: String,
+    },
+    GraphUnloaded {
+        graph_id: String,
+    },
+    /// A graph was upgraded or had aliases resolved when it was loaded
+    GraphMigrated {
+        graph_id: String,
+        from_version: u32,
+        to_version: u32,
+        changes: Vec<GraphChange>,
+    },
+    BlockRegistered {
+        block_id: String,
+    },
+    BlockReplaced {
+        block_id: String,
+    },
+    BlockUnregistered {
+        block_id: String,
+    },
+}
+
+/// Callback invoked after every lifecycle change
+///
+/// Listeners run on the thread that made the change, after the engine has
+/// released its locks, so they may call back into the engine.
+pub type LifecycleListener = Arc<dyn Fn(&LifecycleEvent) + Send + Sync>;
+
+/// Handle returned when adding a listener, used to remove it again
+pub type ListenerId = u64;
+
+/// Registered lifecycle listeners
+#[derive(Default)]
+pub(crate) struct Listeners {
+    next_id: AtomicU64,
+    listeners: RwLock<Vec<(ListenerId, LifecycleListener)>>,
+}
+
+impl Listeners {
+    pub(crate) fn add(&self, listener: LifecycleListener) -> ListenerId {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.listeners
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((id, listener));
+        id
+    }
+
+    pub(crate) fn remove(&self, id: ListenerId) -> bool {
+        let mut listeners = self
+            .listeners
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let before = listeners.len();
+        listeners.retain(|(listener_id, _)| *listener_id != id);
+        listeners.len() != before
+    }
+
+    pub(crate) fn emit(&self, event: LifecycleEvent) {
+        // Clone the list so listeners can add or remove listeners themselves
+        let listeners: Vec<LifecycleListener> = self
+            .listeners
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(_, listener)| Arc::clone(listener))
+            .collect();
+        for listener in listeners {
+            listener(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_add_emit_remove() {
+        let listeners = Listeners::default();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        let id = listeners.add(Arc::new(move |event| {
+            sink.lock().unwrap().push(event.clone());
+        }));
+
+        let event = LifecycleEvent::GraphLoaded {
+            graph_id: "g".to_string(),
+        };
+        listeners.emit(event.clone());
+        assert!(listeners.remove(id));
+        assert!(!listeners.remove(id));
+        listeners.emit(event.clone());
+
+        assert_eq!(*seen.lock().unwrap(), vec![event]);
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        let event = LifecycleEvent::BlockReplaced {
+            block_id: "math.add".to_string(),
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(json, r#"{"type":"block_replaced","block_id":"math.add"}"#);
+    }
+}
@@ -0,0 +1,373 @@
+use crate::{
+    error::{CircuitError, Result},
+    graph::Graph,
+    value::Value,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Resource limits applied to graphs loaded into an engine and to the values
+/// their blocks produce
+///
+/// Every limit is optional; `None` means unlimited. Graph-shape limits are
+/// checked in `Engine::load_graph`, value limits are checked on node config at
+/// load time and on every block output at execution time.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngineLimits {
+    /// Maximum number of nodes per graph
+    pub max_nodes: Option<usize>,
+    /// Maximum number of connections per graph
+    pub max_connections: Option<usize>,
+    /// Maximum nesting depth of `Value::Array`/`Value::Object`
+    pub max_value_depth: Option<usize>,
+    /// Maximum length in bytes of any string value
+    pub max_string_len: Option<usize>,
+    /// Maximum length of any `Value::Bytes` payload
+    pub max_bytes_len: Option<usize>,
+    /// Maximum estimated memory, in bytes, held by all node outputs of one
+    /// execution; an array, object or bytes payload passed between nodes is
+    /// counted once
+    pub max_output_memory: Option<usize>,
+    /// Longest delay a node's retry policy may wait between attempts; zero
+    /// on hosts that cannot block, such as the browser
+    #[serde(
+        rename = "max_retry_delay_ms",
+        with = "crate::serde_duration::option_millis",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_retry_delay: Option<Duration>,
+}
+
+impl EngineLimits {
+    /// Create limits with everything unlimited
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the number of nodes per graph
+    pub fn with_max_nodes(mut self, max: usize) -> Self {
+        self.max_nodes = Some(max);
+        self
+    }
+
+    /// Limit the number of connections per graph
+    pub fn with_max_connections(mut self, max: usize) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// Limit the nesting depth of arrays and objects
+    pub fn with_max_value_depth(mut self, max: usize) -> Self {
+        self.max_value_depth = Some(max);
+        self
+    }
+
+    /// Limit the length of strings
+    pub fn with_max_string_len(mut self, max: usize) -> Self {
+        self.max_string_len = Some(max);
+        self
+    }
+
+    /// Limit the length of byte payloads
+    pub fn with_max_bytes_len(mut self, max: usize) -> Self {
+        self.max_bytes_len = Some(max);
+        self
+    }
+
+    /// Limit the memory held by node outputs during one execution
+    pub fn with_max_output_memory(mut self, max: usize) -> Self {
+        self.max_output_memory = Some(max);
+        self
+    }
+
+    /// Limit the delay between retry attempts of a node
+    pub fn with_max_retry_delay(mut self, max: Duration) -> Self {
+        self.max_retry_delay = Some(max);
+        self
+    }
+
+    /// Check the shape and node configuration of a graph
+    pub fn check_graph(&self, graph: &Graph) -> Result<()> {
+        if let Some(max) = self.max_nodes {
+            if graph.nodes.len() > max {
+                return Err(CircuitError::LimitExceeded(format!(
+                    "Graph '{}' has {} nodes, limit is {}",
+                    graph.id,
+                    graph.nodes.len(),
+                    max
+                )));
+            }
+        }
+        if let Some(max) = self.max_connections {
+            if graph.connections.len() > max {
+                return Err(CircuitError::LimitExceeded(format!(
+                    "Graph '{}' has {} connections, limit is {}",
+                    graph.id,
+                    graph.connections.len(),
+                    max
+                )));
+            }
+        }
+        for node in graph.nodes.values() {
+            if let (Some(max), Some(retry)) = (self.max_retry_delay, &node.retry) {
+                let delay = retry.max_delay();
+                if delay > max {
+                    return Err(CircuitError::LimitExceeded(format!(
+                        "Node '{}' waits {} ms between retries, limit is {} ms",
+                        node.id,
+                        delay.as_millis(),
+                        max.as_millis()
+                    )));
+                }
+            }
+            for (key, value) in &node.config {
+                self.check_value(value).map_err(|e| {
+                    prefix_limit_error(e, &format!("Node '{}' config '{}'", node.id, key))
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check a single value against the depth, string and bytes limits
+    pub fn check_value(&self, value: &Value) -> Result<()> {
+        if !self.has_value_limits() {
+            return Ok(());
+        }
+        self.check_value_at(value, 0)
+    }
+
+    /// Whether any limit requires walking produced values
+    pub fn has_value_limits(&self) -> bool {
+        self.max_value_depth.is_some()
+            || self.max_string_len.is_some()
+            || self.max_bytes_len.is_some()
+    }
+
+    fn check_value_at(&self, value: &Value, depth: usize) -> Result<()> {
+        match value {
+            Value::String(s) => {
+                if let Some(max) = self.max_string_len {
+                    if s.len() > max {
+                        return Err(CircuitError::LimitExceeded(format!(
+                            "string of {} bytes exceeds limit of {}",
+                            s.len(),
+                            max
+                        )));
+                    }
+                }
+            }
+            Value::Bytes(b) => {
+                if let Some(max) = self.max_bytes_len {
+                    if b.len() > max {
+                        return Err(CircuitError::LimitExceeded(format!(
+                            "bytes of length {} exceed limit of {}",
+                            b.len(),
+                            max
+                        )));
+                    }
+                }
+            }
+            Value::Array(items) => {
+                self.check_depth(depth + 1)?;
+                for item in items.iter() {
+                    self.check_value_at(item, depth + 1)?;
+                }
+            }
+            Value::Object(fields) => {
+                self.check_depth(depth + 1)?;
+                for item in fields.values() {
+                    self.check_value_at(item, depth + 1)?;
+                }
+            }
+            Value::Null | Value::Bool(_) | Value::Int(_) | Value::Float(_) => {}
+        }
+        Ok(())
+    }
+
+    fn check_depth(&self, depth: usize) -> Result<()> {
+        match self.max_value_depth {
+            Some(max) if depth > max => Err(CircuitError::LimitExceeded(format!(
+                "value nesting depth exceeds limit of {}",
+                max
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Prefix a limit violation with where it happened
+pub(crate) fn prefix_limit_error(error: CircuitError, location: &str) -> CircuitError {
+    match error {
+        CircuitError::LimitExceeded(message) => {
+            CircuitError::LimitExceeded(format!("{}: {}", location, message))
+        }
+        other => other,
+    }
+}
+
+/// Estimated memory held by the node outputs of one execution
+///
+/// An output sharing its array, object or bytes payload with another live
+/// output adds only its own `Value`; payloads nested in a new value are
+/// counted again.
+#[derive(Default)]
+pub(crate) struct OutputMemory {
+    bytes: usize,
+    /// Live references per payload address
+    shared: HashMap<usize, usize>,
+}
+
+impl OutputMemory {
+    pub(crate) fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub(crate) fn add(&mut self, value: &Value) {
+        let size = match value.payload_addr() {
+            Some(addr) => {
+                let refs = self.shared.entry(addr).or_default();
+                *refs += 1;
+                if *refs == 1 {
+                    value.estimated_size()
+                } else {
+                    std::mem::size_of::<Value>()
+                }
+            }
+            None => value.estimated_size(),
+        };
+        self.bytes += size;
+    }
+
+    pub(crate) fn release(&mut self, value: &Value) {
+        let size = match value.payload_addr() {
+            Some(addr) => match self.shared.get_mut(&addr) {
+                Some(refs) if *refs > 1 => {
+                    *refs -= 1;
+                    std::mem::size_of::<Value>()
+                }
+                _ => {
+                    self.shared.remove(&addr);
+                    value.estimated_size()
+                }
+            },
+            None => value.estimated_size(),
+        };
+        self.bytes = self.bytes.saturating_sub(size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::Node;
+    use crate::retry::RetryPolicy;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn nested(depth: usize) -> Value {
+        let mut value = Value::Int(1);
+        for _ in 0..depth {
+            value = Value::from(vec![value]);
+        }
+        value
+    }
+
+    #[test]
+    fn test_unlimited_by_default() {
+        let limits = EngineLimits::new();
+        assert!(limits.check_value(&nested(64)).is_ok());
+        assert!(limits
+            .check_value(&Value::String("x".repeat(10_000)))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_value_depth_limit() {
+        let limits = EngineLimits::new().with_max_value_depth(2);
+        assert!(limits.check_value(&nested(2)).is_ok());
+        assert!(matches!(
+            limits.check_value(&nested(3)),
+            Err(CircuitError::LimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn test_nested_string_limit() {
+        let limits = EngineLimits::new().with_max_string_len(3);
+        let value = Value::from(vec![Value::String("abcd".to_string())]);
+        assert!(matches!(
+            limits.check_value(&value),
+            Err(CircuitError::LimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn test_bytes_limit() {
+        let limits = EngineLimits::new().with_max_bytes_len(2);
+        assert!(limits
+            .check_value(&Value::Bytes(Arc::new(vec![1, 2])))
+            .is_ok());
+        assert!(limits
+            .check_value(&Value::Bytes(Arc::new(vec![1, 2, 3])))
+            .is_err());
+    }
+
+    #[test]
+    fn test_graph_node_limit() {
+        let mut graph = Graph::new("g".to_string(), "G".to_string());
+        for i in 0..3 {
+            graph
+                .add_node(Node::new(format!("n{}", i), "test"))
+                .unwrap();
+        }
+
+        assert!(EngineLimits::new()
+            .with_max_nodes(3)
+            .check_graph(&graph)
+            .is_ok());
+        assert!(EngineLimits::new()
+            .with_max_nodes(2)
+            .check_graph(&graph)
+            .is_err());
+    }
+
+    #[test]
+    fn test_graph_config_checked() {
+        let mut graph = Graph::new("g".to_string(), "G".to_string());
+        let mut config = HashMap::new();
+        config.insert("value".to_string(), Value::String("too long".to_string()));
+        graph
+            .add_node(Node::new("n", "test").with_config(config))
+            .unwrap();
+
+        let err = EngineLimits::new()
+            .with_max_string_len(4)
+            .check_graph(&graph)
+            .unwrap_err();
+        assert!(err.to_string().contains("Node 'n' config 'value'"));
+    }
+
+    #[test]
+    fn test_retry_delay_limit() {
+        let mut graph = Graph::new("g".to_string(), "G".to_string());
+        graph
+            .add_node(Node::new("n", "test").with_retry(RetryPolicy::new(3)))
+            .unwrap();
+        let limits = EngineLimits::new().with_max_retry_delay(Duration::ZERO);
+        assert!(limits.check_graph(&graph).is_ok());
+
+        let backoff = RetryPolicy::new(3).with_fixed_backoff(Duration::from_millis(10));
+        graph.nodes.get_mut("n").unwrap().retry = Some(backoff);
+        assert!(matches!(
+            limits.check_graph(&graph),
+            Err(CircuitError::LimitExceeded(_))
+        ));
+
+        let json = serde_json::to_string(&limits).unwrap();
+        assert!(json.contains(r#""max_retry_delay_ms":0"#));
+        assert_eq!(serde_json::from_str::<EngineLimits>(&json).unwrap(), limits);
+    }
+}
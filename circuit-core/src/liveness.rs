@@ -0,0 +1,138 @@
+use crate::{
+    execution::ExecutionOptions,
+    graph::{Graph, NodeId},
+    plan::ExecutionPlan,
+    value::Value,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Tracks how many downstream consumers still need each node output, so
+/// outputs can be dropped as soon as the last consumer has run
+pub(crate) struct OutputLiveness {
+    /// Remaining consumers per (node, port)
+    remaining: HashMap<(NodeId, String), usize>,
+    /// Nodes whose outputs are all kept until the end
+    keep_nodes: HashSet<NodeId>,
+    /// Individual ports kept until the end
+    keep_ports: HashSet<(NodeId, String)>,
+}
+
+impl OutputLiveness {
+    /// Count consumers and decide which outputs survive the execution
+    ///
+    /// Only plan nodes that are still to run, i.e. not in `completed`, count
+    /// as consumers. Requested nodes and declared graph outputs are kept.
+    /// When neither is given, the outputs of sink nodes (nodes without
+    /// outgoing connections) are kept instead.
+    pub(crate) fn new(
+        graph: &Graph,
+        plan: &ExecutionPlan,
+        completed: &HashSet<NodeId>,
+        options: &ExecutionOptions,
+    ) -> Self {
+        let mut remaining: HashMap<(NodeId, String), usize> = HashMap::new();
+        let pending = plan
+            .steps
+            .iter()
+            .filter(|step| !completed.contains(&step.node_id));
+        for conn in pending.flat_map(|step| &step.incoming) {
+            *remaining
+                .entry((conn.from_node.clone(), conn.from_port.clone()))
+                .or_default() += 1;
+        }
+
+        let mut keep_nodes: HashSet<NodeId> = options.keep_outputs.iter().cloned().collect();
+        let keep_ports: HashSet<(NodeId, String)> = graph
+            .outputs
+            .iter()
+            .map(|output| (output.node.clone(), output.port.clone()))
+            .collect();
+
+        if keep_nodes.is_empty() && keep_ports.is_empty() {
+            let sources: HashSet<&str> = graph
+                .connections
+                .iter()
+                .map(|conn| conn.from_node.as_str())
+                .collect();
+            keep_nodes = graph
+                .nodes
+                .keys()
+                .filter(|id| !sources.contains(id.as_str()))
+                .cloned()
+                .collect();
+        }
+
+        Self {
+            remaining,
+            keep_nodes,
+            keep_ports,
+        }
+    }
+
+    fn is_kept(&self, node_id: &str, port: &str) -> bool {
+        self.keep_nodes.contains(node_id)
+            || self
+                .keep_ports
+                .contains(&(node_id.to_string(), port.to_string()))
+    }
+
+    /// Drop ports of a freshly executed node that no one consumes
+    pub(crate) fn release_unused(
+        &self,
+        node_id: &str,
+        outputs: &mut HashMap<String, Value>,
+    ) -> Vec<Value> {
+        let unused: Vec<String> = outputs
+            .keys()
+            .filter(|port| {
+                !self.is_kept(node_id, port)
+                    && !self
+                        .remaining
+                        .contains_key(&(node_id.to_string(), port.to_string()))
+            })
+            .cloned()
+            .collect();
+
+        unused
+            .iter()
+            .filter_map(|port| outputs.remove(port))
+            .collect()
+    }
+
+    /// Record that `node_id` has consumed its inputs and drop every upstream
+    /// output that has no consumers left
+    pub(crate) fn consume(
+        &mut self,
+        graph: &Graph,
+        node_id: &str,
+        node_outputs: &mut HashMap<NodeId, HashMap<String, Value>>,
+    ) -> Vec<Value> {
+        let mut released = Vec::new();
+
+        for conn in graph.get_incoming_connections(node_id) {
+            let key = (conn.from_node.clone(), conn.from_port.clone());
+            let Some(count) = self.remaining.get_mut(&key) else {
+                continue;
+            };
+            *count -= 1;
+            if *count > 0 {
+                continue;
+            }
+            self.remaining.remove(&key);
+            if self.is_kept(&conn.from_node, &conn.from_port) {
+                continue;
+            }
+
+            if let Some(outputs) = node_outputs.get_mut(&conn.from_node) {
+                if let Some(value) = outputs.remove(&conn.from_port) {
+                    released.push(value);
+                }
+                if outputs.is_empty() && !self.keep_nodes.contains(&conn.from_node) {
+                    node_outputs.remove(&conn.from_node);
+                }
+            }
+        }
+
+        released
+    }
+}
@@ -0,0 +1,460 @@
+use crate::{
+    error::{CircuitError, Result},
+    graph::{Graph, NodeId, NodeMode},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+
+/// Upgrades a graph document by one format version, in place
+pub type MigrationFn = Arc<dyn Fn(&mut serde_json::Value) -> Result<()> + Send + Sync>;
+
+/// A change made to a graph while loading it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GraphChange {
+    /// The document was upgraded from `from_version` to the next version
+    Migrated {
+        from_version: u32,
+        description: String,
+    },
+    /// The node's block type was an alias
+    BlockRenamed {
+        node_id: NodeId,
+        from: String,
+        to: String,
+    },
+    /// A port of the node, used by a connection, output or pinned value, was an alias
+    PortRenamed {
+        node_id: NodeId,
+        from: String,
+        to: String,
+    },
+}
+
+/// What loading a graph changed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationReport {
+    pub graph_id: String,
+    /// Format version of the document as written
+    pub from_version: u32,
+    pub to_version: u32,
+    pub changes: Vec<GraphChange>,
+}
+
+impl MigrationReport {
+    /// Whether the graph loaded as written
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Migrations that upgrade graph documents one format version at a time
+#[derive(Clone)]
+pub struct MigrationRegistry {
+    steps: BTreeMap<u32, Vec<Migration>>,
+}
+
+#[derive(Clone)]
+struct Migration {
+    description: String,
+    migrate: MigrationFn,
+    /// Whether applying it counts as a change to the graph
+    reported: bool,
+}
+
+impl MigrationRegistry {
+    /// Create a registry with the migrations of every older graph format
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        // Version 1 only added the `format_version` field itself, so
+        // upgrading to it changes nothing worth reporting
+        registry.add(
+            0,
+            "Add format_version".to_string(),
+            Arc::new(|_| Ok(())),
+            false,
+        );
+        registry
+    }
+
+    /// Create a registry without any migrations
+    pub fn empty() -> Self {
+        Self {
+            steps: BTreeMap::new(),
+        }
+    }
+
+    /// Add a migration from `from_version` to the next version
+    ///
+    /// Migrations of the same version run in the order they were added.
+    pub fn register(
+        &mut self,
+        from_version: u32,
+        description: impl Into<String>,
+        migrate: MigrationFn,
+    ) {
+        self.add(from_version, description.into(), migrate, true);
+    }
+
+    fn add(
+        &mut self,
+        from_version: u32,
+        description: String,
+        migrate: MigrationFn,
+        reported: bool,
+    ) {
+        self.steps.entry(from_version).or_default().push(Migration {
+            description,
+            migrate,
+            reported,
+        });
+    }
+
+    /// Upgrade `document` to `to_version` step by step, returning the
+    /// migrations applied
+    ///
+    /// A document without a `format_version` is version 0. Fails, leaving
+    /// the document partly upgraded, if a version has no migration or a
+    /// migration fails.
+    pub fn migrate(
+        &self,
+        document: &mut serde_json::Value,
+        to_version: u32,
+    ) -> Result<Vec<GraphChange>> {
+        let version = document_version(document)?;
+        if version > to_version {
+            return Err(CircuitError::InvalidInput(format!(
+                "Graph format version {} is newer than the supported version {}",
+                version, to_version
+            )));
+        }
+
+        let mut changes = Vec::new();
+        for from_version in version..to_version {
+            let steps = self.steps.get(&from_version).ok_or_else(|| {
+                CircuitError::InvalidInput(format!(
+                    "No migration from graph format version {}",
+                    from_version
+                ))
+            })?;
+            for step in steps {
+                (step.migrate)(document).map_err(|e| {
+                    CircuitError::InvalidInput(format!(
+                        "Migration '{}' from graph format version {} failed: {}",
+                        step.description, from_version, e
+                    ))
+                })?;
+                if step.reported {
+                    changes.push(GraphChange::Migrated {
+                        from_version,
+                        description: step.description.clone(),
+                    });
+                }
+            }
+            document["format_version"] = (from_version + 1).into();
+        }
+        Ok(changes)
+    }
+}
+
+impl Default for MigrationRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for MigrationRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.steps.iter().map(|(version, steps)| {
+                let descriptions: Vec<&str> =
+                    steps.iter().map(|step| step.description.as_str()).collect();
+                (version, descriptions)
+            }))
+            .finish()
+    }
+}
+
+/// Format version of a graph document
+pub(crate) fn document_version(document: &serde_json::Value) -> Result<u32> {
+    if !document.is_object() {
+        return Err(CircuitError::InvalidInput(
+            "Graph document is not a JSON object".to_string(),
+        ));
+    }
+    match document.get("format_version") {
+        None | Some(serde_json::Value::Null) => Ok(0),
+        Some(version) => version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| {
+                CircuitError::InvalidInput(format!("Invalid graph format version {}", version))
+            }),
+    }
+}
+
+/// Old block and port IDs that keep resolving after a rename
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Aliases {
+    blocks: HashMap<String, String>,
+    /// Keyed by block type and old port ID
+    ports: HashMap<(String, String), String>,
+}
+
+impl Aliases {
+    pub(crate) fn add_block(&mut self, alias: &str, block_id: &str) -> Result<()> {
+        if self.block(block_id) == alias {
+            return Err(CircuitError::InvalidInput(format!(
+                "Block alias '{}' -> '{}' would form a cycle",
+                alias, block_id
+            )));
+        }
+        self.blocks.insert(alias.to_string(), block_id.to_string());
+        Ok(())
+    }
+
+    pub(crate) fn add_port(&mut self, block_id: &str, alias: &str, port: &str) -> Result<()> {
+        if self.port(block_id, port) == alias {
+            return Err(CircuitError::InvalidInput(format!(
+                "Port alias '{}' -> '{}' of block type '{}' would form a cycle",
+                alias, port, block_id
+            )));
+        }
+        self.ports
+            .insert((block_id.to_string(), alias.to_string()), port.to_string());
+        Ok(())
+    }
+
+    pub(crate) fn is_block_alias(&self, id: &str) -> bool {
+        self.blocks.contains_key(id)
+    }
+
+    // Adding an alias never closes a cycle, so following them terminates
+    pub(crate) fn block<'a>(&'a self, mut id: &'a str) -> &'a str {
+        while let Some(next) = self.blocks.get(id) {
+            id = next;
+        }
+        id
+    }
+
+    fn port<'a>(&'a self, block_id: &str, mut port: &'a str) -> &'a str {
+        while let Some(next) = self.ports.get(&(block_id.to_string(), port.to_string())) {
+            port = next;
+        }
+        port
+    }
+
+    /// Replace the aliases used by `graph`, returning what was renamed
+    pub(crate) fn apply(&self, graph: &mut Graph) -> Vec<GraphChange> {
+        if self.blocks.is_empty() && self.ports.is_empty() {
+            return Vec::new();
+        }
+        let mut changes = Vec::new();
+        let mut node_ids: Vec<NodeId> = graph.nodes.keys().cloned().collect();
+        node_ids.sort_unstable();
+
+        for node_id in &node_ids {
+            let node = graph
+                .nodes
+                .get_mut(node_id)
+                .expect("node ID from the graph");
+            let block_type = self.block(&node.block_type);
+            if block_type != node.block_type {
+                changes.push(GraphChange::BlockRenamed {
+                    node_id: node_id.clone(),
+                    from: node.block_type.clone(),
+                    to: block_type.to_string(),
+                });
+                node.block_type = block_type.to_string();
+            }
+        }
+
+        let mut rename = |node_id: &str, port: &mut String| {
+            let Some(node) = graph.nodes.get(node_id) else {
+                return;
+            };
+            let renamed = self.port(&node.block_type, port);
+            if renamed != port {
+                let change = GraphChange::PortRenamed {
+                    node_id: node_id.to_string(),
+                    from: port.clone(),
+                    to: renamed.to_string(),
+                };
+                if !changes.contains(&change) {
+                    changes.push(change);
+                }
+                *port = renamed.to_string();
+            }
+        };
+        let mut connections = std::mem::take(&mut graph.connections);
+        for conn in &mut connections {
+            rename(&conn.from_node, &mut conn.from_port);
+            rename(&conn.to_node, &mut conn.to_port);
+        }
+        let mut outputs = std::mem::take(&mut graph.outputs);
+        for output in &mut outputs {
+            rename(&output.node, &mut output.port);
+        }
+        let mut pinned = Vec::new();
+        for node_id in &node_ids {
+            if let NodeMode::Pinned(values) = &graph.nodes[node_id].mode {
+                let mut renamed = HashMap::new();
+                let mut ports: Vec<&String> = values.keys().collect();
+                ports.sort_unstable();
+                for port in ports {
+                    let mut new_port = port.clone();
+                    rename(node_id, &mut new_port);
+                    renamed.insert(new_port, values[port].clone());
+                }
+                pinned.push((node_id.clone(), renamed));
+            }
+        }
+        graph.connections = connections;
+        graph.outputs = outputs;
+        for (node_id, values) in pinned {
+            if let Some(node) = graph.nodes.get_mut(&node_id) {
+                node.mode = NodeMode::Pinned(values);
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{Connection, GraphOutput, Node};
+    use crate::value::Value;
+    use serde_json::json;
+
+    #[test]
+    fn test_migrations_run_step_by_step() {
+        let mut registry = MigrationRegistry::empty();
+        registry.register(
+            2,
+            "Rename title to name",
+            Arc::new(|doc| {
+                let title = doc.as_object_mut().unwrap().remove("title");
+                doc["name"] = title.unwrap_or_default();
+                Ok(())
+            }),
+        );
+        registry.register(
+            1,
+            "Drop legacy field",
+            Arc::new(|doc| {
+                doc.as_object_mut().unwrap().remove("legacy");
+                Ok(())
+            }),
+        );
+
+        let mut doc = json!({"format_version": 1, "title": "G", "legacy": true});
+        let changes = registry.migrate(&mut doc, 3).unwrap();
+        assert_eq!(doc, json!({"format_version": 3, "name": "G"}));
+        assert_eq!(
+            changes,
+            vec![
+                GraphChange::Migrated {
+                    from_version: 1,
+                    description: "Drop legacy field".to_string(),
+                },
+                GraphChange::Migrated {
+                    from_version: 2,
+                    description: "Rename title to name".to_string(),
+                },
+            ]
+        );
+
+        // Already current
+        assert!(registry.migrate(&mut doc, 3).unwrap().is_empty());
+        let newer = registry.migrate(&mut doc, 2).unwrap_err().to_string();
+        assert!(
+            newer.contains("newer than the supported version 2"),
+            "{}",
+            newer
+        );
+        // Unversioned documents are version 0, which has no migration here
+        let missing = registry.migrate(&mut json!({}), 3).unwrap_err().to_string();
+        assert!(
+            missing.contains("No migration from graph format version 0"),
+            "{}",
+            missing
+        );
+        // The built-in upgrade to version 1 changes nothing worth reporting
+        let mut doc = json!({});
+        let changes = MigrationRegistry::new().migrate(&mut doc, 1).unwrap();
+        assert!(changes.is_empty());
+        assert_eq!(doc, json!({"format_version": 1}));
+    }
+
+    #[test]
+    fn test_aliases_rename_blocks_and_ports() {
+        let mut aliases = Aliases::default();
+        aliases.add_block("math.plus", "math.add").unwrap();
+        aliases.add_block("add", "math.plus").unwrap();
+        aliases.add_port("math.add", "x", "a").unwrap();
+        aliases.add_port("math.add", "total", "result").unwrap();
+        assert!(aliases.add_block("math.add", "add").is_err());
+        assert!(aliases.add_port("math.add", "a", "x").is_err());
+
+        let node = |id: &str, block_type: &str| Node::new(id, block_type);
+        let mut graph = Graph::new("g".to_string(), "G".to_string());
+        graph.add_node(node("first", "add")).unwrap();
+        let mut second = node("second", "math.add");
+        second.mode = NodeMode::Pinned(HashMap::from([("total".to_string(), Value::Int(1))]));
+        graph.add_node(second).unwrap();
+        graph
+            .add_connection(Connection {
+                from_node: "first".to_string(),
+                from_port: "total".to_string(),
+                to_node: "second".to_string(),
+                to_port: "x".to_string(),
+            })
+            .unwrap();
+        graph.add_output("first", "total").unwrap();
+
+        let changes = aliases.apply(&mut graph);
+        assert_eq!(graph.nodes["first"].block_type, "math.add");
+        assert_eq!(graph.connections[0].from_port, "result");
+        assert_eq!(graph.connections[0].to_port, "a");
+        assert_eq!(
+            graph.outputs,
+            vec![GraphOutput {
+                node: "first".to_string(),
+                port: "result".to_string(),
+            }]
+        );
+        assert_eq!(
+            graph.nodes["second"].mode,
+            NodeMode::Pinned(HashMap::from([("result".to_string(), Value::Int(1))]))
+        );
+        assert_eq!(
+            changes,
+            vec![
+                GraphChange::BlockRenamed {
+                    node_id: "first".to_string(),
+                    from: "add".to_string(),
+                    to: "math.add".to_string(),
+                },
+                GraphChange::PortRenamed {
+                    node_id: "first".to_string(),
+                    from: "total".to_string(),
+                    to: "result".to_string(),
+                },
+                GraphChange::PortRenamed {
+                    node_id: "second".to_string(),
+                    from: "x".to_string(),
+                    to: "a".to_string(),
+                },
+                GraphChange::PortRenamed {
+                    node_id: "second".to_string(),
+                    from: "total".to_string(),
+                    to: "result".to_string(),
+                },
+            ]
+        );
+        assert!(aliases.apply(&mut graph).is_empty());
+    }
+}
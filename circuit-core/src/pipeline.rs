@@ -0,0 +1,696 @@
+use crate::{
+    block::BlockContext,
+    context::ExecutionContext,
+    engine::{NodeOutputs, RunEnv},
+    error::{panic_message, CircuitError, Result},
+    execution::CancellationToken,
+    graph::{Graph, NodeId},
+    limits::prefix_limit_error,
+    plan::{ExecutionPlan, PlanStep},
+    state::NodeStateSlot,
+    value::Value,
+};
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+
+/// Channel capacity used when `PipelineOptions::capacity` is not set
+pub const DEFAULT_PIPELINE_CAPACITY: usize = 16;
+
+/// Ports and controls of a pipeline started with `Engine::start_pipeline`
+#[derive(Debug, Clone)]
+pub struct PipelineOptions {
+    /// Items buffered on every connection before the sender blocks
+    pub capacity: usize,
+    /// Input ports fed by `Pipeline::send`, addressed as `"node.port"`
+    pub inputs: Vec<String>,
+    /// Output ports returned by `Pipeline::recv`, addressed as `"node.port"`
+    pub outputs: Vec<String>,
+    /// Variables and services exposed to every block
+    pub context: ExecutionContext,
+    /// Token checked before every node runs and exposed to blocks
+    pub cancellation: CancellationToken,
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_PIPELINE_CAPACITY,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            context: ExecutionContext::default(),
+            cancellation: CancellationToken::default(),
+        }
+    }
+}
+
+impl PipelineOptions {
+    /// Create options with the default capacity and no ports
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buffer up to `capacity` items per connection
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Feed the input port `"node.port"` from `Pipeline::send`
+    pub fn with_input(mut self, port: impl Into<String>) -> Self {
+        self.inputs.push(port.into());
+        self
+    }
+
+    /// Return the output port `"node.port"` from `Pipeline::recv`
+    pub fn with_output(mut self, port: impl Into<String>) -> Self {
+        self.outputs.push(port.into());
+        self
+    }
+
+    /// Run with the given variables and services
+    pub fn with_context(mut self, context: ExecutionContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Observe an existing cancellation token
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+}
+
+/// Value of a port for one item; `None` when the node produced none
+type Slot = Option<Value>;
+
+/// Sending half of a pipeline
+///
+/// Dropping it ends the stream: nodes finish the items already sent and stop.
+pub struct PipelineInput {
+    ports: Vec<(String, SyncSender<Slot>)>,
+}
+
+impl PipelineInput {
+    /// Send one item, a value for every input port keyed by `"node.port"`
+    ///
+    /// Blocks while the first nodes are still busy with earlier items, which
+    /// is how backpressure reaches the producer.
+    pub fn send(&self, mut item: HashMap<String, Value>) -> Result<()> {
+        let mut values = Vec::with_capacity(self.ports.len());
+        for (port, _) in &self.ports {
+            let value = item.remove(port).ok_or_else(|| {
+                CircuitError::InvalidInput(format!("Pipeline item has no value for '{}'", port))
+            })?;
+            values.push(value);
+        }
+        if let Some(port) = item.keys().next() {
+            return Err(CircuitError::InvalidInput(format!(
+                "'{}' is not a pipeline input",
+                port
+            )));
+        }
+
+        for ((_, sender), value) in self.ports.iter().zip(values) {
+            sender.send(Some(value)).map_err(|_| stopped())?;
+        }
+        Ok(())
+    }
+}
+
+/// A graph running as a set of node actors connected by bounded channels
+///
+/// Every node downstream of a pipeline input runs on its own thread, taking
+/// one value from each of its input ports per run and sending its outputs on
+/// to the connected nodes. The other nodes run once when the pipeline
+/// starts, and their outputs are passed to every run of the nodes they feed.
+pub struct Pipeline {
+    input: Option<PipelineInput>,
+    outputs: Vec<(String, Receiver<Slot>)>,
+    workers: Vec<JoinHandle<()>>,
+    failure: Arc<Mutex<Option<CircuitError>>>,
+}
+
+impl Pipeline {
+    /// Send one item into the pipeline; see `PipelineInput::send`
+    pub fn send(&self, item: HashMap<String, Value>) -> Result<()> {
+        self.input.as_ref().ok_or_else(stopped)?.send(item)
+    }
+
+    /// Take the sending half, e.g. to produce items on another thread
+    pub fn take_input(&mut self) -> Option<PipelineInput> {
+        self.input.take()
+    }
+
+    /// End the stream; items already sent are still processed
+    pub fn close_input(&mut self) {
+        self.input = None;
+    }
+
+    /// Wait for the next result, the values of the output ports keyed by
+    /// `"node.port"`
+    ///
+    /// Ports whose node produced no value for the item are left out. Once a
+    /// node fails, its ports are left out of the items still coming out of
+    /// the others. Returns `None` once no output has anything left, because
+    /// the input is closed or nodes failed; `finish` tells the two apart.
+    pub fn recv(&self) -> Option<HashMap<String, Value>> {
+        let mut item = HashMap::new();
+        let mut received = false;
+        for (port, receiver) in &self.outputs {
+            if let Ok(slot) = receiver.recv() {
+                received = true;
+                if let Some(value) = slot {
+                    item.insert(port.clone(), value);
+                }
+            }
+        }
+        received.then_some(item)
+    }
+
+    /// Close the input, discard results not yet received and wait for every
+    /// node to stop
+    ///
+    /// Returns the error of the first node that failed.
+    pub fn finish(mut self) -> Result<()> {
+        self.close_input();
+        while self.recv().is_some() {}
+        drop(self.outputs);
+        for worker in self.workers {
+            worker
+                .join()
+                .unwrap_or_else(|payload| panic::resume_unwind(payload));
+        }
+        let failure = self
+            .failure
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        failure.map_or(Ok(()), Err)
+    }
+
+    /// Wire up the node actors and start them
+    ///
+    /// `steps` are the plan steps of the nodes downstream of the inputs, and
+    /// `fixed` the outputs of the nodes that already ran.
+    pub(crate) fn start(
+        graph: &Graph,
+        steps: Vec<PlanStep>,
+        fixed: &NodeOutputs,
+        env: RunEnv,
+        options: &PipelineOptions,
+    ) -> Result<Self> {
+        let capacity = options.capacity.max(1);
+        let mut receivers: HashMap<NodeId, Vec<(String, Receiver<Slot>)>> = HashMap::new();
+        let mut senders: HashMap<NodeId, HashMap<String, Vec<SyncSender<Slot>>>> = HashMap::new();
+
+        let mut input_ports = Vec::new();
+        for port in &options.inputs {
+            let (node_id, port_id) = split_port(graph, port)?;
+            let (sender, receiver) = sync_channel(capacity);
+            receivers
+                .entry(node_id)
+                .or_default()
+                .push((port_id, receiver));
+            input_ports.push((port.clone(), sender));
+        }
+
+        let streamed: HashSet<NodeId> = steps.iter().map(|step| step.node_id.clone()).collect();
+        let mut wired = Vec::with_capacity(steps.len());
+        for step in steps {
+            // Connections from nodes that already ran are fixed inputs
+            let mut fixed_inputs = HashMap::new();
+            for connection in &step.incoming {
+                if streamed.contains(&connection.from_node) {
+                    let (sender, receiver) = sync_channel(capacity);
+                    receivers
+                        .entry(step.node_id.clone())
+                        .or_default()
+                        .push((connection.to_port.clone(), receiver));
+                    senders
+                        .entry(connection.from_node.clone())
+                        .or_default()
+                        .entry(connection.from_port.clone())
+                        .or_default()
+                        .push(sender);
+                } else if let Some(value) = fixed
+                    .get(&connection.from_node)
+                    .and_then(|outputs| outputs.get(&connection.from_port))
+                {
+                    fixed_inputs.insert(connection.to_port.clone(), value.clone());
+                }
+            }
+            wired.push((step, fixed_inputs));
+        }
+
+        let mut outputs = Vec::new();
+        for port in &options.outputs {
+            let (node_id, port_id) = split_port(graph, port)?;
+            if !streamed.contains(&node_id) {
+                return Err(CircuitError::InvalidInput(format!(
+                    "Pipeline output '{}' is not downstream of a pipeline input",
+                    port
+                )));
+            }
+            let (sender, receiver) = sync_channel(capacity);
+            senders
+                .entry(node_id)
+                .or_default()
+                .entry(port_id)
+                .or_default()
+                .push(sender);
+            outputs.push((port.clone(), receiver));
+        }
+
+        let failure = Arc::new(Mutex::new(None));
+        let workers = wired
+            .into_iter()
+            .map(|(step, fixed_inputs)| {
+                let node_id = step.node_id.clone();
+                let actor = NodeActor {
+                    graph_id: graph.id.clone(),
+                    fixed_inputs,
+                    inputs: receivers.remove(&node_id).unwrap_or_default(),
+                    outputs: senders.remove(&node_id).unwrap_or_default(),
+                    step,
+                    env: env.clone(),
+                    context: options.context.clone(),
+                    cancellation: options.cancellation.clone(),
+                };
+                let failure = Arc::clone(&failure);
+                thread::Builder::new()
+                    .name(format!("circuit-pipeline-{}", node_id))
+                    .spawn(move || {
+                        if let Err(e) = actor.run() {
+                            failure
+                                .lock()
+                                .unwrap_or_else(PoisonError::into_inner)
+                                .get_or_insert(e);
+                        }
+                    })
+                    .map_err(|e| CircuitError::Other(e.into()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            input: Some(PipelineInput { ports: input_ports }),
+            outputs,
+            workers,
+            failure,
+        })
+    }
+}
+
+/// One node of a running pipeline
+struct NodeActor {
+    graph_id: String,
+    step: PlanStep,
+    fixed_inputs: HashMap<String, Value>,
+    inputs: Vec<(String, Receiver<Slot>)>,
+    outputs: HashMap<String, Vec<SyncSender<Slot>>>,
+    env: RunEnv,
+    context: ExecutionContext,
+    cancellation: CancellationToken,
+}
+
+impl NodeActor {
+    // Run the block once per item until an input ends or a consumer is gone
+    fn run(mut self) -> Result<()> {
+        let execution = self
+            .context
+            .clone()
+            .with_engine_variables(Arc::clone(&self.env.variables));
+        loop {
+            let mut inputs = self.fixed_inputs.clone();
+            for (port, receiver) in &self.inputs {
+                match receiver.recv() {
+                    Ok(Some(value)) => {
+                        inputs.insert(port.clone(), value);
+                    }
+                    Ok(None) => {}
+                    Err(_) => return Ok(()),
+                }
+            }
+            if self.cancellation.is_cancelled() {
+                return Err(CircuitError::Cancelled);
+            }
+
+            // Overridden nodes emit their fixed outputs without running
+            let mut produced = match self.step.override_outputs(&inputs) {
+                Some(outputs) => outputs,
+                None => self.execute(&execution, inputs)?,
+            };
+
+            for (port, consumers) in &mut self.outputs {
+                // Every consumer expects one slot per item, so absent values
+                // are sent as absent
+                let value = produced.remove(port);
+                if let (Some(value), true) = (&value, self.env.limits.has_value_limits()) {
+                    self.env.limits.check_value(value).map_err(|e| {
+                        prefix_limit_error(
+                            e,
+                            &format!("Node '{}' output '{}'", self.step.node_id, port),
+                        )
+                    })?;
+                }
+                // A consumer that stopped no longer needs values, the others still do
+                consumers.retain(|consumer| consumer.send(value.clone()).is_ok());
+            }
+            if !self.outputs.is_empty() && self.outputs.values().all(Vec::is_empty) {
+                return Ok(());
+            }
+        }
+    }
+
+    // Run the block on one item, isolating panics
+    fn execute(
+        &self,
+        execution: &ExecutionContext,
+        inputs: HashMap<String, Value>,
+    ) -> Result<HashMap<String, Value>> {
+        let node_id = &self.step.node_id;
+        let mut context = BlockContext::for_execution(self.cancellation.clone(), execution.clone());
+        context.inputs = inputs;
+        context.config = self.step.config.clone();
+        context.node_state = Some(NodeStateSlot {
+            states: Arc::clone(&self.env.node_states),
+            graph_id: self.graph_id.clone(),
+            node_id: node_id.clone(),
+        });
+        panic::catch_unwind(AssertUnwindSafe(|| self.step.block.execute(context)))
+            .unwrap_or_else(|payload| {
+                Err(CircuitError::BlockPanicked {
+                    node: node_id.clone(),
+                    message: panic_message(payload.as_ref()),
+                })
+            })
+            .map_err(|e| match e {
+                e @ (CircuitError::Cancelled | CircuitError::BlockPanicked { .. }) => e,
+                e => CircuitError::BlockExecution(format!("Node '{}': {}", node_id, e)),
+            })
+    }
+}
+
+/// Check the pipeline inputs and find the nodes downstream of them
+pub(crate) fn streamed_nodes(
+    graph: &Graph,
+    plan: &ExecutionPlan,
+    inputs: &[String],
+) -> Result<HashSet<NodeId>> {
+    if inputs.is_empty() {
+        return Err(CircuitError::InvalidInput(
+            "A pipeline needs at least one input port".to_string(),
+        ));
+    }
+
+    let mut streamed = HashSet::new();
+    let mut seen = HashSet::new();
+    for port in inputs {
+        let (node_id, port_id) = split_port(graph, port)?;
+        if !seen.insert(port.as_str()) {
+            return Err(CircuitError::InvalidInput(format!(
+                "Pipeline input '{}' is listed twice",
+                port
+            )));
+        }
+        if graph
+            .connections
+            .iter()
+            .any(|c| c.to_node == node_id && c.to_port == port_id)
+        {
+            return Err(CircuitError::InvalidInput(format!(
+                "Pipeline input '{}' is already fed by a connection",
+                port
+            )));
+        }
+        if !plan.steps.iter().any(|step| step.node_id == node_id) {
+            return Err(CircuitError::InvalidInput(format!(
+                "Pipeline input '{}' feeds a node only pinned or muted nodes read",
+                port
+            )));
+        }
+        streamed.insert(node_id);
+    }
+
+    // Steps are in topological order, so upstream nodes are decided first
+    for step in &plan.steps {
+        if step
+            .incoming
+            .iter()
+            .any(|c| streamed.contains(&c.from_node))
+        {
+            streamed.insert(step.node_id.clone());
+        }
+    }
+    Ok(streamed)
+}
+
+fn split_port(graph: &Graph, port: &str) -> Result<(NodeId, String)> {
+    let (node, port_id) = port.split_once('.').ok_or_else(|| {
+        CircuitError::InvalidInput(format!(
+            "Pipeline port '{}' must be addressed as 'node.port'",
+            port
+        ))
+    })?;
+    if !graph.nodes.contains_key(node) {
+        return Err(CircuitError::NodeNotFound(node.to_string()));
+    }
+    Ok((node.to_string(), port_id.to_string()))
+}
+
+fn stopped() -> CircuitError {
+    CircuitError::InvalidInput("Pipeline has stopped; see Pipeline::finish".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::core::ConstantBlock;
+    use crate::blocks::math::{AddBlock, MultiplyBlock};
+    use crate::graph::{Connection, Node, NodeMode};
+    use crate::Engine;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn node(id: &str, block_type: &str, config: HashMap<String, Value>) -> Node {
+        Node::new(id, block_type).with_config(config)
+    }
+
+    fn connect(graph: &mut Graph, from: &str, to: &str, to_port: &str) {
+        let (from_node, from_port) = from.split_once('.').unwrap();
+        graph
+            .add_connection(Connection {
+                from_node: from_node.to_string(),
+                from_port: from_port.to_string(),
+                to_node: to.to_string(),
+                to_port: to_port.to_string(),
+            })
+            .unwrap();
+    }
+
+    // (one + x) * two, with x streamed into "add.b"
+    fn build_stream_engine() -> Engine {
+        let engine = Engine::new();
+        engine.register_block(Arc::new(ConstantBlock)).unwrap();
+        engine.register_block(Arc::new(AddBlock)).unwrap();
+        engine.register_block(Arc::new(MultiplyBlock)).unwrap();
+
+        let mut graph = Graph::new("stream".to_string(), "Stream".to_string());
+        for (id, value) in [("one", 1.0), ("two", 2.0)] {
+            let config = HashMap::from([("value".to_string(), Value::Float(value))]);
+            graph.add_node(node(id, "core.constant", config)).unwrap();
+        }
+        graph
+            .add_node(node("add", "math.add", HashMap::new()))
+            .unwrap();
+        graph
+            .add_node(node("mul", "math.multiply", HashMap::new()))
+            .unwrap();
+        connect(&mut graph, "one.value", "add", "a");
+        connect(&mut graph, "add.result", "mul", "a");
+        connect(&mut graph, "two.value", "mul", "b");
+        engine.load_graph(graph).unwrap();
+        engine
+    }
+
+    fn item(value: f64) -> HashMap<String, Value> {
+        HashMap::from([("add.b".to_string(), Value::Float(value))])
+    }
+
+    fn stream_options() -> PipelineOptions {
+        PipelineOptions::new()
+            .with_capacity(2)
+            .with_input("add.b")
+            .with_output("mul.result")
+    }
+
+    #[test]
+    fn test_pipeline_processes_stream_in_order() {
+        let engine = build_stream_engine();
+        let mut pipeline = engine.start_pipeline("stream", stream_options()).unwrap();
+
+        let input = pipeline.take_input().unwrap();
+        let producer = thread::spawn(move || {
+            for i in 0..100 {
+                input.send(item(f64::from(i))).unwrap();
+            }
+        });
+
+        let mut results = Vec::new();
+        while let Some(result) = pipeline.recv() {
+            results.push(result["mul.result"].clone());
+        }
+        producer.join().unwrap();
+        pipeline.finish().unwrap();
+
+        let expected: Vec<Value> = (0..100)
+            .map(|i| Value::Float((1.0 + f64::from(i)) * 2.0))
+            .collect();
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_pipeline_honors_node_mode() {
+        let engine = build_stream_engine();
+        let mut graph = (*engine.graph("stream").unwrap()).clone();
+        graph.nodes.get_mut("mul").unwrap().mode = NodeMode::Bypass;
+        engine.replace_graph(graph).unwrap();
+
+        let pipeline = engine.start_pipeline("stream", stream_options()).unwrap();
+        pipeline.send(item(4.0)).unwrap();
+        assert_eq!(pipeline.recv().unwrap()["mul.result"], Value::Float(5.0));
+        pipeline.finish().unwrap();
+    }
+
+    #[test]
+    fn test_pipeline_applies_backpressure() {
+        let engine = build_stream_engine();
+        let mut pipeline = engine.start_pipeline("stream", stream_options()).unwrap();
+
+        let sent = Arc::new(AtomicUsize::new(0));
+        let input = pipeline.take_input().unwrap();
+        let producer = {
+            let sent = Arc::clone(&sent);
+            thread::spawn(move || {
+                for i in 0..100 {
+                    input.send(item(f64::from(i))).unwrap();
+                    sent.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+        };
+
+        // Nothing is received, so the producer stalls once the channels fill:
+        // two items per channel plus one held by each node
+        thread::sleep(Duration::from_millis(100));
+        assert!(sent.load(Ordering::SeqCst) <= 8);
+
+        let mut received = 0;
+        while pipeline.recv().is_some() {
+            received += 1;
+        }
+        producer.join().unwrap();
+        assert_eq!(received, 100);
+        pipeline.finish().unwrap();
+    }
+
+    #[test]
+    fn test_pipeline_reports_failing_node() {
+        let engine = build_stream_engine();
+        let pipeline = engine.start_pipeline("stream", stream_options()).unwrap();
+
+        pipeline.send(item(1.0)).unwrap();
+        let wrong_type = HashMap::from([("add.b".to_string(), Value::String("x".into()))]);
+        pipeline.send(wrong_type).unwrap();
+
+        assert!(pipeline.recv().is_some());
+        assert!(pipeline.recv().is_none());
+        let err = pipeline.finish().unwrap_err();
+        assert!(err.to_string().contains("Node 'add'"));
+    }
+
+    // Passes its input on only when it is positive
+    struct PositiveBlock;
+    impl crate::block::Block for PositiveBlock {
+        fn metadata(&self) -> crate::block::BlockMetadata {
+            let port = |id: &str| crate::block::PortDefinition {
+                id: id.to_string(),
+                name: id.to_string(),
+                data_type: "number".to_string(),
+                required: false,
+            };
+            crate::block::BlockMetadata {
+                id: "positive".to_string(),
+                name: "Positive".to_string(),
+                description: "Drops values that are not positive".to_string(),
+                inputs: vec![port("in")],
+                outputs: vec![port("value")],
+                config_schema: HashMap::new(),
+            }
+        }
+
+        fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+            Ok(context
+                .get_input("in")
+                .filter(|value| value.as_float().is_some_and(|x| x > 0.0))
+                .map(|value| ("value".to_string(), value.clone()))
+                .into_iter()
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_pipeline_forwards_absent_outputs() {
+        let engine = build_stream_engine();
+        engine.register_block(Arc::new(PositiveBlock)).unwrap();
+        let mut graph = (*engine.graph("stream").unwrap()).clone();
+        graph
+            .add_node(node("positive", "positive", HashMap::new()))
+            .unwrap();
+        connect(&mut graph, "positive.value", "add", "b");
+        engine.replace_graph(graph).unwrap();
+
+        let options = PipelineOptions::new()
+            .with_input("positive.in")
+            .with_output("positive.value")
+            .with_output("add.result");
+        let mut pipeline = engine.start_pipeline("stream", options).unwrap();
+        for x in [2.0, -1.0, 3.0] {
+            let item = HashMap::from([("positive.in".to_string(), Value::Float(x))]);
+            pipeline.send(item).unwrap();
+        }
+        pipeline.close_input();
+
+        // `math.add` fails on the absent input, so later items are partial
+        let results: Vec<_> = std::iter::from_fn(|| pipeline.recv()).collect();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["positive.value"], Value::Float(2.0));
+        assert_eq!(results[0]["add.result"], Value::Float(3.0));
+        assert!(results[1].is_empty());
+        assert_eq!(
+            results[2],
+            HashMap::from([("positive.value".to_string(), Value::Float(3.0))])
+        );
+        let err = pipeline.finish().unwrap_err();
+        assert!(err.to_string().contains("Missing or invalid input 'b'"));
+    }
+
+    #[test]
+    fn test_pipeline_checks_ports() {
+        let engine = build_stream_engine();
+        let start = |options| engine.start_pipeline("stream", options).err().unwrap();
+
+        assert!(start(PipelineOptions::new())
+            .to_string()
+            .contains("at least one"));
+        let connected = PipelineOptions::new().with_input("add.a");
+        assert!(start(connected).to_string().contains("connection"));
+        let upstream = stream_options().with_output("one.value");
+        assert!(start(upstream).to_string().contains("not downstream"));
+
+        let pipeline = engine.start_pipeline("stream", stream_options()).unwrap();
+        assert!(pipeline.send(HashMap::new()).is_err());
+        pipeline.finish().unwrap();
+    }
+}
@@ -0,0 +1,375 @@
+use crate::{
+    block::{Block, NodePorts},
+    engine::{BlockRegistry, NodeOutputs},
+    error::{CircuitError, Result},
+    graph::{Connection, Graph, NodeId, NodeMode},
+    report::NodeStatus,
+    retry::RetryPolicy,
+    value::Value,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// A graph resolved against a block registry, ready to run many times
+///
+/// Building a plan sorts the graph, looks up every block implementation and
+/// indexes incoming connections once, so repeated executions of the same
+/// graph only pay for running the blocks.
+#[derive(Clone)]
+pub struct ExecutionPlan {
+    pub(crate) steps: Vec<PlanStep>,
+    /// Nodes left out because nothing reads them, in topological order
+    pub(crate) pruned: Vec<NodeId>,
+}
+
+/// One node of an execution plan
+#[derive(Clone)]
+pub(crate) struct PlanStep {
+    pub(crate) node_id: NodeId,
+    pub(crate) block: Arc<dyn Block>,
+    pub(crate) config: HashMap<String, Value>,
+    pub(crate) incoming: Vec<Connection>,
+    pub(crate) retry: Option<RetryPolicy>,
+    pub(crate) mode: NodeMode,
+    /// Resolved ports, kept for nodes that are bypassed or muted
+    pub(crate) ports: Option<NodePorts>,
+}
+
+impl ExecutionPlan {
+    /// Resolve a graph against the given block registry
+    ///
+    /// Nodes whose outputs only reach pinned or muted nodes are left out,
+    /// since nothing reads them.
+    pub fn new(graph: &Graph, blocks: &BlockRegistry) -> Result<Self> {
+        let order = graph.topological_sort()?;
+
+        let mut incoming: HashMap<&str, Vec<Connection>> = HashMap::new();
+        for conn in &graph.connections {
+            incoming
+                .entry(conn.to_node.as_str())
+                .or_default()
+                .push(conn.clone());
+        }
+
+        let mut steps = Vec::with_capacity(order.len());
+        for node_id in order {
+            let node = graph
+                .nodes
+                .get(&node_id)
+                .ok_or_else(|| CircuitError::NodeNotFound(node_id.clone()))?;
+
+            let block = blocks.get(&node.block_type).ok_or_else(|| {
+                CircuitError::Graph(format!("Block type '{}' not found", node.block_type))
+            })?;
+
+            let ports = match node.mode {
+                NodeMode::Bypass | NodeMode::Mute => Some(block.ports_for_config(&node.config)?),
+                NodeMode::Normal | NodeMode::Pinned(_) => None,
+            };
+            steps.push(PlanStep {
+                incoming: incoming.remove(node_id.as_str()).unwrap_or_default(),
+                node_id,
+                block: Arc::clone(block),
+                config: node.config.clone(),
+                retry: node.retry.clone(),
+                mode: node.mode.clone(),
+                ports,
+            });
+        }
+
+        // Pinned and muted nodes ignore their inputs, so nodes that only
+        // feed them, directly or through bypassed nodes, are left out
+        let mut consumers: HashMap<&str, Vec<&str>> = HashMap::new();
+        for conn in &graph.connections {
+            consumers
+                .entry(conn.from_node.as_str())
+                .or_default()
+                .push(conn.to_node.as_str());
+        }
+        let mut needed: HashSet<NodeId> = HashSet::new();
+        for step in steps.iter().rev() {
+            let is_needed = consumers.get(step.node_id.as_str()).is_none_or(|to_nodes| {
+                to_nodes.iter().any(|to_node| {
+                    needed.contains(*to_node)
+                        && graph.nodes.get(*to_node).is_some_and(|node| {
+                            matches!(node.mode, NodeMode::Normal | NodeMode::Bypass)
+                        })
+                })
+            });
+            if is_needed {
+                needed.insert(step.node_id.clone());
+            }
+        }
+        let (mut steps, pruned): (Vec<_>, Vec<_>) = steps
+            .into_iter()
+            .partition(|step| needed.contains(&step.node_id));
+        // Pinned and muted nodes do not wait for the nodes pruned above them
+        for step in &mut steps {
+            step.incoming
+                .retain(|conn| needed.contains(&conn.from_node));
+        }
+        let pruned = pruned.into_iter().map(|step| step.node_id).collect();
+
+        Ok(Self { steps, pruned })
+    }
+
+    /// Node IDs in execution order
+    pub fn node_order(&self) -> Vec<&str> {
+        self.steps
+            .iter()
+            .map(|step| step.node_id.as_str())
+            .collect()
+    }
+
+    /// Node IDs left out of the plan because only pinned or muted nodes
+    /// read them
+    pub fn pruned(&self) -> &[NodeId] {
+        &self.pruned
+    }
+
+    /// Number of nodes in the plan
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Whether the plan has no nodes
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+impl PlanStep {
+    /// Inputs from connected nodes, overridden by the caller's inputs
+    pub(crate) fn resolve_inputs(
+        &self,
+        outputs: &NodeOutputs,
+        graph_inputs: &HashMap<NodeId, HashMap<String, Value>>,
+    ) -> HashMap<String, Value> {
+        let mut inputs = HashMap::new();
+        for connection in &self.incoming {
+            if let Some(value) = outputs
+                .get(&connection.from_node)
+                .and_then(|source| source.get(&connection.from_port))
+            {
+                inputs.insert(connection.to_port.clone(), value.clone());
+            }
+        }
+        if let Some(caller_inputs) = graph_inputs.get(&self.node_id) {
+            inputs.extend(caller_inputs.clone());
+        }
+        inputs
+    }
+
+    /// Status of the node when it finished without an error
+    pub(crate) fn success_status(&self) -> NodeStatus {
+        match self.mode {
+            NodeMode::Normal => NodeStatus::Ok,
+            NodeMode::Pinned(_) => NodeStatus::Cached,
+            NodeMode::Bypass => NodeStatus::Bypassed,
+            NodeMode::Mute => NodeStatus::Muted,
+        }
+    }
+
+    /// Outputs replacing the block's when the node is not run normally
+    pub(crate) fn override_outputs(
+        &self,
+        inputs: &HashMap<String, Value>,
+    ) -> Option<HashMap<String, Value>> {
+        let ports = self.ports.as_ref();
+        match &self.mode {
+            NodeMode::Normal => None,
+            NodeMode::Pinned(outputs) => Some(outputs.clone()),
+            NodeMode::Mute => Some(ports.map_or_else(HashMap::new, |ports| {
+                ports
+                    .outputs
+                    .iter()
+                    .map(|port| (port.id.clone(), Value::Null))
+                    .collect()
+            })),
+            // Blocks that declare no outputs pass every input through
+            NodeMode::Bypass => match ports.filter(|ports| !ports.outputs.is_empty()) {
+                None => Some(inputs.clone()),
+                Some(ports) => Some(
+                    ports
+                        .outputs
+                        .iter()
+                        .map(|output| {
+                            let value = inputs.get(&output.id).or_else(|| {
+                                ports
+                                    .inputs
+                                    .iter()
+                                    .filter(|input| output.accepts_into(input))
+                                    .find_map(|input| inputs.get(&input.id))
+                            });
+                            (output.id.clone(), value.cloned().unwrap_or(Value::Null))
+                        })
+                        .collect(),
+                ),
+            },
+        }
+    }
+}
+
+/// Split graph inputs keyed by `"node.port"` into per-node input maps
+///
+/// Every addressed node must exist in the graph.
+pub fn group_graph_inputs(
+    graph: &Graph,
+    inputs: &HashMap<String, Value>,
+) -> Result<HashMap<NodeId, HashMap<String, Value>>> {
+    let mut grouped: HashMap<NodeId, HashMap<String, Value>> = HashMap::new();
+    for (key, value) in inputs {
+        let (node, port) = key.split_once('.').ok_or_else(|| {
+            CircuitError::InvalidInput(format!(
+                "Graph input '{}' must be addressed as 'node.port'",
+                key
+            ))
+        })?;
+        if !graph.nodes.contains_key(node) {
+            return Err(CircuitError::NodeNotFound(node.to_string()));
+        }
+        grouped
+            .entry(node.to_string())
+            .or_default()
+            .insert(port.to_string(), value.clone());
+    }
+    Ok(grouped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::core::ConstantBlock;
+    use crate::engine::Engine;
+    use crate::execution::ExecutionOptions;
+    use crate::graph::{Node, NodeMode};
+
+    fn registry() -> BlockRegistry {
+        let mut blocks: BlockRegistry = HashMap::new();
+        blocks.insert("core.constant".to_string(), Arc::new(ConstantBlock));
+        blocks
+    }
+
+    fn graph() -> Graph {
+        let mut graph = Graph::new("g".to_string(), "G".to_string());
+        for id in ["a", "b"] {
+            graph.add_node(Node::new(id, "core.constant")).unwrap();
+        }
+        graph
+            .add_connection(Connection {
+                from_node: "a".to_string(),
+                from_port: "value".to_string(),
+                to_node: "b".to_string(),
+                to_port: "value".to_string(),
+            })
+            .unwrap();
+        graph
+    }
+
+    #[test]
+    fn test_plan_orders_and_indexes_connections() {
+        let plan = ExecutionPlan::new(&graph(), &registry()).unwrap();
+        assert_eq!(plan.node_order(), vec!["a", "b"]);
+        assert!(plan.steps[0].incoming.is_empty());
+        assert_eq!(plan.steps[1].incoming.len(), 1);
+    }
+
+    #[test]
+    fn test_plan_unknown_block() {
+        let result = ExecutionPlan::new(&graph(), &HashMap::new());
+        assert!(matches!(result, Err(CircuitError::Graph(_))));
+    }
+
+    #[test]
+    fn test_group_graph_inputs() {
+        let mut inputs = HashMap::new();
+        inputs.insert("b.value".to_string(), Value::Int(1));
+        let grouped = group_graph_inputs(&graph(), &inputs).unwrap();
+        assert_eq!(grouped["b"]["value"], Value::Int(1));
+
+        let mut bad = HashMap::new();
+        bad.insert("missing.value".to_string(), Value::Int(1));
+        assert!(matches!(
+            group_graph_inputs(&graph(), &bad),
+            Err(CircuitError::NodeNotFound(_))
+        ));
+
+        let mut bad = HashMap::new();
+        bad.insert("no_port".to_string(), Value::Int(1));
+        assert!(matches!(
+            group_graph_inputs(&graph(), &bad),
+            Err(CircuitError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_override_outputs_by_mode() {
+        let mut graph = graph();
+        let inputs = HashMap::from([("value".to_string(), Value::Int(3))]);
+        let plan = |graph: &Graph| ExecutionPlan::new(graph, &registry()).unwrap();
+
+        let step = plan(&graph).steps.pop().unwrap();
+        assert_eq!(step.override_outputs(&inputs), None);
+        assert_eq!(step.success_status(), NodeStatus::Ok);
+
+        graph.nodes.get_mut("b").unwrap().mode = NodeMode::Bypass;
+        let step = plan(&graph).steps.pop().unwrap();
+        assert_eq!(step.override_outputs(&inputs), Some(inputs.clone()));
+        assert_eq!(step.success_status(), NodeStatus::Bypassed);
+
+        graph.nodes.get_mut("b").unwrap().mode = NodeMode::Mute;
+        let step = plan(&graph).steps.pop().unwrap();
+        let muted = HashMap::from([("value".to_string(), Value::Null)]);
+        assert_eq!(step.override_outputs(&inputs), Some(muted));
+        assert_eq!(step.success_status(), NodeStatus::Muted);
+
+        let pinned = HashMap::from([("value".to_string(), Value::Int(9))]);
+        graph.nodes.get_mut("b").unwrap().mode = NodeMode::Pinned(pinned.clone());
+        let step = plan(&graph).steps.pop().unwrap();
+        assert_eq!(step.override_outputs(&inputs), Some(pinned));
+        assert_eq!(step.success_status(), NodeStatus::Cached);
+    }
+
+    #[test]
+    fn test_plan_prunes_nodes_only_feeding_pinned_or_muted() {
+        let mut graph = graph();
+        let plan = |graph: &Graph| ExecutionPlan::new(graph, &registry()).unwrap();
+        graph.add_node(Node::new("c", "core.constant")).unwrap();
+        graph
+            .add_connection(Connection {
+                from_node: "b".to_string(),
+                from_port: "value".to_string(),
+                to_node: "c".to_string(),
+                to_port: "value".to_string(),
+            })
+            .unwrap();
+
+        let pinned = HashMap::from([("value".to_string(), Value::Int(9))]);
+        graph.nodes.get_mut("c").unwrap().mode = NodeMode::Pinned(pinned);
+        assert_eq!(plan(&graph).node_order(), vec!["c"]);
+        assert_eq!(plan(&graph).pruned(), ["a", "b"]);
+
+        // Pruned nodes are reported as skipped
+        let engine = Engine::new();
+        engine.register_block(Arc::new(ConstantBlock)).unwrap();
+        let report = engine
+            .execute_report(&graph, &HashMap::new(), &ExecutionOptions::new())
+            .unwrap();
+        assert!(report.is_success());
+        assert_eq!(report.nodes.len(), 3);
+        assert_eq!(report.node("a").unwrap().status, NodeStatus::Skipped);
+        assert_eq!(report.node("b").unwrap().status, NodeStatus::Skipped);
+        assert_eq!(report.node("c").unwrap().status, NodeStatus::Cached);
+
+        // `a` only feeds the pinned `c` through the bypassed `b`
+        graph.nodes.get_mut("b").unwrap().mode = NodeMode::Bypass;
+        assert_eq!(plan(&graph).node_order(), vec!["c"]);
+
+        graph.nodes.get_mut("b").unwrap().mode = NodeMode::Mute;
+        graph.nodes.get_mut("c").unwrap().mode = NodeMode::Normal;
+        assert_eq!(plan(&graph).node_order(), vec!["b", "c"]);
+
+        graph.nodes.get_mut("b").unwrap().mode = NodeMode::Bypass;
+        assert_eq!(plan(&graph).node_order(), vec!["a", "b", "c"]);
+    }
+}
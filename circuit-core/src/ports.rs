@@ -0,0 +1,72 @@
+use crate::{
+    block::NodePorts,
+    engine::BlockRegistry,
+    error::{CircuitError, Result},
+    graph::{Graph, NodeId},
+};
+use std::collections::HashMap;
+
+/// Resolve the ports of every node of `graph` from its block and config
+pub(crate) fn resolve_ports(
+    graph: &Graph,
+    blocks: &BlockRegistry,
+) -> Result<HashMap<NodeId, NodePorts>> {
+    graph
+        .nodes
+        .values()
+        .map(|node| {
+            let block = blocks.get(&node.block_type).ok_or_else(|| {
+                CircuitError::Graph(format!("Unknown block type: {}", node.block_type))
+            })?;
+            let ports = block
+                .ports_for_config(&node.config)
+                .map_err(|e| CircuitError::Graph(format!("Node '{}': {}", node.id, e)))?;
+            Ok((node.id.clone(), ports))
+        })
+        .collect()
+}
+
+/// Check that every connection joins existing ports of compatible types
+pub(crate) fn check_connections(graph: &Graph, ports: &HashMap<NodeId, NodePorts>) -> Result<()> {
+    for connection in &graph.connections {
+        let (Some(from_ports), Some(to_ports)) = (
+            ports.get(&connection.from_node),
+            ports.get(&connection.to_node),
+        ) else {
+            continue;
+        };
+        // Blocks that declare no ports on a side accept any port there
+        let from = match from_ports.output(&connection.from_port) {
+            Some(port) => port,
+            None if from_ports.outputs.is_empty() => continue,
+            None => {
+                return Err(CircuitError::InvalidConnection(format!(
+                    "Node '{}' has no output port '{}'",
+                    connection.from_node, connection.from_port
+                )))
+            }
+        };
+        let to = match to_ports.input(&connection.to_port) {
+            Some(port) => port,
+            None if to_ports.inputs.is_empty() => continue,
+            None => {
+                return Err(CircuitError::InvalidConnection(format!(
+                    "Node '{}' has no input port '{}'",
+                    connection.to_node, connection.to_port
+                )))
+            }
+        };
+        if !from.accepts_into(to) {
+            return Err(CircuitError::InvalidConnection(format!(
+                "Cannot connect {}.{} ({}) to {}.{} ({})",
+                connection.from_node,
+                connection.from_port,
+                from.data_type,
+                connection.to_node,
+                connection.to_port,
+                to.data_type
+            )));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,801 @@
+use crate::{
+    context::{Clock, ExecutionContext, KeyValueStore, RandomSource},
+    engine::NodeOutputs,
+    error::{CircuitError, Result},
+    graph::{Graph, NodeId},
+    plan::PlanStep,
+    snapshot::BlockVersion,
+    value::Value,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// Recording format written by this version of the engine
+pub const RECORDING_FORMAT_VERSION: u32 = 1;
+
+/// Operation on the key-value store service
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreOp {
+    Get,
+    Set,
+    Remove,
+}
+
+/// Result of a call a block made to a nondeterministic service
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "service", rename_all = "snake_case")]
+pub enum ServiceCall {
+    Clock {
+        #[serde(rename = "now_us", with = "crate::serde_duration::micros")]
+        now: Duration,
+    },
+    Random {
+        value: u64,
+    },
+    Store {
+        op: StoreOp,
+        key: String,
+        #[serde(default)]
+        result: Option<Value>,
+    },
+}
+
+impl fmt::Display for ServiceCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceCall::Clock { .. } => f.write_str("clock"),
+            ServiceCall::Random { .. } => f.write_str("random"),
+            ServiceCall::Store { op, key, .. } => write!(f, "store {:?} '{}'", op, key),
+        }
+    }
+}
+
+/// What one node saw and produced in a recorded execution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedNode {
+    pub node_id: NodeId,
+    /// Resolved inputs the node ran with
+    pub inputs: HashMap<String, Value>,
+    /// Outputs, when the node succeeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outputs: Option<HashMap<String, Value>>,
+    /// Error, when the node failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Service calls made by the node, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<ServiceCall>,
+}
+
+/// Everything needed to replay an execution, produced by `Engine::execute_recorded`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionRecording {
+    pub format_version: u32,
+    pub execution_id: String,
+    /// The graph as it ran
+    pub graph: Graph,
+    /// Versions of the blocks the graph uses, sorted by ID
+    pub blocks: Vec<BlockVersion>,
+    /// Caller inputs, keyed by `"node.port"`
+    #[serde(default)]
+    pub inputs: HashMap<String, Value>,
+    /// Variables set in the execution context
+    #[serde(default)]
+    pub variables: HashMap<String, Value>,
+    /// Engine variables when the execution started
+    #[serde(default)]
+    pub engine_variables: HashMap<String, Value>,
+    /// Block state of the graph's nodes when the execution started
+    #[serde(default)]
+    pub node_states: HashMap<NodeId, Value>,
+    /// Nodes that ran, in execution order
+    pub nodes: Vec<RecordedNode>,
+    /// Error that ended the execution
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ExecutionRecording {
+    /// Outputs of the nodes that succeeded
+    pub fn outputs(&self) -> NodeOutputs {
+        self.nodes
+            .iter()
+            .filter_map(|node| Some((node.node_id.clone(), node.outputs.clone()?)))
+            .collect()
+    }
+
+    /// Serialize the recording to JSON, e.g. to write a replay file
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Parse a recording written by this or an older version of the engine
+    pub fn from_json(json: &str) -> Result<Self> {
+        let recording: ExecutionRecording = serde_json::from_str(json)?;
+        if recording.format_version > RECORDING_FORMAT_VERSION {
+            return Err(CircuitError::InvalidInput(format!(
+                "Recording format version {} is newer than the supported version {}",
+                recording.format_version, RECORDING_FORMAT_VERSION
+            )));
+        }
+        Ok(recording)
+    }
+}
+
+/// How a replay first differed from its recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DivergenceKind {
+    /// The node was about to run with different inputs
+    Inputs,
+    /// The node produced different outputs
+    Outputs,
+    /// The node failed where it succeeded, succeeded where it failed, or
+    /// failed with a different error
+    Error,
+    /// The node made different service calls
+    ServiceCalls,
+    /// The node ran, but did not run in the recorded execution
+    NotRecorded,
+}
+
+/// First point where a replay differed from its recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Divergence {
+    pub node_id: NodeId,
+    pub kind: DivergenceKind,
+    pub message: String,
+}
+
+/// Result of `Engine::replay`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayReport {
+    pub execution_id: String,
+    /// First difference from the recording; the replay stops there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub divergence: Option<Divergence>,
+    /// Outputs of the nodes that ran in the replay
+    pub outputs: NodeOutputs,
+    /// Error that ended the replay
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Block versions that differ from the recording
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl ReplayReport {
+    /// Whether the replay did exactly what was recorded
+    pub fn is_faithful(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+/// Records or replays the nodes of a run, threaded through `Engine::run_plan`
+pub(crate) enum Recorder {
+    Record(Recording),
+    Replay(Box<Replay>),
+}
+
+pub(crate) struct Recording {
+    calls: Arc<Mutex<Vec<ServiceCall>>>,
+    inputs: HashMap<String, Value>,
+    nodes: Vec<RecordedNode>,
+}
+
+pub(crate) struct Replay {
+    feed: Arc<Mutex<ReplayFeed>>,
+    recorded: HashMap<NodeId, RecordedNode>,
+    /// Whether the current node's recorded outcome replaced running it
+    substituted: bool,
+    divergence: Option<Divergence>,
+    /// Whether the replay ended on the recorded error of a substituted node
+    substituted_failure: bool,
+}
+
+impl Recorder {
+    /// Start recording; blocks must run with the returned context
+    pub(crate) fn record(context: &ExecutionContext) -> (Self, ExecutionContext) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let context = context
+            .clone()
+            .with_clock(Arc::new(RecordingClock {
+                inner: context.shared_clock(),
+                calls: Arc::clone(&calls),
+            }))
+            .with_random(Arc::new(RecordingRandom {
+                inner: context.shared_random(),
+                calls: Arc::clone(&calls),
+            }))
+            .with_store(Arc::new(RecordingStore {
+                inner: context.shared_store(),
+                calls: Arc::clone(&calls),
+            }));
+        let recording = Recording {
+            calls,
+            inputs: HashMap::new(),
+            nodes: Vec::new(),
+        };
+        (Recorder::Record(recording), context)
+    }
+
+    /// Start replaying; blocks must run with the returned context
+    pub(crate) fn replay(
+        recording: &ExecutionRecording,
+        context: &ExecutionContext,
+    ) -> (Self, ExecutionContext) {
+        let feed = Arc::new(Mutex::new(ReplayFeed::default()));
+        let services = Arc::new(ReplayServices(Arc::clone(&feed)));
+        let mut context = context
+            .clone()
+            .with_clock(services.clone())
+            .with_random(services.clone())
+            .with_store(services);
+        for (name, value) in &recording.variables {
+            context = context.with_variable(name.clone(), value.clone());
+        }
+        let replay = Replay {
+            feed,
+            recorded: recording
+                .nodes
+                .iter()
+                .map(|node| (node.node_id.clone(), node.clone()))
+                .collect(),
+            substituted: false,
+            divergence: None,
+            substituted_failure: false,
+        };
+        (Recorder::Replay(Box::new(replay)), context)
+    }
+
+    /// Called before a node runs; a returned outcome replaces running its block
+    pub(crate) fn before_node(
+        &mut self,
+        step: &PlanStep,
+        inputs: &HashMap<String, Value>,
+    ) -> Option<Result<HashMap<String, Value>>> {
+        match self {
+            Recorder::Record(recording) => {
+                lock(&recording.calls).clear();
+                recording.inputs = inputs.clone();
+                None
+            }
+            Recorder::Replay(replay) => replay.before_node(step, inputs),
+        }
+    }
+
+    /// Called when a node finished; returns false to stop the run
+    pub(crate) fn after_node(
+        &mut self,
+        step: &PlanStep,
+        outcome: &Result<HashMap<String, Value>>,
+    ) -> bool {
+        match self {
+            Recorder::Record(recording) => {
+                recording.nodes.push(RecordedNode {
+                    node_id: step.node_id.clone(),
+                    inputs: std::mem::take(&mut recording.inputs),
+                    outputs: outcome.as_ref().ok().cloned(),
+                    error: outcome.as_ref().err().map(ToString::to_string),
+                    calls: std::mem::take(&mut *lock(&recording.calls)),
+                });
+                true
+            }
+            Recorder::Replay(replay) => replay.after_node(step, outcome),
+        }
+    }
+
+    /// Nodes recorded so far
+    pub(crate) fn into_nodes(self) -> Vec<RecordedNode> {
+        match self {
+            Recorder::Record(recording) => recording.nodes,
+            Recorder::Replay(_) => Vec::new(),
+        }
+    }
+
+    /// First divergence, and whether the replay ended on a substituted
+    /// node's recorded error
+    pub(crate) fn into_divergence(self) -> (Option<Divergence>, bool) {
+        match self {
+            Recorder::Record(_) => (None, false),
+            Recorder::Replay(replay) => (replay.divergence, replay.substituted_failure),
+        }
+    }
+}
+
+impl Replay {
+    fn before_node(
+        &mut self,
+        step: &PlanStep,
+        inputs: &HashMap<String, Value>,
+    ) -> Option<Result<HashMap<String, Value>>> {
+        let node_id = &step.node_id;
+        self.substituted = false;
+        let Some(recorded) = self.recorded.get(node_id) else {
+            return self.diverge(
+                node_id,
+                DivergenceKind::NotRecorded,
+                "Node did not run in the recorded execution".to_string(),
+            );
+        };
+        if let Some(port) = first_difference(&recorded.inputs, inputs) {
+            return self.diverge(
+                node_id,
+                DivergenceKind::Inputs,
+                format!("Input '{}' differs from the recording", port),
+            );
+        }
+
+        if !step.block.is_pure() {
+            // Blocks with outside effects are not run again
+            self.substituted = true;
+            return Some(match (&recorded.outputs, &recorded.error) {
+                (Some(outputs), _) => Ok(outputs.clone()),
+                (None, error) => {
+                    self.substituted_failure = true;
+                    let error = error.clone().unwrap_or_default();
+                    Err(CircuitError::Other(anyhow::anyhow!(error)))
+                }
+            });
+        }
+
+        let mut feed = lock(&self.feed);
+        feed.calls = recorded.calls.iter().cloned().collect();
+        feed.mismatch = None;
+        None
+    }
+
+    fn after_node(&mut self, step: &PlanStep, outcome: &Result<HashMap<String, Value>>) -> bool {
+        if self.divergence.is_some() {
+            return false;
+        }
+        if self.substituted {
+            return true;
+        }
+        let node_id = &step.node_id;
+        let Some(recorded) = self.recorded.get(node_id) else {
+            return false;
+        };
+
+        let mut feed = lock(&self.feed);
+        let calls = match (feed.mismatch.take(), feed.calls.front()) {
+            (Some(mismatch), _) => Some(mismatch),
+            (None, Some(call)) => Some(format!("Recorded {} call was not made", call)),
+            (None, None) => None,
+        };
+        drop(feed);
+        if let Some(message) = calls {
+            self.diverge(node_id, DivergenceKind::ServiceCalls, message);
+            return false;
+        }
+
+        let difference = match (outcome, &recorded.outputs, &recorded.error) {
+            (Ok(outputs), Some(expected), _) => first_difference(expected, outputs).map(|port| {
+                (
+                    DivergenceKind::Outputs,
+                    format!("Output '{}' differs from the recording", port),
+                )
+            }),
+            (Ok(_), None, error) => Some((
+                DivergenceKind::Error,
+                format!(
+                    "Node succeeded but failed in the recording: {}",
+                    error.as_deref().unwrap_or_default()
+                ),
+            )),
+            (Err(e), _, Some(expected)) if e.to_string() == *expected => None,
+            (Err(e), _, _) => Some((
+                DivergenceKind::Error,
+                format!("Node failed unlike in the recording: {}", e),
+            )),
+        };
+        if let Some((kind, message)) = difference {
+            self.diverge(node_id, kind, message);
+            return false;
+        }
+        true
+    }
+
+    // Note the divergence and make the node fail without running
+    fn diverge(
+        &mut self,
+        node_id: &str,
+        kind: DivergenceKind,
+        message: String,
+    ) -> Option<Result<HashMap<String, Value>>> {
+        let error = CircuitError::InvalidInput(message.clone());
+        self.divergence.get_or_insert(Divergence {
+            node_id: node_id.to_string(),
+            kind,
+            message,
+        });
+        Some(Err(error))
+    }
+}
+
+// Port whose value is missing or different on one side, in sorted order
+fn first_difference(
+    expected: &HashMap<String, Value>,
+    actual: &HashMap<String, Value>,
+) -> Option<String> {
+    let mut ports: Vec<&String> = expected.keys().chain(actual.keys()).collect();
+    ports.sort();
+    ports
+        .into_iter()
+        .find(|port| expected.get(*port) != actual.get(*port))
+        .cloned()
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+struct RecordingClock {
+    inner: Arc<dyn Clock>,
+    calls: Arc<Mutex<Vec<ServiceCall>>>,
+}
+
+impl Clock for RecordingClock {
+    fn now(&self) -> Duration {
+        let now = self.inner.now();
+        lock(&self.calls).push(ServiceCall::Clock { now });
+        now
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.inner.sleep(duration);
+    }
+}
+
+struct RecordingRandom {
+    inner: Arc<dyn RandomSource>,
+    calls: Arc<Mutex<Vec<ServiceCall>>>,
+}
+
+impl RandomSource for RecordingRandom {
+    fn next_u64(&self) -> u64 {
+        let value = self.inner.next_u64();
+        lock(&self.calls).push(ServiceCall::Random { value });
+        value
+    }
+}
+
+struct RecordingStore {
+    inner: Arc<dyn KeyValueStore>,
+    calls: Arc<Mutex<Vec<ServiceCall>>>,
+}
+
+impl RecordingStore {
+    fn record(&self, op: StoreOp, key: &str, result: Option<Value>) -> Option<Value> {
+        lock(&self.calls).push(ServiceCall::Store {
+            op,
+            key: key.to_string(),
+            result: result.clone(),
+        });
+        result
+    }
+}
+
+impl KeyValueStore for RecordingStore {
+    fn get(&self, key: &str) -> Option<Value> {
+        self.record(StoreOp::Get, key, self.inner.get(key))
+    }
+
+    fn set(&self, key: &str, value: Value) -> Option<Value> {
+        self.record(StoreOp::Set, key, self.inner.set(key, value))
+    }
+
+    fn remove(&self, key: &str) -> Option<Value> {
+        self.record(StoreOp::Remove, key, self.inner.remove(key))
+    }
+}
+
+/// Recorded service calls of the node being replayed
+#[derive(Default)]
+struct ReplayFeed {
+    calls: VecDeque<ServiceCall>,
+    /// First call that did not match the recording
+    mismatch: Option<String>,
+}
+
+/// Clock, random source and store answering from the recording
+struct ReplayServices(Arc<Mutex<ReplayFeed>>);
+
+impl ReplayServices {
+    // Take the next recorded call if it is what the block asks for
+    fn next<T>(&self, asked: &str, answer: impl FnOnce(&ServiceCall) -> Option<T>) -> Option<T> {
+        let mut feed = lock(&self.0);
+        let answered = feed.calls.front().and_then(answer);
+        if answered.is_some() {
+            feed.calls.pop_front();
+        } else if feed.mismatch.is_none() {
+            let expected = feed
+                .calls
+                .front()
+                .map_or("no call".to_string(), |call| format!("a {} call", call));
+            feed.mismatch = Some(format!(
+                "Made a {} call where the recording has {}",
+                asked, expected
+            ));
+        }
+        answered
+    }
+
+    fn store(&self, op: StoreOp, key: &str) -> Option<Value> {
+        let asked = ServiceCall::Store {
+            op,
+            key: key.to_string(),
+            result: None,
+        };
+        self.next(&asked.to_string(), |call| match call {
+            ServiceCall::Store {
+                op: recorded_op,
+                key: recorded_key,
+                result,
+            } if *recorded_op == op && recorded_key == key => Some(result.clone()),
+            _ => None,
+        })
+        .flatten()
+    }
+}
+
+impl Clock for ReplayServices {
+    fn now(&self) -> Duration {
+        self.next("clock", |call| match call {
+            ServiceCall::Clock { now } => Some(*now),
+            _ => None,
+        })
+        .unwrap_or_default()
+    }
+
+    /// Replays do not wait
+    fn sleep(&self, _duration: Duration) {}
+}
+
+impl RandomSource for ReplayServices {
+    fn next_u64(&self) -> u64 {
+        self.next("random", |call| match call {
+            ServiceCall::Random { value } => Some(*value),
+            _ => None,
+        })
+        .unwrap_or_default()
+    }
+}
+
+impl KeyValueStore for ReplayServices {
+    fn get(&self, key: &str) -> Option<Value> {
+        self.store(StoreOp::Get, key)
+    }
+
+    fn set(&self, key: &str, _value: Value) -> Option<Value> {
+        self.store(StoreOp::Set, key)
+    }
+
+    fn remove(&self, key: &str) -> Option<Value> {
+        self.store(StoreOp::Remove, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{Block, BlockContext, BlockMetadata, PortDefinition};
+    use crate::context::{FakeClock, SeededRandom};
+    use crate::engine::Engine;
+    use crate::execution::ExecutionOptions;
+    use crate::graph::{Connection, Node};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn port(id: &str) -> PortDefinition {
+        PortDefinition {
+            id: id.to_string(),
+            name: id.to_string(),
+            data_type: "number".to_string(),
+            required: true,
+        }
+    }
+
+    fn metadata(id: &str, inputs: Vec<PortDefinition>) -> BlockMetadata {
+        BlockMetadata {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            inputs,
+            outputs: vec![port("value")],
+            config_schema: HashMap::new(),
+        }
+    }
+
+    // Rolls a die from the random source and stamps it with the clock
+    struct RollBlock {
+        version: u32,
+        offset: i64,
+    }
+
+    impl Block for RollBlock {
+        fn metadata(&self) -> BlockMetadata {
+            metadata("test.roll", vec![])
+        }
+
+        fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+            let execution = context.execution();
+            let roll = (execution.random().next_u64() % 6) as i64 + 1 + self.offset;
+            let at = execution.clock().now().as_secs() as i64;
+            Ok(HashMap::from([
+                ("value".to_string(), Value::Int(roll)),
+                ("at".to_string(), Value::Int(at)),
+            ]))
+        }
+
+        fn version(&self) -> u32 {
+            self.version
+        }
+
+        // Random numbers and time come from the recorded services
+        fn is_pure(&self) -> bool {
+            true
+        }
+    }
+
+    // Stands in for a network call; every run returns something new
+    struct FetchBlock {
+        runs: AtomicUsize,
+        fail: bool,
+    }
+
+    impl Block for FetchBlock {
+        fn metadata(&self) -> BlockMetadata {
+            metadata("test.fetch", vec![port("value")])
+        }
+
+        fn execute(&self, context: BlockContext) -> Result<HashMap<String, Value>> {
+            let runs = self.runs.fetch_add(1, Ordering::SeqCst) as i64;
+            if self.fail {
+                return Err(CircuitError::BlockExecution(
+                    "service unavailable".to_string(),
+                ));
+            }
+            let value = context.inputs["value"].as_float().unwrap_or_default() as i64;
+            Ok(HashMap::from([(
+                "value".to_string(),
+                Value::Int(value * 100 + runs),
+            )]))
+        }
+
+        fn is_pure(&self) -> bool {
+            false
+        }
+    }
+
+    // roll -> fetch
+    fn build_engine(roll: RollBlock, fetch: Arc<FetchBlock>) -> Engine {
+        let engine = Engine::new();
+        engine.register_block(Arc::new(roll)).unwrap();
+        engine.register_block(fetch).unwrap();
+        let mut graph = Graph::new("dice".to_string(), "Dice".to_string());
+        for (id, block_type) in [("roll", "test.roll"), ("fetch", "test.fetch")] {
+            graph.add_node(Node::new(id, block_type)).unwrap();
+        }
+        graph
+            .add_connection(Connection {
+                from_node: "roll".to_string(),
+                from_port: "value".to_string(),
+                to_node: "fetch".to_string(),
+                to_port: "value".to_string(),
+            })
+            .unwrap();
+        engine.load_graph(graph).unwrap();
+        engine
+    }
+
+    fn fetch_block(fail: bool) -> Arc<FetchBlock> {
+        Arc::new(FetchBlock {
+            runs: AtomicUsize::new(0),
+            fail,
+        })
+    }
+
+    fn record(engine: &Engine) -> ExecutionRecording {
+        let context = ExecutionContext::new()
+            .with_variable("user", "u-1")
+            .with_clock(Arc::new(FakeClock::new(Duration::from_secs(1_700))))
+            .with_random(Arc::new(SeededRandom::new(7)));
+        let options = ExecutionOptions::new().with_context(context);
+        engine
+            .execute_recorded("dice", &HashMap::new(), &options)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_replay_matches_recording() {
+        let fetch = fetch_block(false);
+        let roll = RollBlock {
+            version: 1,
+            offset: 0,
+        };
+        let engine = build_engine(roll, Arc::clone(&fetch));
+        let recording = record(&engine);
+        assert!(recording.error.is_none());
+        assert_eq!(
+            recording.variables["user"],
+            Value::String("u-1".to_string())
+        );
+        assert_eq!(recording.nodes.len(), 2);
+        assert_eq!(recording.nodes[0].calls.len(), 2);
+        assert_eq!(
+            recording.nodes[0].calls[1],
+            ServiceCall::Clock {
+                now: Duration::from_secs(1_700)
+            }
+        );
+
+        // Replaying from the file does not call the service again
+        let recording = ExecutionRecording::from_json(&recording.to_json().unwrap()).unwrap();
+        let report = engine.replay(&recording, &ExecutionOptions::new()).unwrap();
+        assert!(report.is_faithful(), "{:?}", report.divergence);
+        assert!(report.error.is_none() && report.warnings.is_empty());
+        assert_eq!(report.outputs, recording.outputs());
+        assert_eq!(fetch.runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_replay_reports_first_divergence() {
+        let recording = record(&build_engine(
+            RollBlock {
+                version: 1,
+                offset: 0,
+            },
+            fetch_block(false),
+        ));
+
+        let changed = build_engine(
+            RollBlock {
+                version: 2,
+                offset: 1,
+            },
+            fetch_block(false),
+        );
+        let report = changed
+            .replay(&recording, &ExecutionOptions::new())
+            .unwrap();
+        let divergence = report.divergence.unwrap();
+        assert_eq!(divergence.node_id, "roll");
+        assert_eq!(divergence.kind, DivergenceKind::Outputs);
+        assert!(divergence.message.contains("'value'"));
+        assert!(!report.outputs.contains_key("fetch"));
+        assert_eq!(
+            report.warnings,
+            vec!["Block 'test.roll' is at version 2, recorded at version 1".to_string()]
+        );
+
+        let newer = recording
+            .to_json()
+            .unwrap()
+            .replace("\"format_version\":1", "\"format_version\":99");
+        assert!(ExecutionRecording::from_json(&newer).is_err());
+    }
+
+    #[test]
+    fn test_replay_substitutes_recorded_error() {
+        let roll = || RollBlock {
+            version: 1,
+            offset: 0,
+        };
+        let recording = record(&build_engine(roll(), fetch_block(true)));
+        let error = recording.error.clone().unwrap();
+        assert!(error.contains("service unavailable"));
+        assert!(recording.nodes[1].outputs.is_none());
+
+        // The service is back, but the replay still sees the recorded failure
+        let fetch = fetch_block(false);
+        let engine = build_engine(roll(), Arc::clone(&fetch));
+        let report = engine.replay(&recording, &ExecutionOptions::new()).unwrap();
+        assert!(report.is_faithful());
+        assert_eq!(report.error, Some(error));
+        assert_eq!(fetch.runs.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,228 @@
+use crate::{
+    context::{LogLevel, Logger},
+    engine::NodeOutputs,
+    graph::{Graph, NodeId},
+    value::Value,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// Outcome of a single node in an execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeStatus {
+    /// The block ran and produced its outputs
+    Ok,
+    /// The node did not run
+    Skipped,
+    /// The block returned an error or panicked
+    Failed,
+    /// The outputs were reused without running the block
+    Cached,
+    /// The node passed its inputs through without running the block
+    Bypassed,
+    /// The node emitted nulls without running the block
+    Muted,
+}
+
+/// What happened to one node during an execution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeReport {
+    pub node_id: NodeId,
+    pub status: NodeStatus,
+    /// Time spent in the block including retries, zero for nodes that did not run
+    #[serde(rename = "duration_us", with = "crate::serde_duration::micros")]
+    pub duration: Duration,
+    /// Number of times the block was run, including retries
+    #[serde(default)]
+    pub attempts: u32,
+    /// Error message of a failed node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Structured result of an execution
+///
+/// Unlike the plain output map, a report is also produced for failed runs:
+/// `error` is set, the failing node is marked `Failed` and the nodes that
+/// never ran are marked `Skipped`. Nodes the plan left out because only
+/// pinned or muted nodes read them are marked `Skipped` too.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionReport {
+    pub execution_id: String,
+    pub graph_id: String,
+    /// Per-node outcomes in execution order
+    pub nodes: Vec<NodeReport>,
+    #[serde(rename = "total_duration_us", with = "crate::serde_duration::micros")]
+    pub total_duration: Duration,
+    /// Outputs of every node that ran
+    pub outputs: NodeOutputs,
+    /// Values of the graph's declared outputs, keyed by `"node.port"`
+    pub declared_outputs: HashMap<String, Value>,
+    /// Warnings logged by blocks and noticed by the engine
+    pub warnings: Vec<String>,
+    /// Error that stopped the execution
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ExecutionReport {
+    /// Mark the nodes the run never reached, and the pruned nodes, as
+    /// skipped and collect the declared outputs
+    pub(crate) fn finish(mut self, graph: &Graph, node_order: &[&str], pruned: &[NodeId]) -> Self {
+        let unreached = node_order.iter().skip(self.nodes.len()).copied();
+        for node_id in unreached.chain(pruned.iter().map(String::as_str)) {
+            self.nodes.push(NodeReport {
+                node_id: node_id.to_string(),
+                status: NodeStatus::Skipped,
+                duration: Duration::ZERO,
+                attempts: 0,
+                error: None,
+            });
+        }
+
+        let mut declared_outputs = HashMap::new();
+        for output in &graph.outputs {
+            let key = format!("{}.{}", output.node, output.port);
+            match self
+                .outputs
+                .get(&output.node)
+                .and_then(|o| o.get(&output.port))
+            {
+                Some(value) => {
+                    declared_outputs.insert(key, value.clone());
+                }
+                None if self.error.is_none() => {
+                    self.warnings
+                        .push(format!("Declared output '{}' was not produced", key));
+                }
+                None => {}
+            }
+        }
+        self.declared_outputs = declared_outputs;
+        self
+    }
+
+    /// Whether the execution finished without an error
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Get the report of a node
+    pub fn node(&self, node_id: &str) -> Option<&NodeReport> {
+        self.nodes.iter().find(|node| node.node_id == node_id)
+    }
+
+    /// Number of nodes with the given status
+    pub fn count(&self, status: NodeStatus) -> usize {
+        self.nodes
+            .iter()
+            .filter(|node| node.status == status)
+            .count()
+    }
+}
+
+/// Logger that forwards to another logger and keeps warnings and errors
+pub(crate) struct WarningCollector {
+    inner: Arc<dyn Logger>,
+    warnings: Mutex<Vec<String>>,
+}
+
+impl WarningCollector {
+    pub(crate) fn new(inner: Arc<dyn Logger>) -> Self {
+        Self {
+            inner,
+            warnings: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.warnings.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl Logger for WarningCollector {
+    fn log(&self, level: LogLevel, message: &str) {
+        if level >= LogLevel::Warn {
+            self.warnings
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(message.to_string());
+        }
+        self.inner.log(level, message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::MemoryLogger;
+    use crate::graph::Node;
+
+    fn graph() -> Graph {
+        let mut graph = Graph::new("g".to_string(), "G".to_string());
+        for id in ["a", "b"] {
+            graph.add_node(Node::new(id, "test")).unwrap();
+        }
+        graph.add_output("a", "value").unwrap();
+        graph.add_output("b", "value").unwrap();
+        graph
+    }
+
+    #[test]
+    fn test_report_fills_skipped_nodes_and_declared_outputs() {
+        let mut outputs = NodeOutputs::new();
+        outputs.insert(
+            "a".to_string(),
+            HashMap::from([("value".to_string(), Value::Int(1))]),
+        );
+        let nodes = vec![NodeReport {
+            node_id: "a".to_string(),
+            status: NodeStatus::Ok,
+            duration: Duration::from_micros(5),
+            attempts: 1,
+            error: None,
+        }];
+
+        let report = ExecutionReport {
+            execution_id: "run-1".to_string(),
+            graph_id: "g".to_string(),
+            nodes,
+            total_duration: Duration::from_micros(9),
+            outputs,
+            declared_outputs: HashMap::new(),
+            warnings: Vec::new(),
+            error: None,
+        }
+        .finish(&graph(), &["a", "b"], &[]);
+
+        assert!(report.is_success());
+        assert_eq!(report.node("b").unwrap().status, NodeStatus::Skipped);
+        assert_eq!(report.count(NodeStatus::Ok), 1);
+        assert_eq!(report.declared_outputs["a.value"], Value::Int(1));
+        assert_eq!(
+            report.warnings,
+            vec!["Declared output 'b.value' was not produced".to_string()]
+        );
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["total_duration_us"], 9);
+        assert_eq!(json["nodes"][0]["status"], "ok");
+        assert_eq!(json["nodes"][0]["duration_us"], 5);
+        let back: ExecutionReport = serde_json::from_value(json).unwrap();
+        assert_eq!(back, report);
+    }
+
+    #[test]
+    fn test_warning_collector_forwards_and_keeps_warnings() {
+        let inner = Arc::new(MemoryLogger::new());
+        let collector = WarningCollector::new(inner.clone());
+        collector.log(LogLevel::Info, "hello");
+        collector.log(LogLevel::Warn, "careful");
+
+        assert_eq!(collector.take(), vec!["careful".to_string()]);
+        assert_eq!(inner.entries().len(), 2);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::error::{CircuitError, ErrorKind};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How long to wait between attempts
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Backoff {
+    /// Retry immediately
+    #[default]
+    None,
+    /// Wait the same delay before every retry
+    Fixed {
+        #[serde(rename = "delay_ms", with = "crate::serde_duration::millis")]
+        delay: Duration,
+    },
+    /// Multiply the delay by `multiplier` after every retry, up to `max`
+    Exponential {
+        #[serde(rename = "initial_ms", with = "crate::serde_duration::millis")]
+        initial: Duration,
+        multiplier: f64,
+        #[serde(rename = "max_ms", with = "crate::serde_duration::millis")]
+        max: Duration,
+    },
+}
+
+/// Retry configuration of a node
+///
+/// The engine applies it around `Block::execute`: a failing attempt whose
+/// error kind is retryable is repeated after the backoff delay until
+/// `max_attempts` attempts have been made.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    #[serde(default)]
+    pub backoff: Backoff,
+    /// Error kinds worth retrying; empty means `RetryPolicy::DEFAULT_RETRY_ON`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retry_on: Vec<ErrorKind>,
+}
+
+impl RetryPolicy {
+    /// Transient error kinds retried when `retry_on` is empty
+    pub const DEFAULT_RETRY_ON: &'static [ErrorKind] =
+        &[ErrorKind::BlockExecution, ErrorKind::Timeout];
+
+    /// Retry up to `max_attempts` attempts in total, without delay
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            backoff: Backoff::None,
+            retry_on: Vec::new(),
+        }
+    }
+
+    /// Wait `delay` before every retry
+    pub fn with_fixed_backoff(mut self, delay: Duration) -> Self {
+        self.backoff = Backoff::Fixed { delay };
+        self
+    }
+
+    /// Wait `initial`, then `initial * multiplier`, ... capped at `max`
+    pub fn with_exponential_backoff(
+        mut self,
+        initial: Duration,
+        multiplier: f64,
+        max: Duration,
+    ) -> Self {
+        self.backoff = Backoff::Exponential {
+            initial,
+            multiplier,
+            max,
+        };
+        self
+    }
+
+    /// Only retry errors of the given kind (may be called repeatedly)
+    pub fn retry_on(mut self, kind: ErrorKind) -> Self {
+        self.retry_on.push(kind);
+        self
+    }
+
+    /// Whether another attempt may follow attempt number `attempt` failing with `error`
+    pub fn should_retry(&self, attempt: u32, error: &CircuitError) -> bool {
+        if attempt >= self.max_attempts {
+            return false;
+        }
+        let retry_on = if self.retry_on.is_empty() {
+            Self::DEFAULT_RETRY_ON
+        } else {
+            &self.retry_on
+        };
+        retry_on.contains(&error.kind())
+    }
+
+    /// Longest delay between two attempts
+    pub fn max_delay(&self) -> Duration {
+        let retries = self.max_attempts.saturating_sub(1);
+        if retries == 0 {
+            return Duration::ZERO;
+        }
+        // Delays only grow, or only shrink, from one retry to the next
+        self.delay_after(1).max(self.delay_after(retries))
+    }
+
+    /// Delay before the attempt following attempt number `attempt` (1-based)
+    pub fn delay_after(&self, attempt: u32) -> Duration {
+        match self.backoff {
+            Backoff::None => Duration::ZERO,
+            Backoff::Fixed { delay } => delay,
+            Backoff::Exponential {
+                initial,
+                multiplier,
+                max,
+            } => {
+                let factor = multiplier.powi(attempt.saturating_sub(1) as i32);
+                Duration::try_from_secs_f64(initial.as_secs_f64() * factor)
+                    .map_or(max, |delay| delay.min(max))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_retry() {
+        let error = CircuitError::BlockExecution("flaky".to_string());
+        let policy = RetryPolicy::new(3);
+        assert!(policy.should_retry(1, &error));
+        assert!(policy.should_retry(2, &error));
+        assert!(!policy.should_retry(3, &error));
+        assert!(!policy.should_retry(1, &CircuitError::Cancelled));
+        assert!(!policy.should_retry(1, &CircuitError::Suspended("t".to_string())));
+        assert!(policy.should_retry(1, &CircuitError::Timeout(Duration::from_secs(1))));
+        // Retrying cannot fix a bad graph or input
+        assert!(!policy.should_retry(1, &CircuitError::InvalidInput("x".to_string())));
+        assert!(!policy.should_retry(
+            1,
+            &CircuitError::TypeMismatch {
+                expected: "int".to_string(),
+                actual: "string".to_string(),
+            }
+        ));
+
+        let policy = RetryPolicy::new(3).retry_on(ErrorKind::Timeout);
+        assert!(!policy.should_retry(1, &error));
+        assert!(policy.should_retry(1, &CircuitError::Timeout(Duration::from_secs(1))));
+    }
+
+    #[test]
+    fn test_backoff_delays() {
+        let fixed = RetryPolicy::new(5).with_fixed_backoff(Duration::from_millis(50));
+        assert_eq!(fixed.delay_after(1), Duration::from_millis(50));
+        assert_eq!(fixed.delay_after(4), Duration::from_millis(50));
+
+        let exponential = RetryPolicy::new(5).with_exponential_backoff(
+            Duration::from_millis(100),
+            2.0,
+            Duration::from_millis(350),
+        );
+        let delays: Vec<_> = (1..=4).map(|a| exponential.delay_after(a)).collect();
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_millis(100),
+                Duration::from_millis(200),
+                Duration::from_millis(350),
+                Duration::from_millis(350),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_max_delay() {
+        assert_eq!(RetryPolicy::new(3).max_delay(), Duration::ZERO);
+        let fixed = RetryPolicy::new(1).with_fixed_backoff(Duration::from_millis(50));
+        assert_eq!(fixed.max_delay(), Duration::ZERO);
+        let exponential = RetryPolicy::new(3).with_exponential_backoff(
+            Duration::from_millis(100),
+            2.0,
+            Duration::from_secs(1),
+        );
+        assert_eq!(exponential.max_delay(), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_policy_json() {
+        let json = r#"{"max_attempts": 4, "backoff": {"type": "exponential", "initial_ms": 10, "multiplier": 3.0, "max_ms": 1000}, "retry_on": ["block_execution"]}"#;
+        let policy: RetryPolicy = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            policy,
+            RetryPolicy::new(4)
+                .with_exponential_backoff(
+                    Duration::from_millis(10),
+                    3.0,
+                    Duration::from_millis(1000)
+                )
+                .retry_on(ErrorKind::BlockExecution)
+        );
+
+        let minimal: RetryPolicy = serde_json::from_str(r#"{"max_attempts": 2}"#).unwrap();
+        assert_eq!(minimal, RetryPolicy::new(2));
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{
+    error::{CircuitError, Result},
+    graph::{Graph, NodeId},
+    value::Value,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Snapshot format written by this version of the engine
+///
+/// Fields added in later formats must have serde defaults, so snapshots
+/// written in an older format keep loading.
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+/// A registered block type and its implementation version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockVersion {
+    pub id: String,
+    pub version: u32,
+}
+
+/// Serializable state of an engine, produced by `Engine::snapshot`
+///
+/// Block implementations are not serializable; only their IDs and versions
+/// are recorded, and the host registers the blocks again before calling
+/// `Engine::restore`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineSnapshot {
+    pub format_version: u32,
+    /// Loaded graphs, sorted by ID
+    pub graphs: Vec<Graph>,
+    /// Registered block types, sorted by ID
+    pub blocks: Vec<BlockVersion>,
+    /// Block state of every node, keyed by graph ID and node ID
+    #[serde(default)]
+    pub node_states: HashMap<String, HashMap<NodeId, Value>>,
+    /// Engine variables
+    #[serde(default)]
+    pub variables: HashMap<String, Value>,
+}
+
+impl EngineSnapshot {
+    /// Serialize the snapshot to JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Parse a snapshot written by this or an older version of the engine
+    pub fn from_json(json: &str) -> Result<Self> {
+        let raw: serde_json::Value = serde_json::from_str(json)?;
+        let version = raw
+            .get("format_version")
+            .and_then(serde_json::Value::as_u64)
+            .ok_or_else(|| {
+                CircuitError::InvalidInput("Snapshot has no format_version".to_string())
+            })?;
+        if version > u64::from(SNAPSHOT_FORMAT_VERSION) {
+            return Err(CircuitError::InvalidInput(format!(
+                "Snapshot format version {} is newer than the supported version {}",
+                version, SNAPSHOT_FORMAT_VERSION
+            )));
+        }
+
+        let mut snapshot: EngineSnapshot = serde_json::from_value(raw)?;
+        snapshot.format_version = SNAPSHOT_FORMAT_VERSION;
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_json_checks_format_version() {
+        let minimal =
+            r#"{"format_version": 1, "graphs": [], "blocks": [{"id": "math.add", "version": 1}]}"#;
+        let snapshot = EngineSnapshot::from_json(minimal).unwrap();
+        assert_eq!(snapshot.blocks[0].id, "math.add");
+        assert!(snapshot.node_states.is_empty());
+        let round_trip = EngineSnapshot::from_json(&snapshot.to_json().unwrap()).unwrap();
+        assert_eq!(round_trip.blocks, snapshot.blocks);
+
+        let newer = r#"{"format_version": 99, "graphs": [], "blocks": []}"#;
+        let err = EngineSnapshot::from_json(newer).unwrap_err();
+        assert!(err.to_string().contains("newer"));
+        assert!(EngineSnapshot::from_json(r#"{"graphs": []}"#).is_err());
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{graph::NodeId, value::Value};
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// Block state of every node, keyed by graph ID and node ID
+///
+/// Unlike outputs, node state outlives a single execution: it is what a
+/// stateful block reads back the next time its node runs.
+#[derive(Debug, Default)]
+pub(crate) struct NodeStates {
+    states: RwLock<HashMap<String, HashMap<NodeId, Value>>>,
+}
+
+impl NodeStates {
+    pub(crate) fn get(&self, graph_id: &str, node_id: &str) -> Option<Value> {
+        self.read()
+            .get(graph_id)
+            .and_then(|nodes| nodes.get(node_id))
+            .cloned()
+    }
+
+    pub(crate) fn set(&self, graph_id: &str, node_id: &str, value: Value) {
+        self.write()
+            .entry(graph_id.to_string())
+            .or_default()
+            .insert(node_id.to_string(), value);
+    }
+
+    pub(crate) fn graph(&self, graph_id: &str) -> HashMap<NodeId, Value> {
+        self.read().get(graph_id).cloned().unwrap_or_default()
+    }
+
+    pub(crate) fn set_graph(&self, graph_id: &str, states: HashMap<NodeId, Value>) {
+        let mut all = self.write();
+        if states.is_empty() {
+            all.remove(graph_id);
+        } else {
+            all.insert(graph_id.to_string(), states);
+        }
+    }
+
+    pub(crate) fn all(&self) -> HashMap<String, HashMap<NodeId, Value>> {
+        self.read().clone()
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, HashMap<NodeId, Value>>> {
+        self.states.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, HashMap<NodeId, Value>>> {
+        self.states.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Access to the state of the node a block runs in
+#[derive(Debug, Clone)]
+pub(crate) struct NodeStateSlot {
+    pub(crate) states: Arc<NodeStates>,
+    pub(crate) graph_id: String,
+    pub(crate) node_id: NodeId,
+}
+
+impl NodeStateSlot {
+    pub(crate) fn get(&self) -> Option<Value> {
+        self.states.get(&self.graph_id, &self.node_id)
+    }
+
+    pub(crate) fn set(&self, value: Value) {
+        self.states.set(&self.graph_id, &self.node_id, value);
+    }
+}
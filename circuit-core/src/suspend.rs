@@ -0,0 +1,43 @@
+use crate::{engine::NodeOutputs, graph::NodeId, value::Value};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// State of an execution waiting for the host to resume it
+///
+/// Everything needed to continue the run is kept here, so the host can
+/// serialize it, restart, and hand it back with `Engine::restore_suspended`.
+/// The graph itself is referenced by ID and must be loaded again before
+/// resuming.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SuspendedExecution {
+    pub execution_id: String,
+    pub graph_id: String,
+    /// Node that suspended; it runs again when the execution is resumed
+    pub node_id: NodeId,
+    /// Token the node is waiting for
+    pub token: String,
+    /// Nodes that already finished, in execution order
+    pub completed: Vec<NodeId>,
+    /// Outputs of the finished nodes
+    pub outputs: NodeOutputs,
+    /// Caller-supplied inputs of the nodes that have not finished
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub inputs: HashMap<NodeId, HashMap<String, Value>>,
+}
+
+/// Result of an execution that may suspend
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ExecutionOutcome {
+    /// Every node ran
+    Completed { outputs: NodeOutputs },
+    /// A node is waiting for `Engine::resume`
+    Suspended(SuspendedExecution),
+}
+
+impl ExecutionOutcome {
+    /// Whether the execution is waiting to be resumed
+    pub fn is_suspended(&self) -> bool {
+        matches!(self, ExecutionOutcome::Suspended(_))
+    }
+}
@@ -1,7 +1,12 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Value types that can flow through the circuit
+///
+/// Arrays, objects and bytes are reference-counted, so cloning a `Value` to
+/// hand it to several downstream nodes is cheap. Mutation goes through the
+/// `*_mut` accessors, which copy the payload only when it is shared.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 #[serde(tag = "type", content = "value")]
 pub enum Value {
@@ -17,11 +22,11 @@ pub enum Value {
     /// String value
     String(String),
     /// Array of values
-    Array(Vec<Value>),
+    Array(Arc<Vec<Value>>),
     /// Object/map of values
-    Object(HashMap<String, Value>),
+    Object(Arc<HashMap<String, Value>>),
     /// Raw bytes
-    Bytes(Vec<u8>),
+    Bytes(Arc<Vec<u8>>),
 }
 
 impl Value {
@@ -78,6 +83,77 @@ impl Value {
             _ => None,
         }
     }
+
+    /// Try to get as bytes
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Get a mutable array, copying it first if it is shared
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<Value>> {
+        match self {
+            Value::Array(arr) => Some(Arc::make_mut(arr)),
+            _ => None,
+        }
+    }
+
+    /// Get a mutable object, copying it first if it is shared
+    pub fn as_object_mut(&mut self) -> Option<&mut HashMap<String, Value>> {
+        match self {
+            Value::Object(obj) => Some(Arc::make_mut(obj)),
+            _ => None,
+        }
+    }
+
+    /// Get mutable bytes, copying them first if they are shared
+    pub fn as_bytes_mut(&mut self) -> Option<&mut Vec<u8>> {
+        match self {
+            Value::Bytes(b) => Some(Arc::make_mut(b)),
+            _ => None,
+        }
+    }
+
+    /// Check whether two values share the same heap payload
+    ///
+    /// Always false for scalars and strings, which are not reference-counted.
+    pub fn shares_payload_with(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Array(a), Value::Array(b)) => Arc::ptr_eq(a, b),
+            (Value::Object(a), Value::Object(b)) => Arc::ptr_eq(a, b),
+            (Value::Bytes(a), Value::Bytes(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// Address of the reference-counted payload, if this value has one
+    pub(crate) fn payload_addr(&self) -> Option<usize> {
+        match self {
+            Value::Array(a) => Some(Arc::as_ptr(a) as usize),
+            Value::Object(o) => Some(Arc::as_ptr(o) as usize),
+            Value::Bytes(b) => Some(Arc::as_ptr(b) as usize),
+            _ => None,
+        }
+    }
+
+    /// Approximate number of bytes held by this value, including heap data
+    ///
+    /// Shared payloads are counted in full by every value referring to them.
+    pub fn estimated_size(&self) -> usize {
+        let heap = match self {
+            Value::String(s) => s.len(),
+            Value::Bytes(b) => b.len(),
+            Value::Array(arr) => arr.iter().map(Value::estimated_size).sum(),
+            Value::Object(obj) => obj
+                .iter()
+                .map(|(key, value)| key.len() + value.estimated_size())
+                .sum(),
+            Value::Null | Value::Bool(_) | Value::Int(_) | Value::Float(_) => 0,
+        };
+        std::mem::size_of::<Value>() + heap
+    }
 }
 
 impl From<bool> for Value {
@@ -118,7 +194,13 @@ impl From<&str> for Value {
 
 impl<T: Into<Value>> From<Vec<T>> for Value {
     fn from(v: Vec<T>) -> Self {
-        Value::Array(v.into_iter().map(|item| item.into()).collect())
+        Value::Array(Arc::new(v.into_iter().map(|item| item.into()).collect()))
+    }
+}
+
+impl From<HashMap<String, Value>> for Value {
+    fn from(map: HashMap<String, Value>) -> Self {
+        Value::Object(Arc::new(map))
     }
 }
 
@@ -138,6 +220,36 @@ mod tests {
         assert_eq!(v.as_bool(), Some(true));
     }
 
+    #[test]
+    fn test_estimated_size() {
+        let scalar = Value::Int(1).estimated_size();
+        assert_eq!(scalar, std::mem::size_of::<Value>());
+
+        let s = Value::String("hello".to_string());
+        assert_eq!(s.estimated_size(), scalar + 5);
+
+        let arr = Value::from(vec![Value::Int(1), Value::Int(2)]);
+        assert_eq!(arr.estimated_size(), scalar * 3);
+    }
+
+    #[test]
+    fn test_clone_shares_payload() {
+        let original = Value::from(vec![1, 2, 3]);
+        let copy = original.clone();
+        assert!(copy.shares_payload_with(&original));
+    }
+
+    #[test]
+    fn test_copy_on_write() {
+        let original = Value::from(vec![1, 2, 3]);
+        let mut copy = original.clone();
+        copy.as_array_mut().unwrap().push(Value::Int(4));
+
+        assert!(!copy.shares_payload_with(&original));
+        assert_eq!(original.as_array().unwrap().len(), 3);
+        assert_eq!(copy.as_array().unwrap().len(), 4);
+    }
+
     #[test]
     fn test_serialization() {
         let v = Value::Int(42);
@@ -145,4 +257,16 @@ mod tests {
         let v2: Value = serde_json::from_str(&json).unwrap();
         assert_eq!(v, v2);
     }
+
+    #[test]
+    fn test_shared_serialization_format() {
+        let v = Value::from(vec![1]);
+        let json = serde_json::to_string(&v).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"Array","value":[{"type":"Int","value":1}]}"#
+        );
+        let v2: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(v, v2);
+    }
 }
@@ -3,15 +3,18 @@ use circuit_core::blocks::core::*;
 use circuit_core::blocks::logic::*;
 use circuit_core::blocks::math::*;
 use circuit_core::blocks::string::*;
-use circuit_core::graph::{Connection, Graph, Node};
-use circuit_core::{Engine, Value};
+use circuit_core::graph::{Connection, Graph, Node, NodeMode};
+use circuit_core::{
+    Breakpoint, CircuitError, CompareOp, DebugStatus, DivergenceKind, Engine, EngineLimits,
+    ExecutionOptions, ExecutionRecording, Value,
+};
 use circuit_lang::{flow_to_graph, parse_flow};
 use std::collections::HashMap;
 use std::sync::Arc;
 
 /// Register all 34 block types with the engine
 fn create_engine_with_all_blocks() -> Engine {
-    let mut engine = Engine::new();
+    let engine = Engine::new();
 
     // Core blocks
     engine.register_block(Arc::new(ConstantBlock)).unwrap();
@@ -84,7 +87,7 @@ fn test_calculator_flow_e2e() {
     let flow = parse_flow(&source).expect("Failed to parse calculator.flow");
     let graph = flow_to_graph(&flow).expect("Failed to convert to graph");
 
-    let mut engine = create_engine_with_all_blocks();
+    let engine = create_engine_with_all_blocks();
     engine.load_graph(graph).expect("Failed to load graph");
 
     let results = engine
@@ -106,7 +109,7 @@ fn test_data_pipeline_flow_e2e() {
     let flow = parse_flow(&source).expect("Failed to parse data_pipeline.flow");
     let graph = flow_to_graph(&flow).expect("Failed to convert to graph");
 
-    let mut engine = create_engine_with_all_blocks();
+    let engine = create_engine_with_all_blocks();
     engine.load_graph(graph).expect("Failed to load graph");
 
     let results = engine
@@ -126,7 +129,7 @@ fn test_string_processing_flow_e2e() {
     let flow = parse_flow(&source).expect("Failed to parse string_processing.flow");
     let graph = flow_to_graph(&flow).expect("Failed to convert to graph");
 
-    let mut engine = create_engine_with_all_blocks();
+    let engine = create_engine_with_all_blocks();
     engine.load_graph(graph).expect("Failed to load graph");
 
     let results = engine
@@ -139,6 +142,29 @@ fn test_string_processing_flow_e2e() {
     assert_eq!(result, &Value::String("Hello World".to_string()));
 }
 
+#[test]
+fn test_string_limit_on_block_output_e2e() {
+    // Every configured string fits in 8 bytes, but concat2 produces "Hello World"
+    let source = load_flow_file("string_processing.flow");
+    let flow = parse_flow(&source).expect("Failed to parse string_processing.flow");
+    let graph = flow_to_graph(&flow).expect("Failed to convert to graph");
+
+    let engine = create_engine_with_all_blocks();
+    engine.set_limits(EngineLimits::new().with_max_string_len(8));
+    engine.load_graph(graph).expect("Failed to load graph");
+
+    match engine.execute_graph("string_processing") {
+        Err(CircuitError::LimitExceeded(message)) => {
+            assert!(
+                message.contains("concat2"),
+                "unexpected message: {}",
+                message
+            )
+        }
+        other => panic!("expected LimitExceeded, got {:?}", other),
+    }
+}
+
 #[test]
 fn test_advanced_math_flow_e2e() {
     // abs(sqrt(9) - 4) = abs(3 - 4) = abs(-1) = 1.0
@@ -146,7 +172,7 @@ fn test_advanced_math_flow_e2e() {
     let flow = parse_flow(&source).expect("Failed to parse advanced_math.flow");
     let graph = flow_to_graph(&flow).expect("Failed to convert to graph");
 
-    let mut engine = create_engine_with_all_blocks();
+    let engine = create_engine_with_all_blocks();
     engine.load_graph(graph).expect("Failed to load graph");
 
     let results = engine
@@ -165,7 +191,7 @@ fn test_conditional_flow_e2e() {
     let flow = parse_flow(&source).expect("Failed to parse conditional.flow");
     let graph = flow_to_graph(&flow).expect("Failed to convert to graph");
 
-    let mut engine = create_engine_with_all_blocks();
+    let engine = create_engine_with_all_blocks();
     engine.load_graph(graph).expect("Failed to load graph");
 
     let results = engine
@@ -184,7 +210,7 @@ fn test_string_transform_flow_e2e() {
     let flow = parse_flow(&source).expect("Failed to parse string_transform.flow");
     let graph = flow_to_graph(&flow).expect("Failed to convert to graph");
 
-    let mut engine = create_engine_with_all_blocks();
+    let engine = create_engine_with_all_blocks();
     engine.load_graph(graph).expect("Failed to load graph");
 
     let results = engine
@@ -195,7 +221,7 @@ fn test_string_transform_flow_e2e() {
     let result = split_output.get("result").expect("Missing result");
     assert_eq!(
         result,
-        &Value::Array(vec![
+        &Value::from(vec![
             Value::String("HELLO".to_string()),
             Value::String("WORLD".to_string()),
         ])
@@ -205,7 +231,7 @@ fn test_string_transform_flow_e2e() {
 #[test]
 fn test_programmatic_graph_e2e() {
     // Build a graph in code: clamp(power(2, 10), 0, 500) = 500
-    let mut engine = create_engine_with_all_blocks();
+    let engine = create_engine_with_all_blocks();
 
     let mut graph = Graph::new("programmatic".to_string(), "Programmatic Graph".to_string());
 
@@ -213,69 +239,35 @@ fn test_programmatic_graph_e2e() {
     let mut config_base = HashMap::new();
     config_base.insert("value".to_string(), Value::Float(2.0));
     graph
-        .add_node(Node {
-            id: "base".to_string(),
-            block_type: "core.constant".to_string(),
-            config: config_base,
-            position: None,
-        })
+        .add_node(Node::new("base", "core.constant").with_config(config_base))
         .unwrap();
 
     // Constant: exponent = 10
     let mut config_exp = HashMap::new();
     config_exp.insert("value".to_string(), Value::Float(10.0));
     graph
-        .add_node(Node {
-            id: "exp".to_string(),
-            block_type: "core.constant".to_string(),
-            config: config_exp,
-            position: None,
-        })
+        .add_node(Node::new("exp", "core.constant").with_config(config_exp))
         .unwrap();
 
     // Power: 2^10 = 1024
-    graph
-        .add_node(Node {
-            id: "pow".to_string(),
-            block_type: "math.power".to_string(),
-            config: HashMap::new(),
-            position: None,
-        })
-        .unwrap();
+    graph.add_node(Node::new("pow", "math.power")).unwrap();
 
     // Constant: min = 0
     let mut config_min = HashMap::new();
     config_min.insert("value".to_string(), Value::Float(0.0));
     graph
-        .add_node(Node {
-            id: "min_val".to_string(),
-            block_type: "core.constant".to_string(),
-            config: config_min,
-            position: None,
-        })
+        .add_node(Node::new("min_val", "core.constant").with_config(config_min))
         .unwrap();
 
     // Constant: max = 500
     let mut config_max = HashMap::new();
     config_max.insert("value".to_string(), Value::Float(500.0));
     graph
-        .add_node(Node {
-            id: "max_val".to_string(),
-            block_type: "core.constant".to_string(),
-            config: config_max,
-            position: None,
-        })
+        .add_node(Node::new("max_val", "core.constant").with_config(config_max))
         .unwrap();
 
     // Clamp: clamp(1024, 0, 500) = 500
-    graph
-        .add_node(Node {
-            id: "clamp".to_string(),
-            block_type: "math.clamp".to_string(),
-            config: HashMap::new(),
-            position: None,
-        })
-        .unwrap();
+    graph.add_node(Node::new("clamp", "math.clamp")).unwrap();
 
     // Connections
     graph
@@ -326,3 +318,216 @@ fn test_programmatic_graph_e2e() {
     let result = clamp_output.get("result").expect("Missing result");
     assert_eq!(result.as_float(), Some(500.0));
 }
+
+#[test]
+fn test_pass_through_shares_payload_e2e() {
+    // array → if(true) → gate(open) forwards the array without copying it
+    let engine = create_engine_with_all_blocks();
+    let mut graph = Graph::new("forward".to_string(), "Forward".to_string());
+
+    let constants = [
+        ("array", Value::from(vec![1, 2, 3])),
+        ("flag", Value::Bool(true)),
+        ("fallback", Value::Null),
+    ];
+    for (id, value) in constants {
+        let mut config = HashMap::new();
+        config.insert("value".to_string(), value);
+        graph
+            .add_node(Node::new(id, "core.constant").with_config(config))
+            .unwrap();
+    }
+    for (id, block_type) in [("branch", "control.if"), ("gate", "control.gate")] {
+        graph.add_node(Node::new(id, block_type)).unwrap();
+    }
+
+    let connections = [
+        ("array", "value", "branch", "then_value"),
+        ("fallback", "value", "branch", "else_value"),
+        ("flag", "value", "branch", "condition"),
+        ("branch", "result", "gate", "value"),
+        ("flag", "value", "gate", "open"),
+    ];
+    for (from_node, from_port, to_node, to_port) in connections {
+        graph
+            .add_connection(Connection {
+                from_node: from_node.to_string(),
+                from_port: from_port.to_string(),
+                to_node: to_node.to_string(),
+                to_port: to_port.to_string(),
+            })
+            .unwrap();
+    }
+
+    engine.load_graph(graph).unwrap();
+    let results = engine.execute_graph("forward").unwrap();
+
+    let source = &results["array"]["value"];
+    let forwarded = &results["gate"]["result"];
+    assert_eq!(forwarded, source);
+    assert!(forwarded.shares_payload_with(source));
+}
+
+#[test]
+fn test_connected_parameter_overrides_config_e2e() {
+    // The counter's step comes from `double` instead of its config
+    let source = r#"
+        flow stepped {
+            node start: core.constant { value = 10 }
+            node two: core.constant { value = 2 }
+            node double: math.multiply {}
+            node count: control.counter { step = 1 }
+
+            connect two.value -> double.a
+            connect two.value -> double.b
+            connect start.value -> count.value
+            connect double.result -> count.step
+        }
+    "#;
+    let graph = flow_to_graph(&parse_flow(source).unwrap()).unwrap();
+    let engine = create_engine_with_all_blocks();
+    engine.load_graph(graph).unwrap();
+
+    let results = engine.execute_graph("stepped").unwrap();
+    assert_eq!(results["count"]["result"].as_float(), Some(14.0));
+
+    let step = engine.node_ports("stepped", "count").unwrap();
+    let step = step.input("step").unwrap();
+    assert_eq!(step.data_type, "number");
+    assert!(!step.required);
+
+    // A string cannot drive the numeric step
+    let source = r#"
+        flow wrong_step {
+            node label: core.constant { value = "x" }
+            node upper: string.uppercase {}
+            node count: control.counter {}
+
+            connect label.value -> upper.value
+            connect upper.result -> count.step
+        }
+    "#;
+    let graph = flow_to_graph(&parse_flow(source).unwrap()).unwrap();
+    let err = engine.load_graph(graph).unwrap_err();
+    assert!(matches!(err, CircuitError::InvalidConnection(_)));
+}
+
+#[test]
+fn test_node_modes_from_flow_e2e() {
+    // Pin the divisor and bypass the negation while debugging the pipeline
+    let source = r#"
+        flow debugging {
+            node a: core.constant { value = 12 }
+            node b: core.constant { value = 0 pin value = 4 }
+            node div: math.divide {}
+            node neg: math.negate { bypass }
+            node log: core.debug {}
+            node quiet: core.debug { mute }
+
+            connect a.value -> div.a
+            connect b.value -> div.b
+            connect div.result -> neg.value
+            connect neg.result -> log.value
+            connect neg.result -> quiet.value
+        }
+    "#;
+    let mut graph = flow_to_graph(&parse_flow(source).unwrap()).unwrap();
+    assert!(matches!(graph.nodes["neg"].mode, NodeMode::Bypass));
+    let engine = create_engine_with_all_blocks();
+    engine.load_graph(graph.clone()).unwrap();
+
+    let results = engine.execute_graph("debugging").unwrap();
+    assert_eq!(results["div"]["result"].as_float(), Some(3.0));
+    assert_eq!(results["neg"]["result"].as_float(), Some(3.0));
+    assert_eq!(results["quiet"]["value"], Value::Null);
+
+    // With both loggers muted nothing reads the rest, so it does not run
+    graph.nodes.get_mut("log").unwrap().mode = NodeMode::Mute;
+    engine.load_graph(graph).unwrap();
+    let results = engine.execute_graph("debugging").unwrap();
+    let mut ran: Vec<&str> = results.keys().map(String::as_str).collect();
+    ran.sort();
+    assert_eq!(ran, vec!["log", "quiet"]);
+}
+
+#[test]
+fn test_debug_session_from_flow_e2e() {
+    let source = r#"
+        flow debugged {
+            node price: core.constant { value = 20 }
+            node qty: core.constant { value = 3 }
+            node total: math.multiply {}
+
+            connect price.value -> total.a
+            connect qty.value -> total.b
+        }
+    "#;
+    let graph = flow_to_graph(&parse_flow(source).unwrap()).unwrap();
+    let engine = create_engine_with_all_blocks();
+    engine.load_graph(graph).unwrap();
+
+    let mut session = engine
+        .debug("debugged", &HashMap::new(), &ExecutionOptions::new())
+        .unwrap();
+    session
+        .add_breakpoint(Breakpoint::new("total").with_condition("b", CompareOp::Ge, Value::Int(3)))
+        .unwrap();
+
+    match session.resume() {
+        DebugStatus::Paused {
+            node_id, inputs, ..
+        } => {
+            assert_eq!(node_id, "total");
+            assert_eq!(inputs["a"].as_float(), Some(20.0));
+        }
+        other => panic!("Expected to pause at total, got {:?}", other),
+    }
+    session.set_input("b", Value::Float(4.0)).unwrap();
+    assert_eq!(session.resume(), &DebugStatus::Finished);
+    assert_eq!(session.outputs()["total"]["result"].as_float(), Some(80.0));
+}
+
+#[test]
+fn test_record_and_replay_from_flow_e2e() {
+    let source = r#"
+        flow recorded {
+            node price: core.constant { value = 20 }
+            node qty: core.constant { value = 3 }
+            node total: math.multiply {}
+
+            connect price.value -> total.a
+            connect qty.value -> total.b
+        }
+    "#;
+    let graph = flow_to_graph(&parse_flow(source).unwrap()).unwrap();
+    let engine = create_engine_with_all_blocks();
+    engine.load_graph(graph).unwrap();
+    engine.set_variable("region", "eu");
+
+    let recording = engine
+        .execute_recorded("recorded", &HashMap::new(), &ExecutionOptions::new())
+        .unwrap();
+    assert_eq!(recording.engine_variables["region"], Value::from("eu"));
+    assert_eq!(
+        recording.outputs()["total"]["result"].as_float(),
+        Some(60.0)
+    );
+
+    // A fresh engine with the same blocks replays the saved file
+    let json = recording.to_json().unwrap();
+    let mut recording = ExecutionRecording::from_json(&json).unwrap();
+    let report = create_engine_with_all_blocks()
+        .replay(&recording, &ExecutionOptions::new())
+        .unwrap();
+    assert!(report.is_faithful());
+    assert_eq!(report.outputs, recording.outputs());
+
+    // A node that no longer produces what was recorded is the divergence
+    let qty = recording.graph.nodes.get_mut("qty").unwrap();
+    qty.config.insert("value".to_string(), Value::Int(4));
+    let report = engine.replay(&recording, &ExecutionOptions::new()).unwrap();
+    let divergence = report.divergence.unwrap();
+    assert_eq!(divergence.node_id, "qty");
+    assert_eq!(divergence.kind, DivergenceKind::Outputs);
+    assert!(!report.outputs.contains_key("total"));
+}
@@ -1,34 +1,110 @@
-use circuit_core::{Engine, Graph};
+use circuit_core::{
+    batch::{results_to_jsonl, rows_from_jsonl},
+    error::panic_message,
+    Breakpoint, CancellationToken, DebugSession, EditEvent, Engine, EngineLimits, EngineSnapshot,
+    ExecutionOptions, ExecutionRecording, Graph, GraphEdit, GraphEditor, GraphOp, GraphPatch,
+    LifecycleEvent, Replica, SuspendedExecution, Value,
+};
 use std::collections::HashMap;
 use std::ffi::{CStr, CString};
-use std::os::raw::c_char;
-use std::sync::{Arc, Mutex};
+use std::os::raw::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
+
+type SharedDebugSession = Arc<Mutex<DebugSession>>;
+type SharedGraphEditor = Arc<Mutex<GraphEditor>>;
+type SharedReplica = Arc<Mutex<Replica>>;
 
 // Global engine registry
 lazy_static::lazy_static! {
-    static ref ENGINES: Mutex<HashMap<u64, Arc<Mutex<Engine>>>> = Mutex::new(HashMap::new());
+    // Engines are Sync, so calls on the same handle run concurrently; the
+    // registry lock is only held long enough to look up a handle
+    static ref ENGINES: RwLock<HashMap<u64, Arc<Engine>>> = RwLock::new(HashMap::new());
     static ref NEXT_ENGINE_ID: Mutex<u64> = Mutex::new(0);
+    // Cancellation tokens of pending executions, keyed by (engine handle, execution ID)
+    static ref EXECUTIONS: Mutex<HashMap<(u64, u64), CancellationToken>> = Mutex::new(HashMap::new());
+    static ref NEXT_EXECUTION_ID: Mutex<u64> = Mutex::new(1);
+    // Debug sessions with the handle of the engine they belong to; each
+    // session has its own lock so stepping one does not block the others
+    static ref DEBUG_SESSIONS: Mutex<HashMap<u64, (u64, SharedDebugSession)>> =
+        Mutex::new(HashMap::new());
+    static ref NEXT_DEBUG_SESSION_ID: Mutex<u64> = Mutex::new(1);
+    // Graph editors; they work on their own copy of a graph, not on an engine
+    static ref EDITORS: Mutex<HashMap<u64, SharedGraphEditor>> = Mutex::new(HashMap::new());
+    static ref NEXT_EDITOR_ID: Mutex<u64> = Mutex::new(1);
+    // Collaborative replicas; the host carries their ops to the other replicas
+    static ref REPLICAS: Mutex<HashMap<u64, SharedReplica>> = Mutex::new(HashMap::new());
+    static ref NEXT_REPLICA_ID: Mutex<u64> = Mutex::new(1);
 }
 
 /// Create a new engine instance and return its handle
+/// Returns u64::MAX if the engine could not be created
 #[no_mangle]
 pub extern "C" fn circuit_engine_create() -> u64 {
-    let engine = Arc::new(Mutex::new(Engine::new()));
-    let mut next_id = NEXT_ENGINE_ID.lock().unwrap();
-    let id = *next_id;
-    *next_id += 1;
+    guard(std::ptr::null_mut(), u64::MAX, || {
+        let engine = Arc::new(Engine::new());
+        let mut next_id = lock(&NEXT_ENGINE_ID);
+        let id = *next_id;
+        *next_id += 1;
 
-    ENGINES.lock().unwrap().insert(id, engine);
-    id
+        engines_mut().insert(id, engine);
+        id
+    })
 }
 
 /// Destroy an engine instance
 #[no_mangle]
 pub extern "C" fn circuit_engine_destroy(handle: u64) {
-    ENGINES.lock().unwrap().remove(&handle);
+    guard(std::ptr::null_mut(), (), || {
+        engines_mut().remove(&handle);
+        lock(&EXECUTIONS).retain(|(engine, _), _| *engine != handle);
+        lock(&DEBUG_SESSIONS).retain(|_, (engine, _)| *engine != handle);
+    })
+}
+
+/// Set the resource limits of an engine from a JSON object such as
+/// `{"max_nodes": 500, "max_string_len": 65536}`; omitted limits are unlimited.
+/// Returns 0 on success, non-zero on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_engine_set_limits(
+    handle: u64,
+    json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> i32 {
+    guard(error_out, -1, || {
+        let json_str = match read_c_str(json) {
+            Some(s) => s,
+            None => return -1,
+        };
+
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return -1,
+        };
+
+        let limits: EngineLimits = match serde_json::from_str(json_str) {
+            Ok(l) => l,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to parse limits: {}", e));
+                return -1;
+            }
+        };
+
+        engine_arc.set_limits(limits);
+        0
+    })
 }
 
 /// Load a graph from JSON string
+/// Graphs in an older format are migrated and block and port aliases are
+/// resolved; lifecycle listeners get a graph_migrated event listing the changes.
+/// A loaded graph with the same ID is overwritten.
 /// Returns 0 on success, non-zero on error
 ///
 /// # Safety
@@ -41,49 +117,269 @@ pub extern "C" fn circuit_load_graph(
     json: *const c_char,
     error_out: *mut *mut c_char,
 ) -> i32 {
-    let json_str = unsafe {
-        if json.is_null() {
-            return -1;
-        }
-        match CStr::from_ptr(json).to_str() {
-            Ok(s) => s,
-            Err(_) => return -1,
+    guard(error_out, -1, || {
+        with_graph_json(handle, json, error_out, |engine, json| {
+            engine
+                .load_graph_json(json)
+                .map(|_| ())
+                .map_err(|e| format!("Failed to load graph: {}", e))
+        })
+    })
+}
+
+/// Load a graph from JSON string like circuit_load_graph, failing if a graph
+/// with the same ID is already loaded
+/// Returns 0 on success, non-zero on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_load_new_graph(
+    handle: u64,
+    json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> i32 {
+    guard(error_out, -1, || {
+        with_graph_json(handle, json, error_out, |engine, json| {
+            engine
+                .load_new_graph_json(json)
+                .map(|_| ())
+                .map_err(|e| format!("Failed to load graph: {}", e))
+        })
+    })
+}
+
+/// Replace a loaded graph with a new version from a JSON string
+/// Returns 0 on success, non-zero on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_replace_graph(
+    handle: u64,
+    json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> i32 {
+    guard(error_out, -1, || {
+        with_graph_json(handle, json, error_out, |engine, json| {
+            engine
+                .replace_graph_json(json)
+                .map(|_| ())
+                .map_err(|e| format!("Failed to replace graph: {}", e))
+        })
+    })
+}
+
+/// Upgrade a graph document to the current format and resolve aliases
+/// without loading it. Returns {"graph": Graph, "report": MigrationReport}
+/// as JSON, or null on error.
+/// The string must be freed with circuit_free_string
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_migrate_graph(
+    handle: u64,
+    json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        let json_str = match read_c_str(json) {
+            Some(s) => s,
+            None => return std::ptr::null_mut(),
+        };
+
+        let engine = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return std::ptr::null_mut(),
+        };
+
+        match engine.migrate_graph(json_str) {
+            Ok((graph, report)) => to_json_c_string(
+                &serde_json::json!({ "graph": graph, "report": report }),
+                error_out,
+            ),
+            Err(e) => {
+                set_error(error_out, &format!("Failed to migrate graph: {}", e));
+                std::ptr::null_mut()
+            }
         }
-    };
+    })
+}
 
-    let engine_arc = {
-        let engines = ENGINES.lock().unwrap();
-        match engines.get(&handle) {
-            Some(e) => Arc::clone(e),
-            None => {
-                set_error(error_out, "Invalid engine handle");
-                return -1;
+/// Resolve block type `alias` to `block_id` in graphs loaded from now on.
+/// Returns 0 on success, -1 on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_add_block_alias(
+    handle: u64,
+    alias: *const c_char,
+    block_id: *const c_char,
+    error_out: *mut *mut c_char,
+) -> i32 {
+    guard(error_out, -1, || {
+        let (alias, block_id) = match (read_c_str(alias), read_c_str(block_id)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return -1,
+        };
+
+        let engine = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return -1,
+        };
+
+        match engine.add_block_alias(alias, block_id) {
+            Ok(()) => 0,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to add alias: {}", e));
+                -1
             }
         }
-    };
+    })
+}
 
-    let graph: Graph = match serde_json::from_str(json_str) {
-        Ok(g) => g,
-        Err(e) => {
-            set_error(error_out, &format!("Failed to parse graph: {}", e));
-            return -1;
+/// Resolve port `alias` of block type `block_id` to `port` in graphs loaded
+/// from now on. Returns 0 on success, -1 on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_add_port_alias(
+    handle: u64,
+    block_id: *const c_char,
+    alias: *const c_char,
+    port: *const c_char,
+    error_out: *mut *mut c_char,
+) -> i32 {
+    guard(error_out, -1, || {
+        let (block_id, alias, port) =
+            match (read_c_str(block_id), read_c_str(alias), read_c_str(port)) {
+                (Some(b), Some(a), Some(p)) => (b, a, p),
+                _ => return -1,
+            };
+
+        let engine = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return -1,
+        };
+
+        match engine.add_port_alias(block_id, alias, port) {
+            Ok(()) => 0,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to add alias: {}", e));
+                -1
+            }
         }
-    };
+    })
+}
 
-    let result = {
-        let mut engine = engine_arc.lock().unwrap();
-        engine.load_graph(graph)
-    };
+/// Unload a graph by ID
+/// Returns 0 on success, non-zero on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_unload_graph(
+    handle: u64,
+    graph_id: *const c_char,
+    error_out: *mut *mut c_char,
+) -> i32 {
+    guard(error_out, -1, || {
+        let graph_id_str = match read_c_str(graph_id) {
+            Some(s) => s,
+            None => return -1,
+        };
 
-    match result {
-        Ok(_) => 0,
-        Err(e) => {
-            set_error(error_out, &format!("Failed to load graph: {}", e));
-            -1
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return -1,
+        };
+
+        match engine_arc.unload_graph(graph_id_str) {
+            Ok(_) => 0,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to unload graph: {}", e));
+                -1
+            }
         }
+    })
+}
+
+/// Callback receiving a lifecycle event as JSON, e.g.
+/// {"type": "graph_replaced", "graph_id": "main"}. The string is only valid
+/// for the duration of the call. NULL is rejected at registration.
+pub type CircuitLifecycleCallback =
+    Option<extern "C" fn(event_json: *const c_char, user_data: *mut c_void)>;
+
+// Opaque host pointer handed back to the callback untouched
+struct UserData(*mut c_void);
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+impl UserData {
+    fn as_ptr(&self) -> *mut c_void {
+        self.0
     }
 }
 
+/// Register a callback invoked after graphs are loaded, replaced or unloaded.
+/// `user_data` is passed back to every call and must stay valid until the
+/// listener is removed or the engine destroyed.
+/// Returns a listener ID, or u64::MAX if the engine handle is invalid or
+/// `callback` is NULL.
+#[no_mangle]
+pub extern "C" fn circuit_add_lifecycle_listener(
+    handle: u64,
+    callback: CircuitLifecycleCallback,
+    user_data: *mut c_void,
+) -> u64 {
+    guard(std::ptr::null_mut(), u64::MAX, || {
+        let Some(callback) = callback else {
+            return u64::MAX;
+        };
+        let engine_arc = match get_engine(handle, std::ptr::null_mut()) {
+            Some(e) => e,
+            None => return u64::MAX,
+        };
+
+        let user_data = UserData(user_data);
+        engine_arc.add_lifecycle_listener(Arc::new(move |event: &LifecycleEvent| {
+            let json = serde_json::to_string(event)
+                .ok()
+                .and_then(|j| CString::new(j).ok());
+            if let Some(json) = json {
+                callback(json.as_ptr(), user_data.as_ptr());
+            }
+        }))
+    })
+}
+
+/// Remove a lifecycle listener.
+/// Returns 0 on success, -1 if the handle or listener ID is unknown.
+#[no_mangle]
+pub extern "C" fn circuit_remove_lifecycle_listener(handle: u64, listener_id: u64) -> i32 {
+    guard(std::ptr::null_mut(), -1, || {
+        match get_engine(handle, std::ptr::null_mut()) {
+            Some(engine) if engine.remove_lifecycle_listener(listener_id) => 0,
+            _ => -1,
+        }
+    })
+}
+
 /// Execute a graph and return results as JSON
 /// Returns a C string that must be freed with circuit_free_string
 ///
@@ -97,87 +393,2199 @@ pub extern "C" fn circuit_execute_graph(
     graph_id: *const c_char,
     error_out: *mut *mut c_char,
 ) -> *mut c_char {
-    let graph_id_str = unsafe {
-        if graph_id.is_null() {
-            return std::ptr::null_mut();
+    guard(error_out, std::ptr::null_mut(), || {
+        let graph_id_str = match read_c_str(graph_id) {
+            Some(s) => s,
+            None => return std::ptr::null_mut(),
+        };
+
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return std::ptr::null_mut(),
+        };
+
+        let results = engine_arc.execute_graph(graph_id_str);
+
+        let results = match results {
+            Ok(r) => r,
+            Err(e) => {
+                set_error(error_out, &format!("Execution failed: {}", e));
+                return std::ptr::null_mut();
+            }
+        };
+
+        to_json_c_string(&results, error_out)
+    })
+}
+
+/// Execute a graph and return a structured execution report as JSON.
+/// Failures during the run are described in the report's "error" field and
+/// per-node "status"; NULL is only returned if the run could not start.
+/// Returns a C string that must be freed with circuit_free_string
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_execute_graph_report(
+    handle: u64,
+    graph_id: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        let graph_id_str = match read_c_str(graph_id) {
+            Some(s) => s,
+            None => return std::ptr::null_mut(),
+        };
+
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return std::ptr::null_mut(),
+        };
+
+        match engine_arc.execute_graph_report(graph_id_str, &ExecutionOptions::new()) {
+            Ok(report) => to_json_c_string(&report, error_out),
+            Err(e) => {
+                set_error(error_out, &format!("Execution failed: {}", e));
+                std::ptr::null_mut()
+            }
         }
-        match CStr::from_ptr(graph_id).to_str() {
-            Ok(s) => s,
-            Err(_) => return std::ptr::null_mut(),
+    })
+}
+
+/// Reserve an execution ID that can later be passed to
+/// circuit_execute_graph_with_options and circuit_cancel.
+/// The host owns the ID until circuit_execute_graph_with_options runs with it,
+/// which releases it when the run finishes; release an ID that is not run
+/// with circuit_execution_free.
+/// Returns 0 if the engine handle is invalid.
+#[no_mangle]
+pub extern "C" fn circuit_execution_create(handle: u64) -> u64 {
+    guard(std::ptr::null_mut(), 0, || {
+        if !engines().contains_key(&handle) {
+            return 0;
         }
-    };
 
-    let engine_arc = {
-        let engines = ENGINES.lock().unwrap();
-        match engines.get(&handle) {
-            Some(e) => Arc::clone(e),
-            None => {
-                set_error(error_out, "Invalid engine handle");
-                return std::ptr::null_mut();
+        let mut next_id = lock(&NEXT_EXECUTION_ID);
+        let id = *next_id;
+        *next_id += 1;
+
+        lock(&EXECUTIONS).insert((handle, id), CancellationToken::new());
+        id
+    })
+}
+
+/// Release an execution ID that will not be run.
+/// Returns 0 on success, -1 if the execution ID is unknown.
+#[no_mangle]
+pub extern "C" fn circuit_execution_free(handle: u64, execution_id: u64) -> i32 {
+    guard(std::ptr::null_mut(), -1, || {
+        match lock(&EXECUTIONS).remove(&(handle, execution_id)) {
+            Some(_) => 0,
+            None => -1,
+        }
+    })
+}
+
+/// Cancel a running or not yet started execution.
+/// Returns 0 on success, -1 if the execution ID is unknown.
+#[no_mangle]
+pub extern "C" fn circuit_cancel(handle: u64, execution_id: u64) -> i32 {
+    guard(std::ptr::null_mut(), -1, || {
+        match lock(&EXECUTIONS).get(&(handle, execution_id)) {
+            Some(token) => {
+                token.cancel();
+                0
             }
+            None => -1,
         }
-    };
+    })
+}
 
-    let results = {
-        let engine = engine_arc.lock().unwrap();
-        engine.execute_graph(graph_id_str)
-    };
+/// Execute a graph with execution budgets and return results as JSON.
+/// `execution_id` must come from circuit_execution_create, or be 0 if the
+/// execution does not need to be cancellable. A `timeout_ms` or
+/// `max_node_executions` of 0 means unlimited.
+/// Returns a C string that must be freed with circuit_free_string
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_execute_graph_with_options(
+    handle: u64,
+    graph_id: *const c_char,
+    execution_id: u64,
+    timeout_ms: u64,
+    max_node_executions: u64,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        let graph_id_str = match read_c_str(graph_id) {
+            Some(s) => s,
+            None => return std::ptr::null_mut(),
+        };
 
-    let results = match results {
-        Ok(r) => r,
-        Err(e) => {
-            set_error(error_out, &format!("Execution failed: {}", e));
-            return std::ptr::null_mut();
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return std::ptr::null_mut(),
+        };
+
+        let mut options = ExecutionOptions::new();
+        if timeout_ms > 0 {
+            options = options.with_timeout(Duration::from_millis(timeout_ms));
+        }
+        if max_node_executions > 0 {
+            options = options.with_max_node_executions(max_node_executions as usize);
+        }
+        if execution_id != 0 {
+            match lock(&EXECUTIONS).get(&(handle, execution_id)) {
+                Some(token) => options = options.with_cancellation(token.clone()),
+                None => {
+                    set_error(error_out, "Invalid execution ID");
+                    return std::ptr::null_mut();
+                }
+            }
         }
-    };
 
-    let json = match serde_json::to_string(&results) {
-        Ok(j) => j,
-        Err(e) => {
-            set_error(error_out, &format!("Failed to serialize results: {}", e));
-            return std::ptr::null_mut();
+        let results = engine_arc.execute_graph_with_options(graph_id_str, &options);
+
+        if execution_id != 0 {
+            lock(&EXECUTIONS).remove(&(handle, execution_id));
         }
-    };
 
-    match CString::new(json) {
-        Ok(c_str) => c_str.into_raw(),
-        Err(_) => std::ptr::null_mut(),
-    }
+        let results = match results {
+            Ok(r) => r,
+            Err(e) => {
+                set_error(error_out, &format!("Execution failed: {}", e));
+                return std::ptr::null_mut();
+            }
+        };
+
+        to_json_c_string(&results, error_out)
+    })
 }
 
-/// Free a string allocated by circuit_execute_graph
+/// Execute a graph once per input row
+///
+/// `rows_jsonl` holds one JSON object per line mapping "node.port" to a value.
+/// Returns one JSON line per row, in row order: {"ok": outputs} or
+/// {"error": message}. The returned string must be freed with circuit_free_string.
 ///
 /// # Safety
-/// This function is unsafe because it takes ownership of a raw pointer.
-/// The caller must ensure the pointer was allocated by this library.
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
 #[no_mangle]
 #[allow(clippy::not_unsafe_ptr_arg_deref)]
-pub extern "C" fn circuit_free_string(s: *mut c_char) {
-    if !s.is_null() {
-        unsafe {
-            let _ = CString::from_raw(s);
+pub extern "C" fn circuit_execute_batch_jsonl(
+    handle: u64,
+    graph_id: *const c_char,
+    rows_jsonl: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        let (graph_id_str, rows_str) = match (read_c_str(graph_id), read_c_str(rows_jsonl)) {
+            (Some(g), Some(r)) => (g, r),
+            _ => return std::ptr::null_mut(),
+        };
+
+        let rows = match rows_from_jsonl(rows_str) {
+            Ok(rows) => rows,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to parse rows: {}", e));
+                return std::ptr::null_mut();
+            }
+        };
+
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return std::ptr::null_mut(),
+        };
+
+        let results = engine_arc.execute_batch(graph_id_str, rows);
+
+        let results = match results {
+            Ok(r) => r,
+            Err(e) => {
+                set_error(error_out, &format!("Execution failed: {}", e));
+                return std::ptr::null_mut();
+            }
+        };
+
+        match CString::new(results_to_jsonl(&results)) {
+            Ok(c_str) => c_str.into_raw(),
+            Err(_) => std::ptr::null_mut(),
         }
-    }
+    })
 }
 
-// Helper function to set error message
-fn set_error(error_out: *mut *mut c_char, message: &str) {
-    if !error_out.is_null() {
-        if let Ok(c_str) = CString::new(message) {
-            unsafe {
-                *error_out = c_str.into_raw();
+/// Execute a graph that may suspend to await an external event.
+/// `inputs_json` maps "node.port" to a value and may be NULL for no inputs.
+/// Returns `{"status": "completed", "outputs": ...}` or the suspended
+/// execution as `{"status": "suspended", "execution_id": ..., "token": ...}`,
+/// as a C string that must be freed with circuit_free_string
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_execute_suspendable(
+    handle: u64,
+    graph_id: *const c_char,
+    inputs_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        let graph_id_str = match read_c_str(graph_id) {
+            Some(s) => s,
+            None => return std::ptr::null_mut(),
+        };
+
+        let inputs: HashMap<String, Value> = if inputs_json.is_null() {
+            HashMap::new()
+        } else {
+            match read_c_str(inputs_json).map(serde_json::from_str) {
+                Some(Ok(inputs)) => inputs,
+                Some(Err(e)) => {
+                    set_error(error_out, &format!("Failed to parse inputs: {}", e));
+                    return std::ptr::null_mut();
+                }
+                None => return std::ptr::null_mut(),
+            }
+        };
+
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return std::ptr::null_mut(),
+        };
+
+        match engine_arc.execute_suspendable(graph_id_str, &inputs, &ExecutionOptions::new()) {
+            Ok(outcome) => to_json_c_string(&outcome, error_out),
+            Err(e) => {
+                set_error(error_out, &format!("Execution failed: {}", e));
+                std::ptr::null_mut()
             }
         }
-    }
+    })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Resume a suspended execution with a JSON value.
+/// Returns the outcome like circuit_execute_suspendable
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_resume(
+    handle: u64,
+    execution_id: *const c_char,
+    token: *const c_char,
+    value_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        let (execution_id_str, token_str, value_str) = match (
+            read_c_str(execution_id),
+            read_c_str(token),
+            read_c_str(value_json),
+        ) {
+            (Some(e), Some(t), Some(v)) => (e, t, v),
+            _ => return std::ptr::null_mut(),
+        };
+
+        let value: Value = match serde_json::from_str(value_str) {
+            Ok(v) => v,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to parse value: {}", e));
+                return std::ptr::null_mut();
+            }
+        };
+
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return std::ptr::null_mut(),
+        };
+
+        match engine_arc.resume(execution_id_str, token_str, value) {
+            Ok(outcome) => to_json_c_string(&outcome, error_out),
+            Err(e) => {
+                set_error(error_out, &format!("Resume failed: {}", e));
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Get a suspended execution as JSON so the host can persist it.
+/// Returns NULL if there is no such execution; the string must be freed
+/// with circuit_free_string
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_suspended_execution(
+    handle: u64,
+    execution_id: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        let execution_id_str = match read_c_str(execution_id) {
+            Some(s) => s,
+            None => return std::ptr::null_mut(),
+        };
+
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return std::ptr::null_mut(),
+        };
+
+        match engine_arc.suspended_execution(execution_id_str) {
+            Some(execution) => to_json_c_string(&execution, error_out),
+            None => std::ptr::null_mut(),
+        }
+    })
+}
+
+/// Restore a suspended execution persisted earlier, e.g. after an app restart.
+/// Returns 0 on success, -1 on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_restore_suspended(
+    handle: u64,
+    execution_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> i32 {
+    guard(error_out, -1, || {
+        let json_str = match read_c_str(execution_json) {
+            Some(s) => s,
+            None => return -1,
+        };
+
+        let execution: SuspendedExecution = match serde_json::from_str(json_str) {
+            Ok(e) => e,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to parse execution: {}", e));
+                return -1;
+            }
+        };
+
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return -1,
+        };
+
+        match engine_arc.restore_suspended(execution) {
+            Ok(()) => 0,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to restore execution: {}", e));
+                -1
+            }
+        }
+    })
+}
+
+/// Capture loaded graphs, block versions, node state and variables as JSON,
+/// e.g. before the app is sent to the background.
+/// Returns a C string that must be freed with circuit_free_string
+#[no_mangle]
+pub extern "C" fn circuit_engine_snapshot(handle: u64, error_out: *mut *mut c_char) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return std::ptr::null_mut(),
+        };
+
+        to_json_c_string(&engine_arc.snapshot(), error_out)
+    })
+}
+
+/// Restore a snapshot produced by circuit_engine_snapshot. The block types
+/// it uses must be registered. Returns 0 on success, -1 on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_engine_restore(
+    handle: u64,
+    snapshot_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> i32 {
+    guard(error_out, -1, || {
+        let json_str = match read_c_str(snapshot_json) {
+            Some(s) => s,
+            None => return -1,
+        };
+
+        let snapshot = match EngineSnapshot::from_json(json_str) {
+            Ok(s) => s,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to parse snapshot: {}", e));
+                return -1;
+            }
+        };
+
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return -1,
+        };
+
+        match engine_arc.restore(snapshot) {
+            Ok(()) => 0,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to restore snapshot: {}", e));
+                -1
+            }
+        }
+    })
+}
+
+/// Execute a loaded graph and record it for replay. Returns the
+/// ExecutionRecording as JSON, including a node failure; null on error.
+/// The string must be freed with circuit_free_string
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_execute_recorded(
+    handle: u64,
+    graph_id: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        let graph_id_str = match read_c_str(graph_id) {
+            Some(s) => s,
+            None => return std::ptr::null_mut(),
+        };
+
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return std::ptr::null_mut(),
+        };
+
+        match engine_arc.execute_recorded(graph_id_str, &HashMap::new(), &ExecutionOptions::new()) {
+            Ok(recording) => to_json_c_string(&recording, error_out),
+            Err(e) => {
+                set_error(error_out, &format!("Execution failed: {}", e));
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Replay a recording produced by circuit_execute_recorded. Returns the
+/// ReplayReport as JSON, or null on error. The string must be freed with
+/// circuit_free_string
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_replay(
+    handle: u64,
+    recording_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        let json_str = match read_c_str(recording_json) {
+            Some(s) => s,
+            None => return std::ptr::null_mut(),
+        };
+
+        let recording = match ExecutionRecording::from_json(json_str) {
+            Ok(r) => r,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to parse recording: {}", e));
+                return std::ptr::null_mut();
+            }
+        };
+
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return std::ptr::null_mut(),
+        };
+
+        match engine_arc.replay(&recording, &ExecutionOptions::new()) {
+            Ok(report) => to_json_c_string(&report, error_out),
+            Err(e) => {
+                set_error(error_out, &format!("Replay failed: {}", e));
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Get the ports of a node of a loaded graph, resolved from its block and
+/// config, as NodePorts JSON. The string must be freed with
+/// circuit_free_string
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_node_ports(
+    handle: u64,
+    graph_id: *const c_char,
+    node_id: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        let (graph_id_str, node_id_str) = match (read_c_str(graph_id), read_c_str(node_id)) {
+            (Some(g), Some(n)) => (g, n),
+            _ => return std::ptr::null_mut(),
+        };
+
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return std::ptr::null_mut(),
+        };
+
+        match engine_arc.node_ports(graph_id_str, node_id_str) {
+            Ok(ports) => to_json_c_string(&ports, error_out),
+            Err(e) => {
+                set_error(error_out, &format!("Failed to resolve ports: {}", e));
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Get the ports a node of a block type would have with the given config
+/// JSON, e.g. while an editor changes the config. The string must be freed
+/// with circuit_free_string
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_block_ports(
+    handle: u64,
+    block_type: *const c_char,
+    config_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        let (block_type_str, config_str) = match (read_c_str(block_type), read_c_str(config_json)) {
+            (Some(b), Some(c)) => (b, c),
+            _ => return std::ptr::null_mut(),
+        };
+
+        let config: HashMap<String, Value> = match serde_json::from_str(config_str) {
+            Ok(c) => c,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to parse config: {}", e));
+                return std::ptr::null_mut();
+            }
+        };
+
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return std::ptr::null_mut(),
+        };
+
+        match engine_arc.block_ports(block_type_str, &config) {
+            Ok(ports) => to_json_c_string(&ports, error_out),
+            Err(e) => {
+                set_error(error_out, &format!("Failed to resolve ports: {}", e));
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Start a step-through debug session over a loaded graph.
+/// Nothing runs until the session is stepped or continued.
+/// Returns the session ID, or 0 on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_debug_start(
+    handle: u64,
+    graph_id: *const c_char,
+    error_out: *mut *mut c_char,
+) -> u64 {
+    guard(error_out, 0, || {
+        let graph_id_str = match read_c_str(graph_id) {
+            Some(s) => s,
+            None => return 0,
+        };
+
+        let engine_arc = match get_engine(handle, error_out) {
+            Some(e) => e,
+            None => return 0,
+        };
+
+        match engine_arc.debug(graph_id_str, &HashMap::new(), &ExecutionOptions::new()) {
+            Ok(session) => {
+                let mut next_id = lock(&NEXT_DEBUG_SESSION_ID);
+                let id = *next_id;
+                *next_id += 1;
+
+                lock(&DEBUG_SESSIONS).insert(id, (handle, Arc::new(Mutex::new(session))));
+                id
+            }
+            Err(e) => {
+                set_error(error_out, &format!("Failed to start debug session: {}", e));
+                0
+            }
+        }
+    })
+}
+
+/// End a debug session
+#[no_mangle]
+pub extern "C" fn circuit_debug_end(session: u64) {
+    guard(std::ptr::null_mut(), (), || {
+        lock(&DEBUG_SESSIONS).remove(&session);
+    })
+}
+
+/// Set a breakpoint from JSON such as
+/// `{"node_id": "add", "conditions": [{"port": "a", "op": "gt", "value": ...}]}`.
+/// Returns 0 on success, -1 on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_debug_add_breakpoint(
+    session: u64,
+    breakpoint_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> i32 {
+    guard(error_out, -1, || {
+        let json_str = match read_c_str(breakpoint_json) {
+            Some(s) => s,
+            None => return -1,
+        };
+
+        let breakpoint: Breakpoint = match serde_json::from_str(json_str) {
+            Ok(b) => b,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to parse breakpoint: {}", e));
+                return -1;
+            }
+        };
+
+        let session_arc = match get_debug_session(session, error_out) {
+            Some(s) => s,
+            None => return -1,
+        };
+
+        let result = lock(&session_arc).add_breakpoint(breakpoint);
+        match result {
+            Ok(()) => 0,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to set breakpoint: {}", e));
+                -1
+            }
+        }
+    })
+}
+
+/// Remove the breakpoint on a node.
+/// Returns 0 if it was removed, -1 if there was none
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_debug_remove_breakpoint(session: u64, node_id: *const c_char) -> i32 {
+    guard(std::ptr::null_mut(), -1, || {
+        let node_id_str = match read_c_str(node_id) {
+            Some(s) => s,
+            None => return -1,
+        };
+
+        match get_debug_session(session, std::ptr::null_mut()) {
+            Some(session_arc) if lock(&session_arc).remove_breakpoint(node_id_str) => 0,
+            _ => -1,
+        }
+    })
+}
+
+/// Run the paused node and stop before the next one.
+/// Returns the DebugStatus JSON, e.g. `{"status": "paused", "node_id": ...,
+/// "inputs": ..., "breakpoint": false}`, as a C string that must be freed
+/// with circuit_free_string
+#[no_mangle]
+pub extern "C" fn circuit_debug_step(session: u64, error_out: *mut *mut c_char) -> *mut c_char {
+    guard(
+        error_out,
+        std::ptr::null_mut(),
+        || match get_debug_session(session, error_out) {
+            Some(session_arc) => to_json_c_string(lock(&session_arc).step(), error_out),
+            None => std::ptr::null_mut(),
+        },
+    )
+}
+
+/// Run until a breakpoint matches, a node fails or the graph finishes.
+/// Returns the DebugStatus JSON like circuit_debug_step
+#[no_mangle]
+pub extern "C" fn circuit_debug_continue(session: u64, error_out: *mut *mut c_char) -> *mut c_char {
+    guard(
+        error_out,
+        std::ptr::null_mut(),
+        || match get_debug_session(session, error_out) {
+            Some(session_arc) => to_json_c_string(lock(&session_arc).resume(), error_out),
+            None => std::ptr::null_mut(),
+        },
+    )
+}
+
+/// Get the outputs of the nodes that ran so far as JSON.
+/// Returns a C string that must be freed with circuit_free_string
+#[no_mangle]
+pub extern "C" fn circuit_debug_outputs(session: u64, error_out: *mut *mut c_char) -> *mut c_char {
+    guard(
+        error_out,
+        std::ptr::null_mut(),
+        || match get_debug_session(session, error_out) {
+            Some(session_arc) => to_json_c_string(lock(&session_arc).outputs(), error_out),
+            None => std::ptr::null_mut(),
+        },
+    )
+}
+
+/// Change an input of the paused node to a JSON value before it runs.
+/// Returns 0 on success, -1 on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_debug_set_input(
+    session: u64,
+    port: *const c_char,
+    value_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> i32 {
+    guard(error_out, -1, || {
+        let port_str = match read_c_str(port) {
+            Some(s) => s,
+            None => return -1,
+        };
+
+        with_debug_value(session, value_json, error_out, |session, value| {
+            session.set_input(port_str, value)
+        })
+    })
+}
+
+/// Change an output of a node that already ran to a JSON value.
+/// Returns 0 on success, -1 on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_debug_set_output(
+    session: u64,
+    node_id: *const c_char,
+    port: *const c_char,
+    value_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> i32 {
+    guard(error_out, -1, || {
+        let (node_id_str, port_str) = match (read_c_str(node_id), read_c_str(port)) {
+            (Some(n), Some(p)) => (n, p),
+            _ => return -1,
+        };
+
+        with_debug_value(session, value_json, error_out, |session, value| {
+            session.set_output(node_id_str, port_str, value)
+        })
+    })
+}
+
+/// Start editing a graph given as JSON.
+/// Returns an editor ID, or 0 on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_editor_create(
+    graph_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> u64 {
+    guard(error_out, 0, || {
+        let graph = match parse_graph(graph_json, error_out) {
+            Some(g) => g,
+            None => return 0,
+        };
+
+        let mut next_id = lock(&NEXT_EDITOR_ID);
+        let id = *next_id;
+        *next_id += 1;
+        lock(&EDITORS).insert(id, Arc::new(Mutex::new(GraphEditor::new(graph))));
+        id
+    })
+}
+
+/// Stop editing and drop the editor's history
+#[no_mangle]
+pub extern "C" fn circuit_editor_destroy(editor: u64) {
+    guard(std::ptr::null_mut(), (), || {
+        lock(&EDITORS).remove(&editor);
+    })
+}
+
+/// Apply a GraphEdit given as JSON, e.g.
+/// `{"op": "move_node", "node_id": "add", "position": [10.0, 20.0]}`.
+/// Returns 0 on success, -1 on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_editor_apply(
+    editor: u64,
+    edit_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> i32 {
+    guard(error_out, -1, || {
+        let json_str = match read_c_str(edit_json) {
+            Some(s) => s,
+            None => return -1,
+        };
+
+        let edit: GraphEdit = match serde_json::from_str(json_str) {
+            Ok(e) => e,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to parse edit: {}", e));
+                return -1;
+            }
+        };
+
+        with_editor(editor, error_out, -1, |editor| {
+            editor_status(editor.apply(edit), error_out)
+        })
+    })
+}
+
+/// Group the following edits into one undo step. `label` may be null.
+/// Returns 0 on success, -1 on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_editor_begin_batch(
+    editor: u64,
+    label: *const c_char,
+    error_out: *mut *mut c_char,
+) -> i32 {
+    guard(error_out, -1, || {
+        let label = read_c_str(label).map(str::to_string);
+        with_editor(editor, error_out, -1, |editor| {
+            editor_status(editor.begin_batch(label), error_out)
+        })
+    })
+}
+
+/// Close the open batch, making its edits one undo step.
+/// Returns 0 on success, -1 on error
+#[no_mangle]
+pub extern "C" fn circuit_editor_commit_batch(editor: u64, error_out: *mut *mut c_char) -> i32 {
+    guard(error_out, -1, || {
+        with_editor(editor, error_out, -1, |editor| {
+            editor_status(editor.commit_batch(), error_out)
+        })
+    })
+}
+
+/// Close the open batch, reverting its edits.
+/// Returns 0 on success, -1 on error
+#[no_mangle]
+pub extern "C" fn circuit_editor_cancel_batch(editor: u64, error_out: *mut *mut c_char) -> i32 {
+    guard(error_out, -1, || {
+        with_editor(editor, error_out, -1, |editor| {
+            editor_status(editor.cancel_batch(), error_out)
+        })
+    })
+}
+
+/// Revert the latest undo step.
+/// Returns 1 if a step was undone, 0 if there was none, -1 on error
+#[no_mangle]
+pub extern "C" fn circuit_editor_undo(editor: u64, error_out: *mut *mut c_char) -> i32 {
+    guard(error_out, -1, || {
+        with_editor(editor, error_out, -1, |editor| {
+            history_status(editor.undo(), error_out)
+        })
+    })
+}
+
+/// Apply the latest undone step again.
+/// Returns 1 if a step was redone, 0 if there was none, -1 on error
+#[no_mangle]
+pub extern "C" fn circuit_editor_redo(editor: u64, error_out: *mut *mut c_char) -> i32 {
+    guard(error_out, -1, || {
+        with_editor(editor, error_out, -1, |editor| {
+            history_status(editor.redo(), error_out)
+        })
+    })
+}
+
+/// Get the edited graph as JSON, e.g. to pass to circuit_replace_graph.
+/// The string must be freed with circuit_free_string
+#[no_mangle]
+pub extern "C" fn circuit_editor_graph(editor: u64, error_out: *mut *mut c_char) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        with_editor(editor, error_out, std::ptr::null_mut(), |editor| {
+            to_json_c_string(editor.graph(), error_out)
+        })
+    })
+}
+
+/// Callback receiving an edit event as JSON, e.g.
+/// {"type": "undone", "label": "drag", "edits": [...]}. The string is only
+/// valid for the duration of the call.
+pub type CircuitEditCallback = extern "C" fn(event_json: *const c_char, user_data: *mut c_void);
+
+/// Register a callback invoked after every edit, undo and redo. It runs
+/// while the editor is locked and must not call back into the same editor.
+/// Returns a listener ID, or u64::MAX if the editor ID is invalid.
+#[no_mangle]
+pub extern "C" fn circuit_editor_add_listener(
+    editor: u64,
+    callback: CircuitEditCallback,
+    user_data: *mut c_void,
+) -> u64 {
+    guard(std::ptr::null_mut(), u64::MAX, || {
+        let user_data = UserData(user_data);
+        with_editor(editor, std::ptr::null_mut(), u64::MAX, |editor| {
+            editor.add_listener(Arc::new(move |event: &EditEvent| {
+                let json = serde_json::to_string(event)
+                    .ok()
+                    .and_then(|j| CString::new(j).ok());
+                if let Some(json) = json {
+                    callback(json.as_ptr(), user_data.as_ptr());
+                }
+            }))
+        })
+    })
+}
+
+/// Remove an edit listener.
+/// Returns 0 on success, -1 if the editor or listener ID is unknown.
+#[no_mangle]
+pub extern "C" fn circuit_editor_remove_listener(editor: u64, listener_id: u64) -> i32 {
+    guard(std::ptr::null_mut(), -1, || {
+        with_editor(editor, std::ptr::null_mut(), -1, |editor| {
+            if editor.remove_listener(listener_id) {
+                0
+            } else {
+                -1
+            }
+        })
+    })
+}
+
+/// Create a replica of a graph for collaborative editing. Every replica of a
+/// graph must start from the same graph and have a distinct `replica_id`.
+/// Returns a replica handle, or 0 on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_replica_create(
+    replica_id: *const c_char,
+    graph_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> u64 {
+    guard(error_out, 0, || {
+        let replica_id = match read_c_str(replica_id) {
+            Some(s) => s,
+            None => return 0,
+        };
+        let graph = match parse_graph(graph_json, error_out) {
+            Some(g) => g,
+            None => return 0,
+        };
+
+        let mut next_id = lock(&NEXT_REPLICA_ID);
+        let id = *next_id;
+        *next_id += 1;
+        lock(&REPLICAS).insert(id, Arc::new(Mutex::new(Replica::new(replica_id, graph))));
+        id
+    })
+}
+
+/// Destroy a replica
+#[no_mangle]
+pub extern "C" fn circuit_replica_destroy(replica: u64) {
+    guard(std::ptr::null_mut(), (), || {
+        lock(&REPLICAS).remove(&replica);
+    })
+}
+
+/// Apply a GraphEdit given as JSON to a replica. Returns the op to send to
+/// the other replicas as JSON, or null on error.
+/// The string must be freed with circuit_free_string
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_replica_edit(
+    replica: u64,
+    edit_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        let json_str = match read_c_str(edit_json) {
+            Some(s) => s,
+            None => return std::ptr::null_mut(),
+        };
+
+        let edit: GraphEdit = match serde_json::from_str(json_str) {
+            Ok(e) => e,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to parse edit: {}", e));
+                return std::ptr::null_mut();
+            }
+        };
+
+        with_replica(
+            replica,
+            error_out,
+            std::ptr::null_mut(),
+            |replica| match replica.edit(edit) {
+                Ok(op) => to_json_c_string(&op, error_out),
+                Err(e) => {
+                    set_error(error_out, &format!("Edit failed: {}", e));
+                    std::ptr::null_mut()
+                }
+            },
+        )
+    })
+}
+
+/// Merge a JSON array of ops from other replicas.
+/// Returns the number of ops not seen before, or -1 on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_replica_merge(
+    replica: u64,
+    ops_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> i64 {
+    guard(error_out, -1, || {
+        let json_str = match read_c_str(ops_json) {
+            Some(s) => s,
+            None => return -1,
+        };
+
+        let ops: Vec<GraphOp> = match serde_json::from_str(json_str) {
+            Ok(ops) => ops,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to parse ops: {}", e));
+                return -1;
+            }
+        };
+
+        with_replica(replica, error_out, -1, |replica| replica.merge(ops) as i64)
+    })
+}
+
+/// Get the merged graph of a replica as JSON.
+/// The string must be freed with circuit_free_string
+#[no_mangle]
+pub extern "C" fn circuit_replica_graph(replica: u64, error_out: *mut *mut c_char) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        with_replica(replica, error_out, std::ptr::null_mut(), |replica| {
+            to_json_c_string(replica.graph(), error_out)
+        })
+    })
+}
+
+/// Get the version vector of a replica as JSON, e.g. {"alice": 3, "bob": 5}.
+/// The string must be freed with circuit_free_string
+#[no_mangle]
+pub extern "C" fn circuit_replica_version(
+    replica: u64,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        with_replica(replica, error_out, std::ptr::null_mut(), |replica| {
+            to_json_c_string(&replica.version(), error_out)
+        })
+    })
+}
+
+/// Get the ops a replica at `version_json` has not seen, as a JSON array.
+/// Pass null for `version_json` to get every op.
+/// The string must be freed with circuit_free_string
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_replica_ops_since(
+    replica: u64,
+    version_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        let version = if version_json.is_null() {
+            Default::default()
+        } else {
+            let json_str = match read_c_str(version_json) {
+                Some(s) => s,
+                None => return std::ptr::null_mut(),
+            };
+            match serde_json::from_str(json_str) {
+                Ok(version) => version,
+                Err(e) => {
+                    set_error(error_out, &format!("Failed to parse version: {}", e));
+                    return std::ptr::null_mut();
+                }
+            }
+        };
+
+        with_replica(replica, error_out, std::ptr::null_mut(), |replica| {
+            to_json_c_string(&replica.ops_since(&version), error_out)
+        })
+    })
+}
+
+/// Compute the changes that turn graph A into graph B, both given as JSON.
+/// Returns GraphPatch JSON as a C string that must be freed with
+/// circuit_free_string, or null on error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_graph_diff(
+    a_json: *const c_char,
+    b_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        let (a, b) = match (
+            parse_graph(a_json, error_out),
+            parse_graph(b_json, error_out),
+        ) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return std::ptr::null_mut(),
+        };
+        to_json_c_string(&Graph::diff(&a, &b), error_out)
+    })
+}
+
+/// Apply GraphPatch JSON to a graph given as JSON.
+/// Returns the patched graph JSON as a C string that must be freed with
+/// circuit_free_string, or null on error; conflicts are listed in the error
+///
+/// # Safety
+/// This function is unsafe because it dereferences raw pointers from C.
+/// The caller must ensure the pointers are valid.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_graph_apply_patch(
+    graph_json: *const c_char,
+    patch_json: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut c_char {
+    guard(error_out, std::ptr::null_mut(), || {
+        let mut graph = match parse_graph(graph_json, error_out) {
+            Some(g) => g,
+            None => return std::ptr::null_mut(),
+        };
+
+        let patch_str = match read_c_str(patch_json) {
+            Some(s) => s,
+            None => return std::ptr::null_mut(),
+        };
+
+        let patch: GraphPatch = match serde_json::from_str(patch_str) {
+            Ok(p) => p,
+            Err(e) => {
+                set_error(error_out, &format!("Failed to parse patch: {}", e));
+                return std::ptr::null_mut();
+            }
+        };
+
+        match graph.apply_patch(&patch) {
+            Ok(()) => to_json_c_string(&graph, error_out),
+            Err(e) => {
+                set_error(error_out, &e.to_string());
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Free a string allocated by circuit_execute_graph
+///
+/// # Safety
+/// This function is unsafe because it takes ownership of a raw pointer.
+/// The caller must ensure the pointer was allocated by this library.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn circuit_free_string(s: *mut c_char) {
+    guard(std::ptr::null_mut(), (), || {
+        if !s.is_null() {
+            unsafe {
+                let _ = CString::from_raw(s);
+            }
+        }
+    })
+}
+
+// Run an exported function body, turning a panic into an error instead of
+// unwinding across the C boundary
+fn guard<T>(error_out: *mut *mut c_char, fallback: T, body: impl FnOnce() -> T) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(value) => value,
+        Err(payload) => {
+            set_error(
+                error_out,
+                &format!("Internal panic: {}", panic_message(payload.as_ref())),
+            );
+            fallback
+        }
+    }
+}
+
+// Lock a mutex, recovering the data if a previous holder panicked
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn engines() -> RwLockReadGuard<'static, HashMap<u64, Arc<Engine>>> {
+    ENGINES.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn engines_mut() -> RwLockWriteGuard<'static, HashMap<u64, Arc<Engine>>> {
+    ENGINES.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Parse a graph from a JSON C string and hand it to `apply`
+fn with_graph_json(
+    handle: u64,
+    json: *const c_char,
+    error_out: *mut *mut c_char,
+    apply: impl FnOnce(&Engine, &str) -> Result<(), String>,
+) -> i32 {
+    let json_str = match read_c_str(json) {
+        Some(s) => s,
+        None => return -1,
+    };
+
+    let engine_arc = match get_engine(handle, error_out) {
+        Some(e) => e,
+        None => return -1,
+    };
+
+    match apply(&engine_arc, json_str) {
+        Ok(()) => 0,
+        Err(message) => {
+            set_error(error_out, &message);
+            -1
+        }
+    }
+}
+
+// Look up an engine by handle, reporting an invalid handle through error_out
+fn get_engine(handle: u64, error_out: *mut *mut c_char) -> Option<Arc<Engine>> {
+    let engine = engines().get(&handle).map(Arc::clone);
+    if engine.is_none() {
+        set_error(error_out, "Invalid engine handle");
+    }
+    engine
+}
+
+// Look up a debug session, reporting an invalid ID through error_out
+fn get_debug_session(session: u64, error_out: *mut *mut c_char) -> Option<SharedDebugSession> {
+    let found = lock(&DEBUG_SESSIONS)
+        .get(&session)
+        .map(|(_, session)| Arc::clone(session));
+    if found.is_none() {
+        set_error(error_out, "Invalid debug session");
+    }
+    found
+}
+
+fn parse_graph(graph_json: *const c_char, error_out: *mut *mut c_char) -> Option<Graph> {
+    let json_str = read_c_str(graph_json)?;
+    match serde_json::from_str(json_str) {
+        Ok(graph) => Some(graph),
+        Err(e) => {
+            set_error(error_out, &format!("Failed to parse graph: {}", e));
+            None
+        }
+    }
+}
+
+// Run `apply` with the editor locked
+fn with_editor<T>(
+    editor: u64,
+    error_out: *mut *mut c_char,
+    fallback: T,
+    apply: impl FnOnce(&mut GraphEditor) -> T,
+) -> T {
+    let found = lock(&EDITORS).get(&editor).map(Arc::clone);
+    match found {
+        Some(editor_arc) => apply(&mut lock(&editor_arc)),
+        None => {
+            set_error(error_out, "Invalid editor");
+            fallback
+        }
+    }
+}
+
+// Run `apply` with the replica locked
+fn with_replica<T>(
+    replica: u64,
+    error_out: *mut *mut c_char,
+    fallback: T,
+    apply: impl FnOnce(&mut Replica) -> T,
+) -> T {
+    let found = lock(&REPLICAS).get(&replica).map(Arc::clone);
+    match found {
+        Some(replica_arc) => apply(&mut lock(&replica_arc)),
+        None => {
+            set_error(error_out, "Invalid replica");
+            fallback
+        }
+    }
+}
+
+fn editor_status(result: circuit_core::Result<()>, error_out: *mut *mut c_char) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            set_error(error_out, &format!("Edit failed: {}", e));
+            -1
+        }
+    }
+}
+
+fn history_status(result: circuit_core::Result<bool>, error_out: *mut *mut c_char) -> i32 {
+    match result {
+        Ok(changed) => i32::from(changed),
+        Err(e) => {
+            set_error(error_out, &format!("Edit failed: {}", e));
+            -1
+        }
+    }
+}
+
+// Parse a JSON value and hand it to `apply` with the debug session locked
+fn with_debug_value(
+    session: u64,
+    value_json: *const c_char,
+    error_out: *mut *mut c_char,
+    apply: impl FnOnce(&mut DebugSession, Value) -> circuit_core::Result<()>,
+) -> i32 {
+    let value_str = match read_c_str(value_json) {
+        Some(s) => s,
+        None => return -1,
+    };
+
+    let value: Value = match serde_json::from_str(value_str) {
+        Ok(v) => v,
+        Err(e) => {
+            set_error(error_out, &format!("Failed to parse value: {}", e));
+            return -1;
+        }
+    };
+
+    let session_arc = match get_debug_session(session, error_out) {
+        Some(s) => s,
+        None => return -1,
+    };
+
+    let result = apply(&mut lock(&session_arc), value);
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            set_error(error_out, &format!("Debug session: {}", e));
+            -1
+        }
+    }
+}
+
+// Borrow a UTF-8 string from a C pointer
+fn read_c_str<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(ptr) }.to_str().ok()
+}
+
+// Serialize a value into a newly allocated C string
+fn to_json_c_string<T: serde::Serialize>(value: &T, error_out: *mut *mut c_char) -> *mut c_char {
+    let json = match serde_json::to_string(value) {
+        Ok(j) => j,
+        Err(e) => {
+            set_error(error_out, &format!("Failed to serialize results: {}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    match CString::new(json) {
+        Ok(c_str) => c_str.into_raw(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+// Helper function to set error message
+fn set_error(error_out: *mut *mut c_char, message: &str) {
+    if !error_out.is_null() {
+        if let Ok(c_str) = CString::new(message) {
+            unsafe {
+                *error_out = c_str.into_raw();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engine_lifecycle() {
+        let handle = circuit_engine_create();
+        circuit_engine_destroy(handle);
+    }
+
+    #[test]
+    fn test_execute_batch_jsonl() {
+        let handle = circuit_engine_create();
+        let graph_json = CString::new(
+            r#"{"id": "empty", "name": "Empty", "description": null, "nodes": {}, "connections": []}"#,
+        )
+        .unwrap();
+        let mut error: *mut c_char = std::ptr::null_mut();
+        assert_eq!(
+            circuit_load_graph(handle, graph_json.as_ptr(), &mut error),
+            0
+        );
+
+        let graph_id = CString::new("empty").unwrap();
+        let rows =
+            CString::new("{}\n{\"missing.port\": {\"type\": \"Int\", \"value\": 1}}\n").unwrap();
+        let result =
+            circuit_execute_batch_jsonl(handle, graph_id.as_ptr(), rows.as_ptr(), &mut error);
+        assert!(!result.is_null());
+        let output = unsafe { CStr::from_ptr(result) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        circuit_free_string(result);
+
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines,
+            vec![r#"{"ok":{}}"#, r#"{"error":"Node not found: missing"}"#]
+        );
+
+        circuit_engine_destroy(handle);
+    }
+
+    #[test]
+    fn test_cancel_before_execution() {
+        let handle = circuit_engine_create();
+        let graph_json = CString::new(
+            r#"{"id": "empty", "name": "Empty", "description": null, "nodes": {}, "connections": []}"#,
+        )
+        .unwrap();
+        let mut error: *mut c_char = std::ptr::null_mut();
+        assert_eq!(
+            circuit_load_graph(handle, graph_json.as_ptr(), &mut error),
+            0
+        );
+
+        let execution_id = circuit_execution_create(handle);
+        assert_ne!(execution_id, 0);
+        assert_eq!(circuit_cancel(handle, execution_id), 0);
+
+        let graph_id = CString::new("empty").unwrap();
+        let result = circuit_execute_graph_with_options(
+            handle,
+            graph_id.as_ptr(),
+            execution_id,
+            0,
+            0,
+            &mut error,
+        );
+        assert!(result.is_null());
+        assert!(!error.is_null());
+        let message = unsafe { CStr::from_ptr(error) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(message.contains("cancelled"));
+        circuit_free_string(error);
+
+        // The execution ID is released once the run finishes
+        assert_eq!(circuit_cancel(handle, execution_id), -1);
+        assert_eq!(circuit_execution_free(handle, execution_id), -1);
+
+        // IDs that are never run are released by the host
+        let unused = circuit_execution_create(handle);
+        assert_eq!(circuit_execution_free(handle, unused), 0);
+        assert_eq!(circuit_cancel(handle, unused), -1);
+        circuit_engine_destroy(handle);
+    }
+
+    #[test]
+    fn test_set_limits() {
+        let handle = circuit_engine_create();
+        let mut error: *mut c_char = std::ptr::null_mut();
+
+        let limits = CString::new(r#"{"max_nodes": 10, "max_string_len": 64}"#).unwrap();
+        assert_eq!(
+            circuit_engine_set_limits(handle, limits.as_ptr(), &mut error),
+            0
+        );
+        let engine = engines().get(&handle).map(Arc::clone).unwrap();
+        assert_eq!(engine.limits().max_nodes, Some(10));
+        assert_eq!(engine.limits().max_connections, None);
+
+        let invalid = CString::new("not json").unwrap();
+        assert_eq!(
+            circuit_engine_set_limits(handle, invalid.as_ptr(), &mut error),
+            -1
+        );
+        assert!(!error.is_null());
+        circuit_free_string(error);
+        circuit_engine_destroy(handle);
+    }
+
+    #[test]
+    fn test_guard_converts_panic_to_error() {
+        let mut error: *mut c_char = std::ptr::null_mut();
+        let result = guard(&mut error, -1, || -> i32 { panic!("boom") });
+        assert_eq!(result, -1);
+
+        let message = unsafe { CStr::from_ptr(error) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(message, "Internal panic: boom");
+        circuit_free_string(error);
+    }
+
+    #[test]
+    fn test_concurrent_calls_on_one_engine() {
+        let handle = circuit_engine_create();
+        let graph_json = CString::new(
+            r#"{"id": "g", "name": "G", "description": null, "nodes": {}, "connections": []}"#,
+        )
+        .unwrap();
+        let mut error: *mut c_char = std::ptr::null_mut();
+        assert_eq!(
+            circuit_load_graph(handle, graph_json.as_ptr(), &mut error),
+            0
+        );
+
+        let workers: Vec<_> = (0..8)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    let mut error: *mut c_char = std::ptr::null_mut();
+                    if i % 2 == 0 {
+                        // Replace the graph while other threads execute it
+                        let graph_json = CString::new(
+                            r#"{"id": "g", "name": "G", "description": null, "nodes": {}, "connections": []}"#,
+                        )
+                        .unwrap();
+                        assert_eq!(
+                            circuit_replace_graph(handle, graph_json.as_ptr(), &mut error),
+                            0
+                        );
+                    } else {
+                        let graph_id = CString::new("g").unwrap();
+                        let result = circuit_execute_graph(handle, graph_id.as_ptr(), &mut error);
+                        assert!(!result.is_null());
+                        circuit_free_string(result);
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        circuit_engine_destroy(handle);
+    }
+
+    extern "C" fn count_event(event_json: *const c_char, user_data: *mut c_void) {
+        let json = unsafe { CStr::from_ptr(event_json) }.to_str().unwrap();
+        assert!(json.contains("\"graph_id\":\"g\""));
+        let counter = unsafe { &*(user_data as *const std::sync::atomic::AtomicUsize) };
+        counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_graph_lifecycle_and_listener() {
+        let handle = circuit_engine_create();
+        let counter = std::sync::atomic::AtomicUsize::new(0);
+        let user_data = &counter as *const _ as *mut c_void;
+        assert_eq!(
+            circuit_add_lifecycle_listener(handle, None, user_data),
+            u64::MAX
+        );
+        let listener = circuit_add_lifecycle_listener(handle, Some(count_event), user_data);
+        assert_ne!(listener, u64::MAX);
+
+        let graph_json = CString::new(
+            r#"{"id": "g", "name": "G", "description": null, "nodes": {},
+                "connections": []}"#,
+        )
+        .unwrap();
+        let graph_id = CString::new("g").unwrap();
+        let mut error: *mut c_char = std::ptr::null_mut();
+        assert_eq!(
+            circuit_replace_graph(handle, graph_json.as_ptr(), &mut error),
+            -1
+        );
+        circuit_free_string(error);
+        error = std::ptr::null_mut();
+
+        assert_eq!(
+            circuit_load_graph(handle, graph_json.as_ptr(), &mut error),
+            0
+        );
+        // Loading again overwrites, strict loading refuses
+        assert_eq!(
+            circuit_load_graph(handle, graph_json.as_ptr(), &mut error),
+            0
+        );
+        assert_eq!(
+            circuit_load_new_graph(handle, graph_json.as_ptr(), &mut error),
+            -1
+        );
+        circuit_free_string(error);
+        error = std::ptr::null_mut();
+        assert_eq!(
+            circuit_replace_graph(handle, graph_json.as_ptr(), &mut error),
+            0
+        );
+        assert_eq!(
+            circuit_unload_graph(handle, graph_id.as_ptr(), &mut error),
+            0
+        );
+        assert_eq!(
+            circuit_unload_graph(handle, graph_id.as_ptr(), &mut error),
+            -1
+        );
+        circuit_free_string(error);
+
+        assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 4);
+        assert_eq!(circuit_remove_lifecycle_listener(handle, listener), 0);
+        assert_eq!(circuit_remove_lifecycle_listener(handle, listener), -1);
+        circuit_engine_destroy(handle);
+    }
+
+    extern "C" fn count_edit(event_json: *const c_char, user_data: *mut c_void) {
+        let json = unsafe { CStr::from_ptr(event_json) }.to_str().unwrap();
+        assert!(json.contains("\"label\":\"add\""));
+        let counter = unsafe { &*(user_data as *const std::sync::atomic::AtomicUsize) };
+        counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_graph_editor() {
+        let mut error: *mut c_char = std::ptr::null_mut();
+        let graph_json = CString::new(
+            r#"{"id": "g", "name": "G", "description": null, "nodes": {}, "connections": []}"#,
+        )
+        .unwrap();
+        let editor = circuit_editor_create(graph_json.as_ptr(), &mut error);
+        assert_ne!(editor, 0);
+        let counter = std::sync::atomic::AtomicUsize::new(0);
+        let listener =
+            circuit_editor_add_listener(editor, count_edit, &counter as *const _ as *mut c_void);
+        assert_ne!(listener, u64::MAX);
+
+        let label = CString::new("add").unwrap();
+        assert_eq!(
+            circuit_editor_begin_batch(editor, label.as_ptr(), &mut error),
+            0
+        );
+        let add = CString::new(
+            r#"{"op": "add_node", "node": {"id": "n", "block_type": "core.constant",
+                "config": {}, "position": null}}"#,
+        )
+        .unwrap();
+        let rename =
+            CString::new(r#"{"op": "rename_node", "node_id": "n", "new_id": "m"}"#).unwrap();
+        assert_eq!(circuit_editor_apply(editor, add.as_ptr(), &mut error), 0);
+        assert_eq!(circuit_editor_apply(editor, rename.as_ptr(), &mut error), 0);
+        assert_eq!(circuit_editor_commit_batch(editor, &mut error), 0);
+
+        let graph = |error: &mut *mut c_char| {
+            let json = circuit_editor_graph(editor, error);
+            let graph: Graph =
+                serde_json::from_str(unsafe { CStr::from_ptr(json) }.to_str().unwrap()).unwrap();
+            circuit_free_string(json);
+            graph
+        };
+        assert!(graph(&mut error).nodes.contains_key("m"));
+        assert_eq!(circuit_editor_undo(editor, &mut error), 1);
+        assert!(graph(&mut error).nodes.is_empty());
+        assert_eq!(circuit_editor_undo(editor, &mut error), 0);
+        assert_eq!(circuit_editor_redo(editor, &mut error), 1);
+        assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 4);
+
+        assert_eq!(
+            circuit_editor_apply(editor, rename.as_ptr(), &mut error),
+            -1
+        );
+        circuit_free_string(error);
+        assert_eq!(circuit_editor_remove_listener(editor, listener), 0);
+        circuit_editor_destroy(editor);
+        error = std::ptr::null_mut();
+        assert_eq!(circuit_editor_undo(editor, &mut error), -1);
+        circuit_free_string(error);
+    }
+
+    #[test]
+    fn test_graph_diff_and_apply_patch() {
+        let mut error: *mut c_char = std::ptr::null_mut();
+        let a = CString::new(
+            r#"{"id": "g", "name": "G", "description": null, "nodes": {}, "connections": []}"#,
+        )
+        .unwrap();
+        let b = CString::new(
+            r#"{"id": "g", "name": "Renamed", "description": null, "nodes": {}, "connections": []}"#,
+        )
+        .unwrap();
+        let patch = circuit_graph_diff(a.as_ptr(), b.as_ptr(), &mut error);
+        assert!(!patch.is_null());
+        let patched = circuit_graph_apply_patch(a.as_ptr(), patch, &mut error);
+        assert!(!patched.is_null());
+        let graph: Graph =
+            serde_json::from_str(unsafe { CStr::from_ptr(patched) }.to_str().unwrap()).unwrap();
+        assert_eq!(graph.name, "Renamed");
+        circuit_free_string(patched);
+
+        // The patch expects the name "G"
+        let other = CString::new(
+            r#"{"id": "g", "name": "Other", "description": null, "nodes": {}, "connections": []}"#,
+        )
+        .unwrap();
+        assert!(circuit_graph_apply_patch(other.as_ptr(), patch, &mut error).is_null());
+        let message = unsafe { CStr::from_ptr(error) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(message.contains("name: Value was changed"), "{}", message);
+        circuit_free_string(error);
+        circuit_free_string(patch);
+    }
+
+    #[test]
+    fn test_replicas_merge() {
+        let mut error: *mut c_char = std::ptr::null_mut();
+        let graph_json = CString::new(
+            r#"{"id": "g", "name": "G", "description": null, "nodes": {}, "connections": []}"#,
+        )
+        .unwrap();
+        let alice_id = CString::new("alice").unwrap();
+        let bob_id = CString::new("bob").unwrap();
+        let alice = circuit_replica_create(alice_id.as_ptr(), graph_json.as_ptr(), &mut error);
+        let bob = circuit_replica_create(bob_id.as_ptr(), graph_json.as_ptr(), &mut error);
+        assert_ne!(alice, 0);
+        assert_ne!(bob, 0);
+
+        let add = CString::new(
+            r#"{"op": "add_node", "node": {"id": "n", "block_type": "core.constant",
+                "config": {}, "position": null}}"#,
+        )
+        .unwrap();
+        let op = circuit_replica_edit(alice, add.as_ptr(), &mut error);
+        assert!(!op.is_null());
+        let ops = format!("[{}]", unsafe { CStr::from_ptr(op) }.to_str().unwrap());
+        circuit_free_string(op);
+        let ops = CString::new(ops).unwrap();
+        assert_eq!(circuit_replica_merge(bob, ops.as_ptr(), &mut error), 1);
+        assert_eq!(circuit_replica_merge(bob, ops.as_ptr(), &mut error), 0);
+
+        let graph = circuit_replica_graph(bob, &mut error);
+        let json = unsafe { CStr::from_ptr(graph) }.to_str().unwrap();
+        assert!(serde_json::from_str::<Graph>(json)
+            .unwrap()
+            .nodes
+            .contains_key("n"));
+        circuit_free_string(graph);
+
+        let version = circuit_replica_version(bob, &mut error);
+        let missing = circuit_replica_ops_since(alice, version, &mut error);
+        assert_eq!(unsafe { CStr::from_ptr(missing) }.to_str().unwrap(), "[]");
+        circuit_free_string(missing);
+        circuit_free_string(version);
+
+        // The node already exists
+        assert!(circuit_replica_edit(bob, add.as_ptr(), &mut error).is_null());
+        circuit_free_string(error);
+        circuit_replica_destroy(alice);
+        circuit_replica_destroy(bob);
+        error = std::ptr::null_mut();
+        assert!(circuit_replica_graph(bob, &mut error).is_null());
+        circuit_free_string(error);
+    }
+
+    #[test]
+    fn test_migrate_graph_with_aliases() {
+        let handle = circuit_engine_create();
+        let mut error: *mut c_char = std::ptr::null_mut();
+        let (plus, add) = (
+            CString::new("math.plus").unwrap(),
+            CString::new("math.add").unwrap(),
+        );
+        let (total, result) = (
+            CString::new("total").unwrap(),
+            CString::new("result").unwrap(),
+        );
+        assert_eq!(
+            circuit_add_block_alias(handle, plus.as_ptr(), add.as_ptr(), &mut error),
+            0
+        );
+        assert_eq!(
+            circuit_add_port_alias(
+                handle,
+                add.as_ptr(),
+                total.as_ptr(),
+                result.as_ptr(),
+                &mut error
+            ),
+            0
+        );
+        assert_eq!(
+            circuit_add_block_alias(handle, add.as_ptr(), plus.as_ptr(), &mut error),
+            -1
+        );
+        circuit_free_string(error);
+        error = std::ptr::null_mut();
+
+        let graph_json = CString::new(
+            r#"{"id": "g", "name": "G", "description": null, "connections": [],
+                "nodes": {"n": {"id": "n", "block_type": "math.plus", "config": {}, "position": null}},
+                "outputs": [{"node": "n", "port": "total"}]}"#,
+        )
+        .unwrap();
+        let migrated = circuit_migrate_graph(handle, graph_json.as_ptr(), &mut error);
+        assert!(!migrated.is_null());
+        let json: serde_json::Value =
+            serde_json::from_str(unsafe { CStr::from_ptr(migrated) }.to_str().unwrap()).unwrap();
+        circuit_free_string(migrated);
+        assert_eq!(json["graph"]["format_version"], 1);
+        assert_eq!(json["graph"]["nodes"]["n"]["block_type"], "math.add");
+        assert_eq!(json["graph"]["outputs"][0]["port"], "result");
+        assert_eq!(json["report"]["from_version"], 0);
+        assert_eq!(json["report"]["changes"].as_array().unwrap().len(), 2);
+
+        let invalid = CString::new("[]").unwrap();
+        assert!(circuit_migrate_graph(handle, invalid.as_ptr(), &mut error).is_null());
+        circuit_free_string(error);
+        circuit_engine_destroy(handle);
+    }
+
+    #[test]
+    fn test_execute_graph_report() {
+        let handle = circuit_engine_create();
+        let graph_json = CString::new(
+            r#"{"id": "g", "name": "G", "description": null, "nodes": {}, "connections": []}"#,
+        )
+        .unwrap();
+        let mut error: *mut c_char = std::ptr::null_mut();
+        assert_eq!(
+            circuit_load_graph(handle, graph_json.as_ptr(), &mut error),
+            0
+        );
+
+        let graph_id = CString::new("g").unwrap();
+        let result = circuit_execute_graph_report(handle, graph_id.as_ptr(), &mut error);
+        assert!(!result.is_null());
+        let json = unsafe { CStr::from_ptr(result) }.to_str().unwrap();
+        let report: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(report["graph_id"], "g");
+        assert!(report["execution_id"].is_string());
+        assert!(report.get("error").is_none());
+        circuit_free_string(result);
+
+        let missing = CString::new("missing").unwrap();
+        assert!(circuit_execute_graph_report(handle, missing.as_ptr(), &mut error).is_null());
+        circuit_free_string(error);
+        circuit_engine_destroy(handle);
+    }
+
+    #[test]
+    fn test_restore_and_resume_suspended_execution() {
+        let handle = circuit_engine_create();
+        let mut error: *mut c_char = std::ptr::null_mut();
+        let graph_json = CString::new(
+            r#"{"id": "flow", "name": "Flow", "description": null, "nodes": {}, "connections": []}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            circuit_load_graph(handle, graph_json.as_ptr(), &mut error),
+            0
+        );
+
+        let state = CString::new(
+            r#"{"execution_id": "run-1", "graph_id": "flow", "node_id": "approve", "token": "approval", "completed": [], "outputs": {}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            circuit_restore_suspended(handle, state.as_ptr(), &mut error),
+            0
+        );
+        let execution_id = CString::new("run-1").unwrap();
+        let persisted = circuit_suspended_execution(handle, execution_id.as_ptr(), &mut error);
+        assert!(!persisted.is_null());
+        circuit_free_string(persisted);
+
+        let token = CString::new("approval").unwrap();
+        let value = CString::new(r#"{"type": "Bool", "value": true}"#).unwrap();
+        let result = circuit_resume(
+            handle,
+            execution_id.as_ptr(),
+            token.as_ptr(),
+            value.as_ptr(),
+            &mut error,
+        );
+        assert!(!result.is_null());
+        let json = unsafe { CStr::from_ptr(result) }.to_str().unwrap();
+        let outcome: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(outcome["status"], "completed");
+        circuit_free_string(result);
+
+        // The execution is gone once it completed
+        assert!(circuit_resume(
+            handle,
+            execution_id.as_ptr(),
+            token.as_ptr(),
+            value.as_ptr(),
+            &mut error
+        )
+        .is_null());
+        circuit_free_string(error);
+        error = std::ptr::null_mut();
+
+        let graph_id = CString::new("flow").unwrap();
+        let result =
+            circuit_execute_suspendable(handle, graph_id.as_ptr(), std::ptr::null(), &mut error);
+        assert!(!result.is_null());
+        circuit_free_string(result);
+        let bad_inputs = CString::new("[1]").unwrap();
+        assert!(circuit_execute_suspendable(
+            handle,
+            graph_id.as_ptr(),
+            bad_inputs.as_ptr(),
+            &mut error
+        )
+        .is_null());
+        assert!(unsafe { CStr::from_ptr(error) }
+            .to_str()
+            .unwrap()
+            .contains("Failed to parse inputs"));
+        circuit_free_string(error);
+        circuit_engine_destroy(handle);
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let handle = circuit_engine_create();
+        let mut error: *mut c_char = std::ptr::null_mut();
+        let graph_json = CString::new(
+            r#"{"id": "saved", "name": "Saved", "description": null, "nodes": {}, "connections": []}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            circuit_load_graph(handle, graph_json.as_ptr(), &mut error),
+            0
+        );
+        let snapshot = circuit_engine_snapshot(handle, &mut error);
+        assert!(!snapshot.is_null());
+        circuit_engine_destroy(handle);
+
+        let restored = circuit_engine_create();
+        assert_eq!(circuit_engine_restore(restored, snapshot, &mut error), 0);
+        assert_eq!(
+            engines()[&restored].list_graphs(),
+            vec!["saved".to_string()]
+        );
+        circuit_free_string(snapshot);
+
+        let invalid = CString::new(r#"{"format_version": 99}"#).unwrap();
+        assert_eq!(
+            circuit_engine_restore(restored, invalid.as_ptr(), &mut error),
+            -1
+        );
+        circuit_free_string(error);
+        circuit_engine_destroy(restored);
+    }
+
+    #[test]
+    fn test_execute_recorded_and_replay() {
+        let handle = circuit_engine_create();
+        engines()[&handle]
+            .register_block(Arc::new(circuit_core::blocks::ConstantBlock))
+            .unwrap();
+        let mut error: *mut c_char = std::ptr::null_mut();
+        let graph_json = CString::new(
+            r#"{"id": "one", "name": "One", "description": null,
+                "nodes": {
+                    "one": {"id": "one", "block_type": "core.constant", "position": null,
+                            "config": {"value": {"type": "Float", "value": 1.0}}}
+                },
+                "connections": []}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            circuit_load_graph(handle, graph_json.as_ptr(), &mut error),
+            0
+        );
+
+        let graph_id = CString::new("one").unwrap();
+        let recording = circuit_execute_recorded(handle, graph_id.as_ptr(), &mut error);
+        assert!(!recording.is_null());
+        let report = circuit_replay(handle, recording, &mut error);
+        circuit_free_string(recording);
+        assert!(!report.is_null());
+        let json: serde_json::Value =
+            serde_json::from_str(unsafe { CStr::from_ptr(report) }.to_str().unwrap()).unwrap();
+        circuit_free_string(report);
+        assert!(json.get("divergence").is_none());
+        assert_eq!(json["outputs"]["one"]["value"]["value"], 1.0);
+
+        let invalid = CString::new("{}").unwrap();
+        assert!(circuit_replay(handle, invalid.as_ptr(), &mut error).is_null());
+        circuit_free_string(error);
+        circuit_engine_destroy(handle);
+    }
+
+    #[test]
+    fn test_block_ports_follow_config() {
+        let handle = circuit_engine_create();
+        engines()[&handle]
+            .register_block(Arc::new(circuit_core::blocks::SwitchBlock))
+            .unwrap();
+        let mut error: *mut c_char = std::ptr::null_mut();
+
+        let block_type = CString::new("control.switch").unwrap();
+        let config = CString::new(
+            r#"{"cases": {"type": "Array", "value": [{"type": "String", "value": "red"}]}}"#,
+        )
+        .unwrap();
+        let result = circuit_block_ports(handle, block_type.as_ptr(), config.as_ptr(), &mut error);
+        assert!(!result.is_null());
+        let ports: circuit_core::NodePorts =
+            serde_json::from_str(unsafe { CStr::from_ptr(result) }.to_str().unwrap()).unwrap();
+        circuit_free_string(result);
+        let ids: Vec<&str> = ports.inputs.iter().map(|port| port.id.as_str()).collect();
+        assert_eq!(ids, vec!["selector", "red", "default"]);
+
+        let graph_id = CString::new("missing").unwrap();
+        let node_id = CString::new("n").unwrap();
+        let result = circuit_node_ports(handle, graph_id.as_ptr(), node_id.as_ptr(), &mut error);
+        assert!(result.is_null());
+        circuit_free_string(error);
+        circuit_engine_destroy(handle);
+    }
+
+    #[test]
+    fn test_debug_session() {
+        let handle = circuit_engine_create();
+        engines()[&handle]
+            .register_block(Arc::new(circuit_core::blocks::ConstantBlock))
+            .unwrap();
+        engines()[&handle]
+            .register_block(Arc::new(circuit_core::blocks::NegateBlock))
+            .unwrap();
+        let graph_json = CString::new(
+            r#"{"id": "neg", "name": "Neg", "description": null,
+                "nodes": {
+                    "two": {"id": "two", "block_type": "core.constant", "position": null,
+                            "config": {"value": {"type": "Float", "value": 2.0}}},
+                    "neg": {"id": "neg", "block_type": "math.negate", "position": null, "config": {}}
+                },
+                "connections": [{"from_node": "two", "from_port": "value", "to_node": "neg", "to_port": "value"}]}"#,
+        )
+        .unwrap();
+        let mut error: *mut c_char = std::ptr::null_mut();
+        assert_eq!(
+            circuit_load_graph(handle, graph_json.as_ptr(), &mut error),
+            0
+        );
+
+        let graph_id = CString::new("neg").unwrap();
+        let session = circuit_debug_start(handle, graph_id.as_ptr(), &mut error);
+        assert_ne!(session, 0);
+        let breakpoint = CString::new(r#"{"node_id": "neg"}"#).unwrap();
+        assert_eq!(
+            circuit_debug_add_breakpoint(session, breakpoint.as_ptr(), &mut error),
+            0
+        );
+
+        let status = |result: *mut c_char| {
+            let json: serde_json::Value =
+                serde_json::from_str(unsafe { CStr::from_ptr(result) }.to_str().unwrap()).unwrap();
+            circuit_free_string(result);
+            json
+        };
+        let paused = status(circuit_debug_continue(session, &mut error));
+        assert_eq!(paused["status"], "paused");
+        assert_eq!(paused["node_id"], "neg");
+
+        let port = CString::new("value").unwrap();
+        let value = CString::new(r#"{"type": "Float", "value": 5.0}"#).unwrap();
+        assert_eq!(
+            circuit_debug_set_input(session, port.as_ptr(), value.as_ptr(), &mut error),
+            0
+        );
+        let finished = status(circuit_debug_continue(session, &mut error));
+        assert_eq!(finished["status"], "finished");
+        let outputs = status(circuit_debug_outputs(session, &mut error));
+        assert_eq!(outputs["neg"]["result"]["value"], -5.0);
 
-    #[test]
-    fn test_engine_lifecycle() {
-        let handle = circuit_engine_create();
         circuit_engine_destroy(handle);
+        assert!(circuit_debug_step(session, &mut error).is_null());
+        circuit_free_string(error);
     }
 }
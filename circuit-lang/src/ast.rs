@@ -158,6 +158,15 @@ pub struct NodeDef {
     pub block_type: String,
     pub config: HashMap<String, Value>,
     pub position: Option<(f64, f64)>,
+    /// Forward inputs to outputs instead of running the block
+    #[serde(default)]
+    pub bypass: bool,
+    /// Emit null outputs instead of running the block
+    #[serde(default)]
+    pub mute: bool,
+    /// Fixed output values, keyed by port
+    #[serde(default)]
+    pub pinned: HashMap<String, Value>,
 }
 
 /// Connection between ports
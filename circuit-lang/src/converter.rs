@@ -2,9 +2,10 @@
 
 use crate::ast::*;
 use crate::{LangError, Result};
-use circuit_core::graph::{Connection, Graph, Node};
+use circuit_core::graph::{Connection, Graph, Node, NodeMode};
 use circuit_core::Value as CoreValue;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Convert a FlowDef to a Graph
 pub fn flow_to_graph(flow: &FlowDef) -> Result<Graph> {
@@ -29,18 +30,36 @@ pub fn flow_to_graph(flow: &FlowDef) -> Result<Graph> {
             .map_err(|e| LangError::ValidationError(format!("Failed to add connection: {}", e)))?;
     }
 
+    // Declare graph outputs
+    for output in &flow.outputs {
+        graph
+            .add_output(&output.node, &output.port)
+            .map_err(|e| LangError::ValidationError(format!("Failed to add output: {}", e)))?;
+    }
+
     Ok(graph)
 }
 
 fn node_def_to_node(node_def: &NodeDef) -> Result<Node> {
     let config = convert_value_map(&node_def.config)?;
+    let mode = match (node_def.bypass, node_def.mute, node_def.pinned.is_empty()) {
+        (false, false, true) => NodeMode::Normal,
+        (true, false, true) => NodeMode::Bypass,
+        (false, true, true) => NodeMode::Mute,
+        (false, false, false) => NodeMode::Pinned(convert_value_map(&node_def.pinned)?),
+        _ => {
+            return Err(LangError::ValidationError(format!(
+                "Node '{}' can only use one of bypass, mute and pin",
+                node_def.id
+            )))
+        }
+    };
 
-    Ok(Node {
-        id: node_def.id.clone(),
-        block_type: node_def.block_type.clone(),
-        config,
-        position: node_def.position,
-    })
+    let mut node = Node::new(node_def.id.clone(), node_def.block_type.clone())
+        .with_config(config)
+        .with_mode(mode);
+    node.position = node_def.position;
+    Ok(node)
 }
 
 fn connection_def_to_connection(conn_def: &ConnectionDef) -> Connection {
@@ -71,14 +90,14 @@ fn value_to_core_value(value: &Value) -> Result<CoreValue> {
             for item in arr {
                 result.push(value_to_core_value(item)?);
             }
-            Ok(CoreValue::Array(result))
+            Ok(CoreValue::Array(Arc::new(result)))
         }
         Value::Object(obj) => {
             let mut result = HashMap::new();
             for (key, val) in obj {
                 result.insert(key.clone(), value_to_core_value(val)?);
             }
-            Ok(CoreValue::Object(result))
+            Ok(CoreValue::Object(Arc::new(result)))
         }
     }
 }
@@ -114,6 +133,27 @@ mod tests {
 
         assert_eq!(graph.nodes.len(), 3);
         assert_eq!(graph.connections.len(), 2);
+        assert!(graph.outputs.is_empty());
+    }
+
+    #[test]
+    fn test_convert_declared_outputs() {
+        let source = r#"
+            flow outputs {
+                node n1: core.constant {
+                    value = 1
+                }
+
+                output n1.value
+            }
+        "#;
+
+        let flow = parse_flow(source).expect("Failed to parse");
+        let graph = flow_to_graph(&flow).expect("Failed to convert");
+
+        assert_eq!(graph.outputs.len(), 1);
+        assert_eq!(graph.outputs[0].node, "n1");
+        assert_eq!(graph.outputs[0].port, "value");
     }
 
     #[test]
@@ -158,4 +198,45 @@ mod tests {
         );
         assert_eq!(node.config.get("bool"), Some(&CoreValue::Bool(true)));
     }
+
+    #[test]
+    fn test_convert_node_modes() {
+        let source = r#"
+            flow debug {
+                node a: test.block {
+                    bypass
+                }
+                node b: test.block {
+                    mute
+                }
+                node c: test.block {
+                    pin value = 5
+                    bypass_count = 1
+                }
+            }
+        "#;
+
+        let flow = parse_flow(source).expect("Failed to parse");
+        let graph = flow_to_graph(&flow).expect("Failed to convert");
+
+        assert_eq!(graph.nodes["a"].mode, NodeMode::Bypass);
+        assert_eq!(graph.nodes["b"].mode, NodeMode::Mute);
+        let pinned = HashMap::from([("value".to_string(), CoreValue::Float(5.0))]);
+        assert_eq!(graph.nodes["c"].mode, NodeMode::Pinned(pinned));
+        assert_eq!(
+            graph.nodes["c"].config.get("bypass_count"),
+            Some(&CoreValue::Float(1.0))
+        );
+
+        let conflicting = r#"
+            flow conflicting {
+                node a: test.block {
+                    mute
+                    pin value = 5
+                }
+            }
+        "#;
+        let flow = parse_flow(conflicting).expect("Failed to parse");
+        assert!(flow_to_graph(&flow).is_err());
+    }
 }
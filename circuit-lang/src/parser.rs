@@ -529,12 +529,16 @@ fn parse_flow_body(pair: pest::iterators::Pair<Rule>, flow_def: &mut FlowDef) ->
     Ok(())
 }
 
+#[allow(clippy::collapsible_match)]
 fn parse_node_def(pair: pest::iterators::Pair<Rule>) -> Result<NodeDef> {
     let mut node_def = NodeDef {
         id: String::new(),
         block_type: String::new(),
         config: HashMap::new(),
         position: None,
+        bypass: false,
+        mute: false,
+        pinned: HashMap::new(),
     };
 
     let mut is_id = true;
@@ -557,6 +561,14 @@ fn parse_node_def(pair: pest::iterators::Pair<Rule>) -> Result<NodeDef> {
                             let (key, val) = parse_config_assign(body_item)?;
                             node_def.config.insert(key, val);
                         }
+                        Rule::pin_stmt => {
+                            let (port, val) = parse_config_assign(body_item)?;
+                            node_def.pinned.insert(port, val);
+                        }
+                        Rule::mode_stmt => match body_item.as_str() {
+                            "bypass" => node_def.bypass = true,
+                            _ => node_def.mute = true,
+                        },
                         Rule::position_stmt => {
                             node_def.position = Some(parse_position(body_item)?);
                         }
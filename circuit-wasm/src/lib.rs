@@ -1,13 +1,31 @@
-use circuit_core::{Engine, Graph};
-use std::sync::{Arc, Mutex};
+#[cfg(target_arch = "wasm32")]
+use circuit_core::{
+    async_block::BlockFuture,
+    context::{Clock, TimerFuture},
+    AsyncBlock, BlockContext, BlockMetadata,
+};
+use circuit_core::{
+    batch::{results_to_jsonl, rows_from_jsonl},
+    Breakpoint, CircuitError, DebugSession, EditEvent, Engine, EngineLimits, EngineSnapshot,
+    ExecutionContext, ExecutionOptions, ExecutionRecording, Graph, GraphEdit, GraphEditor, GraphOp,
+    GraphPatch, LifecycleEvent, Replica, SuspendedExecution, Value,
+};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError};
 use wasm_bindgen::prelude::*;
 
 /// WASM wrapper for the Circuit engine
 #[wasm_bindgen]
 pub struct WasmEngine {
-    engine: Arc<Mutex<Engine>>,
+    engine: Arc<Engine>,
+    /// Lifecycle events not yet taken by JavaScript, oldest dropped first
+    /// beyond `MAX_LIFECYCLE_EVENTS`
+    events: Arc<Mutex<VecDeque<LifecycleEvent>>>,
 }
 
+/// Lifecycle events kept for a host that never takes them
+const MAX_LIFECYCLE_EVENTS: usize = 1024;
+
 impl Default for WasmEngine {
     fn default() -> Self {
         Self::new()
@@ -19,22 +37,113 @@ impl WasmEngine {
     /// Create a new WASM engine instance
     #[wasm_bindgen(constructor)]
     pub fn new() -> WasmEngine {
-        WasmEngine {
-            engine: Arc::new(Mutex::new(Engine::new())),
-        }
+        let engine = Arc::new(Engine::with_limits(wasm_limits(EngineLimits::new())));
+        let events = Arc::new(Mutex::new(VecDeque::new()));
+
+        let queue = Arc::clone(&events);
+        engine.add_lifecycle_listener(Arc::new(move |event: &LifecycleEvent| {
+            let mut queue = queue.lock().unwrap_or_else(PoisonError::into_inner);
+            if queue.len() == MAX_LIFECYCLE_EVENTS {
+                queue.pop_front();
+            }
+            queue.push_back(event.clone());
+        }));
+
+        WasmEngine { engine, events }
     }
 
-    /// Load a graph from JSON
+    /// Set resource limits from a JSON object such as `{"max_nodes": 500}`
+    ///
+    /// `max_retry_delay_ms` is always 0, see `wasm_limits`.
+    #[wasm_bindgen(js_name = setLimits)]
+    pub fn set_limits(&self, limits_json: &str) -> Result<(), JsValue> {
+        let limits: EngineLimits = serde_json::from_str(limits_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse limits: {}", e)))?;
+        self.engine.set_limits(wasm_limits(limits));
+        Ok(())
+    }
+
+    /// Load a graph from JSON, returning the MigrationReport as JSON
+    ///
+    /// A loaded graph with the same ID is overwritten.
     #[wasm_bindgen(js_name = loadGraph)]
-    pub fn load_graph(&mut self, graph_json: &str) -> Result<(), JsValue> {
-        let graph: Graph = serde_json::from_str(graph_json)
-            .map_err(|e| JsValue::from_str(&format!("Failed to parse graph: {}", e)))?;
+    pub fn load_graph(&self, graph_json: &str) -> Result<String, JsValue> {
+        let report = self
+            .engine
+            .load_graph_json(graph_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to load graph: {}", e)))?;
+        to_json(&report)
+    }
+
+    /// Load a graph from JSON like `loadGraph`, failing if a graph with the
+    /// same ID is already loaded
+    #[wasm_bindgen(js_name = loadNewGraph)]
+    pub fn load_new_graph(&self, graph_json: &str) -> Result<String, JsValue> {
+        let report = self
+            .engine
+            .load_new_graph_json(graph_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to load graph: {}", e)))?;
+        to_json(&report)
+    }
 
+    /// Replace a loaded graph with a new version from JSON, returning the
+    /// MigrationReport as JSON
+    #[wasm_bindgen(js_name = replaceGraph)]
+    pub fn replace_graph(&self, graph_json: &str) -> Result<String, JsValue> {
+        let report = self
+            .engine
+            .replace_graph_json(graph_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to replace graph: {}", e)))?;
+        to_json(&report)
+    }
+
+    /// Upgrade a graph document and resolve aliases without loading it,
+    /// returning `{"graph": ..., "report": ...}` as JSON
+    #[wasm_bindgen(js_name = migrateGraph)]
+    pub fn migrate_graph(&self, graph_json: &str) -> Result<String, JsValue> {
+        let (graph, report) = self
+            .engine
+            .migrate_graph(graph_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to migrate graph: {}", e)))?;
+        to_json(&serde_json::json!({ "graph": graph, "report": report }))
+    }
+
+    /// Resolve block type `alias` to `block_id` in graphs loaded from now on
+    #[wasm_bindgen(js_name = addBlockAlias)]
+    pub fn add_block_alias(&self, alias: &str, block_id: &str) -> Result<(), JsValue> {
         self.engine
-            .lock()
-            .unwrap()
-            .load_graph(graph)
-            .map_err(|e| JsValue::from_str(&format!("Failed to load graph: {}", e)))
+            .add_block_alias(alias, block_id)
+            .map_err(|e| JsValue::from_str(&format!("Failed to add alias: {}", e)))
+    }
+
+    /// Resolve port `alias` of block type `block_id` to `port` in graphs
+    /// loaded from now on
+    #[wasm_bindgen(js_name = addPortAlias)]
+    pub fn add_port_alias(&self, block_id: &str, alias: &str, port: &str) -> Result<(), JsValue> {
+        self.engine
+            .add_port_alias(block_id, alias, port)
+            .map_err(|e| JsValue::from_str(&format!("Failed to add alias: {}", e)))
+    }
+
+    /// Unload a graph by ID
+    #[wasm_bindgen(js_name = unloadGraph)]
+    pub fn unload_graph(&self, graph_id: &str) -> Result<(), JsValue> {
+        self.engine
+            .unload_graph(graph_id)
+            .map(|_| ())
+            .map_err(|e| JsValue::from_str(&format!("Failed to unload graph: {}", e)))
+    }
+
+    /// Take the lifecycle events recorded since the last call as a JSON array
+    ///
+    /// Only the last 1024 events are kept between calls.
+    #[wasm_bindgen(js_name = takeLifecycleEvents)]
+    pub fn take_lifecycle_events(&self) -> Result<String, JsValue> {
+        let events =
+            std::mem::take(&mut *self.events.lock().unwrap_or_else(PoisonError::into_inner));
+
+        serde_json::to_string(&events)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize events: {}", e)))
     }
 
     /// Execute a graph by ID and return results as JSON
@@ -42,8 +151,6 @@ impl WasmEngine {
     pub fn execute_graph(&self, graph_id: &str) -> Result<String, JsValue> {
         let results = self
             .engine
-            .lock()
-            .unwrap()
             .execute_graph(graph_id)
             .map_err(|e| JsValue::from_str(&format!("Execution failed: {}", e)))?;
 
@@ -51,17 +158,622 @@ impl WasmEngine {
             .map_err(|e| JsValue::from_str(&format!("Failed to serialize results: {}", e)))
     }
 
+    /// Execute a graph by ID and return a structured execution report as JSON
+    ///
+    /// Failures during the run are described in the report rather than thrown.
+    #[wasm_bindgen(js_name = executeGraphReport)]
+    pub fn execute_graph_report(&self, graph_id: &str) -> Result<String, JsValue> {
+        let options = ExecutionOptions::new().with_context(default_context());
+        let report = self
+            .engine
+            .execute_graph_report(graph_id, &options)
+            .map_err(|e| JsValue::from_str(&format!("Execution failed: {}", e)))?;
+
+        serde_json::to_string(&report)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize report: {}", e)))
+    }
+
+    /// Execute a graph by ID, aborting after `max_node_executions` nodes or
+    /// `timeout_ms` milliseconds; 0 means unlimited, as in the FFI
+    ///
+    /// The timeout is measured with `Date.now()` and checked between nodes.
+    #[wasm_bindgen(js_name = executeGraphWithLimits)]
+    pub fn execute_graph_with_limits(
+        &self,
+        graph_id: &str,
+        max_node_executions: u32,
+        timeout_ms: u32,
+    ) -> Result<String, JsValue> {
+        let mut options = ExecutionOptions::new().with_context(default_context());
+        if max_node_executions > 0 {
+            options = options.with_max_node_executions(max_node_executions as usize);
+        }
+        if timeout_ms > 0 {
+            options = options.with_timeout(std::time::Duration::from_millis(u64::from(timeout_ms)));
+        }
+        let results = self
+            .engine
+            .execute_graph_with_options(graph_id, &options)
+            .map_err(|e| JsValue::from_str(&format!("Execution failed: {}", e)))?;
+
+        serde_json::to_string(&results)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize results: {}", e)))
+    }
+
+    /// Execute a graph once per JSON-lines input row
+    ///
+    /// Each line of `rows_jsonl` maps "node.port" to a value. Returns one JSON
+    /// line per row, in row order: `{"ok": outputs}` or `{"error": message}`.
+    #[wasm_bindgen(js_name = executeBatchJsonl)]
+    pub fn execute_batch_jsonl(&self, graph_id: &str, rows_jsonl: &str) -> Result<String, JsValue> {
+        let rows = rows_from_jsonl(rows_jsonl)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse rows: {}", e)))?;
+        let results = self
+            .engine
+            .execute_batch(graph_id, rows)
+            .map_err(|e| JsValue::from_str(&format!("Execution failed: {}", e)))?;
+
+        Ok(results_to_jsonl(&results))
+    }
+
+    /// Execute a graph, awaiting async blocks; resolves to the outputs as JSON
+    #[wasm_bindgen(js_name = executeGraphAsync)]
+    pub fn execute_graph_async(&self, graph_id: String) -> js_sys::Promise {
+        let engine = Arc::clone(&self.engine);
+        wasm_bindgen_futures::future_to_promise(async move {
+            let options = ExecutionOptions::new().with_context(default_context());
+            let results = engine
+                .execute_graph_async(&graph_id, &options)
+                .await
+                .map_err(|e| JsValue::from_str(&format!("Execution failed: {}", e)))?;
+
+            serde_json::to_string(&results)
+                .map(|json| JsValue::from_str(&json))
+                .map_err(|e| JsValue::from_str(&format!("Failed to serialize results: {}", e)))
+        })
+    }
+
+    /// Execute a graph that may suspend to await an external event
+    ///
+    /// `inputs_json` maps `"node.port"` to a value, e.g. `{}` for no inputs.
+    /// Returns `{"status": "completed", "outputs": ...}` or the suspended
+    /// execution as `{"status": "suspended", "execution_id": ..., "token": ...}`.
+    #[wasm_bindgen(js_name = executeSuspendable)]
+    pub fn execute_suspendable(
+        &self,
+        graph_id: &str,
+        inputs_json: &str,
+    ) -> Result<String, JsValue> {
+        let inputs: HashMap<String, Value> = serde_json::from_str(inputs_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse inputs: {}", e)))?;
+        let options = ExecutionOptions::new().with_context(default_context());
+        let outcome = self
+            .engine
+            .execute_suspendable(graph_id, &inputs, &options)
+            .map_err(|e| JsValue::from_str(&format!("Execution failed: {}", e)))?;
+
+        serde_json::to_string(&outcome)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize results: {}", e)))
+    }
+
+    /// Resume a suspended execution with a JSON value
+    #[wasm_bindgen(js_name = resume)]
+    pub fn resume(
+        &self,
+        execution_id: &str,
+        token: &str,
+        value_json: &str,
+    ) -> Result<String, JsValue> {
+        let value: Value = serde_json::from_str(value_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse value: {}", e)))?;
+        let options = ExecutionOptions::new().with_context(default_context());
+        let outcome = self
+            .engine
+            .resume_with_options(execution_id, token, value, &options)
+            .map_err(|e| JsValue::from_str(&format!("Resume failed: {}", e)))?;
+
+        serde_json::to_string(&outcome)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize results: {}", e)))
+    }
+
+    /// Get the ports of a node, resolved from its block and config, as JSON
+    #[wasm_bindgen(js_name = nodePorts)]
+    pub fn node_ports(&self, graph_id: &str, node_id: &str) -> Result<String, JsValue> {
+        let ports = self
+            .engine
+            .node_ports(graph_id, node_id)
+            .map_err(|e| JsValue::from_str(&format!("Failed to resolve ports: {}", e)))?;
+        serde_json::to_string(&ports)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize ports: {}", e)))
+    }
+
+    /// Get the ports a node of `block_type` would have with the given config
+    #[wasm_bindgen(js_name = blockPorts)]
+    pub fn block_ports(&self, block_type: &str, config_json: &str) -> Result<String, JsValue> {
+        let config: HashMap<String, Value> = serde_json::from_str(config_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse config: {}", e)))?;
+        let ports = self
+            .engine
+            .block_ports(block_type, &config)
+            .map_err(|e| JsValue::from_str(&format!("Failed to resolve ports: {}", e)))?;
+        serde_json::to_string(&ports)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize ports: {}", e)))
+    }
+
+    /// Start a step-through debug session over a loaded graph
+    #[wasm_bindgen(js_name = debug)]
+    pub fn debug(&self, graph_id: &str) -> Result<WasmDebugSession, JsValue> {
+        let options = ExecutionOptions::new().with_context(default_context());
+        let session = self
+            .engine
+            .debug(graph_id, &HashMap::new(), &options)
+            .map_err(|e| JsValue::from_str(&format!("Failed to start debug session: {}", e)))?;
+        Ok(WasmDebugSession { session })
+    }
+
+    /// Execute a loaded graph and return the recording as JSON, e.g. to
+    /// save it as a replay file; a node failure is part of the recording
+    #[wasm_bindgen(js_name = executeRecorded)]
+    pub fn execute_recorded(&self, graph_id: &str) -> Result<String, JsValue> {
+        let options = ExecutionOptions::new().with_context(default_context());
+        let recording = self
+            .engine
+            .execute_recorded(graph_id, &HashMap::new(), &options)
+            .map_err(|e| JsValue::from_str(&format!("Execution failed: {}", e)))?;
+        to_json(&recording)
+    }
+
+    /// Replay a recording and return the replay report as JSON
+    #[wasm_bindgen(js_name = replay)]
+    pub fn replay(&self, recording_json: &str) -> Result<String, JsValue> {
+        let recording = ExecutionRecording::from_json(recording_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse recording: {}", e)))?;
+        let options = ExecutionOptions::new().with_context(default_context());
+        let report = self
+            .engine
+            .replay(&recording, &options)
+            .map_err(|e| JsValue::from_str(&format!("Replay failed: {}", e)))?;
+        to_json(&report)
+    }
+
+    /// Get a suspended execution as JSON, e.g. to persist it
+    #[wasm_bindgen(js_name = suspendedExecution)]
+    pub fn suspended_execution(&self, execution_id: &str) -> Option<String> {
+        self.engine
+            .suspended_execution(execution_id)
+            .and_then(|execution| serde_json::to_string(&execution).ok())
+    }
+
+    /// Restore a suspended execution persisted earlier
+    #[wasm_bindgen(js_name = restoreSuspended)]
+    pub fn restore_suspended(&self, execution_json: &str) -> Result<(), JsValue> {
+        let execution: SuspendedExecution = serde_json::from_str(execution_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse execution: {}", e)))?;
+        self.engine
+            .restore_suspended(execution)
+            .map_err(|e| JsValue::from_str(&format!("Failed to restore execution: {}", e)))
+    }
+
+    /// Capture loaded graphs, block versions, node state and variables as JSON
+    #[wasm_bindgen(js_name = snapshot)]
+    pub fn snapshot(&self) -> Result<String, JsValue> {
+        self.engine
+            .snapshot()
+            .to_json()
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize snapshot: {}", e)))
+    }
+
+    /// Restore a snapshot; the blocks it uses must be registered
+    #[wasm_bindgen(js_name = restore)]
+    pub fn restore(&self, snapshot_json: &str) -> Result<(), JsValue> {
+        let snapshot = EngineSnapshot::from_json(snapshot_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse snapshot: {}", e)))?;
+        self.engine
+            .restore(snapshot)
+            .map_err(|e| JsValue::from_str(&format!("Failed to restore snapshot: {}", e)))
+    }
+
     /// List registered block types
     #[wasm_bindgen(js_name = listBlocks)]
     pub fn list_blocks(&self) -> Vec<String> {
-        self.engine.lock().unwrap().list_blocks()
+        self.engine.list_blocks()
     }
 
     /// List loaded graphs
     #[wasm_bindgen(js_name = listGraphs)]
     pub fn list_graphs(&self) -> Vec<String> {
-        self.engine.lock().unwrap().list_graphs()
+        self.engine.list_graphs()
+    }
+}
+
+/// Step-through debug session, created by `WasmEngine.debug`
+#[wasm_bindgen]
+pub struct WasmDebugSession {
+    session: DebugSession,
+}
+
+#[wasm_bindgen]
+impl WasmDebugSession {
+    /// Set a breakpoint from JSON such as `{"node_id": "add", "conditions": [...]}`
+    #[wasm_bindgen(js_name = addBreakpoint)]
+    pub fn add_breakpoint(&mut self, breakpoint_json: &str) -> Result<(), JsValue> {
+        let breakpoint: Breakpoint = serde_json::from_str(breakpoint_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse breakpoint: {}", e)))?;
+        self.session
+            .add_breakpoint(breakpoint)
+            .map_err(|e| JsValue::from_str(&format!("Failed to set breakpoint: {}", e)))
+    }
+
+    /// Remove the breakpoint on a node, returning whether there was one
+    #[wasm_bindgen(js_name = removeBreakpoint)]
+    pub fn remove_breakpoint(&mut self, node_id: &str) -> bool {
+        self.session.remove_breakpoint(node_id)
+    }
+
+    /// Run the paused node and stop before the next one; returns the status JSON
+    #[wasm_bindgen(js_name = step)]
+    pub fn step(&mut self) -> Result<String, JsValue> {
+        to_json(self.session.step())
+    }
+
+    /// Run until a breakpoint matches, a node fails or the graph finishes
+    #[wasm_bindgen(js_name = resume)]
+    pub fn resume(&mut self) -> Result<String, JsValue> {
+        to_json(self.session.resume())
+    }
+
+    /// Get the status JSON without running anything
+    #[wasm_bindgen(js_name = status)]
+    pub fn status(&self) -> Result<String, JsValue> {
+        to_json(self.session.status())
+    }
+
+    /// Get the outputs of the nodes that ran so far as JSON
+    #[wasm_bindgen(js_name = outputs)]
+    pub fn outputs(&self) -> Result<String, JsValue> {
+        to_json(self.session.outputs())
+    }
+
+    /// Change an input of the paused node to a JSON value before it runs
+    #[wasm_bindgen(js_name = setInput)]
+    pub fn set_input(&mut self, port: &str, value_json: &str) -> Result<(), JsValue> {
+        let value = parse_value(value_json)?;
+        self.session
+            .set_input(port, value)
+            .map_err(|e| JsValue::from_str(&format!("Debug session: {}", e)))
+    }
+
+    /// Change an output of a node that already ran to a JSON value
+    #[wasm_bindgen(js_name = setOutput)]
+    pub fn set_output(
+        &mut self,
+        node_id: &str,
+        port: &str,
+        value_json: &str,
+    ) -> Result<(), JsValue> {
+        let value = parse_value(value_json)?;
+        self.session
+            .set_output(node_id, port, value)
+            .map_err(|e| JsValue::from_str(&format!("Debug session: {}", e)))
+    }
+}
+
+/// Graph editor with undo/redo history, shared with the native editors
+#[wasm_bindgen]
+pub struct WasmGraphEditor {
+    editor: GraphEditor,
+    /// Edit events not yet taken by JavaScript
+    events: Arc<Mutex<Vec<EditEvent>>>,
+}
+
+#[wasm_bindgen]
+impl WasmGraphEditor {
+    /// Start editing a graph given as JSON
+    #[wasm_bindgen(constructor)]
+    pub fn new(graph_json: &str) -> Result<WasmGraphEditor, JsValue> {
+        let graph: Graph = serde_json::from_str(graph_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse graph: {}", e)))?;
+        let mut editor = GraphEditor::new(graph);
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        let queue = Arc::clone(&events);
+        editor.add_listener(Arc::new(move |event: &EditEvent| {
+            queue
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(event.clone());
+        }));
+
+        Ok(WasmGraphEditor { editor, events })
+    }
+
+    /// Apply an edit given as JSON, e.g. `{"op": "remove_node", "node_id": "add"}`
+    #[wasm_bindgen(js_name = apply)]
+    pub fn apply(&mut self, edit_json: &str) -> Result<(), JsValue> {
+        let edit: GraphEdit = serde_json::from_str(edit_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse edit: {}", e)))?;
+        self.editor.apply(edit).map_err(edit_error)
+    }
+
+    /// Group the following edits into one undo step
+    #[wasm_bindgen(js_name = beginBatch)]
+    pub fn begin_batch(&mut self, label: Option<String>) -> Result<(), JsValue> {
+        self.editor.begin_batch(label).map_err(edit_error)
+    }
+
+    /// Close the open batch, making its edits one undo step
+    #[wasm_bindgen(js_name = commitBatch)]
+    pub fn commit_batch(&mut self) -> Result<(), JsValue> {
+        self.editor.commit_batch().map_err(edit_error)
+    }
+
+    /// Close the open batch, reverting its edits
+    #[wasm_bindgen(js_name = cancelBatch)]
+    pub fn cancel_batch(&mut self) -> Result<(), JsValue> {
+        self.editor.cancel_batch().map_err(edit_error)
+    }
+
+    /// Revert the latest undo step; returns false when there is none
+    #[wasm_bindgen(js_name = undo)]
+    pub fn undo(&mut self) -> Result<bool, JsValue> {
+        self.editor.undo().map_err(edit_error)
+    }
+
+    /// Apply the latest undone step again; returns false when there is none
+    #[wasm_bindgen(js_name = redo)]
+    pub fn redo(&mut self) -> Result<bool, JsValue> {
+        self.editor.redo().map_err(edit_error)
+    }
+
+    #[wasm_bindgen(js_name = canUndo)]
+    pub fn can_undo(&self) -> bool {
+        self.editor.can_undo()
+    }
+
+    #[wasm_bindgen(js_name = canRedo)]
+    pub fn can_redo(&self) -> bool {
+        self.editor.can_redo()
+    }
+
+    /// Get the edited graph as JSON, e.g. to pass to `WasmEngine.replaceGraph`
+    #[wasm_bindgen(js_name = graph)]
+    pub fn graph(&self) -> Result<String, JsValue> {
+        to_json(self.editor.graph())
+    }
+
+    /// Take the edit events recorded since the last call as a JSON array
+    #[wasm_bindgen(js_name = takeEvents)]
+    pub fn take_events(&self) -> Result<String, JsValue> {
+        let events =
+            std::mem::take(&mut *self.events.lock().unwrap_or_else(PoisonError::into_inner));
+        to_json(&events)
+    }
+}
+
+/// Replica of a graph edited collaboratively; JavaScript carries the ops
+/// between replicas
+#[wasm_bindgen]
+pub struct WasmReplica {
+    replica: Replica,
+}
+
+#[wasm_bindgen]
+impl WasmReplica {
+    /// Create a replica of a graph given as JSON. Every replica of a graph
+    /// must start from the same graph and have a distinct `replica_id`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(replica_id: &str, graph_json: &str) -> Result<WasmReplica, JsValue> {
+        let graph = parse_graph(graph_json)?;
+        Ok(WasmReplica {
+            replica: Replica::new(replica_id, graph),
+        })
     }
+
+    /// Apply an edit given as JSON, returning the op to send to the other
+    /// replicas as JSON
+    #[wasm_bindgen(js_name = edit)]
+    pub fn edit(&mut self, edit_json: &str) -> Result<String, JsValue> {
+        let edit: GraphEdit = serde_json::from_str(edit_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse edit: {}", e)))?;
+        let op = self.replica.edit(edit).map_err(edit_error)?;
+        to_json(&op)
+    }
+
+    /// Merge a JSON array of ops from other replicas; returns how many were new
+    #[wasm_bindgen(js_name = merge)]
+    pub fn merge(&mut self, ops_json: &str) -> Result<usize, JsValue> {
+        let ops: Vec<GraphOp> = serde_json::from_str(ops_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse ops: {}", e)))?;
+        Ok(self.replica.merge(ops))
+    }
+
+    /// Get the merged graph as JSON
+    #[wasm_bindgen(js_name = graph)]
+    pub fn graph(&self) -> Result<String, JsValue> {
+        to_json(self.replica.graph())
+    }
+
+    /// Get the version vector as JSON, e.g. `{"alice": 3, "bob": 5}`
+    #[wasm_bindgen(js_name = version)]
+    pub fn version(&self) -> Result<String, JsValue> {
+        to_json(&self.replica.version())
+    }
+
+    /// Get the ops a replica at `version_json` has not seen; every op when
+    /// no version is given
+    #[wasm_bindgen(js_name = opsSince)]
+    pub fn ops_since(&self, version_json: Option<String>) -> Result<String, JsValue> {
+        let version = match version_json {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| JsValue::from_str(&format!("Failed to parse version: {}", e)))?,
+            None => Default::default(),
+        };
+        to_json(&self.replica.ops_since(&version))
+    }
+
+    /// Get the IDs of the ops skipped by the merge as JSON
+    #[wasm_bindgen(js_name = rejected)]
+    pub fn rejected(&self) -> Result<String, JsValue> {
+        to_json(&self.replica.rejected())
+    }
+}
+
+fn edit_error(e: CircuitError) -> JsValue {
+    JsValue::from_str(&format!("Edit failed: {}", e))
+}
+
+fn parse_value(value_json: &str) -> Result<Value, JsValue> {
+    serde_json::from_str(value_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse value: {}", e)))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, JsValue> {
+    serde_json::to_string(value)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize results: {}", e)))
+}
+
+// The browser cannot block between retries, so graphs whose retry policies
+// wait are rejected at load time
+fn wasm_limits(mut limits: EngineLimits) -> EngineLimits {
+    limits.max_retry_delay = Some(std::time::Duration::ZERO);
+    limits
+}
+
+// Only wasm32 can call into JavaScript
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+impl WasmEngine {
+    /// Register a block implemented by a JavaScript function
+    ///
+    /// `callback(inputsJson, configJson)` returns a string, or a promise of
+    /// one, holding the JSON object of output values.
+    #[wasm_bindgen(js_name = registerAsyncBlock)]
+    pub fn register_async_block(
+        &self,
+        metadata_json: &str,
+        callback: js_sys::Function,
+    ) -> Result<(), JsValue> {
+        let metadata: BlockMetadata = serde_json::from_str(metadata_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse metadata: {}", e)))?;
+        self.engine
+            .register_async_block(Arc::new(JsAsyncBlock { metadata, callback }))
+            .map_err(|e| JsValue::from_str(&format!("Failed to register block: {}", e)))
+    }
+}
+
+/// Async block backed by a JavaScript function
+#[cfg(target_arch = "wasm32")]
+struct JsAsyncBlock {
+    metadata: BlockMetadata,
+    callback: js_sys::Function,
+}
+
+// wasm32 without threads runs everything on one thread, so the JavaScript
+// function is never touched from another thread
+#[cfg(target_arch = "wasm32")]
+unsafe impl Send for JsAsyncBlock {}
+#[cfg(target_arch = "wasm32")]
+unsafe impl Sync for JsAsyncBlock {}
+
+#[cfg(target_arch = "wasm32")]
+impl AsyncBlock for JsAsyncBlock {
+    fn metadata(&self) -> BlockMetadata {
+        self.metadata.clone()
+    }
+
+    fn execute_async(&self, context: BlockContext) -> BlockFuture<'_> {
+        Box::pin(async move {
+            let inputs = serde_json::to_string(&context.inputs)?;
+            let config = serde_json::to_string(&context.config)?;
+            let returned = self
+                .callback
+                .call2(
+                    &JsValue::NULL,
+                    &JsValue::from_str(&inputs),
+                    &JsValue::from_str(&config),
+                )
+                .map_err(|e| CircuitError::BlockExecution(format!("{:?}", e)))?;
+            let resolved =
+                wasm_bindgen_futures::JsFuture::from(js_sys::Promise::resolve(&returned))
+                    .await
+                    .map_err(|e| CircuitError::BlockExecution(format!("{:?}", e)))?;
+            let json = resolved.as_string().ok_or_else(|| {
+                CircuitError::BlockExecution(
+                    "Async block must resolve to a JSON string".to_string(),
+                )
+            })?;
+            Ok::<HashMap<String, Value>, CircuitError>(serde_json::from_str(&json)?)
+        })
+    }
+}
+
+/// Clock backed by JavaScript's `Date.now()`; `SystemTime` is unavailable on wasm32
+#[cfg(target_arch = "wasm32")]
+struct DateClock;
+
+#[cfg(target_arch = "wasm32")]
+impl Clock for DateClock {
+    fn now(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(js_sys::Date::now() / 1000.0)
+    }
+
+    /// The browser's thread cannot block; `WasmEngine` rejects retry backoff,
+    /// see `wasm_limits`
+    fn sleep(&self, _duration: std::time::Duration) {}
+
+    /// Resolves through `setTimeout`
+    fn timer(&self, duration: std::time::Duration) -> TimerFuture {
+        use wasm_bindgen::JsCast;
+
+        let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+            let global = js_sys::global();
+            let set_timeout = js_sys::Reflect::get(&global, &JsValue::from_str("setTimeout"))
+                .ok()
+                .and_then(|f| f.dyn_into::<js_sys::Function>().ok());
+            if let Some(set_timeout) = set_timeout {
+                let ms = JsValue::from_f64(duration.as_secs_f64() * 1000.0);
+                let _ = set_timeout.call2(&global, &resolve, &ms);
+            }
+        });
+        Box::pin(async move {
+            let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+        })
+    }
+}
+
+// Execution context with services that work in the browser
+fn default_context() -> ExecutionContext {
+    let context = ExecutionContext::new();
+    #[cfg(target_arch = "wasm32")]
+    let context = context.with_clock(Arc::new(DateClock));
+    context
+}
+
+/// Compute the changes that turn graph A into graph B, as GraphPatch JSON
+#[wasm_bindgen(js_name = graphDiff)]
+pub fn graph_diff(a_json: &str, b_json: &str) -> Result<String, JsValue> {
+    let a = parse_graph(a_json)?;
+    let b = parse_graph(b_json)?;
+    to_json(&Graph::diff(&a, &b))
+}
+
+/// Apply GraphPatch JSON to a graph and return the patched graph as JSON
+///
+/// Fails listing every conflict when the patch does not fit the graph.
+#[wasm_bindgen(js_name = applyGraphPatch)]
+pub fn apply_graph_patch(graph_json: &str, patch_json: &str) -> Result<String, JsValue> {
+    let mut graph = parse_graph(graph_json)?;
+    let patch: GraphPatch = serde_json::from_str(patch_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse patch: {}", e)))?;
+    graph
+        .apply_patch(&patch)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    to_json(&graph)
+}
+
+fn parse_graph(graph_json: &str) -> Result<Graph, JsValue> {
+    serde_json::from_str(graph_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse graph: {}", e)))
 }
 
 /// Initialize the WASM module
@@ -1,4 +1,4 @@
-use circuit_wasm::WasmEngine;
+use circuit_wasm::{apply_graph_patch, graph_diff, WasmEngine, WasmGraphEditor, WasmReplica};
 use wasm_bindgen_test::*;
 
 wasm_bindgen_test_configure!(run_in_browser);
@@ -17,7 +17,7 @@ fn test_wasm_engine_default() {
 
 #[wasm_bindgen_test]
 fn test_load_graph() {
-    let mut engine = WasmEngine::new();
+    let engine = WasmEngine::new();
 
     let graph_json = r#"{
         "id": "test_graph",
@@ -32,9 +32,34 @@ fn test_load_graph() {
     assert!(engine.list_graphs().contains(&"test_graph".to_string()));
 }
 
+#[wasm_bindgen_test]
+fn test_load_graph_overwrites() {
+    let engine = WasmEngine::new();
+    let graph_json = r#"{"id": "g", "name": "G", "nodes": {}, "connections": []}"#;
+
+    assert!(engine.load_graph(graph_json).is_ok());
+    assert!(engine.load_graph(graph_json).is_ok());
+    assert!(engine.load_new_graph(graph_json).is_err());
+    assert_eq!(engine.list_graphs().len(), 1);
+}
+
+#[wasm_bindgen_test]
+fn test_lifecycle_events_are_bounded() {
+    let engine = WasmEngine::new();
+    let graph_json = r#"{"id": "g", "name": "G", "nodes": {}, "connections": []}"#;
+    for _ in 0..1100 {
+        engine.load_graph(graph_json).unwrap();
+    }
+
+    let events: Vec<serde_json::Value> =
+        serde_json::from_str(&engine.take_lifecycle_events().unwrap()).unwrap();
+    assert_eq!(events.len(), 1024);
+    assert_eq!(events[0]["type"], "graph_replaced");
+}
+
 #[wasm_bindgen_test]
 fn test_load_invalid_graph() {
-    let mut engine = WasmEngine::new();
+    let engine = WasmEngine::new();
     let invalid_json = "{ invalid json }";
 
     let result = engine.load_graph(invalid_json);
@@ -50,7 +75,7 @@ fn test_execute_nonexistent_graph() {
 
 #[wasm_bindgen_test]
 fn test_load_and_list_multiple_graphs() {
-    let mut engine = WasmEngine::new();
+    let engine = WasmEngine::new();
 
     let graph1 = r#"{
         "id": "graph1",
@@ -77,7 +102,7 @@ fn test_load_and_list_multiple_graphs() {
 
 #[wasm_bindgen_test]
 fn test_execute_empty_graph() {
-    let mut engine = WasmEngine::new();
+    let engine = WasmEngine::new();
 
     let graph_json = r#"{
         "id": "empty_graph",
@@ -99,6 +124,26 @@ fn test_execute_empty_graph() {
     assert!(results_json.contains("{}") || results_json.contains("[]"));
 }
 
+#[wasm_bindgen_test]
+fn test_execute_graph_with_limits() {
+    let engine = WasmEngine::new();
+    engine
+        .load_graph(r#"{"id": "flow", "name": "Flow", "nodes": {}, "connections": []}"#)
+        .expect("Failed to load graph");
+
+    assert_eq!(
+        engine.execute_graph_with_limits("flow", 0, 0).unwrap(),
+        "{}"
+    );
+    assert_eq!(
+        engine.execute_graph_with_limits("flow", 10, 1000).unwrap(),
+        "{}"
+    );
+    assert!(engine
+        .execute_graph_with_limits("missing", 0, 1000)
+        .is_err());
+}
+
 #[wasm_bindgen_test]
 fn test_list_blocks_initially_empty() {
     let engine = WasmEngine::new();
@@ -109,7 +154,7 @@ fn test_list_blocks_initially_empty() {
 
 #[wasm_bindgen_test]
 fn test_multiple_engine_instances() {
-    let mut engine1 = WasmEngine::new();
+    let engine1 = WasmEngine::new();
     let engine2 = WasmEngine::new();
 
     let graph_json = r#"{
@@ -142,3 +187,226 @@ fn test_json_serialization_error_handling() {
         assert!(!err_str.is_empty());
     }
 }
+
+#[wasm_bindgen_test]
+fn test_replace_and_unload_graph() {
+    let engine = WasmEngine::new();
+
+    let graph_json = r#"{
+        "id": "live",
+        "name": "Live",
+        "nodes": {},
+        "connections": []
+    }"#;
+
+    assert!(engine.replace_graph(graph_json).is_err());
+    engine.load_graph(graph_json).expect("Failed to load graph");
+    assert!(engine.load_new_graph(graph_json).is_err());
+    engine
+        .replace_graph(graph_json)
+        .expect("Failed to replace graph");
+    engine.unload_graph("live").expect("Failed to unload graph");
+    assert!(engine.list_graphs().is_empty());
+
+    let events = engine.take_lifecycle_events().unwrap();
+    assert!(events.contains("graph_loaded"));
+    assert!(events.contains("graph_replaced"));
+    assert!(events.contains("graph_unloaded"));
+    assert_eq!(engine.take_lifecycle_events().unwrap(), "[]");
+}
+
+#[wasm_bindgen_test]
+fn test_suspend_restore_and_resume() {
+    let engine = WasmEngine::new();
+    engine
+        .load_graph(r#"{"id": "flow", "name": "Flow", "nodes": {}, "connections": []}"#)
+        .expect("Failed to load graph");
+
+    let outcome = engine.execute_suspendable("flow", "{}").unwrap();
+    assert!(outcome.contains(r#""status":"completed""#));
+    assert!(engine.execute_suspendable("flow", "[1]").is_err());
+
+    let suspended = r#"{
+        "execution_id": "run-1",
+        "graph_id": "flow",
+        "node_id": "approve",
+        "token": "approval",
+        "completed": [],
+        "outputs": {}
+    }"#;
+    engine
+        .restore_suspended(suspended)
+        .expect("Failed to restore");
+    assert!(engine.suspended_execution("run-1").is_some());
+    assert!(engine.resume("run-1", "other", "null").is_err());
+
+    let outcome = engine
+        .resume("run-1", "approval", r#"{"type": "Bool", "value": true}"#)
+        .unwrap();
+    assert!(outcome.contains(r#""status":"completed""#));
+    assert!(engine.suspended_execution("run-1").is_none());
+}
+
+#[wasm_bindgen_test]
+fn test_ports_of_unknown_blocks_and_nodes() {
+    let engine = WasmEngine::new();
+    assert!(engine.block_ports("control.switch", "{}").is_err());
+    assert!(engine.block_ports("control.switch", "not json").is_err());
+    assert!(engine.node_ports("missing", "n").is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_debug_session_steps_through_graph() {
+    let engine = WasmEngine::new();
+    engine
+        .load_graph(r#"{"id": "flow", "name": "Flow", "nodes": {}, "connections": []}"#)
+        .expect("Failed to load graph");
+    assert!(engine.debug("missing").is_err());
+
+    let mut session = engine.debug("flow").unwrap();
+    assert!(session.status().unwrap().contains(r#""status":"ready""#));
+    assert!(session.add_breakpoint(r#"{"node_id": "n"}"#).is_err());
+    assert!(session.set_input("value", "null").is_err());
+    assert!(session.resume().unwrap().contains(r#""status":"finished""#));
+    assert_eq!(session.outputs().unwrap(), "{}");
+}
+
+#[wasm_bindgen_test]
+fn test_execute_recorded_and_replay() {
+    let engine = WasmEngine::new();
+    engine
+        .load_graph(r#"{"id": "flow", "name": "Flow", "nodes": {}, "connections": []}"#)
+        .expect("Failed to load graph");
+    assert!(engine.execute_recorded("missing").is_err());
+
+    let recording = engine.execute_recorded("flow").unwrap();
+    assert!(recording.contains(r#""nodes":[]"#));
+    let report = engine.replay(&recording).unwrap();
+    assert!(!report.contains("divergence"));
+    assert!(engine.replay("not json").is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_graph_editor_undo_redo() {
+    assert!(WasmGraphEditor::new("not json").is_err());
+    let mut editor =
+        WasmGraphEditor::new(r#"{"id": "g", "name": "G", "nodes": {}, "connections": []}"#)
+            .unwrap();
+    editor.begin_batch(Some("add".to_string())).unwrap();
+    editor
+        .apply(r#"{"op": "add_node", "node": {"id": "n", "block_type": "core.constant", "config": {}, "position": null}}"#)
+        .unwrap();
+    editor
+        .apply(r#"{"op": "move_node", "node_id": "n", "position": [1.0, 2.0]}"#)
+        .unwrap();
+    editor.commit_batch().unwrap();
+    assert!(editor.graph().unwrap().contains(r#""position":[1.0,2.0]"#));
+    assert!(editor
+        .apply(r#"{"op": "remove_node", "node_id": "x"}"#)
+        .is_err());
+
+    assert!(editor.undo().unwrap());
+    assert!(!editor.can_undo() && editor.can_redo());
+    assert!(editor.graph().unwrap().contains(r#""nodes":{}"#));
+    let events = editor.take_events().unwrap();
+    assert!(events.contains(r#""type":"undone""#));
+    assert_eq!(editor.take_events().unwrap(), "[]");
+}
+
+#[wasm_bindgen_test]
+fn test_graph_diff_and_patch() {
+    let a = r#"{"id": "g", "name": "G", "nodes": {}, "connections": []}"#;
+    let b = r#"{"id": "g", "name": "Renamed", "nodes": {}, "connections": []}"#;
+    let patch = graph_diff(a, b).unwrap();
+    assert!(patch.contains(r#""name":{"from":"G","to":"Renamed"}"#));
+    assert!(apply_graph_patch(a, &patch)
+        .unwrap()
+        .contains(r#""name":"Renamed""#));
+    let other = r#"{"id": "g", "name": "Other", "nodes": {}, "connections": []}"#;
+    assert!(apply_graph_patch(other, &patch).is_err());
+    assert!(graph_diff("not json", b).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_replicas_merge() {
+    let graph = r#"{"id": "g", "name": "G", "nodes": {}, "connections": []}"#;
+    let mut alice = WasmReplica::new("alice", graph).unwrap();
+    let mut bob = WasmReplica::new("bob", graph).unwrap();
+    let add = r#"{"op": "add_node", "node": {"id": "n", "block_type": "core.constant",
+        "config": {}, "position": null}}"#;
+    alice.edit(add).unwrap();
+    assert!(alice.edit(add).is_err());
+
+    let ops = alice.ops_since(Some(bob.version().unwrap())).unwrap();
+    assert_eq!(bob.merge(&ops).unwrap(), 1);
+    assert_eq!(bob.merge(&ops).unwrap(), 0);
+    assert_eq!(alice.graph().unwrap(), bob.graph().unwrap());
+    assert_eq!(bob.rejected().unwrap(), "[]");
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen_test]
+fn test_load_graph_reports_migrations() {
+    let engine = WasmEngine::new();
+    register_block(&engine, "math.add");
+    engine.add_block_alias("math.plus", "math.add").unwrap();
+    engine.add_port_alias("math.add", "x", "a").unwrap();
+    assert!(engine.add_block_alias("math.add", "math.plus").is_err());
+
+    let legacy = r#"{"id": "legacy", "name": "Legacy", "connections": [],
+        "nodes": {"n": {"id": "n", "block_type": "math.plus", "config": {}, "position": null}}}"#;
+    let migrated: serde_json::Value =
+        serde_json::from_str(&engine.migrate_graph(legacy).unwrap()).unwrap();
+    assert_eq!(migrated["graph"]["nodes"]["n"]["block_type"], "math.add");
+
+    let report: serde_json::Value =
+        serde_json::from_str(&engine.load_graph(legacy).unwrap()).unwrap();
+    assert_eq!(report["from_version"], 0);
+    assert_eq!(report["changes"][0]["type"], "block_renamed");
+    assert!(engine
+        .take_lifecycle_events()
+        .unwrap()
+        .contains("graph_migrated"));
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen_test]
+fn test_retry_backoff_rejected() {
+    let engine = WasmEngine::new();
+    register_block(&engine, "test.flaky");
+    let graph = |id: &str, backoff: &str| {
+        format!(
+            r#"{{"id": "{}", "name": "G", "connections": [], "nodes": {{"n": {{"id": "n",
+            "block_type": "test.flaky", "config": {{}}, "position": null,
+            "retry": {{"max_attempts": 3{}}}}}}}}}"#,
+            id, backoff
+        )
+    };
+    let fixed = r#", "backoff": {"type": "fixed", "delay_ms": 10}"#;
+    engine
+        .load_graph(&graph("immediate", ""))
+        .expect("Failed to load graph");
+    let err = engine.load_graph(&graph("fixed", fixed)).unwrap_err();
+    assert!(err.as_string().unwrap().contains("between retries"));
+
+    // Hosts cannot lift the cap
+    engine
+        .set_limits(r#"{"max_retry_delay_ms": 1000}"#)
+        .unwrap();
+    let err = engine.load_graph(&graph("fixed", fixed)).unwrap_err();
+    assert!(err.as_string().unwrap().contains("limit is 0 ms"));
+}
+
+// Register a block with no ports that outputs nothing
+#[cfg(target_arch = "wasm32")]
+fn register_block(engine: &WasmEngine, id: &str) {
+    let metadata = format!(
+        r#"{{"id": "{}", "name": "{}", "description": "", "inputs": [], "outputs": [],
+        "config_schema": {{}}}}"#,
+        id, id
+    );
+    let callback = js_sys::Function::new_with_args("inputs, config", "return '{}';");
+    engine
+        .register_async_block(&metadata, callback)
+        .expect("Failed to register block");
+}
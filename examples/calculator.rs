@@ -9,7 +9,7 @@ use std::sync::Arc;
 
 fn main() {
     // Create a new engine
-    let mut engine = Engine::new();
+    let engine = Engine::new();
 
     // Register blocks
     println!("Registering blocks...");
@@ -25,56 +25,32 @@ fn main() {
     // Create constant node for 5
     let mut config1 = HashMap::new();
     config1.insert("value".to_string(), Value::Float(5.0));
-    let node1 = graph::Node {
-        id: "const_5".to_string(),
-        block_type: "core.constant".to_string(),
-        config: config1,
-        position: Some((0.0, 0.0)),
-    };
+    let node1 = graph::Node::new("const_5", "core.constant")
+        .with_config(config1)
+        .with_position(0.0, 0.0);
 
     // Create constant node for 3
     let mut config2 = HashMap::new();
     config2.insert("value".to_string(), Value::Float(3.0));
-    let node2 = graph::Node {
-        id: "const_3".to_string(),
-        block_type: "core.constant".to_string(),
-        config: config2,
-        position: Some((0.0, 100.0)),
-    };
+    let node2 = graph::Node::new("const_3", "core.constant")
+        .with_config(config2)
+        .with_position(0.0, 100.0);
 
     // Create add node
-    let node3 = graph::Node {
-        id: "add".to_string(),
-        block_type: "math.add".to_string(),
-        config: HashMap::new(),
-        position: Some((200.0, 50.0)),
-    };
+    let node3 = graph::Node::new("add", "math.add").with_position(200.0, 50.0);
 
     // Create constant node for 2
     let mut config4 = HashMap::new();
     config4.insert("value".to_string(), Value::Float(2.0));
-    let node4 = graph::Node {
-        id: "const_2".to_string(),
-        block_type: "core.constant".to_string(),
-        config: config4,
-        position: Some((200.0, 150.0)),
-    };
+    let node4 = graph::Node::new("const_2", "core.constant")
+        .with_config(config4)
+        .with_position(200.0, 150.0);
 
     // Create multiply node
-    let node5 = graph::Node {
-        id: "multiply".to_string(),
-        block_type: "math.multiply".to_string(),
-        config: HashMap::new(),
-        position: Some((400.0, 100.0)),
-    };
+    let node5 = graph::Node::new("multiply", "math.multiply").with_position(400.0, 100.0);
 
     // Create debug node
-    let node6 = graph::Node {
-        id: "debug".to_string(),
-        block_type: "core.debug".to_string(),
-        config: HashMap::new(),
-        position: Some((600.0, 100.0)),
-    };
+    let node6 = graph::Node::new("debug", "core.debug").with_position(600.0, 100.0);
 
     // Add nodes to graph
     graph.add_node(node1).unwrap();